@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+/// The default capacity of the buffer pool in pages.
+pub const DEFAULT_BUFFER_POOL_CAPACITY: usize = 1024;
+
+// A page that is resident in the buffer pool.
+struct Frame {
+    page: usize,
+    data: Vec<u8>,
+    pin_count: usize,
+    is_dirty: bool,
+    is_referenced: bool,
+}
+
+/// A page that has been modified since it was last written to the file.
+pub type DirtyPage = (usize, Vec<u8>);
+
+/// A bounded pool of serialized pages that uses the clock algorithm for eviction.
+///
+/// Each frame has a reference bit that is set whenever its page is accessed. When a page has to be
+/// evicted, the clock hand sweeps over the frames, clearing reference bits until it finds a frame
+/// whose bit is already clear. Pinned and dirty frames are never evicted; if every frame is pinned
+/// or dirty, the pool grows beyond its capacity until the dirty pages are written back and pages
+/// are unpinned.
+pub struct BufferPool {
+    frames: Vec<Frame>,
+    page_table: HashMap<usize, usize>,
+    free_frames: Vec<usize>,
+    hand: usize,
+    capacity: usize,
+}
+
+impl BufferPool {
+    pub fn new(capacity: usize) -> Self {
+        BufferPool {
+            frames: Vec::new(),
+            page_table: HashMap::new(),
+            free_frames: Vec::new(),
+            hand: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Returns `true` if the pool holds more pages than its capacity, because every frame that
+    /// could have been evicted was pinned or dirty.
+    pub fn is_over_capacity(&self) -> bool {
+        self.page_table.len() > self.capacity
+    }
+
+    /// Returns the data of a page if it is resident and marks it as recently used.
+    pub fn get(&mut self, page: usize) -> Option<&[u8]> {
+        let index = *self.page_table.get(&page)?;
+        let frame = &mut self.frames[index];
+        frame.is_referenced = true;
+        Some(&frame.data)
+    }
+
+    /// Inserts or replaces the data of a page, evicting another page if the pool is full.
+    pub fn insert(&mut self, page: usize, data: Vec<u8>, is_dirty: bool) {
+        if let Some(&index) = self.page_table.get(&page) {
+            let frame = &mut self.frames[index];
+            frame.data = data;
+            frame.is_dirty |= is_dirty;
+            frame.is_referenced = true;
+            return;
+        }
+
+        self.evict(self.capacity - 1);
+        let frame = Frame {
+            page,
+            data,
+            pin_count: 0,
+            is_dirty,
+            is_referenced: true,
+        };
+        match self.free_frames.pop() {
+            Some(index) => {
+                self.frames[index] = frame;
+                self.page_table.insert(page, index);
+            }
+            None => {
+                self.page_table.insert(page, self.frames.len());
+                self.frames.push(frame);
+            }
+        }
+    }
+
+    /// Prevents a resident page from being evicted until it is unpinned as many times as it was
+    /// pinned. Returns `false` if the page is not resident.
+    pub fn pin(&mut self, page: usize) -> bool {
+        match self.page_table.get(&page) {
+            Some(&index) => {
+                self.frames[index].pin_count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn unpin(&mut self, page: usize) {
+        if let Some(&index) = self.page_table.get(&page) {
+            let frame = &mut self.frames[index];
+            frame.pin_count = frame.pin_count.saturating_sub(1);
+        }
+    }
+
+    /// Sets the capacity of the pool and evicts pages until the pool is within its capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.shrink();
+    }
+
+    /// Evicts pages until the pool is within its capacity.
+    pub fn shrink(&mut self) {
+        self.evict(self.capacity);
+    }
+
+    /// Returns `true` if a page is resident and has been modified since it was last written to the
+    /// file.
+    pub fn is_dirty(&self, page: usize) -> bool {
+        self.page_table
+            .get(&page)
+            .is_some_and(|index| self.frames[*index].is_dirty)
+    }
+
+    /// Returns the dirty pages in ascending order of pages without marking them as clean, so that
+    /// they are kept if writing them to the file fails.
+    pub fn get_dirty_pages(&self) -> Vec<DirtyPage> {
+        let mut dirty_pages: Vec<_> = self
+            .frames
+            .iter()
+            .filter(|frame| frame.is_dirty)
+            .map(|frame| (frame.page, frame.data.clone()))
+            .collect();
+        dirty_pages.sort_by_key(|dirty_page| dirty_page.0);
+        dirty_pages
+    }
+
+    /// Marks every page as clean once the dirty pages have been written to the file.
+    pub fn mark_clean(&mut self) {
+        for frame in &mut self.frames {
+            frame.is_dirty = false;
+        }
+    }
+
+    /// Removes a page, including a pinned or dirty page.
+    pub fn remove(&mut self, page: usize) {
+        if let Some(&index) = self.page_table.get(&page) {
+            self.release_frame(index);
+        }
+    }
+
+    /// Removes the pages at or after `len`, including pinned and dirty pages.
+    pub fn truncate(&mut self, len: usize) {
+        let indices: Vec<_> = self
+            .page_table
+            .iter()
+            .filter(|(page, _)| **page >= len)
+            .map(|(_, index)| *index)
+            .collect();
+        for index in indices {
+            self.release_frame(index);
+        }
+    }
+
+    // A released frame keeps its slot in `frames` so that the indices of other frames are stable,
+    // and is reused by the next inserted page.
+    fn release_frame(&mut self, index: usize) {
+        let frame = &mut self.frames[index];
+        self.page_table.remove(&frame.page);
+        frame.data = Vec::new();
+        frame.pin_count = 0;
+        frame.is_dirty = false;
+        frame.is_referenced = false;
+        frame.page = usize::MAX;
+        self.free_frames.push(index);
+    }
+
+    // Evicts pages until at most `len` pages are resident or every page is pinned or dirty.
+    fn evict(&mut self, len: usize) {
+        // two sweeps clear every reference bit, so a third sweep that finds nothing means that
+        // every resident page is pinned or dirty
+        let mut remaining_steps = 3 * self.frames.len();
+        while self.page_table.len() > len && remaining_steps > 0 {
+            remaining_steps -= 1;
+            let index = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            let frame = &mut self.frames[index];
+            if frame.page == usize::MAX || frame.pin_count > 0 || frame.is_dirty {
+                continue;
+            }
+            if frame.is_referenced {
+                frame.is_referenced = false;
+                continue;
+            }
+
+            self.release_frame(index);
+            remaining_steps = 3 * self.frames.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferPool;
+
+    #[test]
+    fn test_get_insert() {
+        let mut pool = BufferPool::new(2);
+        assert!(pool.get(0).is_none());
+        pool.insert(0, vec![0], false);
+        assert_eq!(pool.get(0), Some(&[0][..]));
+        pool.insert(0, vec![1], true);
+        assert_eq!(pool.get(0), Some(&[1][..]));
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut pool = BufferPool::new(2);
+        pool.insert(0, vec![0], false);
+        pool.insert(1, vec![1], false);
+
+        // every reference bit is set, so the clock hand clears them and evicts the first page
+        pool.insert(2, vec![2], false);
+        assert!(pool.get(0).is_none());
+
+        // the reference bit of the second page is cleared, so it is evicted before the third page
+        pool.get(2);
+        pool.insert(3, vec![3], false);
+        assert!(pool.get(1).is_none());
+        assert!(pool.get(2).is_some());
+        assert!(pool.get(3).is_some());
+        assert!(!pool.is_over_capacity());
+    }
+
+    #[test]
+    fn test_pin() {
+        let mut pool = BufferPool::new(1);
+        assert!(!pool.pin(0));
+        pool.insert(0, vec![0], false);
+        assert!(pool.pin(0));
+
+        // pinned pages are not evicted, so the pool grows beyond its capacity
+        pool.insert(1, vec![1], false);
+        assert!(pool.is_over_capacity());
+        assert!(pool.get(0).is_some());
+        assert!(pool.get(1).is_some());
+
+        pool.unpin(0);
+        pool.insert(2, vec![2], false);
+        assert!(!pool.is_over_capacity());
+        assert!(pool.get(0).is_none());
+        assert!(pool.get(1).is_none());
+    }
+
+    #[test]
+    fn test_dirty_pages() {
+        let mut pool = BufferPool::new(2);
+        pool.insert(2, vec![2], true);
+        pool.insert(1, vec![1], false);
+        pool.insert(0, vec![0], true);
+        pool.insert(3, vec![3], true);
+
+        // dirty pages are not evicted until they are marked as clean
+        assert!(pool.get(1).is_none());
+        assert!(pool.is_over_capacity());
+        assert!(pool.is_dirty(2));
+        assert!(!pool.is_dirty(1));
+        assert_eq!(
+            pool.get_dirty_pages(),
+            vec![(0, vec![0]), (2, vec![2]), (3, vec![3])],
+        );
+        pool.shrink();
+        assert!(pool.is_over_capacity());
+        pool.mark_clean();
+        assert!(pool.get_dirty_pages().is_empty());
+        assert!(!pool.is_dirty(2));
+        pool.shrink();
+        assert!(!pool.is_over_capacity());
+
+        pool.set_capacity(1);
+        assert!(!pool.is_over_capacity());
+        pool.insert(1, vec![1], true);
+        pool.truncate(1);
+        assert!(pool.get(1).is_none());
+        pool.insert(1, vec![1], true);
+        pool.pin(1);
+        pool.remove(1);
+        assert!(pool.get(1).is_none());
+        assert!(pool.get(2).is_none());
+        assert!(pool.get(3).is_none());
+    }
+}
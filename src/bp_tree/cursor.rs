@@ -0,0 +1,482 @@
+use crate::bp_tree::node::{InternalNode, LeafNode, Node, Value};
+use crate::bp_tree::pager::{Pager, Result};
+use crate::entry::Entry;
+use serde::de::DeserializeOwned;
+use std::borrow::Borrow;
+
+/// The position of an entry in a leaf node of the tree.
+///
+/// The internal nodes on the path from the root to the leaf node are kept with the index of the
+/// child that was followed, so that the position can move to the previous leaf node as well as
+/// the next one. The nodes are copies, so the tree must not be modified while a position is used.
+pub struct Position<T, U> {
+    stack: Vec<(InternalNode<T, Value<U>>, usize)>,
+    leaf: LeafNode<T, Value<U>>,
+    index: usize,
+}
+
+impl<T, U> Position<T, U>
+where
+    T: DeserializeOwned,
+    U: DeserializeOwned,
+{
+    // Follows the leftmost or rightmost children from a page down to a leaf node, and positions
+    // at the first or last entry of the leaf node. The leaf node may be empty.
+    fn descend(
+        pager: &mut Pager<T, U>,
+        page: usize,
+        mut stack: Vec<(InternalNode<T, Value<U>>, usize)>,
+        is_rightmost: bool,
+    ) -> Result<Self> {
+        let mut curr_node = pager.get_page(page)?;
+        while let Node::Internal(curr_internal_node) = curr_node {
+            let index = if is_rightmost {
+                curr_internal_node.len
+            } else {
+                0
+            };
+            let next_page = curr_internal_node.pointers[index];
+            stack.push((curr_internal_node, index));
+            curr_node = pager.get_page(next_page)?;
+        }
+
+        match curr_node {
+            Node::Leaf(leaf) => {
+                let index = if is_rightmost {
+                    leaf.len.saturating_sub(1)
+                } else {
+                    0
+                };
+                Ok(Position { stack, leaf, index })
+            }
+            _ => panic!("Expected a leaf node."),
+        }
+    }
+
+    // Follows the children that may contain a key down to a leaf node, and positions at the
+    // first entry of the leaf node that is not less than the key. The index may be the length of
+    // the leaf node.
+    fn search<V>(pager: &mut Pager<T, U>, key: &V) -> Result<Self>
+    where
+        T: Borrow<V>,
+        V: Ord + ?Sized,
+    {
+        let mut stack = Vec::new();
+        let mut curr_node = pager.get_page(pager.get_root_page())?;
+        while let Node::Internal(curr_internal_node) = curr_node {
+            let index = curr_internal_node.search(key);
+            let next_page = curr_internal_node.pointers[index];
+            stack.push((curr_internal_node, index));
+            curr_node = pager.get_page(next_page)?;
+        }
+
+        match curr_node {
+            Node::Leaf(leaf) => {
+                let index = leaf.lower_bound(key);
+                Ok(Position { stack, leaf, index })
+            }
+            _ => panic!("Expected a leaf node."),
+        }
+    }
+
+    // Moves to the first entry of the next non-empty leaf node.
+    fn next_leaf(mut self, pager: &mut Pager<T, U>) -> Result<Option<Self>> {
+        while let Some((curr_internal_node, index)) = self.stack.pop() {
+            if index == curr_internal_node.len {
+                continue;
+            }
+            let next_page = curr_internal_node.pointers[index + 1];
+            self.stack.push((curr_internal_node, index + 1));
+            self = Self::descend(pager, next_page, self.stack, false)?;
+            if self.leaf.len > 0 {
+                return Ok(Some(self));
+            }
+        }
+        Ok(None)
+    }
+
+    // Moves to the last entry of the previous non-empty leaf node.
+    fn prev_leaf(mut self, pager: &mut Pager<T, U>) -> Result<Option<Self>> {
+        while let Some((curr_internal_node, index)) = self.stack.pop() {
+            if index == 0 {
+                continue;
+            }
+            let prev_page = curr_internal_node.pointers[index - 1];
+            self.stack.push((curr_internal_node, index - 1));
+            self = Self::descend(pager, prev_page, self.stack, true)?;
+            if self.leaf.len > 0 {
+                return Ok(Some(self));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the position of the entry with the minimum key, or `None` if the tree is empty.
+    pub fn first(pager: &mut Pager<T, U>) -> Result<Option<Self>> {
+        let position = Self::descend(pager, pager.get_root_page(), Vec::new(), false)?;
+        if position.leaf.len > 0 {
+            Ok(Some(position))
+        } else {
+            position.next_leaf(pager)
+        }
+    }
+
+    /// Returns the position of the entry with the maximum key, or `None` if the tree is empty.
+    pub fn last(pager: &mut Pager<T, U>) -> Result<Option<Self>> {
+        let position = Self::descend(pager, pager.get_root_page(), Vec::new(), true)?;
+        if position.leaf.len > 0 {
+            Ok(Some(position))
+        } else {
+            position.prev_leaf(pager)
+        }
+    }
+
+    /// Returns the position of the first entry whose key is greater than or equal to `key`.
+    pub fn ceil<V>(pager: &mut Pager<T, U>, key: &V) -> Result<Option<Self>>
+    where
+        T: Borrow<V>,
+        V: Ord + ?Sized,
+    {
+        let position = Self::search(pager, key)?;
+        if position.index < position.leaf.len {
+            Ok(Some(position))
+        } else {
+            position.next_leaf(pager)
+        }
+    }
+
+    /// Returns the position of the last entry whose key is less than or equal to `key`.
+    pub fn floor<V>(pager: &mut Pager<T, U>, key: &V) -> Result<Option<Self>>
+    where
+        T: Borrow<V>,
+        V: Ord + ?Sized,
+    {
+        let mut position = Self::search(pager, key)?;
+        position.index = position.leaf.upper_bound(key);
+        if position.index > 0 {
+            position.index -= 1;
+            Ok(Some(position))
+        } else {
+            position.prev_leaf(pager)
+        }
+    }
+
+    /// Moves to the entry with the next key.
+    pub fn next(mut self, pager: &mut Pager<T, U>) -> Result<Option<Self>> {
+        if self.index + 1 < self.leaf.len {
+            self.index += 1;
+            Ok(Some(self))
+        } else {
+            self.next_leaf(pager)
+        }
+    }
+
+    /// Moves to the entry with the previous key.
+    pub fn prev(mut self, pager: &mut Pager<T, U>) -> Result<Option<Self>> {
+        if self.index > 0 {
+            self.index -= 1;
+            Ok(Some(self))
+        } else {
+            self.prev_leaf(pager)
+        }
+    }
+
+    pub fn get_entry(&self) -> &Entry<T, Value<U>> {
+        self.leaf.entries[self.index]
+            .as_ref()
+            .expect("Expected some entry.")
+    }
+
+    /// Removes the entry from the copy of its leaf node. The position must be moved before the
+    /// entry is accessed again.
+    pub fn take_entry(&mut self) -> Entry<T, Value<U>> {
+        self.leaf.entries[self.index]
+            .take()
+            .expect("Expected some entry.")
+    }
+}
+
+/// A cursor over a `BpMap<T, U>` that can seek to a key and move both forward and backward.
+///
+/// A cursor is positioned either at an entry of the map or at no entry at all. It is created
+/// before the first entry, and moving it past the first or last entry leaves it positioned at no
+/// entry. The cursor holds a mutable borrow of the map, so the map cannot be modified while the
+/// cursor is alive.
+///
+/// # Examples
+///
+/// ```
+/// # use extended_collections::bp_tree::Result;
+/// # fn foo() -> Result<()> {
+/// # use std::fs;
+/// use extended_collections::bp_tree::BpMap;
+///
+/// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_struct", 4, 8)?;
+/// map.insert(1, 1)?;
+/// map.insert(3, 3)?;
+/// map.insert(5, 5)?;
+///
+/// let mut cursor = map.cursor();
+/// cursor.seek(&2)?;
+/// assert_eq!(cursor.key(), Some(&3));
+/// assert_eq!(cursor.value(), Some(&3));
+///
+/// cursor.next()?;
+/// assert_eq!(cursor.key(), Some(&5));
+///
+/// cursor.prev()?;
+/// cursor.prev()?;
+/// assert_eq!(cursor.key(), Some(&1));
+///
+/// cursor.prev()?;
+/// assert_eq!(cursor.key(), None);
+/// # fs::remove_file("example_bp_map_cursor_struct")?;
+/// # Ok(())
+/// # }
+/// # foo().unwrap();
+/// ```
+pub struct BpMapCursor<'a, T, U> {
+    pager: &'a mut Pager<T, U>,
+    position: Option<Position<T, U>>,
+    entry: Option<(T, U)>,
+}
+
+impl<'a, T, U> BpMapCursor<'a, T, U>
+where
+    T: Clone + DeserializeOwned,
+    U: Clone + DeserializeOwned,
+{
+    pub(crate) fn new(pager: &'a mut Pager<T, U>) -> Self {
+        BpMapCursor {
+            pager,
+            position: None,
+            entry: None,
+        }
+    }
+
+    // Moves the cursor to a position and reads the entry at the position.
+    fn set_position(&mut self, position: Option<Position<T, U>>) -> Result<()> {
+        self.entry = match position {
+            Some(ref position) => {
+                let entry = position.get_entry();
+                let value = self.pager.read_value(entry.value.clone())?;
+                Some((entry.key.clone(), value))
+            }
+            None => None,
+        };
+        self.position = position;
+        Ok(())
+    }
+
+    /// Positions the cursor at the first entry whose key is greater than or equal to `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_seek", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.seek(&1)?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.seek(&2)?;
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// cursor.seek(&4)?;
+    /// assert_eq!(cursor.key(), None);
+    /// # fs::remove_file("example_bp_map_cursor_seek")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn seek<V>(&mut self, key: &V) -> Result<()>
+    where
+        T: Borrow<V>,
+        V: Ord + ?Sized,
+    {
+        let position = Position::ceil(self.pager, key)?;
+        self.set_position(position)
+    }
+
+    /// Positions the cursor at the entry with the minimum key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_seek_to_first", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// # fs::remove_file("example_bp_map_cursor_seek_to_first")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        let position = Position::first(self.pager)?;
+        self.set_position(position)
+    }
+
+    /// Positions the cursor at the entry with the maximum key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_seek_to_last", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.seek_to_last()?;
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// # fs::remove_file("example_bp_map_cursor_seek_to_last")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        let position = Position::last(self.pager)?;
+        self.set_position(position)
+    }
+
+    /// Moves the cursor to the entry with the next key. Does nothing if the cursor is not
+    /// positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_next", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.seek_to_first()?;
+    /// cursor.next()?;
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// cursor.next()?;
+    /// assert_eq!(cursor.key(), None);
+    /// # fs::remove_file("example_bp_map_cursor_next")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<()> {
+        let position = match self.position.take() {
+            Some(position) => position.next(self.pager)?,
+            None => return Ok(()),
+        };
+        self.set_position(position)
+    }
+
+    /// Moves the cursor to the entry with the previous key. Does nothing if the cursor is not
+    /// positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_prev", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.seek_to_last()?;
+    /// cursor.prev()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.prev()?;
+    /// assert_eq!(cursor.key(), None);
+    /// # fs::remove_file("example_bp_map_cursor_prev")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn prev(&mut self) -> Result<()> {
+        let position = match self.position.take() {
+            Some(position) => position.prev(self.pager)?,
+            None => return Ok(()),
+        };
+        self.set_position(position)
+    }
+
+    /// Returns the key of the entry that the cursor is positioned at, or `None` if the cursor is
+    /// not positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_key", 4, 8)?;
+    /// map.insert(1, 1)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// assert_eq!(cursor.key(), None);
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// # fs::remove_file("example_bp_map_cursor_key")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn key(&self) -> Option<&T> {
+        self.entry.as_ref().map(|entry| &entry.0)
+    }
+
+    /// Returns the value of the entry that the cursor is positioned at, or `None` if the cursor is
+    /// not positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor_value", 4, 8)?;
+    /// map.insert(1, 2)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// assert_eq!(cursor.value(), None);
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.value(), Some(&2));
+    /// # fs::remove_file("example_bp_map_cursor_value")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn value(&self) -> Option<&U> {
+        self.entry.as_ref().map(|entry| &entry.1)
+    }
+}
@@ -1,16 +1,40 @@
-use crate::bp_tree::node::{InsertCases, InternalNode, LeafNode, Node, BLOCK_SIZE};
-use crate::bp_tree::pager::{Pager, Result};
+use crate::bp_tree::cursor::{BpMapCursor, Position};
+use crate::bp_tree::node::{InsertCases, InternalNode, LeafNode, Node, Value, BLOCK_SIZE};
+use crate::bp_tree::pager::{Error, Pager, Result};
+use crate::bp_tree::wal::WriteAheadLog;
+use crate::bp_tree::SyncPolicy;
 use crate::entry::Entry;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use std::borrow::Borrow;
+use std::cmp;
+use std::fs;
 use std::mem;
+use std::ops::{Bound, RangeBounds};
 use std::path::Path;
 
 // (page, node, index)
-type SearchHistory<T, U> = Vec<(usize, Node<T, U>, usize)>;
+type SearchHistory<T, U> = Vec<(usize, Node<T, Value<U>>, usize)>;
 // (page, node, history)
-type SearchOutcome<T, U> = (usize, Node<T, U>, SearchHistory<T, U>);
+type SearchOutcome<T, U> = (usize, Node<T, Value<U>>, SearchHistory<T, U>);
+
+// Returns the number of entries or children of the nodes in a level that is built bottom-up with a
+// fill factor. Nodes are never filled below their minimum length, so that they do not underflow.
+fn get_fill_len(min_len: usize, max_len: usize, fill_factor: f64) -> usize {
+    let fill_len = (max_len as f64 * fill_factor).ceil() as usize;
+    cmp::min(cmp::max(fill_len, min_len), max_len)
+}
+
+// Returns the lengths of the last nodes of a level, which hold fewer than `fill_len + min_len`
+// items. If they do not fit in a single node, they are split evenly between two nodes, each of
+// which has at least the minimum length.
+fn get_last_lens(len: usize, max_len: usize) -> Vec<usize> {
+    if len <= max_len {
+        vec![len]
+    } else {
+        vec![len / 2, len - len / 2]
+    }
+}
 
 /// An ordered map implemented using an on-disk B+ tree.
 ///
@@ -18,6 +42,21 @@ type SearchOutcome<T, U> = (usize, Node<T, U>, SearchHistory<T, U>);
 /// which each internal node contains keys and pointers to other nodes, and each leaf node
 /// contains keys and values.
 ///
+/// Nodes are stored in fixed-size slotted pages that hold a fixed number of variable-length
+/// entries: the header of a page is followed by the offsets of its entries, which are packed at the
+/// end of the page. Keys and values that do not fit in their maximum sizes are split across a chain
+/// of overflow pages, so the entry in the page only holds the first page of the chain.
+///
+/// Pages are read and written through a bounded buffer pool, so repeated reads of the same nodes
+/// do not touch the file. Every operation that modifies the map is atomic: the pages it modifies
+/// are appended as a single record to a write-ahead log stored next to the file of the map, with
+/// `-wal` appended to its path. The log is checkpointed into the file when it grows too large,
+/// when `sync` is called, or when the map is dropped. If the process crashes, `open` replays the
+/// log and always sees the state after the last operation that was written to the log. An
+/// operation that fails partway is rolled back and leaves the map unchanged. The log is only
+/// forced onto stable storage according to its `SyncPolicy`, so a loss of power may lose the most
+/// recent operations, but never leaves a partial operation behind.
+///
 /// # Examples
 ///
 /// ```
@@ -49,7 +88,9 @@ pub struct BpMap<T, U> {
 
 impl<T, U> BpMap<T, U> {
     /// Constructs a new, empty `BpMap<T, U>` with maximum sizes for keys and values, and creates a
-    /// file for data persistence.
+    /// file for data persistence. Keys and values are variable-length and their sizes are measured
+    /// after serialization. Keys and values that are larger than their maximum sizes are stored in
+    /// overflow pages.
     ///
     /// # Examples
     ///
@@ -72,8 +113,8 @@ impl<T, U> BpMap<T, U> {
         U: Serialize,
         P: AsRef<Path>,
     {
-        let leaf_degree = LeafNode::<T, U>::get_degree(key_size, value_size);
-        let internal_degree = InternalNode::<T, U>::get_degree(key_size);
+        let leaf_degree = LeafNode::<T, Value<U>>::get_degree(key_size, value_size);
+        let internal_degree = InternalNode::<T, Value<U>>::get_degree(key_size);
         Pager::new(
             file_path,
             key_size,
@@ -113,8 +154,10 @@ impl<T, U> BpMap<T, U> {
         U: Serialize,
         P: AsRef<Path>,
     {
-        assert!(LeafNode::<T, U>::get_max_size(leaf_degree, key_size, value_size) <= BLOCK_SIZE);
-        assert!(InternalNode::<T, U>::get_max_size(internal_degree, key_size) <= BLOCK_SIZE);
+        assert!(
+            LeafNode::<T, Value<U>>::get_max_size(leaf_degree, key_size, value_size) <= BLOCK_SIZE
+        );
+        assert!(InternalNode::<T, Value<U>>::get_max_size(internal_degree, key_size) <= BLOCK_SIZE);
         Pager::new(
             file_path,
             key_size,
@@ -125,6 +168,143 @@ impl<T, U> BpMap<T, U> {
         .map(|pager| BpMap { pager })
     }
 
+    /// Constructs a new `BpMap<T, U>` from entries that are sorted by key in strictly ascending
+    /// order, and creates a file for data persistence. The leaf nodes and then each level of
+    /// internal nodes are written bottom-up in a single sequential pass, and each node is filled
+    /// to `fill_factor` of its capacity to leave room for future insertions. Returns
+    /// `Error::InvalidFillFactor` if `fill_factor` is not in the range `(0, 1]`, and
+    /// `Error::UnsortedKey` if a key is not greater than the previous key. If loading fails, the
+    /// partially written file and its log are removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let entries = (0..1000).map(|key| (key, u64::from(key)));
+    /// let mut map: BpMap<u32, u64> =
+    ///     BpMap::bulk_load("example_bp_map_bulk_load", 4, 8, entries, 0.9)?;
+    /// assert_eq!(map.len(), 1000);
+    /// assert_eq!(map.get(&500)?, Some(500));
+    /// # fs::remove_file("example_bp_map_bulk_load")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn bulk_load<P, I>(
+        file_path: P,
+        key_size: u64,
+        value_size: u64,
+        entries: I,
+        fill_factor: f64,
+    ) -> Result<BpMap<T, U>>
+    where
+        T: Clone + DeserializeOwned + Ord + Serialize,
+        U: DeserializeOwned + Serialize,
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (T, U)>,
+    {
+        if !(0.0 < fill_factor && fill_factor <= 1.0) {
+            return Err(Error::InvalidFillFactor(fill_factor));
+        }
+        let mut map = BpMap::new(&file_path, key_size, value_size)?;
+        if let Err(error) = map.load_entries(entries, fill_factor) {
+            drop(map);
+            let _ = fs::remove_file(&file_path);
+            let _ = fs::remove_file(WriteAheadLog::get_path(&file_path));
+            return Err(error);
+        }
+        Ok(map)
+    }
+
+    fn load_entries<I>(&mut self, entries: I, fill_factor: f64) -> Result<()>
+    where
+        T: Clone + DeserializeOwned + Ord + Serialize,
+        U: DeserializeOwned + Serialize,
+        I: IntoIterator<Item = (T, U)>,
+    {
+        let map = self;
+        let leaf_degree = map.pager.get_leaf_degree();
+        let min_leaf_len = leaf_degree.div_ceil(2);
+        let leaf_len = get_fill_len(min_leaf_len, leaf_degree, fill_factor);
+
+        // the first key and the page of each node in the level that is being built
+        let mut level = Vec::new();
+        // a leaf node is only written once enough entries follow it to fill the next leaf node
+        // to its minimum length
+        let mut pending_entries: Vec<Entry<T, U>> = Vec::new();
+        let mut len = 0;
+        for (key, value) in entries {
+            if let Some(entry) = pending_entries.last() {
+                if key <= entry.key {
+                    return Err(Error::UnsortedKey);
+                }
+            }
+            pending_entries.push(Entry { key, value });
+            len += 1;
+
+            if pending_entries.len() >= leaf_len + min_leaf_len {
+                let remaining_entries = pending_entries.split_off(leaf_len);
+                let leaf_entries = mem::replace(&mut pending_entries, remaining_entries);
+                let first_key = leaf_entries[0].key.clone();
+                level.push((first_key, map.pager.append_leaf(leaf_entries, false)?));
+            }
+        }
+
+        if pending_entries.is_empty() {
+            return Ok(());
+        }
+        let last_lens = get_last_lens(pending_entries.len(), leaf_degree);
+        for (index, leaf_len) in last_lens.iter().enumerate() {
+            let remaining_entries = pending_entries.split_off(*leaf_len);
+            let leaf_entries = mem::replace(&mut pending_entries, remaining_entries);
+            let first_key = leaf_entries[0].key.clone();
+            let is_last = index + 1 == last_lens.len();
+            level.push((first_key, map.pager.append_leaf(leaf_entries, is_last)?));
+        }
+
+        let internal_degree = map.pager.get_internal_degree();
+        let min_children_len = internal_degree.div_ceil(2);
+        // every internal node has at least two children so that each level is smaller
+        let children_len = get_fill_len(
+            cmp::max(min_children_len, 2),
+            internal_degree + 1,
+            fill_factor,
+        );
+        while level.len() > 1 {
+            let mut node_lens = Vec::new();
+            let mut remaining_len = level.len();
+            while remaining_len >= children_len + min_children_len {
+                node_lens.push(children_len);
+                remaining_len -= children_len;
+            }
+            node_lens.extend(get_last_lens(remaining_len, internal_degree + 1));
+
+            let mut children = level.into_iter();
+            level = Vec::new();
+            for node_len in node_lens {
+                let mut node = InternalNode::new(internal_degree);
+                let mut first_key = None;
+                for (index, (key, page)) in children.by_ref().take(node_len).enumerate() {
+                    if index == 0 {
+                        first_key = Some(key);
+                    } else {
+                        node.keys[index - 1] = Some(key);
+                    }
+                    node.pointers[index] = page;
+                }
+                node.len = node_len - 1;
+                let page = map.pager.append_node(&Node::Internal(node))?;
+                level.push((first_key.expect("Expected some key."), page));
+            }
+        }
+
+        map.pager.finish_bulk_load(level[0].1, len)
+    }
+
     /// Opens an existing `BpMap<T, U>` from a file.
     ///
     /// # Examples
@@ -146,6 +326,67 @@ impl<T, U> BpMap<T, U> {
         Pager::open(file_path).map(|pager| BpMap { pager })
     }
 
+    /// Sets the maximum number of pages that are kept in the buffer pool. The default capacity is
+    /// 1024 pages. Modified pages are kept until the next checkpoint even if they no longer fit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_set_buffer_pool_capacity", 4, 8)?;
+    /// map.set_buffer_pool_capacity(16);
+    /// map.insert(1, 1)?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// # fs::remove_file("example_bp_map_set_buffer_pool_capacity")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_buffer_pool_capacity(&mut self, capacity: usize) {
+        self.pager.set_buffer_pool_capacity(capacity)
+    }
+
+    /// Sets how often the write-ahead log is synchronized to disk. The default policy is
+    /// `SyncPolicy::Every(100)`. With `SyncPolicy::Always`, every operation is durable once it
+    /// returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::{BpMap, SyncPolicy};
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_set_sync_policy", 4, 8)?;
+    /// map.set_sync_policy(SyncPolicy::Always);
+    /// map.insert(1, 1)?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// # fs::remove_file("example_bp_map_set_sync_policy")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_sync_policy(&mut self, sync_policy: SyncPolicy) {
+        self.pager.set_sync_policy(sync_policy)
+    }
+
+    // Commits the pages written by an operation if it succeeded, and otherwise rolls them back so
+    // that they are neither logged with a later operation nor checkpointed.
+    fn finish_operation<V>(&mut self, result: Result<V>) -> Result<V> {
+        match result.and_then(|ret| self.pager.commit().map(|_| ret)) {
+            Ok(ret) => Ok(ret),
+            Err(error) => {
+                self.pager.rollback()?;
+                Err(error)
+            }
+        }
+    }
+
     fn search_node<V>(&mut self, key: &V) -> Result<SearchOutcome<T, U>>
     where
         T: Borrow<V> + DeserializeOwned,
@@ -170,10 +411,8 @@ impl<T, U> BpMap<T, U> {
     /// Inserts a key-value pair into the map. If the key already exists in the map, it will return
     /// and replace the old key-value pair.
     ///
-    /// # Panics
-    ///
-    /// Panics if attempting to insert a key or value that exceeds the maximum key or value size
-    /// specified on creation.
+    /// Keys and values that exceed the maximum sizes specified on creation are stored in chains of
+    /// overflow pages.
     ///
     /// # Examples
     ///
@@ -198,8 +437,16 @@ impl<T, U> BpMap<T, U> {
         T: Clone + DeserializeOwned + Ord + Serialize,
         U: DeserializeOwned + Serialize,
     {
-        self.pager.validate_key(&key)?;
-        self.pager.validate_value(&value)?;
+        let result = self.insert_entry(key, value);
+        self.finish_operation(result)
+    }
+
+    fn insert_entry(&mut self, key: T, value: U) -> Result<Option<(T, U)>>
+    where
+        T: Clone + DeserializeOwned + Ord + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        let value = self.pager.write_value(value)?;
         let (mut curr_page, mut curr_node, mut stack) = self.search_node(&key)?;
 
         let mut split_node_entry = None;
@@ -218,7 +465,8 @@ impl<T, U> BpMap<T, U> {
                 Some(InsertCases::Entry(entry)) => {
                     self.pager
                         .write_node(curr_page, &Node::Leaf(curr_leaf_node))?;
-                    return Ok(Some((entry.key, entry.value)));
+                    let value = self.pager.remove_value(entry.value)?;
+                    return Ok(Some((entry.key, value)));
                 }
                 None => self
                     .pager
@@ -284,6 +532,16 @@ impl<T, U> BpMap<T, U> {
     /// # foo().unwrap();
     /// ```
     pub fn remove<V>(&mut self, key: &V) -> Result<Option<(T, U)>>
+    where
+        T: Borrow<V> + Clone + DeserializeOwned + Ord + Serialize,
+        U: DeserializeOwned + Serialize,
+        V: Ord + ?Sized,
+    {
+        let result = self.remove_entry(key);
+        self.finish_operation(result)
+    }
+
+    fn remove_entry<V>(&mut self, key: &V) -> Result<Option<(T, U)>>
     where
         T: Borrow<V> + Clone + DeserializeOwned + Ord + Serialize,
         U: DeserializeOwned + Serialize,
@@ -459,7 +717,13 @@ impl<T, U> BpMap<T, U> {
                     .write_node(curr_page, &Node::Internal(curr_node))?;
             }
         }
-        Ok(ret.map(|entry| (entry.key, entry.value)))
+        match ret {
+            Some(entry) => {
+                let value = self.pager.remove_value(entry.value)?;
+                Ok(Some((entry.key, value)))
+            }
+            None => Ok(None),
+        }
     }
 
     /// Checks if a key exists in the map.
@@ -518,9 +782,15 @@ impl<T, U> BpMap<T, U> {
     {
         let (_, curr_node, _) = self.search_node(key)?;
         match curr_node {
-            Node::Leaf(mut curr_leaf_node) => Ok(curr_leaf_node.search(key).and_then(|index| {
-                mem::replace(&mut curr_leaf_node.entries[index], None).map(|entry| entry.value)
-            })),
+            Node::Leaf(mut curr_leaf_node) => {
+                let entry = curr_leaf_node
+                    .search(key)
+                    .and_then(|index| mem::replace(&mut curr_leaf_node.entries[index], None));
+                match entry {
+                    Some(entry) => self.pager.read_value(entry.value).map(Some),
+                    None => Ok(None),
+                }
+            }
             _ => panic!("Expected a leaf node."),
         }
     }
@@ -593,7 +863,34 @@ impl<T, U> BpMap<T, U> {
         T: Serialize,
         U: Serialize,
     {
-        self.pager.clear()
+        let result = self.pager.clear();
+        self.finish_operation(result)
+    }
+
+    /// Syncs the write-ahead log to disk and checkpoints it into the file of the map, so that
+    /// every completed operation is durable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_sync", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.sync()?;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::open("example_bp_map_sync")?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// # fs::remove_file("example_bp_map_sync")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn sync(&mut self) -> Result<()> {
+        self.pager.checkpoint()
     }
 
     /// Returns the minimum key of the map. Returns `None` if the map is empty.
@@ -728,6 +1025,200 @@ impl<T, U> BpMap<T, U> {
             _ => panic!("Expected a leaf node."),
         }
     }
+
+    /// Returns the greatest key in the map that is less than or equal to a particular key.
+    /// Returns `None` if such a key does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_floor", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// assert_eq!(map.floor(&0)?, None);
+    /// assert_eq!(map.floor(&2)?, Some(1));
+    /// # fs::remove_file("example_bp_map_floor")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn floor<V>(&mut self, key: &V) -> Result<Option<T>>
+    where
+        T: Borrow<V> + DeserializeOwned,
+        U: DeserializeOwned,
+        V: Ord + ?Sized,
+    {
+        let position = Position::floor(&mut self.pager, key)?;
+        Ok(position.map(|mut position| position.take_entry().key))
+    }
+
+    /// Returns the least key in the map that is greater than or equal to a particular key.
+    /// Returns `None` if such a key does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_ceil", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// assert_eq!(map.ceil(&0)?, Some(1));
+    /// assert_eq!(map.ceil(&2)?, None);
+    /// # fs::remove_file("example_bp_map_ceil")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn ceil<V>(&mut self, key: &V) -> Result<Option<T>>
+    where
+        T: Borrow<V> + DeserializeOwned,
+        U: DeserializeOwned,
+        V: Ord + ?Sized,
+    {
+        let position = Position::ceil(&mut self.pager, key)?;
+        Ok(position.map(|mut position| position.take_entry().key))
+    }
+
+    /// Returns an iterator over the map. The iterator will yield key-value pairs in ascending
+    /// order of keys, or in descending order if it is reversed. Iterating does not modify the
+    /// map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_iter", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut iterator = map.iter()?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.iter()?.rev().map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_file("example_bp_map_iter")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn iter(&mut self) -> Result<BpMapIter<'_, T, U>>
+    where
+        T: DeserializeOwned + Ord,
+        U: DeserializeOwned,
+    {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the entries of the map whose keys are within a range. The
+    /// iterator will yield key-value pairs in ascending order of keys, or in descending order if
+    /// it is reversed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_range", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut iterator = map.range(2..)?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), Some((3, 3)));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.range(..3)?.rev().map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_file("example_bp_map_range")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn range<R>(&mut self, range: R) -> Result<BpMapIter<'_, T, U>>
+    where
+        T: DeserializeOwned + Ord,
+        U: DeserializeOwned,
+        R: RangeBounds<T>,
+    {
+        let front = match range.start_bound() {
+            Bound::Included(key) => Position::ceil(&mut self.pager, key)?,
+            Bound::Excluded(key) => match Position::ceil(&mut self.pager, key)? {
+                Some(position) if position.get_entry().key == *key => {
+                    position.next(&mut self.pager)?
+                }
+                position => position,
+            },
+            Bound::Unbounded => Position::first(&mut self.pager)?,
+        };
+        let back = match range.end_bound() {
+            Bound::Included(key) => Position::floor(&mut self.pager, key)?,
+            Bound::Excluded(key) => match Position::floor(&mut self.pager, key)? {
+                Some(position) if position.get_entry().key == *key => {
+                    position.prev(&mut self.pager)?
+                }
+                position => position,
+            },
+            Bound::Unbounded => Position::last(&mut self.pager)?,
+        };
+
+        Ok(BpMapIter {
+            pager: &mut self.pager,
+            front,
+            back,
+        })
+    }
+
+    /// Returns a cursor over the map that is positioned before the first entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::bp_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::bp_tree::BpMap;
+    ///
+    /// let mut map: BpMap<u32, u64> = BpMap::new("example_bp_map_cursor", 4, 8)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut cursor = map.cursor();
+    /// cursor.seek_to_last()?;
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// assert_eq!(cursor.value(), Some(&2));
+    /// # fs::remove_file("example_bp_map_cursor")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn cursor(&mut self) -> BpMapCursor<'_, T, U>
+    where
+        T: Clone + DeserializeOwned,
+        U: Clone + DeserializeOwned,
+    {
+        BpMapCursor::new(&mut self.pager)
+    }
 }
 
 impl<'a, T, U> IntoIterator for &'a mut BpMap<T, U>
@@ -748,7 +1239,7 @@ where
 /// This iterator traverses the elements of the map in ascending order and yields owned entries.
 pub struct BpMapIterMut<'a, T, U> {
     pager: &'a mut Pager<T, U>,
-    curr_node: LeafNode<T, U>,
+    curr_node: LeafNode<T, Value<U>>,
     curr_index: usize,
 }
 
@@ -781,18 +1272,94 @@ where
         self.curr_index += 1;
         self.curr_node.entries[self.curr_index - 1]
             .take()
-            .map(|entry| Ok((entry.key, entry.value)))
+            .map(|Entry { key, value }| self.pager.read_value(value).map(|value| (key, value)))
+    }
+}
+
+/// An iterator for `BpMap<T, U>`.
+///
+/// This iterator traverses the elements of the map in ascending order, or in descending order if
+/// it is reversed, and yields owned entries without modifying the map.
+pub struct BpMapIter<'a, T, U> {
+    pager: &'a mut Pager<T, U>,
+    front: Option<Position<T, U>>,
+    back: Option<Position<T, U>>,
+}
+
+impl<'a, T, U> BpMapIter<'a, T, U>
+where
+    T: DeserializeOwned + Ord,
+    U: DeserializeOwned,
+{
+    // Returns `true` if the front and back positions have crossed, so every entry between them has
+    // been yielded.
+    fn is_finished(&self) -> bool {
+        match (&self.front, &self.back) {
+            (Some(front), Some(back)) => front.get_entry().key > back.get_entry().key,
+            _ => true,
+        }
+    }
+}
+
+impl<'a, T, U> Iterator for BpMapIter<'a, T, U>
+where
+    T: 'a + DeserializeOwned + Ord,
+    U: 'a + DeserializeOwned,
+{
+    type Item = Result<(T, U)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished() {
+            self.front = None;
+            self.back = None;
+            return None;
+        }
+
+        let mut front = self.front.take().expect("Expected a position.");
+        let Entry { key, value } = front.take_entry();
+        self.front = match front.next(self.pager) {
+            Ok(front) => front,
+            Err(error) => return Some(Err(error)),
+        };
+        Some(self.pager.read_value(value).map(|value| (key, value)))
+    }
+}
+
+impl<'a, T, U> DoubleEndedIterator for BpMapIter<'a, T, U>
+where
+    T: 'a + DeserializeOwned + Ord,
+    U: 'a + DeserializeOwned,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.is_finished() {
+            self.front = None;
+            self.back = None;
+            return None;
+        }
+
+        let mut back = self.back.take().expect("Expected a position.");
+        let Entry { key, value } = back.take_entry();
+        self.back = match back.prev(self.pager) {
+            Ok(back) => back,
+            Err(error) => return Some(Err(error)),
+        };
+        Some(self.pager.read_value(value).map(|value| (key, value)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{BpMap, Result};
+    use crate::bp_tree::{Error, SyncPolicy};
     use std::fs;
+    use std::io::Write;
+    use std::mem;
+    use std::ops::Bound;
     use std::panic;
 
     fn teardown(test_name: &str) {
         fs::remove_file(test_name).ok();
+        fs::remove_file(format!("{}-wal", test_name)).ok();
     }
 
     fn run_test<T>(test: T, test_name: &str)
@@ -873,6 +1440,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_overflow_key() {
+        let test_name = "test_overflow_key";
+        run_test(
+            || {
+                let mut map: BpMap<String, u64> = BpMap::with_degrees(test_name, 16, 8, 3, 3)?;
+                // keys of up to 500 bytes are stored in overflow pages of leaf and internal nodes
+                let get_key = |key: u64| format!("{:03}", key).repeat(key as usize % 170);
+                for key in 0..100 {
+                    assert_eq!(map.insert(get_key(key), key)?, None);
+                }
+                for key in 0..100 {
+                    assert_eq!(map.get(&get_key(key))?, Some(key));
+                }
+                let mut expected: Vec<_> = (0..100).map(|key| (get_key(key), key)).collect();
+                expected.sort();
+                assert_eq!(map.iter()?.collect::<Result<Vec<_>>>()?, expected);
+
+                // removing keys and rewriting their nodes deallocates their overflow pages
+                for key in 0..100 {
+                    assert_eq!(map.remove(&get_key(key))?, Some((get_key(key), key)));
+                }
+                map.sync()?;
+                let file_len = fs::metadata(test_name)?.len();
+
+                for key in 0..100 {
+                    map.insert(get_key(key), key)?;
+                }
+                for key in 0..100 {
+                    map.remove(&get_key(key))?;
+                }
+                map.sync()?;
+                assert_eq!(fs::metadata(test_name)?.len(), file_len);
+                assert!(map.is_empty());
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_overflow_value() {
+        let test_name = "test_overflow_value";
+        run_test(
+            || {
+                let mut map: BpMap<String, Vec<u8>> = BpMap::with_degrees(test_name, 16, 16, 3, 3)?;
+                for key in 0..20u8 {
+                    map.insert(key.to_string(), vec![key; usize::from(key) * 50])?;
+                }
+                for key in 0..20u8 {
+                    assert_eq!(
+                        map.get(&key.to_string())?,
+                        Some(vec![key; usize::from(key) * 50]),
+                    );
+                }
+
+                // replacing and removing values deallocates their overflow pages
+                assert_eq!(
+                    map.insert(19.to_string(), vec![0])?,
+                    Some((19.to_string(), vec![19; 950])),
+                );
+                assert_eq!(map.get(&19.to_string())?, Some(vec![0]));
+                for key in 0..20u8 {
+                    map.remove(&key.to_string())?;
+                }
+                map.sync()?;
+                let file_len = fs::metadata(test_name)?.len();
+
+                for key in 0..20u8 {
+                    map.insert(key.to_string(), vec![key; usize::from(key) * 50])?;
+                }
+                map.sync()?;
+                assert_eq!(fs::metadata(test_name)?.len(), file_len);
+
+                let entries: Vec<_> = map.iter_mut()?.collect::<Result<_>>()?;
+                assert_eq!(entries.len(), 20);
+                for (key, value) in entries {
+                    let key: u8 = key.parse().unwrap();
+                    assert_eq!(value, vec![key; usize::from(key) * 50]);
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
     #[test]
     fn test_get() {
         let test_name = "test_get";
@@ -903,13 +1556,13 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn test_insert_panic() {
-        let test_name = "test_insert_panic";
+    fn test_insert_large_value() {
+        let test_name = "test_insert_large_value";
         run_test(
             || {
                 let mut map: BpMap<u32, Box<[u32]>> = BpMap::new(test_name, 4, 12)?;
                 map.insert(0, Box::new([0, 1]))?;
+                assert_eq!(map.get(&0)?, Some(vec![0, 1].into_boxed_slice()));
                 Ok(())
             },
             test_name,
@@ -1005,4 +1658,518 @@ mod tests {
             test_name,
         );
     }
+
+    #[test]
+    fn test_buffer_pool() {
+        let test_name = "test_buffer_pool";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                map.set_buffer_pool_capacity(16);
+                for key in 0..100 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.sync()?;
+                for key in 0..100 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+
+                // resident pages are read without touching the file
+                map.get(&99)?;
+                let file_len = fs::metadata(test_name)?.len() as usize;
+                fs::OpenOptions::new()
+                    .write(true)
+                    .open(test_name)?
+                    .write_all(&vec![0xFF; file_len])?;
+                assert_eq!(map.get(&99)?, Some(99));
+                assert_eq!(map.max()?, Some(99));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_sync() {
+        let test_name = "test_sync";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                map.set_buffer_pool_capacity(1);
+                for key in 0..100 {
+                    map.insert(key, u64::from(key))?;
+                }
+                for key in (0..100).step_by(2) {
+                    map.remove(&key)?;
+                }
+                map.sync()?;
+
+                let mut map: BpMap<u32, u64> = BpMap::open(test_name)?;
+                assert_eq!(map.len(), 50);
+                for key in 0..100 {
+                    let expected = if key % 2 == 0 {
+                        None
+                    } else {
+                        Some(u64::from(key))
+                    };
+                    assert_eq!(map.get(&key)?, expected);
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        let test_name = "test_recovery";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                for key in 0..100 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.sync()?;
+                for key in (0..100).step_by(2) {
+                    map.remove(&key)?;
+                }
+
+                // leaking the map simulates a crash before the log is checkpointed
+                mem::forget(map);
+                fs::OpenOptions::new()
+                    .append(true)
+                    .open(format!("{}-wal", test_name))?
+                    .write_all(&[0, 0, 0, 0, 0, 0, 1, 0, 0xFF])?;
+
+                let mut map: BpMap<u32, u64> = BpMap::open(test_name)?;
+                assert_eq!(map.len(), 50);
+                for key in 0..100 {
+                    let expected = if key % 2 == 0 {
+                        None
+                    } else {
+                        Some(u64::from(key))
+                    };
+                    assert_eq!(map.get(&key)?, expected);
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_recovery_corruption() {
+        let test_name = "test_recovery_corruption";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                map.set_sync_policy(SyncPolicy::Always);
+                for key in 0..10 {
+                    map.insert(key, u64::from(key))?;
+                }
+
+                // leaking the map simulates a crash before the log is checkpointed
+                mem::forget(map);
+                let wal_name = format!("{}-wal", test_name);
+                let mut buffer = fs::read(&wal_name)?;
+                // corrupts the length of the first record
+                buffer[7] ^= 1;
+                fs::write(&wal_name, &buffer)?;
+
+                match BpMap::<u32, u64>::open(test_name) {
+                    Err(Error::Corruption { offset: 0, .. }) => {}
+                    _ => panic!("Expected corruption."),
+                }
+                assert_eq!(fs::read(&wal_name)?, buffer);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_crash_consistency() {
+        let test_name = "test_crash_consistency";
+        let snapshot_name = "test_crash_consistency_snapshot";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                map.set_buffer_pool_capacity(4);
+                for key in 0..60 {
+                    if key < 40 {
+                        map.insert(key, u64::from(key))?;
+                    } else {
+                        map.remove(&(key - 40))?;
+                    }
+
+                    // copying the files of a live map simulates a crash after the operation
+                    fs::copy(test_name, snapshot_name)?;
+                    fs::copy(
+                        format!("{}-wal", test_name),
+                        format!("{}-wal", snapshot_name),
+                    )?;
+                    let mut snapshot: BpMap<u32, u64> = BpMap::open(snapshot_name)?;
+                    let removed = key.saturating_sub(39);
+                    assert_eq!(snapshot.len(), (key + 1 - 2 * removed) as usize);
+                    for expected_key in 0..40 {
+                        let is_present = removed <= expected_key && expected_key <= key;
+                        assert_eq!(snapshot.contains_key(&expected_key)?, is_present);
+                    }
+                }
+                Ok(())
+            },
+            test_name,
+        );
+        teardown(snapshot_name);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let test_name = "test_rollback";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                for key in 0..50 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.sync()?;
+                for key in 50..100 {
+                    map.insert(key, u64::from(key))?;
+                }
+
+                // rolling back uncommitted operations simulates operations that failed partway
+                for key in 100..120 {
+                    map.insert_entry(key, u64::from(key))?;
+                    map.pager.rollback()?;
+                }
+                for key in (0..100).step_by(3) {
+                    map.remove_entry(&key)?;
+                    map.pager.rollback()?;
+                }
+                map.pager.clear()?;
+                map.pager.rollback()?;
+                map.insert(120, 120)?;
+
+                for reopen in &[false, true] {
+                    if *reopen {
+                        map = BpMap::open(test_name)?;
+                    }
+                    assert_eq!(map.len(), 101);
+                    for key in 0..121 {
+                        let expected = if key < 100 || key == 120 {
+                            Some(u64::from(key))
+                        } else {
+                            None
+                        };
+                        assert_eq!(map.get(&key)?, expected);
+                    }
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_floor_ceil() {
+        let test_name = "test_floor_ceil";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                assert_eq!(map.floor(&0)?, None);
+                assert_eq!(map.ceil(&0)?, None);
+                for key in (0..100).step_by(2) {
+                    map.insert(key, u64::from(key))?;
+                }
+                for key in 0..100 {
+                    assert_eq!(map.floor(&key)?, Some(key - key % 2));
+                    let expected = if key == 99 { None } else { Some(key + key % 2) };
+                    assert_eq!(map.ceil(&key)?, expected);
+                }
+                assert_eq!(map.floor(&100)?, Some(98));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_iter() {
+        let test_name = "test_iter";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                assert!(map.iter()?.next().is_none());
+                for key in 0..100 {
+                    map.insert(key, u64::from(key))?;
+                }
+                let expected: Vec<_> = (0..100).map(|key| (key, u64::from(key))).collect();
+                assert_eq!(map.iter()?.collect::<Result<Vec<_>>>()?, expected);
+
+                let mut reversed = map.iter()?.rev().collect::<Result<Vec<_>>>()?;
+                reversed.reverse();
+                assert_eq!(reversed, expected);
+
+                // the front and back of the iterator meet without yielding an entry twice
+                let mut iter = map.iter()?;
+                let mut front = Vec::new();
+                let mut back = Vec::new();
+                while let Some(entry) = iter.next() {
+                    front.push(entry?);
+                    match iter.next_back() {
+                        Some(entry) => back.push(entry?),
+                        None => break,
+                    }
+                }
+                back.reverse();
+                front.extend(back);
+                assert_eq!(front, expected);
+                assert_eq!(map.len(), 100);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let test_name = "test_range";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                for key in (0..100).step_by(2) {
+                    map.insert(key, u64::from(key))?;
+                }
+
+                fn keys<I>(iter: I) -> Result<Vec<u32>>
+                where
+                    I: Iterator<Item = Result<(u32, u64)>>,
+                {
+                    iter.map(|entry| entry.map(|entry| entry.0)).collect()
+                }
+                assert_eq!(keys(map.range(10..16)?)?, vec![10, 12, 14]);
+                assert_eq!(keys(map.range(10..=16)?)?, vec![10, 12, 14, 16]);
+                assert_eq!(keys(map.range(11..17)?)?, vec![12, 14, 16]);
+                assert_eq!(keys(map.range(95..)?)?, vec![96, 98]);
+                assert_eq!(keys(map.range(..3)?)?, vec![0, 2]);
+                assert_eq!(keys(map.range(..3)?.rev())?, vec![2, 0]);
+                assert_eq!(keys(map.range(11..12)?)?, Vec::<u32>::new());
+                assert_eq!(keys(map.range(100..)?)?, Vec::<u32>::new());
+                assert_eq!(
+                    keys(map.range((Bound::Excluded(10), Bound::Excluded(16)))?)?,
+                    vec![12, 14],
+                );
+                assert_eq!(
+                    keys(map.range((Bound::Excluded(10), Bound::Excluded(12)))?)?,
+                    Vec::<u32>::new(),
+                );
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let test_name = "test_cursor";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::with_degrees(test_name, 4, 8, 3, 3)?;
+                {
+                    let mut cursor = map.cursor();
+                    cursor.seek_to_first()?;
+                    assert_eq!(cursor.key(), None);
+                    cursor.seek_to_last()?;
+                    assert_eq!(cursor.key(), None);
+                }
+                for key in (0..100).step_by(2) {
+                    map.insert(key, u64::from(key))?;
+                }
+
+                let mut cursor = map.cursor();
+                cursor.next()?;
+                assert_eq!(cursor.key(), None);
+
+                cursor.seek(&51)?;
+                for key in (52..100).step_by(2) {
+                    assert_eq!(cursor.key(), Some(&key));
+                    assert_eq!(cursor.value(), Some(&u64::from(key)));
+                    cursor.next()?;
+                }
+                assert_eq!(cursor.key(), None);
+
+                cursor.seek_to_last()?;
+                for key in (0..100).step_by(2).rev() {
+                    assert_eq!(cursor.key(), Some(&key));
+                    cursor.prev()?;
+                }
+                assert_eq!(cursor.key(), None);
+
+                // moving back and forth across leaf nodes
+                cursor.seek(&20)?;
+                for _ in 0..10 {
+                    cursor.next()?;
+                }
+                for _ in 0..15 {
+                    cursor.prev()?;
+                }
+                assert_eq!(cursor.key(), Some(&10));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        let test_name = "test_bulk_load";
+        run_test(
+            || {
+                // large keys keep the degrees small, so the tree has several internal levels
+                let entries = (0..2000).map(|key| (format!("{:05}", key), key));
+                let map: BpMap<String, u64> = BpMap::bulk_load(test_name, 200, 8, entries, 0.5)?;
+                assert_eq!(map.len(), 2000);
+                drop(map);
+
+                let mut map: BpMap<String, u64> = BpMap::open(test_name)?;
+                let expected: Vec<_> = (0..2000).map(|key| (format!("{:05}", key), key)).collect();
+                assert_eq!(map.iter()?.collect::<Result<Vec<_>>>()?, expected);
+                assert_eq!(map.iter_mut()?.collect::<Result<Vec<_>>>()?, expected);
+                let mut reversed = map.iter()?.rev().collect::<Result<Vec<_>>>()?;
+                reversed.reverse();
+                assert_eq!(reversed, expected);
+
+                // the loaded tree can be modified like any other tree
+                for key in (0..2000).step_by(2) {
+                    assert_eq!(
+                        map.remove(&format!("{:05}", key))?,
+                        Some((format!("{:05}", key), key)),
+                    );
+                }
+                map.insert(String::from("00000"), 0)?;
+                for key in (1..2000).step_by(2) {
+                    assert_eq!(
+                        map.remove(&format!("{:05}", key))?,
+                        Some((format!("{:05}", key), key)),
+                    );
+                }
+                assert_eq!(map.len(), 1);
+                assert_eq!(map.min()?, Some(String::from("00000")));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_bulk_load_fill_factor() {
+        let test_name = "test_bulk_load_fill_factor";
+        run_test(
+            || {
+                let entries = (0..10_000).map(|key| (key, u64::from(key)));
+                let map: BpMap<u32, u64> = BpMap::bulk_load(test_name, 4, 8, entries, 1.0)?;
+                drop(map);
+                let full_len = fs::metadata(test_name)?.len();
+
+                let entries = (0..10_000).map(|key| (key, u64::from(key)));
+                let mut map: BpMap<u32, u64> = BpMap::bulk_load(test_name, 4, 8, entries, 0.5)?;
+                drop(map);
+                assert!(fs::metadata(test_name)?.len() > full_len * 3 / 2);
+
+                map = BpMap::open(test_name)?;
+                assert_eq!(map.len(), 10_000);
+                for key in 0..10_000 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_bulk_load_overflow_value() {
+        let test_name = "test_bulk_load_overflow_value";
+        run_test(
+            || {
+                // keys with more than two repetitions are stored in overflow pages
+                let get_key = |key: u8| format!("{:03}", key).repeat(usize::from(key) % 10 + 1);
+                let expected: Vec<_> = (0..100u8)
+                    .map(|key| (get_key(key), vec![key; usize::from(key) * 20]))
+                    .collect();
+                let mut map: BpMap<String, Vec<u8>> =
+                    BpMap::bulk_load(test_name, 16, 16, expected.clone(), 1.0)?;
+                assert_eq!(map.iter_mut()?.collect::<Result<Vec<_>>>()?, expected);
+                assert_eq!(
+                    map.remove(&get_key(99))?,
+                    Some((get_key(99), vec![99; 1980]))
+                );
+                assert_eq!(map.get(&get_key(98))?, Some(vec![98; 1960]));
+                map.insert(get_key(99), vec![99])?;
+                assert_eq!(map.get(&get_key(99))?, Some(vec![99]));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_bulk_load_empty() {
+        let test_name = "test_bulk_load_empty";
+        run_test(
+            || {
+                let mut map: BpMap<u32, u64> = BpMap::bulk_load(test_name, 4, 8, Vec::new(), 1.0)?;
+                assert!(map.is_empty());
+                map.insert(1, 1)?;
+                assert_eq!(map.get(&1)?, Some(1));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_bulk_load_unsorted() {
+        let test_name = "test_bulk_load_unsorted";
+        run_test(
+            || {
+                let entries = vec![(1, 1), (2, 2), (2, 3)];
+                match BpMap::<u32, u64>::bulk_load(test_name, 4, 8, entries, 1.0) {
+                    Err(Error::UnsortedKey) => {}
+                    _ => panic!("Expected an unsorted key."),
+                }
+                // the error is detected after some leaf nodes were written
+                let entries = (0..1000).chain(Some(500)).map(|key| (key, u64::from(key)));
+                match BpMap::<u32, u64>::bulk_load(test_name, 4, 8, entries, 1.0) {
+                    Err(Error::UnsortedKey) => {}
+                    _ => panic!("Expected an unsorted key."),
+                }
+
+                // the partially written map is removed
+                assert!(fs::metadata(test_name).is_err());
+                assert!(fs::metadata(format!("{}-wal", test_name)).is_err());
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_bulk_load_invalid_fill_factor() {
+        let test_name = "test_bulk_load_invalid_fill_factor";
+        run_test(
+            || {
+                for fill_factor in &[0.0, -0.5, 1.5] {
+                    match BpMap::<u32, u64>::bulk_load(test_name, 4, 8, vec![(1, 1)], *fill_factor)
+                    {
+                        Err(Error::InvalidFillFactor(value)) => assert_eq!(value, *fill_factor),
+                        _ => panic!("Expected an invalid fill factor."),
+                    }
+                }
+                assert!(fs::metadata(test_name).is_err());
+                Ok(())
+            },
+            test_name,
+        );
+    }
 }
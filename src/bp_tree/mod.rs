@@ -12,9 +12,14 @@ macro_rules! init_array(
     )
 );
 
+mod buffer_pool;
+mod cursor;
 mod map;
 mod node;
 mod pager;
+mod wal;
 
-pub use self::map::BpMap;
+pub use self::cursor::BpMapCursor;
+pub use self::map::{BpMap, BpMapIter, BpMapIterMut};
 pub use self::pager::{Error, Result};
+pub use crate::wal::SyncPolicy;
@@ -1,15 +1,44 @@
 use crate::entry::Entry;
-use serde_derive::{Deserialize, Serialize};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
 use std::borrow::Borrow;
 use std::cmp::{self, Ordering};
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::mem;
 
 const U64_SIZE: u64 = mem::size_of::<u64>() as u64;
-const OPT_U64_SIZE: u64 = mem::size_of::<Option<u64>>() as u64;
 pub const BLOCK_SIZE: u64 = 4096;
 
-#[derive(Serialize, Deserialize)]
+// The kind of a page, which is stored in its first byte.
+const INTERNAL_PAGE: u8 = 0;
+const LEAF_PAGE: u8 = 1;
+const FREE_PAGE: u8 = 2;
+const OVERFLOW_PAGE: u8 = 3;
+
+// Every page starts with a header that has
+// 1) the kind of the page (1 byte)
+// 2) whether any key of the page is stored in overflow pages (1 byte)
+// 3) the number of cells of a node, or the length of the data of an overflow page (u16, 2 bytes)
+// 4) the page that is linked from the page, which is the first child of an internal node, the
+//    next leaf node, the next free page, or the next overflow page (u64, 8 bytes)
+const PAGE_HEADER_SIZE: u64 = 12;
+// The header of a node is followed by the offset of each of its cells (u16, 2 bytes), and the
+// cells are packed at the end of the page.
+const SLOT_SIZE: u64 = 2;
+const NO_PAGE: u64 = u64::MAX;
+
+// A key or value in a cell is a flag followed by either the serialized item, or the first page of
+// the overflow chain that holds the serialized item (u64, 8 bytes).
+const INLINE: u8 = 0;
+const OVERFLOW: u8 = 1;
+
+#[inline]
+fn get_stored_size(size: u64) -> u64 {
+    1 + cmp::max(size, U64_SIZE)
+}
+
 pub struct InternalNode<T, U> {
     pub len: usize,
     pub keys: Box<[Option<T>]>,
@@ -18,29 +47,21 @@ pub struct InternalNode<T, U> {
 }
 
 impl<T, U> InternalNode<T, U> {
-    // 1) a usize is encoded as u64 (8 bytes)
-    // 2) a boxed slice is encoded as a tuple of u64 (8 bytes) and the items
-    #[inline]
-    fn get_constant_size() -> u64 {
-        U64_SIZE * 4 + mem::size_of::<PhantomData<U>>() as u64
-    }
-
+    // a cell holds a key and the pointer to its right (u64, 8 bytes)
     #[inline]
-    fn get_payload_size(key_size: u64) -> u64 {
-        let option_size = mem::size_of::<Option<T>>() as u64;
-        let entry_size = mem::size_of::<T>() as u64;
-        U64_SIZE + key_size + option_size - entry_size
+    fn get_cell_size(key_size: u64) -> u64 {
+        SLOT_SIZE + get_stored_size(key_size) + U64_SIZE
     }
 
     #[inline]
     pub fn get_degree(key_size: u64) -> usize {
-        let payload_capacity = BLOCK_SIZE - Self::get_constant_size();
-        (payload_capacity / Self::get_payload_size(key_size)) as usize
+        let cell_capacity = BLOCK_SIZE - PAGE_HEADER_SIZE;
+        (cell_capacity / Self::get_cell_size(key_size)) as usize
     }
 
     #[inline]
     pub fn get_max_size(degree: usize, key_size: u64) -> u64 {
-        Self::get_constant_size() + degree as u64 * Self::get_payload_size(key_size)
+        PAGE_HEADER_SIZE + degree as u64 * Self::get_cell_size(key_size)
     }
 
     pub fn new(degree: usize) -> Self {
@@ -175,7 +196,6 @@ impl<T, U> InternalNode<T, U> {
     }
 }
 
-#[derive(Serialize, Deserialize)]
 pub struct LeafNode<T, U> {
     pub len: usize,
     pub entries: Box<[Option<Entry<T, U>>]>,
@@ -191,29 +211,21 @@ pub enum InsertCases<T, U> {
 }
 
 impl<T, U> LeafNode<T, U> {
-    // 1) a usize is encoded as u64 (8 bytes)
-    // 2) a boxed slice is encoded as a tuple of u64 (8 bytes) and the items
+    // a cell holds a key and its value
     #[inline]
-    fn get_constant_size() -> u64 {
-        U64_SIZE * 2 + OPT_U64_SIZE
-    }
-
-    #[inline]
-    fn get_payload_size(key_size: u64, value_size: u64) -> u64 {
-        let option_size = mem::size_of::<Option<Entry<T, U>>>() as u64;
-        let entry_size = mem::size_of::<Entry<T, U>>() as u64;
-        key_size + value_size + option_size - entry_size
+    fn get_cell_size(key_size: u64, value_size: u64) -> u64 {
+        SLOT_SIZE + get_stored_size(key_size) + get_stored_size(value_size)
     }
 
     #[inline]
     pub fn get_degree(key_size: u64, value_size: u64) -> usize {
-        let payload_capacity = BLOCK_SIZE - Self::get_constant_size();
-        (payload_capacity / Self::get_payload_size(key_size, value_size)) as usize
+        let cell_capacity = BLOCK_SIZE - PAGE_HEADER_SIZE;
+        (cell_capacity / Self::get_cell_size(key_size, value_size)) as usize
     }
 
     #[inline]
     pub fn get_max_size(degree: usize, key_size: u64, value_size: u64) -> u64 {
-        Self::get_constant_size() + degree as u64 * Self::get_payload_size(key_size, value_size)
+        PAGE_HEADER_SIZE + degree as u64 * Self::get_cell_size(key_size, value_size)
     }
 
     pub fn new(degree: usize) -> Self {
@@ -345,6 +357,44 @@ impl<T, U> LeafNode<T, U> {
         None
     }
 
+    // Returns the number of leading entries whose keys satisfy `predicate`, which has to hold for
+    // a prefix of the entries.
+    fn partition_point<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut lo = 0;
+        let mut hi = self.len;
+        while lo < hi {
+            let mid = lo + ((hi - lo) >> 1);
+            let entry = self.entries[mid].as_ref().expect("Expected some entry.");
+            if predicate(&entry.key) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Returns the index of the first entry whose key is greater than or equal to `search_key`.
+    pub fn lower_bound<V>(&self, search_key: &V) -> usize
+    where
+        T: Borrow<V>,
+        V: Ord + ?Sized,
+    {
+        self.partition_point(|key| key.borrow() < search_key)
+    }
+
+    /// Returns the index of the first entry whose key is greater than `search_key`.
+    pub fn upper_bound<V>(&self, search_key: &V) -> usize
+    where
+        T: Borrow<V>,
+        V: Ord + ?Sized,
+    {
+        self.partition_point(|key| key.borrow() <= search_key)
+    }
+
     pub fn merge(&mut self, node: &mut LeafNode<T, U>) {
         assert!(self.len + node.len <= self.entries.len());
         self.next_leaf = node.next_leaf.take();
@@ -356,11 +406,70 @@ impl<T, U> LeafNode<T, U> {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A key or value stored in a cell of a page. Keys and values that are larger than the maximum key
+/// or value size of the map are stored in a chain of overflow pages, and the cell only stores the
+/// first page of the chain.
+#[derive(Clone)]
+pub enum Value<U> {
+    Inline(U),
+    Overflow(usize),
+}
+
+impl<U> Value<U> {
+    #[inline]
+    fn as_ref(&self) -> Value<&U> {
+        match self {
+            Value::Inline(ref item) => Value::Inline(item),
+            Value::Overflow(page) => Value::Overflow(*page),
+        }
+    }
+}
+
+fn write_item<W, V>(writer: &mut W, item: Value<&V>) -> bincode::Result<()>
+where
+    W: Write,
+    V: Serialize,
+{
+    match item {
+        Value::Inline(item) => {
+            writer.write_u8(INLINE)?;
+            bincode::serialize_into(writer, item)
+        }
+        Value::Overflow(page) => {
+            writer.write_u8(OVERFLOW)?;
+            writer.write_u64::<BigEndian>(page as u64)?;
+            Ok(())
+        }
+    }
+}
+
+fn read_item<R, V>(reader: &mut R) -> bincode::Result<Value<V>>
+where
+    R: Read,
+    V: DeserializeOwned,
+{
+    match reader.read_u8()? {
+        INLINE => Ok(Value::Inline(bincode::deserialize_from(reader)?)),
+        OVERFLOW => Ok(Value::Overflow(reader.read_u64::<BigEndian>()? as usize)),
+        flag => Err(Box::new(bincode::ErrorKind::Custom(format!(
+            "Invalid cell flag {}.",
+            flag
+        )))),
+    }
+}
+
+/// Returns `true` if any key of the node that is stored in a page is stored in overflow pages.
+#[inline]
+pub fn has_overflow_keys(page: &[u8]) -> bool {
+    page[1] != 0
+}
+
 pub enum Node<T, U> {
     Internal(InternalNode<T, U>),
     Leaf(LeafNode<T, U>),
     Free(Option<usize>),
+    // a part of a serialized key or value and the next page of its overflow chain
+    Overflow(Vec<u8>, Option<usize>),
 }
 
 impl<T, U> Node<T, U> {
@@ -376,27 +485,252 @@ impl<T, U> Node<T, U> {
             InternalNode::<T, U>::get_max_size(internal_degree, key_size),
         )
     }
+
+    #[inline]
+    pub fn get_overflow_capacity(node_size: u64) -> u64 {
+        node_size - PAGE_HEADER_SIZE
+    }
+
+    /// Returns the keys of the node in ascending order.
+    pub fn keys(&self) -> Vec<&T> {
+        match self {
+            Node::Internal(ref node) => node.keys[..node.len]
+                .iter()
+                .map(|key| key.as_ref().expect("Expected some key."))
+                .collect(),
+            Node::Leaf(ref node) => node.entries[..node.len]
+                .iter()
+                .map(|entry| &entry.as_ref().expect("Expected some entry.").key)
+                .collect(),
+            Node::Free(_) | Node::Overflow(..) => Vec::new(),
+        }
+    }
+
+    /// Replaces each key of the node with the result of `f`, or returns the first error of `f`.
+    pub fn try_map_keys<V, E, F>(self, mut f: F) -> Result<Node<V, U>, E>
+    where
+        F: FnMut(T) -> Result<V, E>,
+    {
+        match self {
+            Node::Internal(node) => {
+                let keys = node
+                    .keys
+                    .into_vec()
+                    .into_iter()
+                    .map(|key| key.map(&mut f).transpose())
+                    .collect::<Result<Vec<_>, E>>()?;
+                Ok(Node::Internal(InternalNode {
+                    len: node.len,
+                    keys: keys.into_boxed_slice(),
+                    pointers: node.pointers,
+                    _marker: PhantomData,
+                }))
+            }
+            Node::Leaf(node) => {
+                let entries = node
+                    .entries
+                    .into_vec()
+                    .into_iter()
+                    .map(|entry| {
+                        entry
+                            .map(|Entry { key, value }| f(key).map(|key| Entry { key, value }))
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>, E>>()?;
+                Ok(Node::Leaf(LeafNode {
+                    len: node.len,
+                    entries: entries.into_boxed_slice(),
+                    next_leaf: node.next_leaf,
+                }))
+            }
+            Node::Free(next_page) => Ok(Node::Free(next_page)),
+            Node::Overflow(data, next_page) => Ok(Node::Overflow(data, next_page)),
+        }
+    }
+}
+
+impl<T, U> Node<T, Value<U>> {
+    /// Encodes the node as a slotted page of `page_size` bytes. `key_pages` has the first page of
+    /// the overflow chain of each key that is not stored in the page, in the order of the keys.
+    pub fn to_page(&self, key_pages: &[Option<usize>], page_size: u64) -> bincode::Result<Vec<u8>>
+    where
+        T: Serialize,
+        U: Serialize,
+    {
+        let stored_key = |index: usize, key| match key_pages[index] {
+            Some(page) => Value::Overflow(page),
+            None => Value::Inline(key),
+        };
+
+        let mut page = vec![0; page_size as usize];
+        let mut cells = Vec::new();
+        let (kind, len, link) = match self {
+            Node::Internal(ref node) => {
+                for (index, key) in self.keys().into_iter().enumerate() {
+                    let mut cell = Vec::new();
+                    write_item(&mut cell, stored_key(index, key))?;
+                    cell.write_u64::<BigEndian>(node.pointers[index + 1] as u64)?;
+                    cells.push(cell);
+                }
+                (INTERNAL_PAGE, node.len, Some(node.pointers[0]))
+            }
+            Node::Leaf(ref node) => {
+                for (index, entry) in node.entries[..node.len].iter().enumerate() {
+                    let entry = entry.as_ref().expect("Expected some entry.");
+                    let mut cell = Vec::new();
+                    write_item(&mut cell, stored_key(index, &entry.key))?;
+                    write_item(&mut cell, entry.value.as_ref())?;
+                    cells.push(cell);
+                }
+                (LEAF_PAGE, node.len, node.next_leaf)
+            }
+            Node::Free(next_page) => (FREE_PAGE, 0, *next_page),
+            Node::Overflow(ref data, next_page) => {
+                let start = PAGE_HEADER_SIZE as usize;
+                page[start..start + data.len()].copy_from_slice(data);
+                (OVERFLOW_PAGE, data.len(), *next_page)
+            }
+        };
+
+        let mut header = &mut page[..PAGE_HEADER_SIZE as usize];
+        header.write_u8(kind)?;
+        header.write_u8(key_pages.iter().any(Option::is_some) as u8)?;
+        header.write_u16::<BigEndian>(len as u16)?;
+        header.write_u64::<BigEndian>(link.map_or(NO_PAGE, |page| page as u64))?;
+
+        let mut cell_offset = page.len();
+        for (index, cell) in cells.iter().enumerate() {
+            cell_offset -= cell.len();
+            page[cell_offset..cell_offset + cell.len()].copy_from_slice(cell);
+            let slot_offset = (PAGE_HEADER_SIZE + index as u64 * SLOT_SIZE) as usize;
+            (&mut page[slot_offset..]).write_u16::<BigEndian>(cell_offset as u16)?;
+        }
+        assert!(PAGE_HEADER_SIZE + cells.len() as u64 * SLOT_SIZE <= cell_offset as u64);
+        Ok(page)
+    }
+}
+
+impl<T, U> Node<Value<T>, Value<U>> {
+    /// Decodes a node from a slotted page. The keys of the node are not resolved, so keys that are
+    /// stored in overflow pages are returned as the first pages of their chains.
+    pub fn from_page(
+        page: &[u8],
+        leaf_degree: usize,
+        internal_degree: usize,
+    ) -> bincode::Result<Self>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+    {
+        let get_cell = |index: usize| -> bincode::Result<&[u8]> {
+            let slot_offset = (PAGE_HEADER_SIZE + index as u64 * SLOT_SIZE) as usize;
+            let cell_offset = (&page[slot_offset..]).read_u16::<BigEndian>()?;
+            Ok(&page[cell_offset as usize..])
+        };
+
+        let mut header = &page[..PAGE_HEADER_SIZE as usize];
+        let kind = header.read_u8()?;
+        header.read_u8()?;
+        let len = header.read_u16::<BigEndian>()? as usize;
+        let link = match header.read_u64::<BigEndian>()? {
+            NO_PAGE => None,
+            page => Some(page as usize),
+        };
+
+        match kind {
+            INTERNAL_PAGE => {
+                let mut node = InternalNode::new(internal_degree);
+                node.len = len;
+                node.pointers[0] = link.expect("Expected a child.");
+                for index in 0..len {
+                    let mut cell = get_cell(index)?;
+                    node.keys[index] = Some(read_item(&mut cell)?);
+                    node.pointers[index + 1] = cell.read_u64::<BigEndian>()? as usize;
+                }
+                Ok(Node::Internal(node))
+            }
+            LEAF_PAGE => {
+                let mut node = LeafNode::new(leaf_degree);
+                node.len = len;
+                node.next_leaf = link;
+                for index in 0..len {
+                    let mut cell = get_cell(index)?;
+                    let key = read_item(&mut cell)?;
+                    let value = read_item(&mut cell)?;
+                    node.entries[index] = Some(Entry { key, value });
+                }
+                Ok(Node::Leaf(node))
+            }
+            FREE_PAGE => Ok(Node::Free(link)),
+            OVERFLOW_PAGE => {
+                let start = PAGE_HEADER_SIZE as usize;
+                Ok(Node::Overflow(page[start..start + len].to_vec(), link))
+            }
+            kind => Err(Box::new(bincode::ErrorKind::Custom(format!(
+                "Invalid page kind {}.",
+                kind
+            )))),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{InsertCases, InternalNode, LeafNode, Node};
+    use super::{
+        get_stored_size, has_overflow_keys, InsertCases, InternalNode, LeafNode, Node, Value,
+    };
     use crate::entry::Entry;
     use std::marker::PhantomData;
 
     #[test]
     fn test_node_get_max_size() {
-        assert_eq!(Node::<u32, u64>::get_max_size(4, 8, 1, 1), 52);
+        assert_eq!(Node::<u32, u64>::get_max_size(4, 8, 1, 1), 32);
+    }
+
+    #[test]
+    fn test_node_get_overflow_capacity() {
+        assert_eq!(Node::<u32, u64>::get_overflow_capacity(32), 20);
+    }
+
+    #[test]
+    fn test_get_stored_size() {
+        // an item that is smaller than a page number still has room for one
+        assert_eq!(get_stored_size(4), 9);
+        assert_eq!(get_stored_size(16), 17);
     }
 
     #[test]
     fn test_internal_node_degree() {
-        assert_eq!(InternalNode::<u32, u64>::get_degree(4), 254);
+        assert_eq!(InternalNode::<u32, u64>::get_degree(4), 214);
     }
 
     #[test]
     fn test_internal_node_get_max_size() {
-        assert_eq!(InternalNode::<u32, u64>::get_max_size(1, 4), 48);
+        assert_eq!(InternalNode::<u32, u64>::get_max_size(1, 4), 31);
+    }
+
+    #[test]
+    fn test_internal_node_page() {
+        let node = Node::<String, Value<u64>>::Internal(InternalNode {
+            len: 2,
+            keys: Box::new([Some(String::from("a")), Some(String::from("bb")), None]),
+            pointers: Box::new([3, 1, 2, 0]),
+            _marker: PhantomData,
+        });
+        let page = node.to_page(&[None, Some(7)], 128).unwrap();
+        assert_eq!(page.len(), 128);
+        assert!(has_overflow_keys(&page));
+
+        match Node::<Value<String>, Value<u64>>::from_page(&page, 3, 3).unwrap() {
+            Node::Internal(node) => {
+                assert_eq!(node.len, 2);
+                assert!(matches!(node.keys[0], Some(Value::Inline(ref key)) if key == "a"));
+                assert!(matches!(node.keys[1], Some(Value::Overflow(7))));
+                assert!(node.keys[2].is_none());
+                assert_eq!(*node.pointers, [3, 1, 2, 0]);
+            }
+            _ => panic!("Expected internal node."),
+        }
     }
 
     #[test]
@@ -569,12 +903,102 @@ mod tests {
 
     #[test]
     fn test_leaf_node_degree() {
-        assert_eq!(LeafNode::<u32, u64>::get_degree(4, 8), 203);
+        assert_eq!(LeafNode::<u32, u64>::get_degree(4, 8), 204);
     }
 
     #[test]
     fn test_leaf_node_get_max_size() {
-        assert_eq!(LeafNode::<u32, u64>::get_max_size(1, 4, 8), 52);
+        assert_eq!(LeafNode::<u32, u64>::get_max_size(1, 4, 8), 32);
+    }
+
+    #[test]
+    fn test_leaf_node_page() {
+        let node = Node::<String, Value<Vec<u8>>>::Leaf(LeafNode {
+            len: 2,
+            entries: Box::new([
+                Some(Entry {
+                    key: String::from("a"),
+                    value: Value::Overflow(4),
+                }),
+                Some(Entry {
+                    key: String::from("b"),
+                    value: Value::Inline(vec![1, 2]),
+                }),
+                None,
+            ]),
+            next_leaf: Some(5),
+        });
+        let page = node.to_page(&[Some(6), None], 128).unwrap();
+        assert!(has_overflow_keys(&page));
+
+        match Node::<Value<String>, Value<Vec<u8>>>::from_page(&page, 3, 3).unwrap() {
+            Node::Leaf(node) => {
+                assert_eq!(node.len, 2);
+                let entry = node.entries[0].as_ref().unwrap();
+                assert!(matches!(entry.key, Value::Overflow(6)));
+                assert!(matches!(entry.value, Value::Overflow(4)));
+                let entry = node.entries[1].as_ref().unwrap();
+                assert!(matches!(entry.key, Value::Inline(ref key) if key == "b"));
+                assert!(matches!(entry.value, Value::Inline(ref value) if *value == [1, 2]));
+                assert!(node.entries[2].is_none());
+                assert_eq!(node.next_leaf, Some(5));
+            }
+            _ => panic!("Expected leaf node."),
+        }
+    }
+
+    #[test]
+    fn test_leaf_node_page_full() {
+        // a full leaf node whose keys and values have the maximum sizes fits in its page
+        let key_size = bincode::serialized_size(&"k".repeat(16)).unwrap();
+        let value_size = bincode::serialized_size(&"v".repeat(32)).unwrap();
+        let degree = LeafNode::<String, String>::get_degree(key_size, value_size);
+        let mut node = LeafNode::new(degree);
+        for index in 0..degree {
+            node.entries[index] = Some(Entry {
+                key: format!("{:016}", index),
+                value: Value::Inline("v".repeat(32)),
+            });
+        }
+        node.len = degree;
+        let page_size = LeafNode::<String, String>::get_max_size(degree, key_size, value_size);
+        let page = Node::Leaf(node)
+            .to_page(&vec![None; degree], page_size)
+            .unwrap();
+        assert!(!has_overflow_keys(&page));
+
+        match Node::<Value<String>, Value<String>>::from_page(&page, degree, 1).unwrap() {
+            Node::Leaf(node) => {
+                assert_eq!(node.len, degree);
+                let entry = node.entries[degree - 1].as_ref().unwrap();
+                assert!(
+                    matches!(entry.key, Value::Inline(ref key) if *key == format!("{:016}", degree - 1))
+                );
+            }
+            _ => panic!("Expected leaf node."),
+        }
+    }
+
+    #[test]
+    fn test_free_and_overflow_page() {
+        let page = Node::<u32, Value<u64>>::Free(Some(3))
+            .to_page(&[], 32)
+            .unwrap();
+        assert!(matches!(
+            Node::<Value<u32>, Value<u64>>::from_page(&page, 1, 1).unwrap(),
+            Node::Free(Some(3))
+        ));
+
+        let page = Node::<u32, Value<u64>>::Overflow(vec![1, 2, 3], None)
+            .to_page(&[], 32)
+            .unwrap();
+        match Node::<Value<u32>, Value<u64>>::from_page(&page, 1, 1).unwrap() {
+            Node::Overflow(data, next_page) => {
+                assert_eq!(data, [1, 2, 3]);
+                assert_eq!(next_page, None);
+            }
+            _ => panic!("Expected overflow node."),
+        }
     }
 
     #[test]
@@ -744,6 +1168,30 @@ mod tests {
         assert_eq!(n.search(&6), None);
     }
 
+    #[test]
+    fn test_leaf_node_bounds() {
+        let n = LeafNode::<u32, u64> {
+            len: 3,
+            entries: Box::new([
+                Some(Entry { key: 1, value: 1 }),
+                Some(Entry { key: 3, value: 3 }),
+                Some(Entry { key: 5, value: 5 }),
+                None,
+            ]),
+            next_leaf: None,
+        };
+
+        assert_eq!(n.lower_bound(&0), 0);
+        assert_eq!(n.lower_bound(&1), 0);
+        assert_eq!(n.lower_bound(&2), 1);
+        assert_eq!(n.lower_bound(&5), 2);
+        assert_eq!(n.lower_bound(&6), 3);
+        assert_eq!(n.upper_bound(&0), 0);
+        assert_eq!(n.upper_bound(&1), 1);
+        assert_eq!(n.upper_bound(&4), 2);
+        assert_eq!(n.upper_bound(&5), 3);
+    }
+
     #[test]
     fn test_leaf_node_remove() {
         let mut n = LeafNode::<u32, u64> {
@@ -1,16 +1,20 @@
-use crate::bp_tree::node::{LeafNode, Node};
+use crate::bp_tree::buffer_pool::{BufferPool, DirtyPage, DEFAULT_BUFFER_POOL_CAPACITY};
+use crate::bp_tree::node::{self, LeafNode, Node, Value};
+use crate::bp_tree::wal::WriteAheadLog;
+use crate::bp_tree::SyncPolicy;
+use crate::entry::Entry;
 use bincode::{self, deserialize, serialize, serialized_size};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_derive::{Deserialize, Serialize};
-use std::borrow::Borrow;
+use std::collections::BTreeMap;
 use std::error;
 use std::fmt;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
 use std::mem;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::result;
 
 /// Convenience `Error` enum for `bp_tree`.
@@ -20,6 +24,17 @@ pub enum Error {
     IOError(io::Error),
     /// A serialization or deserialization error.
     SerdeError(bincode::Error),
+    /// A key that is not greater than the previous key was passed to `BpMap::bulk_load`.
+    UnsortedKey,
+    /// A fill factor that is not in the range `(0, 1]` was passed to `BpMap::bulk_load`.
+    InvalidFillFactor(f64),
+    /// A corrupted record in the middle of the write-ahead log.
+    Corruption {
+        /// The path of the corrupted file.
+        path: PathBuf,
+        /// The offset of the corrupted record in the file.
+        offset: u64,
+    },
 }
 
 impl From<io::Error> for Error {
@@ -39,6 +54,7 @@ impl error::Error for Error {
         match self {
             Error::IOError(ref error) => error.source(),
             Error::SerdeError(ref error) => error.source(),
+            Error::UnsortedKey | Error::InvalidFillFactor(_) | Error::Corruption { .. } => None,
         }
     }
 }
@@ -48,6 +64,13 @@ impl fmt::Display for Error {
         match self {
             Error::IOError(ref error) => write!(f, "{}", error),
             Error::SerdeError(ref error) => write!(f, "{}", error),
+            Error::UnsortedKey => write!(f, "Keys must be loaded in ascending order."),
+            Error::InvalidFillFactor(fill_factor) => {
+                write!(f, "Fill factor {} is not in the range (0, 1].", fill_factor)
+            }
+            Error::Corruption { ref path, offset } => {
+                write!(f, "Corruption in {} at offset {}.", path.display(), offset)
+            }
         }
     }
 }
@@ -55,7 +78,7 @@ impl fmt::Display for Error {
 /// Convenience `Result` type for `bp_tree`.
 pub type Result<T> = result::Result<T, Error>;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Metadata {
     pages: usize,
     len: usize,
@@ -67,9 +90,32 @@ struct Metadata {
     free_page: Option<usize>,
 }
 
+// The size of the write-ahead log in bytes after which it is checkpointed.
+const WAL_CHECKPOINT_SIZE: u64 = 16 * 1024 * 1024;
+
+// The metadata and the pages written by a committed operation.
+type Commit = (Metadata, Vec<DirtyPage>);
+
+/// Reads and writes the pages of a file through a buffer pool and a write-ahead log.
+///
+/// Pages are cached in their serialized form, so reading a resident page does not touch the file.
+/// The pages written by an operation are pinned in the buffer pool until the operation is
+/// committed, which appends the pages and the metadata to the write-ahead log as a single record,
+/// or rolled back, which restores the pages and the metadata of the last commit. Modified pages
+/// are only written to the file by a checkpoint, which happens when the log or the buffer pool is
+/// full, when the pager is synced, or when the pager is dropped. The file therefore only changes
+/// during checkpoints, and a checkpoint that is interrupted by a crash is completed by replaying
+/// the log when the file is opened again. The root page is pinned so that it is never evicted.
 pub struct Pager<T, U> {
     db_file: File,
     metadata: Metadata,
+    // the metadata as of the last commit, which is restored by a rollback
+    committed_metadata: Metadata,
+    buffer_pool: BufferPool,
+    wal: WriteAheadLog,
+    // the pages written by the operation that has not been committed yet, and the committed data
+    // of the pages that were only in the buffer pool and the log
+    uncommitted_pages: BTreeMap<usize, Option<Vec<u8>>>,
     _marker: PhantomData<(T, U)>,
 }
 
@@ -86,9 +132,13 @@ impl<T, U> Pager<T, U> {
         U: Serialize,
         P: AsRef<Path>,
     {
+        // the log of a previous map at the same path must not be replayed into the new map
+        let mut wal = WriteAheadLog::open(file_path.as_ref())?;
+        wal.clear()?;
+
         let header_size = Self::get_metadata_size();
         let body_size =
-            Node::<T, U>::get_max_size(key_size, value_size, leaf_degree, internal_degree) as u64;
+            Node::<T, Value<U>>::get_max_size(key_size, value_size, leaf_degree, internal_degree);
         let metadata = Metadata {
             pages: 1,
             len: 0,
@@ -111,14 +161,20 @@ impl<T, U> Pager<T, U> {
         db_file.write_all(serialized_metadata)?;
 
         db_file.seek(SeekFrom::Start(header_size))?;
-        let serialized_node = &serialize(&Node::Leaf(LeafNode::<T, U>::new(leaf_degree)))?;
-        db_file.write_all(serialized_node)?;
+        let root_node = Node::Leaf(LeafNode::<T, Value<U>>::new(leaf_degree));
+        db_file.write_all(&root_node.to_page(&[], body_size)?)?;
+        db_file.sync_all()?;
 
-        let pager = Pager {
+        let mut pager = Pager {
             db_file,
+            committed_metadata: metadata.clone(),
             metadata,
+            buffer_pool: BufferPool::new(DEFAULT_BUFFER_POOL_CAPACITY),
+            wal,
+            uncommitted_pages: BTreeMap::new(),
             _marker: PhantomData,
         };
+        pager.pin_page(0)?;
 
         Ok(pager)
     }
@@ -131,28 +187,63 @@ impl<T, U> Pager<T, U> {
             .read(true)
             .write(true)
             .create(true)
-            .open(file_path)?;
+            .open(file_path.as_ref())?;
         db_file.seek(SeekFrom::Start(0))?;
 
         let mut buffer: Vec<u8> = vec![0; Self::get_metadata_size() as usize];
         db_file.read_exact(buffer.as_mut_slice())?;
-        let metadata = deserialize(buffer.as_slice())?;
+        let metadata: Metadata = deserialize(buffer.as_slice())?;
 
-        Ok(Pager {
+        // the log is replayed before the pager exists, since dropping a pager checkpoints and
+        // removes the log, which would discard a log that is corrupted
+        let mut wal = WriteAheadLog::open(file_path)?;
+        let records = wal.replay()?;
+
+        let mut pager = Pager {
             db_file,
+            committed_metadata: metadata.clone(),
             metadata,
+            buffer_pool: BufferPool::new(DEFAULT_BUFFER_POOL_CAPACITY),
+            wal,
+            uncommitted_pages: BTreeMap::new(),
             _marker: PhantomData,
-        })
+        };
+        pager.recover(records)?;
+        let root_page = pager.metadata.root_page;
+        pager.pin_page(root_page)?;
+
+        Ok(pager)
+    }
+
+    // Writes the pages of every committed operation in the replayed records of the log to the
+    // file. Operations whose records are torn were already discarded by the replay.
+    fn recover(&mut self, records: Vec<Vec<u8>>) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        for record in records {
+            let (metadata, pages): Commit = deserialize(&record)?;
+            self.metadata = metadata;
+            self.write_pages(pages)?;
+        }
+        self.committed_metadata = self.metadata.clone();
+        self.write_file()?;
+        self.wal.clear()
     }
 
     #[inline]
     fn get_node_size(&self) -> u64 {
-        Node::<T, U>::get_max_size(
+        Node::<T, Value<U>>::get_max_size(
             self.metadata.key_size,
             self.metadata.value_size,
             self.metadata.leaf_degree,
             self.metadata.internal_degree,
-        ) as u64
+        )
+    }
+
+    #[inline]
+    fn get_overflow_capacity(&self) -> usize {
+        Node::<T, Value<U>>::get_overflow_capacity(self.get_node_size()) as usize
     }
 
     #[inline]
@@ -180,11 +271,7 @@ impl<T, U> Pager<T, U> {
 
     pub fn set_len(&mut self, len: usize) -> Result<()> {
         self.metadata.len = len;
-        self.db_file.seek(SeekFrom::Start(0))?;
-        let serialized_metadata = &serialize(&self.metadata)?;
-        self.db_file
-            .write_all(serialized_metadata)
-            .map_err(Error::IOError)
+        Ok(())
     }
 
     pub fn get_root_page(&self) -> usize {
@@ -192,27 +279,79 @@ impl<T, U> Pager<T, U> {
     }
 
     pub fn set_root_page(&mut self, new_root_page: usize) -> Result<()> {
+        self.unpin_page(self.metadata.root_page);
         self.metadata.root_page = new_root_page;
+        self.pin_page(new_root_page)
+    }
+
+    pub fn set_buffer_pool_capacity(&mut self, capacity: usize) {
+        self.buffer_pool.set_capacity(capacity);
+    }
+
+    pub fn set_sync_policy(&mut self, sync_policy: SyncPolicy) {
+        self.wal.set_sync_policy(sync_policy);
+    }
+
+    fn write_pages(&mut self, pages: Vec<DirtyPage>) -> Result<()> {
+        for (index, data) in pages {
+            let offset = self.calculate_page_offset(index);
+            self.db_file.seek(SeekFrom::Start(offset))?;
+            self.db_file.write_all(&data)?;
+        }
+        Ok(())
+    }
+
+    // Writes the metadata, truncates the file to its pages, and syncs the file to disk.
+    fn write_file(&mut self) -> Result<()> {
         self.db_file.seek(SeekFrom::Start(0))?;
         let serialized_metadata = &serialize(&self.metadata)?;
-        self.db_file
-            .write_all(serialized_metadata)
-            .map_err(Error::IOError)
+        self.db_file.write_all(serialized_metadata)?;
+        let len = self.calculate_page_offset(self.metadata.pages);
+        self.db_file.set_len(len)?;
+        self.db_file.sync_all().map_err(Error::IOError)
+    }
+
+    // Returns the serialized page, reading it into the buffer pool if it is not resident.
+    fn read_page(&mut self, index: usize) -> Result<&[u8]> {
+        if self.buffer_pool.get(index).is_none() {
+            let offset = self.calculate_page_offset(index);
+            let node_size = self.get_node_size();
+            self.db_file.seek(SeekFrom::Start(offset))?;
+            let mut buffer = vec![0; node_size as usize];
+            self.db_file.read_exact(buffer.as_mut_slice())?;
+            self.buffer_pool.insert(index, buffer, false);
+        }
+        Ok(self
+            .buffer_pool
+            .get(index)
+            .expect("Expected a resident page."))
+    }
+
+    /// Keeps a page in the buffer pool until it is unpinned.
+    pub fn pin_page(&mut self, index: usize) -> Result<()> {
+        if !self.buffer_pool.pin(index) {
+            self.read_page(index)?;
+            self.buffer_pool.pin(index);
+        }
+        Ok(())
+    }
+
+    pub fn unpin_page(&mut self, index: usize) {
+        self.buffer_pool.unpin(index);
     }
 
-    pub fn get_page(&mut self, index: usize) -> Result<Node<T, U>>
+    pub fn get_page(&mut self, index: usize) -> Result<Node<T, Value<U>>>
     where
         T: DeserializeOwned,
         U: DeserializeOwned,
     {
-        let offset = self.calculate_page_offset(index);
-        self.db_file.seek(SeekFrom::Start(offset))?;
-        let mut buffer: Vec<u8> = vec![0; self.get_node_size() as usize];
-        self.db_file.read_exact(buffer.as_mut_slice())?;
-        deserialize(buffer.as_slice()).map_err(Error::SerdeError)
+        let leaf_degree = self.metadata.leaf_degree;
+        let internal_degree = self.metadata.internal_degree;
+        let node = Node::from_page(self.read_page(index)?, leaf_degree, internal_degree)?;
+        node.try_map_keys(|key| self.read_item(key))
     }
 
-    pub fn allocate_node(&mut self, new_node: &Node<T, U>) -> Result<usize>
+    pub fn allocate_node(&mut self, new_node: &Node<T, Value<U>>) -> Result<usize>
     where
         T: DeserializeOwned + Serialize,
         U: DeserializeOwned + Serialize,
@@ -220,38 +359,18 @@ impl<T, U> Pager<T, U> {
         match self.metadata.free_page {
             None => {
                 self.metadata.pages += 1;
-                let len = self.calculate_page_offset(self.metadata.pages);
-                let node_size = self.get_node_size();
-                self.db_file.set_len(len)?;
-                self.db_file.seek(SeekFrom::Start(len - node_size))?;
-                let serialized_node = &serialize(&new_node)?;
-                self.db_file.write_all(serialized_node)?;
-
-                self.db_file.seek(SeekFrom::Start(0))?;
-                let serialized_metadata = &serialize(&self.metadata)?;
-                self.db_file.write_all(serialized_metadata)?;
-
-                Ok(self.metadata.pages - 1)
+                let index = self.metadata.pages - 1;
+                self.store_node(index, new_node)?;
+                Ok(index)
             }
             Some(free_page) => {
-                let offset = self.calculate_page_offset(free_page);
-                let mut buffer: Vec<u8> = vec![0; self.get_node_size() as usize];
-
-                self.db_file.seek(SeekFrom::Start(offset))?;
-                self.db_file.read_exact(buffer.as_mut_slice())?;
-
-                self.db_file.seek(SeekFrom::Start(offset))?;
-                let serialized_node = &serialize(&new_node)?;
-                self.db_file.write_all(serialized_node)?;
-
-                match deserialize(buffer.as_slice())? {
-                    Node::Free::<T, U>(new_free_page) => self.metadata.free_page = new_free_page,
+                match self.get_page(free_page)? {
+                    Node::Free::<T, Value<U>>(new_free_page) => {
+                        self.metadata.free_page = new_free_page
+                    }
                     _ => panic!("Expected a free node."),
                 }
-                self.db_file.seek(SeekFrom::Start(0))?;
-                let serialized_metadata = &serialize(&self.metadata)?;
-                self.db_file.write_all(serialized_metadata)?;
-
+                self.store_node(free_page, new_node)?;
                 Ok(free_page)
             }
         }
@@ -259,34 +378,145 @@ impl<T, U> Pager<T, U> {
 
     pub fn deallocate_node(&mut self, index: usize) -> Result<()>
     where
-        T: Serialize,
-        U: Serialize,
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
     {
-        let offset = self.calculate_page_offset(index);
+        // the overflow pages of the keys are deallocated first, since they change the free list
+        self.free_key_chains(index)?;
+        self.store_node(index, &Node::Free(self.metadata.free_page))?;
+        self.metadata.free_page = Some(index);
+        Ok(())
+    }
 
-        self.db_file.seek(SeekFrom::Start(offset))?;
-        let serialized_node = &serialize(&Node::Free::<T, U>(self.metadata.free_page))?;
-        self.db_file.write_all(serialized_node)?;
+    /// Replaces the node that is stored in a page. The overflow pages of the keys of a node are
+    /// owned by its page, so the overflow pages of the old keys are deallocated and the large
+    /// keys of the new node are written to new overflow pages.
+    pub fn write_node(&mut self, index: usize, node: &Node<T, Value<U>>) -> Result<()>
+    where
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        self.free_key_chains(index)?;
+        self.store_node(index, node)
+    }
 
-        self.metadata.free_page = Some(index);
-        self.db_file.seek(SeekFrom::Start(0))?;
-        let serialized_metadata = &serialize(&self.metadata)?;
-        self.db_file
-            .write_all(serialized_metadata)
-            .map_err(Error::IOError)
+    // Deallocates the overflow pages of the keys of the node that is stored in a page.
+    fn free_key_chains(&mut self, index: usize) -> Result<()>
+    where
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        let leaf_degree = self.metadata.leaf_degree;
+        let internal_degree = self.metadata.internal_degree;
+        let page = self.read_page(index)?;
+        if !node::has_overflow_keys(page) {
+            return Ok(());
+        }
+        let node: Node<Value<T>, Value<U>> = Node::from_page(page, leaf_degree, internal_degree)?;
+        for key in node.keys() {
+            if let Value::Overflow(first_page) = *key {
+                self.deallocate_overflow_chain(first_page)?;
+            }
+        }
+        Ok(())
     }
 
-    pub fn write_node(&mut self, index: usize, node: &Node<T, U>) -> Result<()>
+    // Writes a node to a page without deallocating the overflow pages of the keys that were stored
+    // in the page before, which is only correct for new, free, or truncated pages.
+    fn store_node(&mut self, index: usize, node: &Node<T, Value<U>>) -> Result<()>
     where
-        T: Serialize,
-        U: Serialize,
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
     {
-        let offset = self.calculate_page_offset(index);
-        self.db_file.seek(SeekFrom::Start(offset))?;
-        let serialized_node = &serialize(&node)?;
-        self.db_file
-            .write_all(serialized_node)
-            .map_err(Error::IOError)
+        let mut key_pages = Vec::new();
+        for key in node.keys() {
+            key_pages.push(self.write_key(key)?);
+        }
+        let page = node.to_page(&key_pages, self.get_node_size())?;
+        self.store_page(index, page);
+        Ok(())
+    }
+
+    fn store_page(&mut self, index: usize, page: Vec<u8>) {
+        if !self.uncommitted_pages.contains_key(&index) {
+            // a dirty page is only in the buffer pool and the log, so it is kept for a rollback
+            let committed_data = if self.buffer_pool.is_dirty(index) {
+                self.buffer_pool.get(index).map(<[u8]>::to_vec)
+            } else {
+                None
+            };
+            self.uncommitted_pages.insert(index, committed_data);
+            self.buffer_pool.insert(index, page, true);
+            self.buffer_pool.pin(index);
+        } else {
+            self.buffer_pool.insert(index, page, true);
+        }
+    }
+
+    /// Appends the pages written since the last commit and the metadata to the write-ahead log
+    /// as a single record, and checkpoints if the log or the buffer pool is full. If appending the
+    /// record fails, the operation is not committed and has to be rolled back.
+    pub fn commit(&mut self) -> Result<()> {
+        if self.uncommitted_pages.is_empty() {
+            return Ok(());
+        }
+
+        let mut commit_pages = Vec::with_capacity(self.uncommitted_pages.len());
+        for index in self.uncommitted_pages.keys() {
+            let data = self
+                .buffer_pool
+                .get(*index)
+                .expect("Expected a resident page.")
+                .to_vec();
+            commit_pages.push((*index, data));
+        }
+        self.wal
+            .append(&serialize(&(&self.metadata, &commit_pages))?)?;
+
+        for index in mem::take(&mut self.uncommitted_pages).into_keys() {
+            self.buffer_pool.unpin(index);
+        }
+        self.committed_metadata = self.metadata.clone();
+
+        if self.wal.get_len() >= WAL_CHECKPOINT_SIZE || self.buffer_pool.is_over_capacity() {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// Discards the pages written since the last commit and restores the metadata of the last
+    /// commit, so that an operation that failed partway leaves no partial modification behind.
+    pub fn rollback(&mut self) -> Result<()> {
+        self.unpin_page(self.metadata.root_page);
+        for (index, committed_data) in mem::take(&mut self.uncommitted_pages) {
+            match committed_data {
+                Some(data) => {
+                    self.buffer_pool.unpin(index);
+                    self.buffer_pool.insert(index, data, true);
+                }
+                None => self.buffer_pool.remove(index),
+            }
+        }
+        self.metadata = self.committed_metadata.clone();
+        self.pin_page(self.metadata.root_page)
+    }
+
+    /// Writes the pages of every committed operation to the file and empties the write-ahead log.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        if self.wal.get_len() == 0 {
+            return Ok(());
+        }
+
+        // the log has to be durable before the file is modified, so that a checkpoint that is
+        // interrupted can be completed from the log
+        self.wal.sync()?;
+        let dirty_pages = self.buffer_pool.get_dirty_pages();
+        self.write_pages(dirty_pages)?;
+        self.write_file()?;
+        self.buffer_pool.mark_clean();
+        self.wal.clear()?;
+        self.buffer_pool.shrink();
+        Ok(())
     }
 
     pub fn clear(&mut self) -> Result<()>
@@ -294,42 +524,284 @@ impl<T, U> Pager<T, U> {
         T: Serialize,
         U: Serialize,
     {
-        let header_size = Self::get_metadata_size();
-        let body_size = self.get_node_size();
+        // the pages that are removed from the buffer pool must not be only in the log, so that a
+        // rollback can read them from the file again
+        self.checkpoint()?;
+        self.unpin_page(self.metadata.root_page);
+        self.buffer_pool.truncate(1);
         self.metadata.pages = 1;
         self.metadata.len = 0;
         self.metadata.root_page = 0;
         self.metadata.free_page = None;
-        self.db_file.set_len(header_size + body_size)?;
+        // the overflow pages of the keys of the old root page have been truncated, so they are not
+        // deallocated
+        let root_node = Node::Leaf(LeafNode::<T, Value<U>>::new(self.metadata.leaf_degree));
+        self.store_page(0, root_node.to_page(&[], self.get_node_size())?);
+        self.pin_page(0)
+    }
 
-        self.db_file.seek(SeekFrom::Start(0))?;
-        let serialized_metadata = &serialize(&self.metadata)?;
-        self.db_file.write_all(serialized_metadata)?;
+    // Writes serialized data to a new chain of overflow pages and returns its first page.
+    fn write_overflow_chain(&mut self, data: &[u8]) -> Result<usize>
+    where
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        let mut next_page = None;
+        // the chain is written from its end so that each page can point to the next page
+        for chunk in data.chunks(self.get_overflow_capacity()).rev() {
+            let page = self.allocate_node(&Node::Overflow(chunk.to_vec(), next_page))?;
+            next_page = Some(page);
+        }
+        Ok(next_page.expect("Expected an overflow page."))
+    }
 
-        self.db_file.seek(SeekFrom::Start(header_size))?;
-        let serialized_node = &serialize(&Node::Leaf(LeafNode::<T, U>::new(
-            self.metadata.leaf_degree,
-        )))?;
-        self.db_file
-            .write_all(serialized_node)
-            .map_err(Error::IOError)
+    // Returns the pages of an overflow chain and the serialized data that is stored in them.
+    fn read_overflow_chain(&mut self, first_page: usize) -> Result<(Vec<usize>, Vec<u8>)>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+    {
+        let mut pages = Vec::new();
+        let mut buffer = Vec::new();
+        let mut curr_page = Some(first_page);
+        while let Some(page) = curr_page {
+            match self.get_page(page)? {
+                Node::Overflow(data, next_page) => {
+                    buffer.extend(data);
+                    curr_page = next_page;
+                }
+                _ => panic!("Expected an overflow node."),
+            }
+            pages.push(page);
+        }
+        Ok((pages, buffer))
     }
 
-    pub fn validate_key<V>(&self, key: &V) -> Result<()>
+    // Deallocates the pages of an overflow chain and returns the serialized data that was stored
+    // in them.
+    fn deallocate_overflow_chain(&mut self, first_page: usize) -> Result<Vec<u8>>
     where
-        T: Borrow<V>,
-        V: Serialize + ?Sized,
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
     {
-        assert!(serialized_size(key)? <= self.metadata.key_size);
-        Ok(())
+        let (pages, buffer) = self.read_overflow_chain(first_page)?;
+        for page in pages {
+            self.deallocate_node(page)?;
+        }
+        Ok(buffer)
+    }
+
+    // Returns the first page of a new overflow chain that stores a key if it does not fit in the
+    // maximum key size.
+    fn write_key(&mut self, key: &T) -> Result<Option<usize>>
+    where
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        if serialized_size(key)? <= self.metadata.key_size {
+            return Ok(None);
+        }
+        self.write_overflow_chain(&serialize(key)?).map(Some)
     }
 
-    pub fn validate_value<V>(&self, value: &V) -> Result<()>
+    /// Stores a value in its leaf node if it fits in the maximum value size, or in a chain of
+    /// overflow pages otherwise.
+    pub fn write_value(&mut self, value: U) -> Result<Value<U>>
     where
-        U: Borrow<V>,
-        V: Serialize + ?Sized,
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
     {
-        assert!(serialized_size(value)? <= self.metadata.value_size);
-        Ok(())
+        if serialized_size(&value)? <= self.metadata.value_size {
+            return Ok(Value::Inline(value));
+        }
+        self.write_overflow_chain(&serialize(&value)?)
+            .map(Value::Overflow)
+    }
+
+    // Returns a key or value that is stored either in a cell or in a chain of overflow pages.
+    fn read_item<V>(&mut self, item: Value<V>) -> Result<V>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        match item {
+            Value::Inline(item) => Ok(item),
+            Value::Overflow(first_page) => {
+                let (_, buffer) = self.read_overflow_chain(first_page)?;
+                deserialize(&buffer).map_err(Error::SerdeError)
+            }
+        }
+    }
+
+    /// Returns a value that is stored in a leaf node.
+    pub fn read_value(&mut self, value: Value<U>) -> Result<U>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+    {
+        self.read_item(value)
+    }
+
+    /// Returns a value that was removed from a leaf node and deallocates its overflow pages.
+    pub fn remove_value(&mut self, value: Value<U>) -> Result<U>
+    where
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        match value {
+            Value::Inline(value) => Ok(value),
+            Value::Overflow(first_page) => {
+                let buffer = self.deallocate_overflow_chain(first_page)?;
+                deserialize(&buffer).map_err(Error::SerdeError)
+            }
+        }
+    }
+
+    // Returns the first page of the overflow chain of a key or value that does not fit in
+    // `max_size` bytes. The pages of the chain are assigned from `next_page` and the serialized
+    // item is added to `chains`, which are appended after their node by `append_node_and_chains`.
+    fn plan_overflow_chain<V>(
+        &self,
+        item: &V,
+        max_size: u64,
+        next_page: &mut usize,
+        chains: &mut Vec<Vec<u8>>,
+    ) -> Result<Option<usize>>
+    where
+        V: Serialize,
+    {
+        if serialized_size(item)? <= max_size {
+            return Ok(None);
+        }
+        let serialized_item = serialize(item)?;
+        let first_page = *next_page;
+        *next_page += serialized_item.len().div_ceil(self.get_overflow_capacity());
+        chains.push(serialized_item);
+        Ok(Some(first_page))
+    }
+
+    // Writes a page at the end of the file without going through the buffer pool or the
+    // write-ahead log.
+    fn append_page(&mut self, page: &[u8]) -> Result<usize> {
+        let index = self.metadata.pages;
+        let offset = self.calculate_page_offset(index);
+        self.db_file.seek(SeekFrom::Start(offset))?;
+        self.db_file.write_all(page)?;
+        self.metadata.pages += 1;
+        Ok(index)
+    }
+
+    // Appends a node followed by the overflow chains that were planned for it.
+    fn append_node_and_chains(
+        &mut self,
+        node: &Node<T, Value<U>>,
+        key_pages: &[Option<usize>],
+        chains: Vec<Vec<u8>>,
+    ) -> Result<usize>
+    where
+        T: Serialize,
+        U: Serialize,
+    {
+        let node_size = self.get_node_size();
+        let index = self.append_page(&node.to_page(key_pages, node_size)?)?;
+        let capacity = self.get_overflow_capacity();
+        for serialized_item in chains {
+            let chunks: Vec<_> = serialized_item.chunks(capacity).collect();
+            for (chunk_index, chunk) in chunks.iter().enumerate() {
+                let next_page = if chunk_index + 1 < chunks.len() {
+                    Some(self.metadata.pages + 1)
+                } else {
+                    None
+                };
+                let overflow_node = Node::<T, Value<U>>::Overflow(chunk.to_vec(), next_page);
+                self.append_page(&overflow_node.to_page(&[], node_size)?)?;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Writes a node to a new page at the end of the file, followed by the overflow pages of its
+    /// keys, without going through the buffer pool or the write-ahead log. Appended pages are only
+    /// part of the map after `finish_bulk_load` is called.
+    pub fn append_node(&mut self, node: &Node<T, Value<U>>) -> Result<usize>
+    where
+        T: Serialize,
+        U: Serialize,
+    {
+        let mut next_page = self.metadata.pages + 1;
+        let mut chains = Vec::new();
+        let mut key_pages = Vec::new();
+        for key in node.keys() {
+            let key_size = self.metadata.key_size;
+            key_pages.push(self.plan_overflow_chain(key, key_size, &mut next_page, &mut chains)?);
+        }
+        self.append_node_and_chains(node, &key_pages, chains)
+    }
+
+    /// Appends a leaf node with some entries, followed by the overflow pages of its keys and
+    /// values, and returns the page of the leaf node. Unless the leaf node is the last leaf node,
+    /// the next leaf node must be appended right after it.
+    pub fn append_leaf(&mut self, entries: Vec<Entry<T, U>>, is_last: bool) -> Result<usize>
+    where
+        T: Serialize,
+        U: Serialize,
+    {
+        let key_size = self.metadata.key_size;
+        let value_size = self.metadata.value_size;
+        // the overflow pages are written in order after the leaf node
+        let mut next_page = self.metadata.pages + 1;
+        let mut chains = Vec::new();
+        let mut key_pages = Vec::new();
+        let mut leaf = LeafNode::new(self.metadata.leaf_degree);
+        for Entry { key, value } in entries {
+            key_pages.push(self.plan_overflow_chain(
+                &key,
+                key_size,
+                &mut next_page,
+                &mut chains,
+            )?);
+            let value =
+                match self.plan_overflow_chain(&value, value_size, &mut next_page, &mut chains)? {
+                    Some(first_page) => Value::Overflow(first_page),
+                    None => Value::Inline(value),
+                };
+            leaf.entries[leaf.len] = Some(Entry { key, value });
+            leaf.len += 1;
+        }
+        if !is_last {
+            leaf.next_leaf = Some(next_page);
+        }
+        self.append_node_and_chains(&Node::Leaf(leaf), &key_pages, chains)
+    }
+
+    /// Replaces the empty tree of a new map with the tree that was appended to the file, and
+    /// deallocates the empty root leaf node.
+    pub fn finish_bulk_load(&mut self, root_page: usize, len: usize) -> Result<()>
+    where
+        T: DeserializeOwned + Serialize,
+        U: DeserializeOwned + Serialize,
+    {
+        // the appended pages have to be durable before the commit that references them, so
+        // that a crash leaves either the empty map or the complete map
+        let file_len = self.calculate_page_offset(self.metadata.pages);
+        self.db_file.set_len(file_len)?;
+        self.db_file.sync_data()?;
+
+        self.metadata.len = len;
+        self.set_root_page(root_page)?;
+        self.deallocate_node(0)?;
+        self.commit()?;
+        self.checkpoint()
+    }
+}
+
+impl<T, U> Drop for Pager<T, U> {
+    fn drop(&mut self) {
+        // errors cannot be returned from a destructor, and committed operations that could not be
+        // checkpointed are recovered from the log when the map is opened
+        if self.rollback().is_ok() && self.checkpoint().is_ok() {
+            let _ = self.wal.remove_file();
+        }
     }
 }
@@ -0,0 +1,124 @@
+use crate::bp_tree::pager::{Error, Result};
+use crate::wal::{self, SyncPolicy};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// An append-only log of the pages written by committed operations that have not been
+/// checkpointed into the file of the map yet.
+///
+/// Each record is stored as its length, a CRC32C checksum of its contents, and then its contents.
+/// An incomplete record at the end of the log is assumed to be the result of a torn write and is
+/// discarded, so an operation is either replayed completely or not at all. A record whose checksum
+/// does not match in the middle of the log is reported as corruption.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    log_file: File,
+    len: u64,
+    sync_policy: SyncPolicy,
+    unsynced_count: usize,
+}
+
+impl WriteAheadLog {
+    /// Returns the path of the log of the map stored at `path`.
+    pub fn get_path<P>(path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let mut log_path = path.as_ref().as_os_str().to_owned();
+        log_path.push("-wal");
+        PathBuf::from(log_path)
+    }
+
+    /// Opens the log of the map stored at `path`, creating it if it does not exist.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = Self::get_path(path);
+        let log_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let len = log_file.metadata()?.len();
+
+        Ok(WriteAheadLog {
+            path,
+            log_file,
+            len,
+            sync_policy: SyncPolicy::default(),
+            unsynced_count: 0,
+        })
+    }
+
+    pub fn get_len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn set_sync_policy(&mut self, sync_policy: SyncPolicy) {
+        self.sync_policy = sync_policy;
+    }
+
+    /// Reads the contents of all valid records in the log and discards a torn record at the end
+    /// of the log. Returns `Error::Corruption` if a record in the middle of the log is corrupted.
+    pub fn replay(&mut self) -> Result<Vec<Vec<u8>>> {
+        let records =
+            wal::read_records(&mut self.log_file)?.map_err(|offset| Error::Corruption {
+                path: self.path.clone(),
+                offset,
+            })?;
+        self.len = self.log_file.stream_position()?;
+        Ok(records)
+    }
+
+    /// Appends a record to the log and synchronizes the log according to the sync policy. A
+    /// record that is only partially appended is removed, so that it does not corrupt the records
+    /// that are appended after it.
+    pub fn append(&mut self, buffer: &[u8]) -> Result<()> {
+        let record = wal::encode_record(buffer);
+        if let Err(error) = self.log_file.write_all(&record) {
+            self.log_file.set_len(self.len)?;
+            self.log_file.seek(SeekFrom::Start(self.len))?;
+            return Err(Error::IOError(error));
+        }
+        self.len += record.len() as u64;
+
+        self.unsynced_count += 1;
+        if self.sync_policy.should_sync(self.unsynced_count) {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Forces the appended records onto stable storage.
+    pub fn sync(&mut self) -> Result<()> {
+        if self.unsynced_count > 0 {
+            self.log_file.sync_data()?;
+            self.unsynced_count = 0;
+        }
+        Ok(())
+    }
+
+    /// Removes every record from the log.
+    pub fn clear(&mut self) -> Result<()> {
+        self.log_file.set_len(0)?;
+        self.log_file.seek(SeekFrom::Start(0))?;
+        self.log_file.sync_all()?;
+        self.len = 0;
+        self.unsynced_count = 0;
+        Ok(())
+    }
+
+    /// Removes the file of an empty log.
+    pub fn remove_file(&self) -> Result<()> {
+        if self.len != 0 {
+            return Ok(());
+        }
+        match fs::remove_file(&self.path) {
+            Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(()),
+            result => result.map_err(From::from),
+        }
+    }
+}
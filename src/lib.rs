@@ -60,3 +60,4 @@ pub mod skiplist;
 pub mod splay_tree;
 pub mod sync;
 pub mod treap;
+mod wal;
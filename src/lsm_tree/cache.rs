@@ -0,0 +1,375 @@
+use crate::lsm_tree::Result;
+use lru::LruCache;
+use std::fs;
+use std::hash::Hash;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The default capacity of the block cache in bytes.
+pub const DEFAULT_BLOCK_CACHE_CAPACITY: usize = 8 * 1024 * 1024;
+/// The default maximum number of files that are kept open by the file cache.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 256;
+
+/// The files of a SSTable that are read through a `TableCache`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileKind {
+    Index,
+    Data,
+}
+
+impl FileKind {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            FileKind::Index => "index.dat",
+            FileKind::Data => "data.dat",
+        }
+    }
+}
+
+/// A block that has been verified and decompressed, and the size of the block in its file.
+pub type Block = (Arc<[u8]>, u64);
+
+// An open file that is shared by concurrent readers, which seek and read while holding its lock.
+type SharedFile = Arc<Mutex<fs::File>>;
+
+// A least recently used cache whose entries are charged a cost, such as a number of bytes. Entries
+// are evicted until the total charge is at most the capacity.
+struct SizedLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    entries: LruCache<K, (V, usize)>,
+    capacity: usize,
+    usage: usize,
+}
+
+impl<K, V> SizedLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    fn new(capacity: usize) -> Self {
+        SizedLruCache {
+            entries: LruCache::unbounded(),
+            capacity,
+            usage: 0,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|entry| &entry.0)
+    }
+
+    // Inserts an entry unless its charge exceeds the capacity of the cache.
+    fn insert(&mut self, key: K, value: V, charge: usize) {
+        if charge > self.capacity {
+            return;
+        }
+        if let Some((_, old_charge)) = self.entries.put(key, (value, charge)) {
+            self.usage -= old_charge;
+        }
+        self.usage += charge;
+        self.evict();
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some((_, charge)) = self.entries.pop(key) {
+            self.usage -= charge;
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.usage > self.capacity {
+            match self.entries.pop_lru() {
+                Some((_, (_, charge))) => self.usage -= charge,
+                None => break,
+            }
+        }
+    }
+}
+
+/// The hit and miss counters of a `TableCache`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub block_cache_hit_count: u64,
+    pub block_cache_miss_count: u64,
+    pub file_cache_hit_count: u64,
+    pub file_cache_miss_count: u64,
+}
+
+/// A cache of the index and data blocks and of the open files of SSTables that is shared by all
+/// SSTables of a compaction strategy. The block cache is bounded by a capacity in bytes, and each
+/// block is charged its decompressed size. The file cache is bounded by a maximum number of open
+/// files.
+pub struct TableCache {
+    next_id: AtomicU64,
+    blocks: Mutex<SizedLruCache<(u64, FileKind, u64), Block>>,
+    files: Mutex<SizedLruCache<(u64, FileKind), SharedFile>>,
+    block_cache_hit_count: AtomicU64,
+    block_cache_miss_count: AtomicU64,
+    file_cache_hit_count: AtomicU64,
+    file_cache_miss_count: AtomicU64,
+}
+
+impl TableCache {
+    pub fn new(block_cache_capacity: usize, max_open_files: usize) -> Self {
+        TableCache {
+            next_id: AtomicU64::new(0),
+            blocks: Mutex::new(SizedLruCache::new(block_cache_capacity)),
+            files: Mutex::new(SizedLruCache::new(max_open_files)),
+            block_cache_hit_count: AtomicU64::new(0),
+            block_cache_miss_count: AtomicU64::new(0),
+            file_cache_hit_count: AtomicU64::new(0),
+            file_cache_miss_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a new identifier for a SSTable whose blocks and files are cached.
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn set_block_cache_capacity(&self, capacity: usize) {
+        self.blocks.lock().unwrap().set_capacity(capacity);
+    }
+
+    pub fn set_max_open_files(&self, max_open_files: usize) {
+        self.files.lock().unwrap().set_capacity(max_open_files);
+    }
+
+    /// Returns the block at an offset of a file of a SSTable, reading it with `read_block` after
+    /// seeking to the offset if it is not cached. Returns `None` if `read_block` reached the end of
+    /// the file.
+    pub fn get_block<F>(
+        &self,
+        id: u64,
+        kind: FileKind,
+        path: &Path,
+        offset: u64,
+        read_block: F,
+    ) -> Result<Option<Block>>
+    where
+        F: FnOnce(&mut fs::File, &Path) -> Result<Option<(Vec<u8>, u64)>>,
+    {
+        let key = (id, kind, offset);
+        if let Some(block) = self.blocks.lock().unwrap().get(&key) {
+            self.block_cache_hit_count.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(block.clone()));
+        }
+        self.block_cache_miss_count.fetch_add(1, Ordering::Relaxed);
+
+        // the lock of the cache is not held while the block is read, so concurrent misses of the
+        // same block may both read it
+        let file = self.get_file(id, kind, path)?;
+        let block = {
+            let mut file = file.lock().unwrap();
+            file.seek(SeekFrom::Start(offset))?;
+            match read_block(&mut file, path)? {
+                Some((buffer, size)) => (Arc::from(buffer), size),
+                None => return Ok(None),
+            }
+        };
+
+        self.blocks
+            .lock()
+            .unwrap()
+            .insert(key, block.clone(), block.0.len());
+        Ok(Some(block))
+    }
+
+    fn get_file(&self, id: u64, kind: FileKind, path: &Path) -> Result<SharedFile> {
+        let key = (id, kind);
+        if let Some(file) = self.files.lock().unwrap().get(&key) {
+            self.file_cache_hit_count.fetch_add(1, Ordering::Relaxed);
+            return Ok(Arc::clone(file));
+        }
+        self.file_cache_miss_count.fetch_add(1, Ordering::Relaxed);
+
+        let file = Arc::new(Mutex::new(fs::File::open(path)?));
+        self.files.lock().unwrap().insert(key, Arc::clone(&file), 1);
+        Ok(file)
+    }
+
+    /// Closes the cached files of a SSTable. Its cached blocks are evicted once they are least
+    /// recently used.
+    pub fn remove_files(&self, id: u64) {
+        let mut files = self.files.lock().unwrap();
+        files.remove(&(id, FileKind::Index));
+        files.remove(&(id, FileKind::Data));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            block_cache_hit_count: self.block_cache_hit_count.load(Ordering::Relaxed),
+            block_cache_miss_count: self.block_cache_miss_count.load(Ordering::Relaxed),
+            file_cache_hit_count: self.file_cache_hit_count.load(Ordering::Relaxed),
+            file_cache_miss_count: self.file_cache_miss_count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for TableCache {
+    fn default() -> Self {
+        TableCache::new(DEFAULT_BLOCK_CACHE_CAPACITY, DEFAULT_MAX_OPEN_FILES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileKind, SizedLruCache, TableCache};
+    use crate::lsm_tree::compaction::{CompactionStrategy, SizeTieredStrategy};
+    use crate::lsm_tree::{LsmMap, Result};
+    use rand::Rng;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io::Read;
+    use std::path::Path;
+
+    #[test]
+    fn test_sized_lru_cache() {
+        let mut cache = SizedLruCache::new(10);
+        cache.insert(1, "a", 4);
+        cache.insert(2, "b", 4);
+        assert_eq!(cache.get(&1), Some(&"a"));
+
+        // the least recently used entry is evicted
+        cache.insert(3, "c", 4);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.usage, 8);
+
+        // entries that are larger than the capacity are not inserted
+        cache.insert(4, "d", 11);
+        assert_eq!(cache.get(&4), None);
+        assert_eq!(cache.get(&1), Some(&"a"));
+
+        cache.set_capacity(4);
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.get(&1), Some(&"a"));
+
+        cache.remove(&1);
+        assert_eq!(cache.usage, 0);
+    }
+
+    fn read_all(file: &mut fs::File, _: &Path) -> Result<Option<(Vec<u8>, u64)>> {
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        let size = buffer.len() as u64;
+        Ok(Some((buffer, size)))
+    }
+
+    #[test]
+    fn test_table_cache() {
+        let test_name = "test_table_cache";
+        fs::create_dir(test_name).unwrap();
+        let path = Path::new(test_name).join(FileKind::Data.file_name());
+        fs::write(&path, b"abcdef").unwrap();
+
+        let cache = TableCache::default();
+        let id = cache.next_id();
+        let get_block = |offset| {
+            cache
+                .get_block(id, FileKind::Data, &path, offset, read_all)
+                .unwrap()
+                .map(|block| block.0.to_vec())
+        };
+
+        assert_eq!(get_block(2), Some(b"cdef".to_vec()));
+        assert_eq!(get_block(2), Some(b"cdef".to_vec()));
+        assert_eq!(get_block(6), None);
+        assert_eq!(get_block(0), Some(b"abcdef".to_vec()));
+
+        let stats = cache.stats();
+        assert_eq!(stats.block_cache_hit_count, 1);
+        assert_eq!(stats.block_cache_miss_count, 3);
+        assert_eq!(stats.file_cache_hit_count, 2);
+        assert_eq!(stats.file_cache_miss_count, 1);
+
+        // removed files are opened again
+        cache.remove_files(id);
+        cache.set_block_cache_capacity(0);
+        assert_eq!(get_block(4), Some(b"ef".to_vec()));
+        assert_eq!(cache.stats().file_cache_miss_count, 2);
+
+        // at most one file is kept open
+        cache.set_max_open_files(1);
+        let other_id = cache.next_id();
+        cache
+            .get_block(other_id, FileKind::Data, &path, 0, read_all)
+            .unwrap();
+        assert_eq!(cache.files.lock().unwrap().usage, 1);
+        assert_eq!(get_block(4), Some(b"ef".to_vec()));
+        assert_eq!(cache.stats().file_cache_miss_count, 4);
+
+        fs::remove_dir_all(test_name).unwrap();
+    }
+
+    fn check_cache<C>(map: &mut LsmMap<u32, u64, C>) -> Result<()>
+    where
+        C: CompactionStrategy<u32, u64>,
+    {
+        let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+        let mut expected = BTreeMap::new();
+        for _ in 0..10_000 {
+            let key = rng.gen_range(0, 5000);
+            let value = rng.gen::<u64>();
+            map.insert(key, value)?;
+            expected.insert(key, value);
+        }
+        map.flush()?;
+
+        // hot keys are read repeatedly
+        for _ in 0..10 {
+            for key in 0..100 {
+                assert_eq!(map.get(&key)?, expected.get(&key).cloned());
+            }
+        }
+        let actual = map.range(1000..2000)?.collect::<Result<Vec<_>>>()?;
+        let expected_range: Vec<_> = expected.range(1000..2000).map(|e| (*e.0, *e.1)).collect();
+        assert_eq!(actual, expected_range);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lsm_map_cache() {
+        let test_name = "test_lsm_map_cache";
+        let sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5).unwrap();
+        let mut map = LsmMap::new(sts).unwrap();
+        check_cache(&mut map).unwrap();
+
+        let stats = map.stats().unwrap();
+        assert!(stats.block_cache_hit_count > stats.block_cache_miss_count);
+        assert!(stats.file_cache_hit_count > 0);
+
+        drop(map);
+        fs::remove_dir_all(test_name).unwrap();
+    }
+
+    #[test]
+    fn test_lsm_map_without_cache() {
+        let test_name = "test_lsm_map_without_cache";
+        let mut sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5).unwrap();
+        sts.set_block_cache_capacity(0);
+        sts.set_max_open_files(0);
+        let mut map = LsmMap::new(sts).unwrap();
+        check_cache(&mut map).unwrap();
+
+        // every block and file is read from disk
+        let stats = map.stats().unwrap();
+        assert_eq!(stats.block_cache_hit_count, 0);
+        assert_eq!(stats.file_cache_hit_count, 0);
+        assert!(stats.block_cache_miss_count > 0);
+        assert_eq!(stats.file_cache_miss_count, stats.block_cache_miss_count);
+
+        drop(map);
+        fs::remove_dir_all(test_name).unwrap();
+    }
+}
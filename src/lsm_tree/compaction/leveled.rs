@@ -1,26 +1,38 @@
 use crate::entry::Entry;
-use crate::lsm_tree::compaction::{CompactionIter, CompactionStrategy};
-use crate::lsm_tree::{sstable, Result, SSTable, SSTableBuilder, SSTableDataIter, SSTableValue};
-use bincode::{deserialize, serialize};
+use crate::lsm_tree::cache::TableCache;
+use crate::lsm_tree::compaction::manifest::{
+    self, Manifest, ManifestMetadata, SSTablePath, VersionEdit,
+};
+use crate::lsm_tree::compaction::stats::{LevelStats, StatsRecorder};
+use crate::lsm_tree::compaction::{
+    CompactionIter, CompactionStats, CompactionStrategy, EventListener,
+};
+use crate::lsm_tree::merge;
+use crate::lsm_tree::{
+    sstable, Compression, FilterPolicy, MergeOperator, MergingCursor, RangeIter, Result, SSTable,
+    SSTableBuilder, SSTableDataIter, SSTableValue, Snapshot, SnapshotList, SortedRun,
+    VersionFilter, VisibleIter,
+};
+use bincode::deserialize;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Borrow;
-use std::cell::Cell;
 use std::cmp;
-use std::collections::{BTreeMap, BinaryHeap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashSet, VecDeque};
 use std::fmt::{self, Debug};
 use std::fs;
 use std::hash::Hash;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Seek, SeekFrom};
+use std::iter;
 use std::mem;
-use std::ops::Bound::{Included, Unbounded};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(deserialize = "T: DeserializeOwned, U: DeserializeOwned"))]
@@ -33,10 +45,30 @@ where
     max_sstable_size: u64,
     max_initial_level_count: usize,
     growth_factor: u64,
+    #[serde(skip)]
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    #[serde(skip)]
+    stats: Arc<StatsRecorder>,
+    #[serde(skip)]
+    cache: Arc<TableCache>,
+    #[serde(skip)]
+    filter_policy: FilterPolicy<T>,
+    #[serde(skip)]
     sstables: Vec<Arc<SSTable<T, U>>>,
+    #[serde(skip)]
     levels: Vec<BTreeMap<T, Arc<SSTable<T, U>>>>,
 }
 
+// The metadata file of older versions, which contained the paths of all SSTables.
+type LegacyLeveledMetadata<T, U> = (
+    LeveledMetadata<T, U>,
+    Vec<PathBuf>,
+    Vec<BTreeMap<T, PathBuf>>,
+);
+
+// The SSTables that are not in a level and the SSTables of each level in ascending order of key.
+type LeveledSSTables<T, U> = (Vec<Arc<SSTable<T, U>>>, Vec<Vec<Arc<SSTable<T, U>>>>);
+
 impl<T, U> LeveledMetadata<T, U>
 where
     T: Ord,
@@ -54,6 +86,10 @@ where
             max_sstable_size,
             max_initial_level_count,
             growth_factor,
+            merge_operator: None,
+            stats: Arc::new(StatsRecorder::default()),
+            cache: Arc::new(TableCache::default()),
+            filter_policy: FilterPolicy::default(),
             sstables: Vec::new(),
             levels: Vec::new(),
         }
@@ -73,6 +109,90 @@ where
 
         self.levels[index].insert(sstable.summary.key_range.1.clone(), sstable);
     }
+
+    pub fn get_sstables(&self) -> Vec<Arc<SSTable<T, U>>> {
+        self.sstables
+            .iter()
+            .chain(self.levels.iter().flat_map(|level| level.values()))
+            .cloned()
+            .collect()
+    }
+
+    // Removes and returns the SSTables that are not in a level and the SSTables of each level
+    // whose keys intersect a range. The key range is expanded until no remaining SSTable
+    // intersects a removed SSTable, so the removed SSTables can be merged into the last level
+    // without overlapping any remaining SSTable.
+    pub fn remove_intersecting_sstables(
+        &mut self,
+        range: &(Bound<T>, Bound<T>),
+    ) -> LeveledSSTables<T, U>
+    where
+        T: Clone,
+    {
+        let mut removed_sstables = Vec::new();
+        let mut removed_levels = vec![Vec::new(); self.levels.len()];
+        let mut key_range: Option<(T, T)> = None;
+
+        loop {
+            let is_removed = |sstable: &Arc<SSTable<T, U>>| match &key_range {
+                Some(key_range) => sstable::is_intersecting(key_range, &sstable.summary.key_range),
+                None => sstable::is_intersecting_range(&sstable.summary.key_range, range),
+            };
+
+            let (sstables, remaining_sstables): (Vec<_>, Vec<_>) = mem::take(&mut self.sstables)
+                .into_iter()
+                .partition(is_removed);
+            self.sstables = remaining_sstables;
+
+            let mut new_sstables = sstables.clone();
+            removed_sstables.extend(sstables);
+            for (index, level) in self.levels.iter_mut().enumerate() {
+                let (level_sstables, remaining_level): (BTreeMap<_, _>, BTreeMap<_, _>) =
+                    mem::take(level)
+                        .into_iter()
+                        .partition(|level_entry| is_removed(&level_entry.1));
+                *level = remaining_level;
+
+                new_sstables.extend(level_sstables.values().cloned());
+                removed_levels[index].extend(level_sstables.into_iter().map(|entry| entry.1));
+            }
+
+            if new_sstables.is_empty() {
+                break;
+            }
+
+            for sstable in new_sstables {
+                let sstable_range = sstable.summary.key_range.clone();
+                key_range = Some(match key_range {
+                    Some(key_range) => sstable::merge_ranges(key_range, sstable_range),
+                    None => sstable_range,
+                });
+            }
+        }
+
+        for level in &mut removed_levels {
+            level.sort_by(|x, y| x.summary.key_range.0.cmp(&y.summary.key_range.0));
+        }
+        (removed_sstables, removed_levels)
+    }
+}
+
+impl<T, U> ManifestMetadata for LeveledMetadata<T, U>
+where
+    T: Ord,
+{
+    fn get_sstable_paths(&self) -> Vec<SSTablePath> {
+        let path_iter = self
+            .sstables
+            .iter()
+            .map(|sstable| (None, sstable.path.clone()));
+        let level_path_iter = self.levels.iter().enumerate().flat_map(|(index, level)| {
+            level
+                .values()
+                .map(move |sstable| (Some(index), sstable.path.clone()))
+        });
+        path_iter.chain(level_path_iter).collect()
+    }
 }
 
 impl<T, U> Debug for LeveledMetadata<T, U>
@@ -115,8 +235,10 @@ where
     is_compacting: Arc<AtomicBool>,
     curr_logical_time: u64,
     logical_time_file: fs::File,
-    metadata_lock_count: Rc<Cell<u64>>,
-    metadata_file: fs::File,
+    snapshot_list: SnapshotList,
+    compacting_sstable_paths: HashSet<PathBuf>,
+    compression: Compression,
+    manifest: Manifest,
     curr_metadata: Arc<Mutex<LeveledMetadata<T, U>>>,
     next_metadata: Arc<Mutex<Option<LeveledMetadata<T, U>>>>,
 }
@@ -157,41 +279,32 @@ where
     {
         fs::create_dir(path.as_ref())?;
 
-        let metadata_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path.as_ref().join("metadata.dat"))?;
+        let metadata = LeveledMetadata::new(
+            max_in_memory_size,
+            max_sstable_count,
+            max_sstable_size,
+            max_initial_level_count,
+            growth_factor,
+        );
+        let manifest = Manifest::create(path.as_ref(), &metadata)?;
         let logical_time_file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
             .open(path.as_ref().join("logical_time.dat"))?;
-        let mut ret = LeveledStrategy {
+        Ok(LeveledStrategy {
             path: PathBuf::from(path.as_ref()),
             compaction_thread_join_handle: None,
             is_compacting: Arc::new(AtomicBool::new(false)),
             curr_logical_time: 0,
             logical_time_file,
-            metadata_lock_count: Rc::new(Cell::new(0)),
-            metadata_file,
-            curr_metadata: Arc::new(Mutex::new(LeveledMetadata::new(
-                max_in_memory_size,
-                max_sstable_count,
-                max_sstable_size,
-                max_initial_level_count,
-                growth_factor,
-            ))),
+            snapshot_list: SnapshotList::new(),
+            compacting_sstable_paths: HashSet::new(),
+            compression: Compression::None,
+            manifest,
+            curr_metadata: Arc::new(Mutex::new(metadata)),
             next_metadata: Arc::new(Mutex::new(None)),
-        };
-
-        {
-            let curr_metadata = ret.curr_metadata.lock().unwrap();
-            ret.metadata_file.seek(SeekFrom::Start(0))?;
-            ret.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
-
-        Ok(ret)
+        })
     }
 
     /// Opens an existing `LeveledStrategy<T, U>` from a folder.
@@ -212,83 +325,229 @@ where
     /// ```
     pub fn open<P>(path: P) -> Result<Self>
     where
-        T: DeserializeOwned,
+        T: Clone + DeserializeOwned,
         U: DeserializeOwned,
         P: AsRef<Path>,
     {
-        let mut metadata_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(path.as_ref().join("metadata.dat"))?;
+        let (manifest, metadata) = if Manifest::exists(path.as_ref()) {
+            let (manifest, mut metadata, sstable_paths): (_, LeveledMetadata<T, U>, _) =
+                Manifest::open(path.as_ref())?;
+            for (location, sstable_path) in sstable_paths {
+                let sstable = Arc::new(SSTable::with_cache(sstable_path, &metadata.cache)?);
+                match location {
+                    Some(index) => metadata.insert_sstable(index, sstable),
+                    None => metadata.push_sstable(sstable),
+                }
+            }
+            (manifest, metadata)
+        } else {
+            // migrates the metadata file of older versions into a new manifest
+            let metadata_path = path.as_ref().join("metadata.dat");
+            let (mut metadata, sstable_paths, level_paths): LegacyLeveledMetadata<T, U> =
+                deserialize(&fs::read(metadata_path.as_path())?)?;
+            for sstable_path in sstable_paths {
+                metadata.push_sstable(Arc::new(SSTable::with_cache(
+                    sstable_path,
+                    &metadata.cache,
+                )?));
+            }
+            for (index, level) in level_paths.into_iter().enumerate() {
+                for sstable_path in level.into_iter().map(|level_entry| level_entry.1) {
+                    metadata.insert_sstable(
+                        index,
+                        Arc::new(SSTable::with_cache(sstable_path, &metadata.cache)?),
+                    );
+                }
+            }
+            let manifest = Manifest::create(path.as_ref(), &metadata)?;
+            fs::remove_file(metadata_path)?;
+            (manifest, metadata)
+        };
+        manifest::remove_unreferenced_sstables(path.as_ref(), &metadata.get_sstable_paths())?;
+
         let mut logical_time_file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .open(path.as_ref().join("logical_time.dat"))?;
-        let mut buffer = Vec::new();
-        metadata_file.read_to_end(&mut buffer)?;
         logical_time_file.seek(SeekFrom::Start(0))?;
+        // the logical time file is not synchronized, so it may be older than the SSTables after a
+        // crash
+        let curr_logical_time = metadata
+            .get_sstables()
+            .iter()
+            .map(|sstable| sstable.summary.logical_time_range.1 + 1)
+            .fold(logical_time_file.read_u64::<BigEndian>()?, cmp::max);
         Ok(LeveledStrategy {
             path: PathBuf::from(path.as_ref()),
             compaction_thread_join_handle: None,
             is_compacting: Arc::new(AtomicBool::new(false)),
-            curr_logical_time: logical_time_file.read_u64::<BigEndian>()?,
+            curr_logical_time,
             logical_time_file,
-            metadata_lock_count: Rc::new(Cell::new(0)),
-            metadata_file,
-            curr_metadata: Arc::new(Mutex::new(deserialize(&buffer)?)),
+            snapshot_list: SnapshotList::new(),
+            compacting_sstable_paths: HashSet::new(),
+            compression: Compression::None,
+            manifest,
+            curr_metadata: Arc::new(Mutex::new(metadata)),
             next_metadata: Arc::new(Mutex::new(None)),
         })
     }
 
-    fn try_replace_metadata(
-        &self,
-        curr_metadata: &mut MutexGuard<'_, LeveledMetadata<T, U>>,
-    ) -> Result<bool> {
-        let mut next_metadata = self.next_metadata.lock().unwrap();
+    fn try_replace_metadata(&mut self) -> Result<()>
+    where
+        T: Clone,
+    {
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let next_metadata = match self.next_metadata.lock().unwrap().take() {
+            Some(next_metadata) => next_metadata,
+            None => return Ok(()),
+        };
 
-        if let Some(next_metadata) = next_metadata.take() {
-            let logical_time_opt = next_metadata
-                .levels
-                .iter()
-                .map(|level_entry| {
-                    level_entry
-                        .iter()
-                        .map(|level_entry| level_entry.1.summary.logical_time_range.1)
-                        .max()
-                })
-                .max()
-                .and_then(|max_opt| max_opt);
-            let old_sstables = mem::replace(&mut curr_metadata.sstables, next_metadata.sstables);
-            let old_levels = mem::replace(&mut curr_metadata.levels, next_metadata.levels);
-            curr_metadata.sstables.extend(
-                old_sstables
-                    .iter()
-                    .filter(|sstable| Some(sstable.summary.logical_time_range.0) > logical_time_opt)
-                    .map(|sstable| Arc::clone(sstable)),
-            );
+        let old_sstable_paths = curr_metadata.get_sstable_paths();
 
-            let path_iter = curr_metadata.sstables.iter().map(|sstable| &sstable.path);
-            let level_path_iter = curr_metadata
-                .levels
+        // sstables that were added after the compaction started are kept
+        let old_sstables = mem::replace(&mut curr_metadata.sstables, next_metadata.sstables);
+        let old_levels = mem::replace(&mut curr_metadata.levels, next_metadata.levels);
+        curr_metadata.sstables.extend(
+            old_sstables
                 .iter()
-                .flat_map(|level| level.iter().map(|level_entry| &level_entry.1.path));
-            let new_sstable_paths: HashSet<_> = path_iter.chain(level_path_iter).collect();
+                .filter(|sstable| !self.compacting_sstable_paths.contains(&sstable.path))
+                .map(|sstable| Arc::clone(sstable)),
+        );
 
-            let old_path_iter = old_sstables.iter().map(|sstable| &sstable.path);
-            let old_level_path_iter = old_levels
-                .iter()
-                .flat_map(|level| level.iter().map(|level_entry| &level_entry.1.path));
+        // the compaction must be recorded in the manifest before the old sstables are removed
+        let new_sstable_paths = curr_metadata.get_sstable_paths();
+        let edit = VersionEdit::new(&old_sstable_paths, &new_sstable_paths);
+        self.manifest.append(&edit, &*curr_metadata)?;
 
-            for path in old_path_iter.chain(old_level_path_iter) {
-                if !new_sstable_paths.contains(path) {
-                    fs::remove_dir_all(path)?;
-                }
+        // the old sstables are removed once they are no longer read by any cursor
+        let old_sstable_iter = old_sstables
+            .iter()
+            .chain(old_levels.iter().flat_map(|level| level.values()));
+        for old_sstable in old_sstable_iter {
+            if !new_sstable_paths
+                .iter()
+                .any(|entry| entry.1 == old_sstable.path)
+            {
+                old_sstable.mark_obsolete();
             }
-
-            Ok(true)
-        } else {
-            Ok(false)
         }
+
+        Ok(())
+    }
+
+    /// Sets the compression codec used for the data blocks of new SSTables. Existing SSTables are
+    /// not rewritten, but will be compressed when they are compacted. The codec is not persisted,
+    /// so it must be set again after the compaction strategy is opened. Defaults to
+    /// `Compression::None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{CompactionStrategy, LeveledStrategy};
+    /// use extended_collections::lsm_tree::Compression;
+    ///
+    /// let mut strategy: LeveledStrategy<u32, u32> =
+    ///     LeveledStrategy::new("leveled_strategy_set_compression", 10000, 4, 50000, 10, 10)?;
+    /// strategy.set_compression(Compression::Lz4);
+    /// assert_eq!(strategy.get_compression(), Compression::Lz4);
+    /// # fs::remove_dir_all("leveled_strategy_set_compression")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Sets the filters that are built for new SSTables. Existing SSTables keep their filters
+    /// until they are compacted. The filter policy is not persisted, so it must be set again after
+    /// the compaction strategy is opened. Defaults to `FilterPolicy::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{CompactionStrategy, LeveledStrategy};
+    /// use extended_collections::lsm_tree::FilterPolicy;
+    ///
+    /// let mut strategy: LeveledStrategy<u32, u32> =
+    ///     LeveledStrategy::new("leveled_strategy_set_filter_policy", 10000, 4, 50000, 10, 10)?;
+    /// strategy.set_filter_policy(FilterPolicy {
+    ///     false_positive_rate: 0.01,
+    ///     ..FilterPolicy::default()
+    /// });
+    /// assert_eq!(strategy.get_filter_policy().false_positive_rate, 0.01);
+    /// # fs::remove_dir_all("leveled_strategy_set_filter_policy")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_filter_policy(&mut self, filter_policy: FilterPolicy<T>) {
+        self.curr_metadata.lock().unwrap().filter_policy = filter_policy;
+    }
+
+    /// Sets the capacity in bytes of the block cache, which holds the checksummed and decompressed
+    /// index and data blocks that were read by point reads, range scans and cursors. Blocks are
+    /// evicted in least recently used order, and a capacity of `0` disables the block cache.
+    /// Compactions read blocks without filling the block cache. The capacity is not persisted, so
+    /// it must be set again after the compaction strategy is opened. Defaults to 8 MiB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::LeveledStrategy;
+    ///
+    /// let mut strategy: LeveledStrategy<u32, u32> =
+    ///     LeveledStrategy::new("leveled_strategy_set_block_cache_capacity", 10000, 4, 50000, 10, 10)?;
+    /// strategy.set_block_cache_capacity(64 * 1024 * 1024);
+    /// # fs::remove_dir_all("leveled_strategy_set_block_cache_capacity")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_block_cache_capacity(&mut self, capacity: usize) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .cache
+            .set_block_cache_capacity(capacity);
+    }
+
+    /// Sets the maximum number of index and data files of SSTables that the file cache keeps open
+    /// between reads. Files are closed in least recently used order, and a maximum of `0` disables
+    /// the file cache. The maximum is not persisted, so it must be set again after the compaction
+    /// strategy is opened. Defaults to 256.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::LeveledStrategy;
+    ///
+    /// let mut strategy: LeveledStrategy<u32, u32> =
+    ///     LeveledStrategy::new("leveled_strategy_set_max_open_files", 10000, 4, 50000, 10, 10)?;
+    /// strategy.set_max_open_files(1024);
+    /// # fs::remove_dir_all("leveled_strategy_set_max_open_files")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_max_open_files(&mut self, max_open_files: usize) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .cache
+            .set_max_open_files(max_open_files);
     }
 
     fn compact<P>(
@@ -296,6 +555,8 @@ where
         is_compacting: &Arc<AtomicBool>,
         mut metadata_snapshot: LeveledMetadata<T, U>,
         next_metadata: &Arc<Mutex<Option<LeveledMetadata<T, U>>>>,
+        snapshot_logical_times: BTreeSet<u64>,
+        compression: Compression,
     ) -> Result<()>
     where
         T: Clone + DeserializeOwned + Hash + Serialize,
@@ -303,6 +564,9 @@ where
         P: AsRef<Path>,
     {
         println!("Started compacting.");
+        let start_time = Instant::now();
+        metadata_snapshot.stats.record_compaction_start();
+        let old_sstables = metadata_snapshot.get_sstables();
 
         if metadata_snapshot.levels.is_empty() {
             metadata_snapshot.levels.push(BTreeMap::new());
@@ -326,26 +590,48 @@ where
             .map(|entry| entry.1.data_iter())
             .collect();
 
-        let mut sstable_builder = SSTableBuilder::new(path.as_ref(), entry_count_hint)?;
+        let mut sstable_builder = SSTableBuilder::new(
+            path.as_ref(),
+            entry_count_hint,
+            compression,
+            &metadata_snapshot.filter_policy,
+        )?;
 
-        let compaction_iter = LeveledIter::new(None, sstable_data_iters, vec![level_data_iter])?;
+        let compaction_iter = VersionFilter::new(
+            LeveledIter::new(sstable_data_iters, vec![level_data_iter])?,
+            snapshot_logical_times.clone(),
+            metadata_snapshot.levels.len() == 1,
+            metadata_snapshot.merge_operator.clone(),
+        );
 
         for entry in compaction_iter {
             let (key, value) = entry?;
 
-            if metadata_snapshot.levels.len() > 1 || value.data.is_some() {
-                sstable_builder.append(key, value)?;
-            }
-
-            if sstable_builder.size > metadata_snapshot.max_sstable_size {
-                let new_sstable = Arc::new(SSTable::new(sstable_builder.flush()?)?);
+            // all versions of a key must be in the same SSTable to keep a level non-overlapping
+            if sstable_builder.size > metadata_snapshot.max_sstable_size
+                && !Self::is_last_key(&sstable_builder, &key)
+            {
+                let new_sstable = Arc::new(SSTable::with_cache(
+                    sstable_builder.flush()?,
+                    &metadata_snapshot.cache,
+                )?);
                 metadata_snapshot.insert_sstable(0, new_sstable);
-                sstable_builder = SSTableBuilder::new(path.as_ref(), entry_count_hint)?;
+                sstable_builder = SSTableBuilder::new(
+                    path.as_ref(),
+                    entry_count_hint,
+                    compression,
+                    &metadata_snapshot.filter_policy,
+                )?;
             }
+
+            sstable_builder.append(key, value)?;
         }
 
         if sstable_builder.key_range.is_some() {
-            let new_sstable = Arc::new(SSTable::new(sstable_builder.flush()?)?);
+            let new_sstable = Arc::new(SSTable::with_cache(
+                sstable_builder.flush()?,
+                &metadata_snapshot.cache,
+            )?);
             metadata_snapshot.insert_sstable(0, new_sstable);
         }
 
@@ -377,7 +663,12 @@ where
                         .expect("Expected SSTable to remove to exist.")
                 };
 
-                let mut sstable_builder = SSTableBuilder::new(path.as_ref(), entry_count_hint)?;
+                let mut sstable_builder = SSTableBuilder::new(
+                    path.as_ref(),
+                    entry_count_hint,
+                    compression,
+                    &metadata_snapshot.filter_policy,
+                )?;
 
                 if index + 1 == metadata_snapshot.levels.len() {
                     metadata_snapshot.insert_sstable(index + 1, sstable);
@@ -396,36 +687,56 @@ where
 
                 metadata_snapshot.levels[index + 1] = new_level;
 
-                let compaction_iter = LeveledIter::new(
-                    None,
-                    vec![sstable_data_iter],
-                    vec![old_level
-                        .into_iter()
-                        .map(|level_entry| level_entry.1.data_iter())
-                        .collect()],
-                )?;
+                let compaction_iter = VersionFilter::new(
+                    LeveledIter::new(
+                        vec![sstable_data_iter],
+                        vec![old_level
+                            .into_iter()
+                            .map(|level_entry| level_entry.1.data_iter())
+                            .collect()],
+                    )?,
+                    snapshot_logical_times.clone(),
+                    index + 1 == metadata_snapshot.levels.len() - 1,
+                    metadata_snapshot.merge_operator.clone(),
+                );
 
                 for entry in compaction_iter {
                     let (key, value) = entry?;
 
-                    if index + 1 != metadata_snapshot.levels.len() - 1 || value.data.is_some() {
-                        sstable_builder.append(key, value)?;
-                    }
-
-                    if sstable_builder.size > metadata_snapshot.max_sstable_size {
-                        let new_sstable = Arc::new(SSTable::new(sstable_builder.flush()?)?);
+                    if sstable_builder.size > metadata_snapshot.max_sstable_size
+                        && !Self::is_last_key(&sstable_builder, &key)
+                    {
+                        let new_sstable = Arc::new(SSTable::with_cache(
+                            sstable_builder.flush()?,
+                            &metadata_snapshot.cache,
+                        )?);
                         metadata_snapshot.insert_sstable(index + 1, new_sstable);
-                        sstable_builder = SSTableBuilder::new(path.as_ref(), entry_count_hint)?;
+                        sstable_builder = SSTableBuilder::new(
+                            path.as_ref(),
+                            entry_count_hint,
+                            compression,
+                            &metadata_snapshot.filter_policy,
+                        )?;
                     }
+
+                    sstable_builder.append(key, value)?;
                 }
 
                 if sstable_builder.key_range.is_some() {
-                    let new_sstable = Arc::new(SSTable::new(sstable_builder.flush()?)?);
+                    let new_sstable = Arc::new(SSTable::with_cache(
+                        sstable_builder.flush()?,
+                        &metadata_snapshot.cache,
+                    )?);
                     metadata_snapshot.insert_sstable(index + 1, new_sstable);
                 }
             }
         }
 
+        metadata_snapshot.stats.record_compaction_finish(
+            &old_sstables,
+            &metadata_snapshot.get_sstables(),
+            start_time.elapsed(),
+        );
         *next_metadata.lock().unwrap() = Some(metadata_snapshot);
 
         is_compacting.store(false, Ordering::Release);
@@ -434,6 +745,13 @@ where
         Ok(())
     }
 
+    fn is_last_key(sstable_builder: &SSTableBuilder<T, U>, key: &T) -> bool {
+        match sstable_builder.key_range {
+            Some((_, ref last_key)) => last_key == key,
+            None => false,
+        }
+    }
+
     fn spawn_compaction_thread(&mut self, metadata_snapshot: LeveledMetadata<T, U>)
     where
         T: 'static + Clone + DeserializeOwned + Hash + Send + Serialize + Sync,
@@ -442,10 +760,23 @@ where
         let path = self.path.clone();
         let next_metadata = self.next_metadata.clone();
         let is_compacting = self.is_compacting.clone();
+        let snapshot_logical_times = self.snapshot_list.logical_times();
+        let compression = self.compression;
+        self.compacting_sstable_paths = metadata_snapshot
+            .sstables
+            .iter()
+            .map(|sstable| sstable.path.clone())
+            .collect();
         self.is_compacting.store(true, Ordering::Release);
         self.compaction_thread_join_handle = Some(thread::spawn(move || {
-            let compaction_result =
-                LeveledStrategy::compact(path, &is_compacting, metadata_snapshot, &next_metadata);
+            let compaction_result = LeveledStrategy::compact(
+                path,
+                &is_compacting,
+                metadata_snapshot,
+                &next_metadata,
+                snapshot_logical_times,
+                compression,
+            );
 
             match compaction_result {
                 Ok(_) => println!("Compaction terminated successfully."),
@@ -458,6 +789,103 @@ where
     }
 }
 
+impl<T, U> LeveledStrategy<T, U>
+where
+    T: 'static + Clone + DeserializeOwned + Hash + Ord + Send + Serialize + Sync,
+    U: 'static + Clone + DeserializeOwned + Send + Serialize + Sync,
+{
+    fn get_at<V>(&mut self, key: &V, logical_time: Option<u64>) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let mut versions = Vec::new();
+        for sstable in &curr_metadata.sstables {
+            versions.extend(
+                curr_metadata
+                    .stats
+                    .get_versions(sstable, key, logical_time)?,
+            );
+        }
+        versions.sort();
+
+        // each level only contains versions that are older than the versions in previous levels,
+        // so the levels are only searched until a value or tombstone is found
+        for level in &curr_metadata.levels {
+            if merge::is_resolved(&versions) {
+                break;
+            }
+
+            let sstable_opt = level
+                .range((Included(key), Unbounded))
+                .next()
+                .map(|entry| entry.1);
+            if let Some(sstable) = sstable_opt {
+                versions.extend(
+                    curr_metadata
+                        .stats
+                        .get_versions(sstable, key, logical_time)?,
+                );
+            }
+        }
+
+        merge::resolve_versions(versions, curr_metadata.merge_operator.as_ref())
+    }
+
+    fn range_at(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        logical_time: Option<u64>,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        let prefix = curr_metadata.filter_policy.get_range_prefix(&range);
+        let may_contain_prefix = |sstable: &Arc<SSTable<T, U>>| {
+            curr_metadata
+                .filter_policy
+                .may_contain_prefix(sstable, prefix.as_deref())
+        };
+
+        // sstables whose prefix filter does not contain the prefix of the range are skipped
+        let mut sorted_runs: Vec<SortedRun<T, U>> = curr_metadata
+            .sstables
+            .iter()
+            .filter(|sstable| sstable::is_intersecting_range(&sstable.summary.key_range, &range))
+            .filter(|sstable| may_contain_prefix(sstable))
+            .map(|sstable| vec![Arc::clone(sstable)])
+            .collect();
+
+        // each level is keyed by the maximum key of its SSTables, so the first SSTable that can
+        // intersect the range is the first one whose maximum key is not before the range
+        let level_start = match range.0 {
+            Included(ref key) | Excluded(ref key) => Included(key),
+            Unbounded => Unbounded,
+        };
+        sorted_runs.extend(curr_metadata.levels.iter().map(|level| {
+            level
+                .range::<T, _>((level_start, Unbounded))
+                .map(|level_entry| level_entry.1)
+                .take_while(|sstable| {
+                    sstable::is_intersecting_range(&sstable.summary.key_range, &range)
+                })
+                .filter(|sstable| may_contain_prefix(sstable))
+                .cloned()
+                .collect()
+        }));
+        let cursor = MergingCursor::new(Vec::new(), sorted_runs);
+        let compaction_iter = VisibleIter::new(
+            RangeIter::new(cursor, range)?,
+            logical_time,
+            curr_metadata.merge_operator.clone(),
+        );
+
+        Ok(Box::new(compaction_iter))
+    }
+}
+
 impl<T, U> CompactionStrategy<T, U> for LeveledStrategy<T, U>
 where
     T: 'static + Clone + DeserializeOwned + Hash + Ord + Send + Serialize + Sync,
@@ -467,6 +895,18 @@ where
         self.path.as_path()
     }
 
+    fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    fn get_filter_policy(&self) -> FilterPolicy<T> {
+        self.curr_metadata.lock().unwrap().filter_policy.clone()
+    }
+
+    fn set_merge_operator(&mut self, merge_operator: Arc<dyn MergeOperator<U>>) {
+        self.curr_metadata.lock().unwrap().merge_operator = Some(merge_operator);
+    }
+
     fn get_max_in_memory_size(&self) -> u64 {
         self.curr_metadata.lock().unwrap().max_in_memory_size
     }
@@ -480,27 +920,40 @@ where
         Ok(ret)
     }
 
-    fn try_compact(&mut self, sstable: SSTable<T, U>) -> Result<()> {
+    fn advance_logical_time(&mut self, logical_time: u64) -> Result<()> {
+        if logical_time > self.curr_logical_time {
+            self.curr_logical_time = logical_time;
+            self.logical_time_file.seek(SeekFrom::Start(0))?;
+            self.logical_time_file
+                .write_u64::<BigEndian>(self.curr_logical_time)?;
+        }
+        Ok(())
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        self.snapshot_list.acquire(self.curr_logical_time)
+    }
+
+    fn try_compact(&mut self, mut sstable: SSTable<T, U>) -> Result<()> {
         {
             let mut curr_metadata = self.curr_metadata.lock().unwrap();
+            sstable.set_cache(&curr_metadata.cache);
+            curr_metadata.stats.record_flush(&sstable);
+            let edit = VersionEdit {
+                added: vec![(None, sstable.path.clone())],
+                removed: Vec::new(),
+            };
             curr_metadata.push_sstable(Arc::new(sstable));
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
+            self.manifest.append(&edit, &*curr_metadata)?;
         }
 
-        if self.is_compacting.load(Ordering::Acquire) || self.metadata_lock_count.get() != 0 {
+        if self.is_compacting.load(Ordering::Acquire) {
             return Ok(());
         }
 
         // taking snapshot of current metadata
-        let metadata_snapshot = {
-            let mut curr_metadata = self.curr_metadata.lock().unwrap();
-            if self.try_replace_metadata(&mut curr_metadata)? {
-                self.metadata_file.seek(SeekFrom::Start(0))?;
-                self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-            }
-            curr_metadata.clone()
-        };
+        self.try_replace_metadata()?;
+        let metadata_snapshot = self.curr_metadata.lock().unwrap().clone();
 
         if metadata_snapshot.sstables.len() > metadata_snapshot.max_sstable_count {
             self.spawn_compaction_thread(metadata_snapshot);
@@ -516,59 +969,167 @@ where
                 Err(error) => println!("Child thread terminated with error: {:?}", error),
             }
 
-            let mut curr_metadata = self.curr_metadata.lock().unwrap();
-            if self.try_replace_metadata(&mut curr_metadata)? {
-                self.metadata_file.seek(SeekFrom::Start(0))?;
-                self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-            }
+            self.try_replace_metadata()?;
         }
         Ok(())
     }
 
-    fn get<V>(&mut self, key: &V) -> Result<Option<SSTableValue<U>>>
-    where
-        T: Borrow<V>,
-        V: Ord + Hash + ?Sized,
-    {
-        let mut curr_metadata = self.curr_metadata.lock().unwrap();
-        if self.try_replace_metadata(&mut curr_metadata)? {
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
+    fn compact_range(&mut self, range: (Bound<T>, Bound<T>)) -> Result<()> {
+        self.flush()?;
+        let mut metadata_snapshot = self.curr_metadata.lock().unwrap().clone();
+        let old_sstables = metadata_snapshot.get_sstables();
+        let compacting_sstable_paths = metadata_snapshot
+            .sstables
+            .iter()
+            .map(|sstable| sstable.path.clone())
+            .collect();
+        let (sstables, levels) = metadata_snapshot.remove_intersecting_sstables(&range);
+        if sstables.is_empty() && levels.iter().all(|level| level.is_empty()) {
+            return Ok(());
         }
 
-        let mut ret = None;
-        for sstable in &curr_metadata.sstables {
-            let res = sstable.get(&key)?;
-            if res.is_some() && (ret.is_none() || res < ret) {
-                ret = res;
+        let start_time = Instant::now();
+        metadata_snapshot.stats.record_compaction_start();
+        if metadata_snapshot.levels.is_empty() {
+            metadata_snapshot.levels.push(BTreeMap::new());
+        }
+        let last_index = metadata_snapshot.levels.len() - 1;
+        let entry_count_hint = sstables
+            .iter()
+            .chain(levels.iter().flatten())
+            .map(|sstable| sstable.summary.entry_count)
+            .max()
+            .unwrap_or(0);
+
+        // no remaining sstable intersects the compacted sstables, so all tombstones are purged
+        let compaction_iter = VersionFilter::new(
+            LeveledIter::new(
+                sstables.iter().map(|sstable| sstable.data_iter()).collect(),
+                levels
+                    .iter()
+                    .map(|level| level.iter().map(|sstable| sstable.data_iter()).collect())
+                    .collect(),
+            )?,
+            self.snapshot_list.logical_times(),
+            true,
+            metadata_snapshot.merge_operator.clone(),
+        );
+
+        let mut sstable_builder = SSTableBuilder::new(
+            self.path.as_path(),
+            entry_count_hint,
+            self.compression,
+            &metadata_snapshot.filter_policy,
+        )?;
+        for entry in compaction_iter {
+            let (key, value) = entry?;
+
+            if sstable_builder.size > metadata_snapshot.max_sstable_size
+                && !Self::is_last_key(&sstable_builder, &key)
+            {
+                let new_sstable = Arc::new(SSTable::with_cache(
+                    sstable_builder.flush()?,
+                    &metadata_snapshot.cache,
+                )?);
+                metadata_snapshot.insert_sstable(last_index, new_sstable);
+                sstable_builder = SSTableBuilder::new(
+                    self.path.as_path(),
+                    entry_count_hint,
+                    self.compression,
+                    &metadata_snapshot.filter_policy,
+                )?;
             }
+
+            sstable_builder.append(key, value)?;
         }
 
-        if ret.is_some() {
-            return Ok(ret);
+        if sstable_builder.key_range.is_some() {
+            let new_sstable = Arc::new(SSTable::with_cache(
+                sstable_builder.flush()?,
+                &metadata_snapshot.cache,
+            )?);
+            metadata_snapshot.insert_sstable(last_index, new_sstable);
         }
 
-        for level in &curr_metadata.levels {
-            let sstable_opt = level
-                .range((Included(key), Unbounded))
-                .next()
-                .map(|entry| entry.1);
-            if let Some(sstable) = sstable_opt {
-                if let Some(value) = sstable.get(key)? {
-                    return Ok(Some(value));
+        metadata_snapshot.stats.record_compaction_finish(
+            &old_sstables,
+            &metadata_snapshot.get_sstables(),
+            start_time.elapsed(),
+        );
+        self.compacting_sstable_paths = compacting_sstable_paths;
+        *self.next_metadata.lock().unwrap() = Some(metadata_snapshot);
+        self.try_replace_metadata()
+    }
+
+    fn ingest(&mut self, sstables: Vec<SSTable<T, U>>) -> Result<()> {
+        self.flush()?;
+
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let mut added = Vec::new();
+        for mut sstable in sstables {
+            sstable.set_cache(&curr_metadata.cache);
+
+            // the sstable is placed right above the first level that intersects it, so that it
+            // shadows the older versions of its keys
+            let location = {
+                let is_intersecting = |other: &Arc<SSTable<T, U>>| {
+                    sstable::is_intersecting(&sstable.summary.key_range, &other.summary.key_range)
+                };
+                if curr_metadata.sstables.iter().any(is_intersecting) {
+                    None
+                } else {
+                    let index = curr_metadata
+                        .levels
+                        .iter()
+                        .position(|level| level.values().any(is_intersecting));
+                    match index {
+                        Some(0) => None,
+                        Some(index) => Some(index - 1),
+                        None => Some(curr_metadata.levels.len().saturating_sub(1)),
+                    }
                 }
+            };
+
+            let sstable = Arc::new(sstable);
+            added.push((location, sstable.path.clone()));
+            match location {
+                Some(index) => curr_metadata.insert_sstable(index, sstable),
+                None => curr_metadata.push_sstable(sstable),
             }
         }
+        let edit = VersionEdit {
+            added,
+            removed: Vec::new(),
+        };
+        self.manifest.append(&edit, &*curr_metadata)
+    }
+
+    fn checkpoint(&mut self, path: &Path) -> Result<()> {
+        self.flush()?;
 
-        Ok(None)
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        manifest::create_checkpoint(path, &*curr_metadata, self.curr_logical_time)
+    }
+
+    fn get<V>(&mut self, key: &V) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.get_at(key, None)
+    }
+
+    fn get_snapshot<V>(&mut self, key: &V, snapshot: &Snapshot) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.get_at(key, Some(snapshot.get_logical_time()))
     }
 
     fn len_hint(&mut self) -> Result<usize> {
-        let mut curr_metadata = self.curr_metadata.lock().unwrap();
-        if self.try_replace_metadata(&mut curr_metadata)? {
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
 
         let sstables_len_hint: usize = curr_metadata
             .sstables
@@ -608,19 +1169,21 @@ where
 
         let mut curr_metadata = self.curr_metadata.lock().unwrap();
         let mut next_metadata = self.next_metadata.lock().unwrap();
-        curr_metadata.sstables.clear();
-        curr_metadata.levels.clear();
-        *next_metadata = None;
-
-        for dir_entry in fs::read_dir(self.path.as_path())? {
-            let dir_path = dir_entry?.path();
-            if dir_path.is_dir() {
-                fs::remove_dir_all(dir_path)?;
-            }
+        let mut old_sstables = mem::take(&mut curr_metadata.sstables);
+        let mut old_levels = mem::take(&mut curr_metadata.levels);
+        if let Some(next_metadata) = next_metadata.take() {
+            old_sstables.extend(next_metadata.sstables);
+            old_levels.extend(next_metadata.levels);
         }
 
-        self.metadata_file.seek(SeekFrom::Start(0))?;
-        self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
+        // the manifest is rewritten before the sstables are removed
+        self.manifest.rotate(&*curr_metadata)?;
+        let old_sstable_iter = old_sstables
+            .iter()
+            .chain(old_levels.iter().flat_map(|level| level.values()));
+        for sstable in old_sstable_iter {
+            sstable.mark_obsolete();
+        }
 
         Ok(())
     }
@@ -633,51 +1196,97 @@ where
     }
 
     fn max(&mut self) -> Result<Option<T>> {
-        match self.iter()?.last() {
+        match self.iter()?.next_back() {
             Some(entry) => Ok(Some(entry?.0)),
             None => Ok(None),
         }
     }
 
     fn iter(&mut self) -> Result<Box<CompactionIter<T, U>>> {
-        let mut curr_metadata = self.curr_metadata.lock().unwrap();
-        // should never need to replace metadata as the compaction thread should not be running
-        // when yielding calling iter.
-        if self.try_replace_metadata(&mut curr_metadata)? {
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
+        let compaction_iter = self
+            .range((Unbounded, Unbounded))?
+            .filter_map(|entry_result| match entry_result {
+                Ok(entry) => {
+                    let (key, value) = entry;
+                    value.data.into_value().map(|value| Ok((key, value)))
+                }
+                Err(error) => Some(Err(error)),
+            });
+
+        Ok(Box::new(compaction_iter))
+    }
 
-        let sstable_data_iters = curr_metadata
+    fn range(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.range_at(range, None)
+    }
+
+    fn range_snapshot(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        snapshot: &Snapshot,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.range_at(range, Some(snapshot.get_logical_time()))
+    }
+
+    fn get_sorted_runs(&mut self) -> Result<Vec<SortedRun<T, U>>> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let mut sorted_runs: Vec<SortedRun<T, U>> = curr_metadata
             .sstables
             .iter()
-            .map(|sstable| sstable.data_iter())
+            .map(|sstable| vec![Arc::clone(sstable)])
             .collect();
-        let level_data_iters = curr_metadata
-            .levels
-            .iter()
-            .map(|level| {
-                level
+        sorted_runs.extend(
+            curr_metadata
+                .levels
+                .iter()
+                .map(|level| level.values().cloned().collect()),
+        );
+
+        Ok(sorted_runs)
+    }
+
+    fn stats(&mut self) -> Result<CompactionStats> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        let levels = iter::once(LevelStats::new(&curr_metadata.sstables))
+            .chain(
+                curr_metadata
+                    .levels
                     .iter()
-                    .map(|level_entry| level_entry.1.data_iter())
-                    .collect()
-            })
+                    .map(|level| LevelStats::new(level.values())),
+            )
             .collect();
-        let metadata_lock_count = Rc::clone(&self.metadata_lock_count);
-        let compaction_iter = LeveledIter::new(
-            Some(metadata_lock_count),
-            sstable_data_iters,
-            level_data_iters,
-        )?
-        .filter_map(|entry_result| match entry_result {
-            Ok(entry) => {
-                let (key, value) = entry;
-                value.data.map(|value| Ok((key, value)))
+        Ok(curr_metadata.stats.stats(levels, &curr_metadata.cache))
+    }
+
+    fn set_event_listener(&mut self, event_listener: Arc<dyn EventListener>) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .stats
+            .set_event_listener(event_listener);
+    }
+
+    fn verify(&mut self) -> Result<()> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        for sstable in &curr_metadata.sstables {
+            sstable.verify()?;
+        }
+
+        for level in &curr_metadata.levels {
+            for sstable in level.values() {
+                sstable.verify()?;
             }
-            Err(error) => Some(Err(error)),
-        });
+        }
 
-        Ok(Box::new(compaction_iter))
+        Ok(())
     }
 }
 
@@ -690,11 +1299,9 @@ enum LeveledIterEntryIndex {
 type LeveledIterEntry<T, U> = cmp::Reverse<(T, SSTableValue<U>, LeveledIterEntryIndex)>;
 
 struct LeveledIter<T, U> {
-    metadata_lock_count: Option<Rc<Cell<u64>>>,
     sstable_data_iters: Vec<SSTableDataIter<T, U>>,
     level_data_iters: Vec<VecDeque<SSTableDataIter<T, U>>>,
     entries: BinaryHeap<LeveledIterEntry<T, U>>,
-    last_key_opt: Option<T>,
 }
 
 impl<T, U> LeveledIter<T, U>
@@ -719,14 +1326,9 @@ where
     }
 
     pub fn new(
-        metadata_lock_count: Option<Rc<Cell<u64>>>,
         mut sstable_data_iters: Vec<SSTableDataIter<T, U>>,
         mut level_data_iters: Vec<VecDeque<SSTableDataIter<T, U>>>,
     ) -> Result<Self> {
-        if let Some(ref metadata_lock_count) = metadata_lock_count {
-            metadata_lock_count.set(metadata_lock_count.get() + 1);
-        }
-
         let mut entries = BinaryHeap::new();
 
         for (index, sstable_data_iter) in sstable_data_iters.iter_mut().enumerate() {
@@ -752,11 +1354,9 @@ where
         }
 
         Ok(LeveledIter {
-            metadata_lock_count,
             sstable_data_iters,
             level_data_iters,
             entries,
-            last_key_opt: None,
         })
     }
 }
@@ -769,42 +1369,23 @@ where
     type Item = Result<(T, SSTableValue<U>)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(cmp::Reverse((key, value, index))) = self.entries.pop() {
-            let entry_opt = match index {
-                LeveledIterEntryIndex::LevelIndex(index) => {
-                    Self::get_next_level_entry(&mut self.level_data_iters[index])
-                }
-                LeveledIterEntryIndex::SSTableIndex(index) => self.sstable_data_iters[index].next(),
-            };
-
-            if let Some(entry) = entry_opt {
-                match entry {
-                    Ok(entry) => self
-                        .entries
-                        .push(cmp::Reverse((entry.key, entry.value, index))),
-                    Err(error) => return Some(Err(error)),
-                }
+        let cmp::Reverse((key, value, index)) = self.entries.pop()?;
+        let entry_opt = match index {
+            LeveledIterEntryIndex::LevelIndex(index) => {
+                Self::get_next_level_entry(&mut self.level_data_iters[index])
             }
+            LeveledIterEntryIndex::SSTableIndex(index) => self.sstable_data_iters[index].next(),
+        };
 
-            let should_return = match self.last_key_opt {
-                Some(ref last_key) => *last_key != key,
-                None => true,
-            };
-
-            self.last_key_opt = Some(key.clone());
-
-            if should_return {
-                return Some(Ok((key, value)));
+        if let Some(entry) = entry_opt {
+            match entry {
+                Ok(entry) => self
+                    .entries
+                    .push(cmp::Reverse((entry.key, entry.value, index))),
+                Err(error) => return Some(Err(error)),
             }
         }
-        None
-    }
-}
 
-impl<T, U> Drop for LeveledIter<T, U> {
-    fn drop(&mut self) {
-        if let Some(ref metadata_lock_count) = self.metadata_lock_count {
-            metadata_lock_count.set(metadata_lock_count.get() - 1);
-        }
+        Some(Ok((key, value)))
     }
 }
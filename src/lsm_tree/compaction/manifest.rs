@@ -0,0 +1,359 @@
+use crate::lsm_tree::{Error, Result};
+use crate::wal;
+use bincode::{deserialize, serialize};
+use byteorder::{BigEndian, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE_NAME: &str = "manifest.dat";
+const TEMPORARY_MANIFEST_FILE_NAME: &str = "manifest.tmp";
+
+// The number of version edits after which the manifest is rewritten as a single version edit.
+const MAX_EDIT_COUNT: usize = 1024;
+
+/// The location of a SSTable in a compaction strategy. `None` denotes the SSTables that are not
+/// in any level.
+pub type SSTableLocation = Option<usize>;
+
+/// The location and path of a SSTable in a compaction strategy.
+pub type SSTablePath = (SSTableLocation, PathBuf);
+
+/// Metadata of a compaction strategy that can be persisted in a manifest. The SSTables of the
+/// metadata must not be serialized, as they are persisted as version edits.
+pub trait ManifestMetadata: Serialize {
+    /// Returns the location and path of every SSTable in the metadata.
+    fn get_sstable_paths(&self) -> Vec<SSTablePath>;
+}
+
+/// A change to the set of SSTables of a compaction strategy. Removals are applied before
+/// additions, so a SSTable can be moved by removing it and adding it to a different location.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct VersionEdit {
+    pub added: Vec<SSTablePath>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl VersionEdit {
+    pub fn new(old_sstable_paths: &[SSTablePath], new_sstable_paths: &[SSTablePath]) -> Self {
+        let old_set: HashSet<_> = old_sstable_paths.iter().collect();
+        let new_set: HashSet<_> = new_sstable_paths.iter().collect();
+        VersionEdit {
+            added: new_sstable_paths
+                .iter()
+                .filter(|entry| !old_set.contains(entry))
+                .cloned()
+                .collect(),
+            removed: old_sstable_paths
+                .iter()
+                .filter(|entry| !new_set.contains(entry))
+                .map(|entry| entry.1.clone())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    fn apply(self, sstable_paths: &mut Vec<SSTablePath>) {
+        let removed: HashSet<_> = self.removed.into_iter().collect();
+        sstable_paths.retain(|entry| !removed.contains(&entry.1));
+        sstable_paths.extend(self.added);
+    }
+}
+
+/// An append-only log of the version edits of a compaction strategy.
+///
+/// The first record of the manifest is the metadata of the compaction strategy without its
+/// SSTables, and every following record is a version edit. When the manifest becomes too long,
+/// it is rewritten into a temporary file with a single version edit that adds all current
+/// SSTables, and then atomically renamed over the existing manifest.
+pub struct Manifest {
+    path: PathBuf,
+    manifest_file: fs::File,
+    edit_count: usize,
+}
+
+impl Manifest {
+    /// Returns `true` if a manifest exists in a directory.
+    pub fn exists<P>(path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        path.as_ref().join(MANIFEST_FILE_NAME).exists()
+    }
+
+    /// Creates a new manifest in a directory with the current state of some metadata, replacing
+    /// any existing manifest.
+    pub fn create<P, M>(path: P, metadata: &M) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        M: ManifestMetadata,
+    {
+        let path = PathBuf::from(path.as_ref());
+        write_manifest(path.as_path(), metadata, metadata.get_sstable_paths())?;
+
+        let manifest_path = path.join(MANIFEST_FILE_NAME);
+        let mut manifest_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(manifest_path.as_path())?;
+        manifest_file.seek(SeekFrom::End(0))?;
+
+        Ok(Manifest {
+            path,
+            manifest_file,
+            edit_count: 1,
+        })
+    }
+
+    /// Opens the manifest in a directory and returns the metadata and the location and path of
+    /// every SSTable after all version edits are applied. Any incomplete version edit at the end
+    /// of the manifest is discarded, and a corrupted version edit in the middle of the manifest
+    /// returns `Error::Corruption`.
+    pub fn open<P, M>(path: P) -> Result<(Self, M, Vec<SSTablePath>)>
+    where
+        P: AsRef<Path>,
+        M: DeserializeOwned,
+    {
+        let path = PathBuf::from(path.as_ref());
+        let manifest_path = path.join(MANIFEST_FILE_NAME);
+        let mut manifest_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(manifest_path.as_path())?;
+
+        let records =
+            wal::read_records(&mut manifest_file)?.map_err(|offset| Error::Corruption {
+                path: manifest_path.clone(),
+                offset,
+            })?;
+
+        let mut record_iter = records.into_iter();
+        let metadata = match record_iter.next() {
+            Some(record) => deserialize(&record)?,
+            None => {
+                return Err(Error::Corruption {
+                    path: manifest_path,
+                    offset: 0,
+                })
+            }
+        };
+
+        let mut sstable_paths = Vec::new();
+        let mut edit_count = 0;
+        for record in record_iter {
+            let edit: VersionEdit = deserialize(&record)?;
+            edit.apply(&mut sstable_paths);
+            edit_count += 1;
+        }
+
+        let manifest = Manifest {
+            path,
+            manifest_file,
+            edit_count,
+        };
+        Ok((manifest, metadata, sstable_paths))
+    }
+
+    /// Appends a version edit to the manifest. The metadata is the state after the version edit
+    /// is applied and is used to rewrite the manifest if it becomes too long.
+    pub fn append<M>(&mut self, edit: &VersionEdit, metadata: &M) -> Result<()>
+    where
+        M: ManifestMetadata,
+    {
+        if edit.is_empty() {
+            return Ok(());
+        }
+
+        if self.edit_count >= MAX_EDIT_COUNT {
+            return self.rotate(metadata);
+        }
+
+        self.manifest_file
+            .write_all(&wal::encode_record(&serialize(edit)?))?;
+        self.manifest_file.sync_data()?;
+        self.edit_count += 1;
+        Ok(())
+    }
+
+    /// Rewrites the manifest with the current state of some metadata.
+    pub fn rotate<M>(&mut self, metadata: &M) -> Result<()>
+    where
+        M: ManifestMetadata,
+    {
+        *self = Manifest::create(self.path.as_path(), metadata)?;
+        Ok(())
+    }
+}
+
+// Writes a manifest with some metadata and a single version edit that adds some SSTables into a
+// temporary file, and then atomically renames it over any existing manifest.
+fn write_manifest<M>(path: &Path, metadata: &M, sstable_paths: Vec<SSTablePath>) -> Result<()>
+where
+    M: ManifestMetadata,
+{
+    let temporary_path = path.join(TEMPORARY_MANIFEST_FILE_NAME);
+    let manifest_path = path.join(MANIFEST_FILE_NAME);
+
+    let edit = VersionEdit {
+        added: sstable_paths,
+        removed: Vec::new(),
+    };
+    let mut buffer = wal::encode_record(&serialize(metadata)?);
+    buffer.extend(wal::encode_record(&serialize(&edit)?));
+
+    let mut temporary_file = fs::File::create(temporary_path.as_path())?;
+    temporary_file.write_all(&buffer)?;
+    temporary_file.sync_all()?;
+    drop(temporary_file);
+
+    fs::rename(temporary_path.as_path(), manifest_path.as_path())?;
+    fs::File::open(path)?.sync_all()?;
+    Ok(())
+}
+
+/// Writes a checkpoint of the SSTables, metadata and logical time of a compaction strategy into a
+/// new directory. The files of each SSTable are hard linked into the directory, or copied if they
+/// cannot be linked, and the manifest of the checkpoint refers to the linked SSTables. SSTables
+/// are never modified after they are written, so the checkpoint is not affected by later writes.
+pub fn create_checkpoint<P, M>(path: P, metadata: &M, logical_time: u64) -> Result<()>
+where
+    P: AsRef<Path>,
+    M: ManifestMetadata,
+{
+    fs::create_dir(path.as_ref())?;
+
+    let mut sstable_paths = Vec::new();
+    for (location, sstable_path) in metadata.get_sstable_paths() {
+        let checkpoint_sstable_path = match sstable_path.file_name() {
+            Some(sstable_name) => path.as_ref().join(sstable_name),
+            None => {
+                let message = "Expected a SSTable directory.";
+                return Err(Error::from(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    message,
+                )));
+            }
+        };
+        fs::create_dir(checkpoint_sstable_path.as_path())?;
+        for dir_entry in fs::read_dir(sstable_path.as_path())? {
+            let dir_entry = dir_entry?;
+            let file_path = checkpoint_sstable_path.join(dir_entry.file_name());
+            if fs::hard_link(dir_entry.path(), file_path.as_path()).is_err() {
+                fs::copy(dir_entry.path(), file_path.as_path())?;
+                fs::File::open(file_path.as_path())?.sync_all()?;
+            }
+        }
+        fs::File::open(checkpoint_sstable_path.as_path())?.sync_all()?;
+        sstable_paths.push((location, checkpoint_sstable_path));
+    }
+
+    let mut logical_time_file = fs::File::create(path.as_ref().join("logical_time.dat"))?;
+    logical_time_file.write_u64::<BigEndian>(logical_time)?;
+    logical_time_file.sync_all()?;
+
+    // the manifest is written last, so an incomplete checkpoint cannot be opened
+    write_manifest(path.as_ref(), metadata, sstable_paths)
+}
+
+/// Removes all directories in the directory of a compaction strategy that are not SSTables in a
+/// set of SSTable paths. These directories are left behind by flushes or compactions that did
+/// not complete, or by compactions whose SSTables were not removed before a crash.
+pub fn remove_unreferenced_sstables<P>(path: P, sstable_paths: &[SSTablePath]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let sstable_names: HashSet<OsString> = sstable_paths
+        .iter()
+        .filter_map(|entry| entry.1.file_name().map(OsString::from))
+        .collect();
+
+    for dir_entry in fs::read_dir(path.as_ref())? {
+        let dir_entry = dir_entry?;
+        if dir_entry.file_type()?.is_dir() && !sstable_names.contains(&dir_entry.file_name()) {
+            fs::remove_dir_all(dir_entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsm_tree::compaction::LeveledStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use std::fs;
+    use std::io::Write;
+    use std::panic;
+    use std::path::Path;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_recovery() {
+        let test_name = "test_manifest_recovery";
+        run_test(
+            || {
+                let ls = LeveledStrategy::new(test_name, 1000, 4, 4000, 10, 10)?;
+                let mut map = LsmMap::new(ls)?;
+
+                for key in 0..5000u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.flush()?;
+                drop(map);
+
+                // a SSTable from an incomplete compaction and a torn version edit
+                let orphan_path = Path::new(test_name).join("orphan");
+                fs::create_dir(&orphan_path)?;
+                fs::write(orphan_path.join("data.dat"), [1, 2, 3])?;
+                let mut manifest_file = fs::OpenOptions::new()
+                    .append(true)
+                    .open(Path::new(test_name).join("manifest.dat"))?;
+                manifest_file.write_all(&[0, 0, 0, 0, 0, 0, 0, 100, 1, 2, 3])?;
+                drop(manifest_file);
+
+                let ls: LeveledStrategy<u32, u64> = LeveledStrategy::open(test_name)?;
+                let mut map = LsmMap::new(ls)?;
+                assert!(!orphan_path.exists());
+                assert_eq!(map.len()?, 5000);
+                for key in 0..5000u32 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+
+                for key in 5000..10_000u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.flush()?;
+                drop(map);
+
+                let ls: LeveledStrategy<u32, u64> = LeveledStrategy::open(test_name)?;
+                let mut map = LsmMap::new(ls)?;
+                assert_eq!(map.len()?, 10_000);
+                map.verify()?;
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
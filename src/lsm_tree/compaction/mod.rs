@@ -1,18 +1,30 @@
 //! Strategies for merging disk-resident sorted runs of data.
 
 mod leveled;
+mod manifest;
 mod size_tiered;
+mod stats;
+mod time_window;
 
 pub use self::leveled::LeveledStrategy;
 pub use self::size_tiered::SizeTieredStrategy;
+pub use self::stats::{CompactionStats, Event, EventListener, LevelStats};
+pub use self::time_window::TimeWindowStrategy;
 
-use crate::lsm_tree::{Result, SSTable, SSTableValue};
+use crate::lsm_tree::{
+    Compression, FilterPolicy, MergeOperator, Result, SSTable, SSTableValue, Snapshot, SortedRun,
+};
 use std::borrow::Borrow;
 use std::hash::Hash;
+use std::ops::Bound;
 use std::path::Path;
+use std::sync::Arc;
 
-/// An iterator for the disk-resident data.
-pub type CompactionIter<T, U> = dyn Iterator<Item = Result<(T, U)>>;
+/// An iterator for the disk-resident data that can also be iterated in descending order of keys.
+pub type CompactionIter<T, U> = dyn DoubleEndedIterator<Item = Result<(T, U)>>;
+
+/// An iterator over the keys of the data that can also be iterated in descending order.
+pub type KeyIter<T> = dyn DoubleEndedIterator<Item = Result<T>>;
 
 /// Trait for types that have compaction logic for disk-resident data.
 ///
@@ -22,12 +34,31 @@ pub trait CompactionStrategy<T, U> {
     /// Returns the path of the disk-resident data.
     fn get_path(&self) -> &Path;
 
+    /// Returns the compression codec used for the data blocks of new SSTables.
+    fn get_compression(&self) -> Compression;
+
+    /// Returns the filters that are built for new SSTables.
+    fn get_filter_policy(&self) -> FilterPolicy<T>;
+
+    /// Sets the merge operator used to fold merge operands during reads and compactions. The merge
+    /// operator is not persisted, so it must be set again after the compaction strategy is opened.
+    fn set_merge_operator(&mut self, merge_operator: Arc<dyn MergeOperator<U>>);
+
     /// Returns the maximum size of the in-memory tree in bytes.
     fn get_max_in_memory_size(&self) -> u64;
 
     /// Returns and increments the current logical time of the compaction strategy.
     fn get_and_increment_logical_time(&mut self) -> Result<u64>;
 
+    /// Raises the current logical time of the compaction strategy to `logical_time` if it is
+    /// smaller, so that later writes are newer than every version that was recovered from the
+    /// write-ahead log.
+    fn advance_logical_time(&mut self, logical_time: u64) -> Result<()>;
+
+    /// Returns a snapshot pinned at the current logical time of the compaction strategy. While the
+    /// snapshot is alive, compactions will keep all versions of entries visible to the snapshot.
+    fn snapshot(&mut self) -> Snapshot;
+
     /// Adds a SSTable to the compaction strategy and compacts the SSTables being tracked, if
     /// needed.
     fn try_compact(&mut self, sstable: SSTable<T, U>) -> Result<()>;
@@ -36,6 +67,24 @@ pub trait CompactionStrategy<T, U> {
     /// the compaction strategy.
     fn flush(&mut self) -> Result<()>;
 
+    /// Waits until the current compaction thread, if any, terminates and then compacts every
+    /// SSTable whose keys intersect a range into a fully merged run in the calling thread. The
+    /// versions that are shadowed by a newer version and are not visible to any live snapshot are
+    /// dropped, and tombstones are dropped if no remaining SSTable can contain an older version of
+    /// their key.
+    fn compact_range(&mut self, range: (Bound<T>, Bound<T>)) -> Result<()>;
+
+    /// Waits until the current compaction thread, if any, terminates and then adds SSTables that
+    /// were written outside of the compaction strategy and whose key ranges do not overlap. The
+    /// entries of the SSTables must be newer than all entries of the compaction strategy. Each
+    /// SSTable is placed into the deepest level where it is not shadowed by an older SSTable.
+    fn ingest(&mut self, sstables: Vec<SSTable<T, U>>) -> Result<()>;
+
+    /// Waits until the current compaction thread, if any, terminates and then writes a checkpoint
+    /// of the compaction strategy into a new directory that can be opened as a compaction strategy
+    /// of the same type.
+    fn checkpoint(&mut self, path: &Path) -> Result<()>;
+
     /// Searches through disk-resident data and returns the value associated with a particular key.
     /// It will return `None` if the key does not exist in the disk-resident data.
     fn get<V>(&mut self, key: &V) -> Result<Option<SSTableValue<U>>>
@@ -43,6 +92,14 @@ pub trait CompactionStrategy<T, U> {
         T: Borrow<V>,
         V: Ord + Hash + ?Sized;
 
+    /// Searches through disk-resident data and returns the value associated with a particular key
+    /// that is visible to a snapshot. It will return `None` if the key does not exist in the
+    /// disk-resident data at the time of the snapshot.
+    fn get_snapshot<V>(&mut self, key: &V, snapshot: &Snapshot) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized;
+
     /// Returns the approximate number of items in the disk-resident data.
     fn len_hint(&mut self) -> Result<usize>;
 
@@ -64,6 +121,41 @@ pub trait CompactionStrategy<T, U> {
     fn max(&mut self) -> Result<Option<T>>;
 
     /// Returns an iterator over the disk-resident data. The iterator will yield key-value pairs
-    /// in ascending order.
+    /// in ascending order, or in descending order if it is reversed.
     fn iter(&mut self) -> Result<Box<CompactionIter<T, U>>>;
+
+    /// Returns an iterator over the disk-resident data with keys in a particular range. The
+    /// iterator will yield the most recent value of each key in ascending order, including
+    /// tombstones so that the values can be merged with the in-memory tree. SSTables whose keys
+    /// do not intersect the range are skipped.
+    fn range(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>>;
+
+    /// Returns an iterator over the disk-resident data with keys in a particular range that is
+    /// visible to a snapshot. The iterator will yield the most recent value of each key at the
+    /// time of the snapshot in ascending order, including tombstones.
+    fn range_snapshot(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        snapshot: &Snapshot,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>>;
+
+    /// Returns the current SSTables of the disk-resident data as sorted runs. The SSTables are
+    /// reference counted, so they remain readable after they are compacted until the sorted runs
+    /// are dropped.
+    fn get_sorted_runs(&mut self) -> Result<Vec<SortedRun<T, U>>>;
+
+    /// Returns statistics of the SSTables of the disk-resident data, and of the flushes,
+    /// compactions and filter probes since the compaction strategy was constructed or opened.
+    fn stats(&mut self) -> Result<CompactionStats>;
+
+    /// Sets the listener that is notified of flushes and compactions. The listener is not
+    /// persisted, so it must be set again after the compaction strategy is opened.
+    fn set_event_listener(&mut self, event_listener: Arc<dyn EventListener>);
+
+    /// Verifies the checksums of every block and file of every SSTable in the disk-resident data.
+    /// Returns `Error::Corruption` for the first corrupted block or file that is found.
+    fn verify(&mut self) -> Result<()>;
 }
@@ -1,26 +1,38 @@
 use crate::entry::Entry;
-use crate::lsm_tree::compaction::{CompactionIter, CompactionStrategy};
-use crate::lsm_tree::{sstable, Result, SSTable, SSTableBuilder, SSTableDataIter, SSTableValue};
-use bincode::{deserialize, serialize};
+use crate::lsm_tree::cache::TableCache;
+use crate::lsm_tree::compaction::manifest::{
+    self, Manifest, ManifestMetadata, SSTablePath, VersionEdit,
+};
+use crate::lsm_tree::compaction::stats::{LevelStats, StatsRecorder};
+use crate::lsm_tree::compaction::{
+    CompactionIter, CompactionStats, CompactionStrategy, EventListener,
+};
+use crate::lsm_tree::merge;
+use crate::lsm_tree::{
+    sstable, Compression, FilterPolicy, MergeOperator, MergingCursor, RangeIter, Result, SSTable,
+    SSTableBuilder, SSTableDataIter, SSTableValue, Snapshot, SnapshotList, SortedRun,
+    VersionFilter, VisibleIter,
+};
+use bincode::deserialize;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Borrow;
-use std::cell::Cell;
 use std::cmp;
-use std::collections::{BinaryHeap, HashSet};
+use std::collections::{BTreeSet, BinaryHeap, HashSet};
 use std::fs;
 use std::hash::Hash;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Seek, SeekFrom};
 use std::iter::FromIterator;
 use std::marker::Send;
 use std::mem;
+use std::ops::Bound::{self, Unbounded};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(deserialize = "T: DeserializeOwned, U: DeserializeOwned"))]
@@ -30,6 +42,15 @@ struct SizeTieredMetadata<T, U> {
     min_sstable_size: u64,
     bucket_low: f64,
     bucket_high: f64,
+    #[serde(skip)]
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    #[serde(skip)]
+    stats: Arc<StatsRecorder>,
+    #[serde(skip)]
+    cache: Arc<TableCache>,
+    #[serde(skip)]
+    filter_policy: FilterPolicy<T>,
+    #[serde(skip)]
     sstables: Vec<Arc<SSTable<T, U>>>,
 }
 
@@ -47,6 +68,10 @@ impl<T, U> SizeTieredMetadata<T, U> {
             min_sstable_size,
             bucket_low,
             bucket_high,
+            merge_operator: None,
+            stats: Arc::new(StatsRecorder::default()),
+            cache: Arc::new(TableCache::default()),
+            filter_policy: FilterPolicy::default(),
             sstables: Vec::new(),
         }
     }
@@ -88,7 +113,45 @@ impl<T, U> SizeTieredMetadata<T, U> {
         }
     }
 
-    fn compact<P>(&mut self, path: P, range: (usize, usize)) -> Result<()>
+    // groups the sstables into buckets of similar sizes in ascending order of size
+    pub fn get_buckets(&self) -> Vec<Vec<Arc<SSTable<T, U>>>> {
+        let mut sstables = self.sstables.clone();
+        sstables.sort_by_key(|sstable| sstable.summary.size);
+
+        let mut buckets: Vec<Vec<Arc<SSTable<T, U>>>> = Vec::new();
+        let mut bucket_size = 0;
+        for sstable in sstables {
+            let curr_size = sstable.summary.size;
+            let in_last_bucket = match buckets.last() {
+                Some(bucket) => {
+                    let start_size = bucket[0].summary.size;
+                    let curr_avg = (bucket_size + curr_size) as f64 / (bucket.len() + 1) as f64;
+                    let in_min_bucket = curr_size <= self.min_sstable_size;
+                    let in_bucket = curr_avg * self.bucket_low <= start_size as f64
+                        && curr_size as f64 <= curr_avg * self.bucket_high;
+                    in_min_bucket || in_bucket
+                }
+                None => false,
+            };
+
+            if in_last_bucket {
+                buckets.last_mut().unwrap().push(sstable);
+            } else {
+                buckets.push(vec![sstable]);
+                bucket_size = 0;
+            }
+            bucket_size += curr_size;
+        }
+        buckets
+    }
+
+    fn compact<P>(
+        &mut self,
+        path: P,
+        range: (usize, usize),
+        snapshot_logical_times: BTreeSet<u64>,
+        compression: Compression,
+    ) -> Result<()>
     where
         T: Clone + DeserializeOwned + Hash + Ord + Serialize,
         U: DeserializeOwned + Serialize,
@@ -125,6 +188,8 @@ impl<T, U> SizeTieredMetadata<T, U> {
                 .iter()
                 .map(|sstable| sstable.summary.entry_count)
                 .sum(),
+            compression,
+            &self.filter_policy,
         )?;
 
         let old_sstable_data_iters = old_sstables
@@ -132,25 +197,35 @@ impl<T, U> SizeTieredMetadata<T, U> {
             .map(|sstable| sstable.data_iter())
             .collect();
 
-        drop(old_sstables);
-
-        let compaction_iter = SizeTieredIter::new(None, old_sstable_data_iters)?;
+        let compaction_iter = VersionFilter::new(
+            SizeTieredIter::new(old_sstable_data_iters)?,
+            snapshot_logical_times,
+            purge_tombstone,
+            self.merge_operator.clone(),
+        );
         for entry in compaction_iter {
             let (key, value) = entry?;
-
-            if !purge_tombstone || value.data.is_some() {
-                sstable_builder.append(key, value)?;
-            }
+            sstable_builder.append(key, value)?;
         }
 
         if sstable_builder.key_range.is_some() {
-            self.push_sstable(Arc::new(SSTable::new(sstable_builder.flush()?)?));
+            let sstable = SSTable::with_cache(sstable_builder.flush()?, &self.cache)?;
+            self.push_sstable(Arc::new(sstable));
         }
 
         Ok(())
     }
 }
 
+impl<T, U> ManifestMetadata for SizeTieredMetadata<T, U> {
+    fn get_sstable_paths(&self) -> Vec<SSTablePath> {
+        self.sstables
+            .iter()
+            .map(|sstable| (None, sstable.path.clone()))
+            .collect()
+    }
+}
+
 /// A compaction strategy based on bucketing SSTables by their sizes and then compacting buckets
 /// when they become too full.
 ///
@@ -172,8 +247,10 @@ pub struct SizeTieredStrategy<T, U> {
     is_compacting: Arc<AtomicBool>,
     curr_logical_time: u64,
     logical_time_file: fs::File,
-    metadata_lock_count: Rc<Cell<u64>>,
-    metadata_file: fs::File,
+    snapshot_list: SnapshotList,
+    compacting_sstable_paths: HashSet<PathBuf>,
+    compression: Compression,
+    manifest: Manifest,
     curr_metadata: Arc<Mutex<SizeTieredMetadata<T, U>>>,
     next_metadata: Arc<Mutex<Option<SizeTieredMetadata<T, U>>>>,
 }
@@ -211,41 +288,32 @@ impl<T, U> SizeTieredStrategy<T, U> {
     {
         fs::create_dir(path.as_ref())?;
 
-        let metadata_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path.as_ref().join("metadata.dat"))?;
+        let metadata = SizeTieredMetadata::new(
+            max_in_memory_size,
+            max_sstable_count,
+            min_sstable_size,
+            bucket_low,
+            bucket_high,
+        );
+        let manifest = Manifest::create(path.as_ref(), &metadata)?;
         let logical_time_file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
             .open(path.as_ref().join("logical_time.dat"))?;
-        let mut ret = SizeTieredStrategy {
+        Ok(SizeTieredStrategy {
             path: PathBuf::from(path.as_ref()),
             compaction_thread_join_handle: None,
             is_compacting: Arc::new(AtomicBool::new(false)),
             curr_logical_time: 0,
             logical_time_file,
-            metadata_lock_count: Rc::new(Cell::new(0)),
-            metadata_file,
-            curr_metadata: Arc::new(Mutex::new(SizeTieredMetadata::new(
-                max_in_memory_size,
-                max_sstable_count,
-                min_sstable_size,
-                bucket_low,
-                bucket_high,
-            ))),
+            snapshot_list: SnapshotList::new(),
+            compacting_sstable_paths: HashSet::new(),
+            compression: Compression::None,
+            manifest,
+            curr_metadata: Arc::new(Mutex::new(metadata)),
             next_metadata: Arc::new(Mutex::new(None)),
-        };
-
-        {
-            let curr_metadata = ret.curr_metadata.lock().unwrap();
-            ret.metadata_file.seek(SeekFrom::Start(0))?;
-            ret.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
-
-        Ok(ret)
+        })
     }
 
     /// Opens an existing `SizeTieredStrategy<T, U>` from a folder.
@@ -270,36 +338,183 @@ impl<T, U> SizeTieredStrategy<T, U> {
         U: DeserializeOwned,
         P: AsRef<Path>,
     {
-        let mut metadata_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(path.as_ref().join("metadata.dat"))?;
+        let (manifest, metadata) = if Manifest::exists(path.as_ref()) {
+            let (manifest, mut metadata, sstable_paths): (_, SizeTieredMetadata<T, U>, _) =
+                Manifest::open(path.as_ref())?;
+            for (_, sstable_path) in sstable_paths {
+                metadata.push_sstable(Arc::new(SSTable::with_cache(
+                    sstable_path,
+                    &metadata.cache,
+                )?));
+            }
+            (manifest, metadata)
+        } else {
+            // migrates the metadata file of older versions into a new manifest
+            let metadata_path = path.as_ref().join("metadata.dat");
+            let (mut metadata, sstable_paths): (SizeTieredMetadata<T, U>, Vec<PathBuf>) =
+                deserialize(&fs::read(metadata_path.as_path())?)?;
+            for sstable_path in sstable_paths {
+                metadata.push_sstable(Arc::new(SSTable::with_cache(
+                    sstable_path,
+                    &metadata.cache,
+                )?));
+            }
+            let manifest = Manifest::create(path.as_ref(), &metadata)?;
+            fs::remove_file(metadata_path)?;
+            (manifest, metadata)
+        };
+        manifest::remove_unreferenced_sstables(path.as_ref(), &metadata.get_sstable_paths())?;
+
         let mut logical_time_file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .open(path.as_ref().join("logical_time.dat"))?;
-        let mut buffer = Vec::new();
-        metadata_file.read_to_end(&mut buffer)?;
         logical_time_file.seek(SeekFrom::Start(0))?;
+        // the logical time file is not synchronized, so it may be older than the SSTables after a
+        // crash
+        let curr_logical_time = metadata
+            .sstables
+            .iter()
+            .map(|sstable| sstable.summary.logical_time_range.1 + 1)
+            .fold(logical_time_file.read_u64::<BigEndian>()?, cmp::max);
         Ok(SizeTieredStrategy {
             path: PathBuf::from(path.as_ref()),
             compaction_thread_join_handle: None,
             is_compacting: Arc::new(AtomicBool::new(false)),
-            curr_logical_time: logical_time_file.read_u64::<BigEndian>()?,
+            curr_logical_time,
             logical_time_file,
-            metadata_lock_count: Rc::new(Cell::new(0)),
-            metadata_file,
-            curr_metadata: Arc::new(Mutex::new(deserialize(&buffer)?)),
+            snapshot_list: SnapshotList::new(),
+            compacting_sstable_paths: HashSet::new(),
+            compression: Compression::None,
+            manifest,
+            curr_metadata: Arc::new(Mutex::new(metadata)),
             next_metadata: Arc::new(Mutex::new(None)),
         })
     }
 
+    /// Sets the compression codec used for the data blocks of new SSTables. Existing SSTables are
+    /// not rewritten, but will be compressed when they are compacted. The codec is not persisted,
+    /// so it must be set again after the compaction strategy is opened. Defaults to
+    /// `Compression::None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{CompactionStrategy, SizeTieredStrategy};
+    /// use extended_collections::lsm_tree::Compression;
+    ///
+    /// let mut strategy: SizeTieredStrategy<u32, u32> =
+    ///     SizeTieredStrategy::new("size_tiered_strategy_set_compression", 10000, 4, 50000, 0.5, 1.5)?;
+    /// strategy.set_compression(Compression::Lz4);
+    /// assert_eq!(strategy.get_compression(), Compression::Lz4);
+    /// # fs::remove_dir_all("size_tiered_strategy_set_compression")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Sets the filters that are built for new SSTables. Existing SSTables keep their filters
+    /// until they are compacted. The filter policy is not persisted, so it must be set again after
+    /// the compaction strategy is opened. Defaults to `FilterPolicy::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{CompactionStrategy, SizeTieredStrategy};
+    /// use extended_collections::lsm_tree::FilterPolicy;
+    ///
+    /// let mut strategy: SizeTieredStrategy<u32, u32> =
+    ///     SizeTieredStrategy::new("size_tiered_strategy_set_filter_policy", 10000, 4, 50000, 0.5, 1.5)?;
+    /// strategy.set_filter_policy(FilterPolicy {
+    ///     false_positive_rate: 0.01,
+    ///     ..FilterPolicy::default()
+    /// });
+    /// assert_eq!(strategy.get_filter_policy().false_positive_rate, 0.01);
+    /// # fs::remove_dir_all("size_tiered_strategy_set_filter_policy")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_filter_policy(&mut self, filter_policy: FilterPolicy<T>) {
+        self.curr_metadata.lock().unwrap().filter_policy = filter_policy;
+    }
+
+    /// Sets the capacity in bytes of the block cache, which holds the checksummed and decompressed
+    /// index and data blocks that were read by point reads, range scans and cursors. Blocks are
+    /// evicted in least recently used order, and a capacity of `0` disables the block cache.
+    /// Compactions read blocks without filling the block cache. The capacity is not persisted, so
+    /// it must be set again after the compaction strategy is opened. Defaults to 8 MiB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    ///
+    /// let mut strategy: SizeTieredStrategy<u32, u32> =
+    ///     SizeTieredStrategy::new("size_tiered_strategy_set_block_cache_capacity", 10000, 4, 50000, 0.5, 1.5)?;
+    /// strategy.set_block_cache_capacity(64 * 1024 * 1024);
+    /// # fs::remove_dir_all("size_tiered_strategy_set_block_cache_capacity")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_block_cache_capacity(&mut self, capacity: usize) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .cache
+            .set_block_cache_capacity(capacity);
+    }
+
+    /// Sets the maximum number of index and data files of SSTables that the file cache keeps open
+    /// between reads. Files are closed in least recently used order, and a maximum of `0` disables
+    /// the file cache. The maximum is not persisted, so it must be set again after the compaction
+    /// strategy is opened. Defaults to 256.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    ///
+    /// let mut strategy: SizeTieredStrategy<u32, u32> =
+    ///     SizeTieredStrategy::new("size_tiered_strategy_set_max_open_files", 10000, 4, 50000, 0.5, 1.5)?;
+    /// strategy.set_max_open_files(1024);
+    /// # fs::remove_dir_all("size_tiered_strategy_set_max_open_files")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_max_open_files(&mut self, max_open_files: usize) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .cache
+            .set_max_open_files(max_open_files);
+    }
+
     fn compact<P>(
         path: P,
         is_compacting: &Arc<AtomicBool>,
         mut metadata_snapshot: SizeTieredMetadata<T, U>,
         next_metadata: &Arc<Mutex<Option<SizeTieredMetadata<T, U>>>>,
         range: (usize, usize),
+        snapshot_logical_times: BTreeSet<u64>,
+        compression: Compression,
     ) -> Result<()>
     where
         T: Clone + DeserializeOwned + Hash + Ord + Serialize,
@@ -307,8 +522,16 @@ impl<T, U> SizeTieredStrategy<T, U> {
         P: AsRef<Path>,
     {
         println!("Started compacting.");
-
-        metadata_snapshot.compact(path, range)?;
+        let start_time = Instant::now();
+        metadata_snapshot.stats.record_compaction_start();
+        let old_sstables = metadata_snapshot.sstables.clone();
+
+        metadata_snapshot.compact(path, range, snapshot_logical_times, compression)?;
+        metadata_snapshot.stats.record_compaction_finish(
+            &old_sstables,
+            &metadata_snapshot.sstables,
+            start_time.elapsed(),
+        );
         *next_metadata.lock().unwrap() = Some(metadata_snapshot);
         is_compacting.store(false, Ordering::Release);
 
@@ -327,6 +550,13 @@ impl<T, U> SizeTieredStrategy<T, U> {
         let path = self.path.clone();
         let next_metadata = self.next_metadata.clone();
         let is_compacting = self.is_compacting.clone();
+        let snapshot_logical_times = self.snapshot_list.logical_times();
+        let compression = self.compression;
+        self.compacting_sstable_paths = metadata_snapshot
+            .sstables
+            .iter()
+            .map(|sstable| sstable.path.clone())
+            .collect();
         self.is_compacting.store(true, Ordering::Release);
         self.compaction_thread_join_handle = Some(thread::spawn(move || {
             let compaction_result = SizeTieredStrategy::compact(
@@ -335,6 +565,8 @@ impl<T, U> SizeTieredStrategy<T, U> {
                 metadata_snapshot,
                 &next_metadata,
                 range,
+                snapshot_logical_times,
+                compression,
             );
 
             match compaction_result {
@@ -347,38 +579,98 @@ impl<T, U> SizeTieredStrategy<T, U> {
         }));
     }
 
-    fn try_replace_metadata(
-        &self,
-        curr_metadata: &mut MutexGuard<'_, SizeTieredMetadata<T, U>>,
-    ) -> Result<bool> {
-        let mut next_metadata = self.next_metadata.lock().unwrap();
+    fn try_replace_metadata(&mut self) -> Result<()> {
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let next_metadata = match self.next_metadata.lock().unwrap().take() {
+            Some(next_metadata) => next_metadata,
+            None => return Ok(()),
+        };
 
-        if let Some(next_metadata) = next_metadata.take() {
-            let logical_time_opt = next_metadata
-                .sstables
+        // sstables that were added after the compaction started are kept
+        let old_sstables = mem::replace(&mut curr_metadata.sstables, next_metadata.sstables);
+        curr_metadata.sstables.extend(
+            old_sstables
                 .iter()
-                .map(|sstable| sstable.summary.logical_time_range.1)
-                .max();
-            let old_sstables = mem::replace(&mut curr_metadata.sstables, next_metadata.sstables);
-            curr_metadata.sstables.extend(
-                old_sstables
-                    .iter()
-                    .filter(|sstable| Some(sstable.summary.logical_time_range.1) > logical_time_opt)
-                    .map(|sstable| Arc::clone(sstable)),
-            );
+                .filter(|sstable| !self.compacting_sstable_paths.contains(&sstable.path))
+                .map(|sstable| Arc::clone(sstable)),
+        );
+
+        // the compaction must be recorded in the manifest before the old sstables are removed
+        let old_sstable_paths: Vec<_> = old_sstables
+            .iter()
+            .map(|sstable| (None, sstable.path.clone()))
+            .collect();
+        let edit = VersionEdit::new(&old_sstable_paths, &curr_metadata.get_sstable_paths());
+        self.manifest.append(&edit, &*curr_metadata)?;
 
-            let new_sstable_iter = curr_metadata.sstables.iter().map(|sstable| &sstable.path);
-            let new_sstable_paths: HashSet<&PathBuf> = HashSet::from_iter(new_sstable_iter);
+        // the old sstables are removed once they are no longer read by any cursor
+        let new_sstable_iter = curr_metadata.sstables.iter().map(|sstable| &sstable.path);
+        let new_sstable_paths: HashSet<&PathBuf> = HashSet::from_iter(new_sstable_iter);
 
-            for old_sstable in old_sstables {
-                if !new_sstable_paths.contains(&old_sstable.path) {
-                    fs::remove_dir_all(old_sstable.path.as_path())?;
-                }
+        for old_sstable in old_sstables {
+            if !new_sstable_paths.contains(&old_sstable.path) {
+                old_sstable.mark_obsolete();
             }
-            Ok(true)
-        } else {
-            Ok(false)
         }
+        Ok(())
+    }
+}
+
+impl<T, U> SizeTieredStrategy<T, U>
+where
+    T: 'static + Clone + DeserializeOwned + Hash + Ord + Send + Serialize + Sync,
+    U: 'static + Clone + DeserializeOwned + Send + Serialize + Sync,
+{
+    fn get_at<V>(&mut self, key: &V, logical_time: Option<u64>) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let mut versions = Vec::new();
+        for sstable in &curr_metadata.sstables {
+            versions.extend(
+                curr_metadata
+                    .stats
+                    .get_versions(sstable, key, logical_time)?,
+            );
+        }
+        versions.sort();
+
+        merge::resolve_versions(versions, curr_metadata.merge_operator.as_ref())
+    }
+
+    fn range_at(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        logical_time: Option<u64>,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        let prefix = curr_metadata.filter_policy.get_range_prefix(&range);
+
+        // sstables whose prefix filter does not contain the prefix of the range are skipped
+        let sorted_runs = curr_metadata
+            .sstables
+            .iter()
+            .filter(|sstable| sstable::is_intersecting_range(&sstable.summary.key_range, &range))
+            .filter(|sstable| {
+                curr_metadata
+                    .filter_policy
+                    .may_contain_prefix(sstable, prefix.as_deref())
+            })
+            .map(|sstable| vec![Arc::clone(sstable)])
+            .collect();
+        let cursor = MergingCursor::new(Vec::new(), sorted_runs);
+        let compaction_iter = VisibleIter::new(
+            RangeIter::new(cursor, range)?,
+            logical_time,
+            curr_metadata.merge_operator.clone(),
+        );
+
+        Ok(Box::new(compaction_iter))
     }
 }
 
@@ -391,6 +683,18 @@ where
         self.path.as_path()
     }
 
+    fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    fn get_filter_policy(&self) -> FilterPolicy<T> {
+        self.curr_metadata.lock().unwrap().filter_policy.clone()
+    }
+
+    fn set_merge_operator(&mut self, merge_operator: Arc<dyn MergeOperator<U>>) {
+        self.curr_metadata.lock().unwrap().merge_operator = Some(merge_operator);
+    }
+
     fn get_max_in_memory_size(&self) -> u64 {
         self.curr_metadata.lock().unwrap().max_in_memory_size
     }
@@ -404,27 +708,40 @@ where
         Ok(ret)
     }
 
-    fn try_compact(&mut self, sstable: SSTable<T, U>) -> Result<()> {
+    fn advance_logical_time(&mut self, logical_time: u64) -> Result<()> {
+        if logical_time > self.curr_logical_time {
+            self.curr_logical_time = logical_time;
+            self.logical_time_file.seek(SeekFrom::Start(0))?;
+            self.logical_time_file
+                .write_u64::<BigEndian>(self.curr_logical_time)?;
+        }
+        Ok(())
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        self.snapshot_list.acquire(self.curr_logical_time)
+    }
+
+    fn try_compact(&mut self, mut sstable: SSTable<T, U>) -> Result<()> {
         {
             let mut curr_metadata = self.curr_metadata.lock().unwrap();
+            sstable.set_cache(&curr_metadata.cache);
+            curr_metadata.stats.record_flush(&sstable);
+            let edit = VersionEdit {
+                added: vec![(None, sstable.path.clone())],
+                removed: Vec::new(),
+            };
             curr_metadata.push_sstable(Arc::new(sstable));
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
+            self.manifest.append(&edit, &*curr_metadata)?;
         }
 
-        if self.is_compacting.load(Ordering::Acquire) || self.metadata_lock_count.get() != 0 {
+        if self.is_compacting.load(Ordering::Acquire) {
             return Ok(());
         }
 
         // taking snapshot of current metadata
-        let mut metadata_snapshot = {
-            let mut curr_metadata = self.curr_metadata.lock().unwrap();
-            if self.try_replace_metadata(&mut curr_metadata)? {
-                self.metadata_file.seek(SeekFrom::Start(0))?;
-                self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-            }
-            curr_metadata.clone()
-        };
+        self.try_replace_metadata()?;
+        let mut metadata_snapshot = self.curr_metadata.lock().unwrap().clone();
 
         if let Some(range) = metadata_snapshot.get_compaction_range() {
             self.spawn_compaction_thread(metadata_snapshot, range);
@@ -440,43 +757,93 @@ where
                 Err(error) => println!("Child thread terminated with error: {:?}", error),
             }
 
-            let mut curr_metadata = self.curr_metadata.lock().unwrap();
-            if self.try_replace_metadata(&mut curr_metadata)? {
-                self.metadata_file.seek(SeekFrom::Start(0))?;
-                self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-            }
+            self.try_replace_metadata()?;
         }
         Ok(())
     }
 
+    fn compact_range(&mut self, range: (Bound<T>, Bound<T>)) -> Result<()> {
+        self.flush()?;
+        let mut metadata_snapshot = self.curr_metadata.lock().unwrap().clone();
+
+        // the sstables that intersect the range are moved to the end to be compacted together
+        let (mut sstables, intersecting_sstables): (Vec<_>, Vec<_>) =
+            metadata_snapshot.sstables.drain(..).partition(|sstable| {
+                !sstable::is_intersecting_range(&sstable.summary.key_range, &range)
+            });
+        if intersecting_sstables.is_empty() {
+            return Ok(());
+        }
+        let compaction_range = (sstables.len(), sstables.len() + intersecting_sstables.len());
+        sstables.extend(intersecting_sstables);
+        metadata_snapshot.sstables = sstables;
+
+        let start_time = Instant::now();
+        metadata_snapshot.stats.record_compaction_start();
+        let old_sstables = metadata_snapshot.sstables.clone();
+        metadata_snapshot.compact(
+            self.path.as_path(),
+            compaction_range,
+            self.snapshot_list.logical_times(),
+            self.compression,
+        )?;
+        metadata_snapshot.stats.record_compaction_finish(
+            &old_sstables,
+            &metadata_snapshot.sstables,
+            start_time.elapsed(),
+        );
+
+        self.compacting_sstable_paths = old_sstables
+            .iter()
+            .map(|sstable| sstable.path.clone())
+            .collect();
+        *self.next_metadata.lock().unwrap() = Some(metadata_snapshot);
+        self.try_replace_metadata()
+    }
+
+    fn ingest(&mut self, sstables: Vec<SSTable<T, U>>) -> Result<()> {
+        self.flush()?;
+
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let mut added = Vec::new();
+        for mut sstable in sstables {
+            sstable.set_cache(&curr_metadata.cache);
+            added.push((None, sstable.path.clone()));
+            curr_metadata.push_sstable(Arc::new(sstable));
+        }
+        let edit = VersionEdit {
+            added,
+            removed: Vec::new(),
+        };
+        self.manifest.append(&edit, &*curr_metadata)
+    }
+
+    fn checkpoint(&mut self, path: &Path) -> Result<()> {
+        self.flush()?;
+
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        manifest::create_checkpoint(path, &*curr_metadata, self.curr_logical_time)
+    }
+
     fn get<V>(&mut self, key: &V) -> Result<Option<SSTableValue<U>>>
     where
         T: Borrow<V>,
         V: Ord + Hash + ?Sized,
     {
-        let mut curr_metadata = self.curr_metadata.lock().unwrap();
-        if self.try_replace_metadata(&mut curr_metadata)? {
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
-
-        let mut ret = None;
-        for sstable in &curr_metadata.sstables {
-            let res = sstable.get(&key)?;
-            if res.is_some() && (ret.is_none() || res < ret) {
-                ret = res;
-            }
-        }
+        self.get_at(key, None)
+    }
 
-        Ok(ret)
+    fn get_snapshot<V>(&mut self, key: &V, snapshot: &Snapshot) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.get_at(key, Some(snapshot.get_logical_time()))
     }
 
     fn len_hint(&mut self) -> Result<usize> {
-        let mut curr_metadata = self.curr_metadata.lock().unwrap();
-        if self.try_replace_metadata(&mut curr_metadata)? {
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
 
         let len_hint = curr_metadata
             .sstables
@@ -505,19 +872,17 @@ where
 
         let mut curr_metadata = self.curr_metadata.lock().unwrap();
         let mut next_metadata = self.next_metadata.lock().unwrap();
-        curr_metadata.sstables.clear();
-        *next_metadata = None;
-
-        for dir_entry in fs::read_dir(self.path.as_path())? {
-            let dir_path = dir_entry?.path();
-            if dir_path.is_dir() {
-                fs::remove_dir_all(dir_path)?;
-            }
+        let old_sstables = mem::take(&mut curr_metadata.sstables);
+        let compacted_sstables = next_metadata
+            .take()
+            .map_or_else(Vec::new, |next_metadata| next_metadata.sstables);
+
+        // the manifest is rewritten before the sstables are removed
+        self.manifest.rotate(&*curr_metadata)?;
+        for sstable in old_sstables.iter().chain(compacted_sstables.iter()) {
+            sstable.mark_obsolete();
         }
 
-        self.metadata_file.seek(SeekFrom::Start(0))?;
-        self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-
         Ok(())
     }
 
@@ -529,47 +894,90 @@ where
     }
 
     fn max(&mut self) -> Result<Option<T>> {
-        match self.iter()?.last() {
+        match self.iter()?.next_back() {
             Some(entry) => Ok(Some(entry?.0)),
             None => Ok(None),
         }
     }
 
     fn iter(&mut self) -> Result<Box<CompactionIter<T, U>>> {
-        let mut curr_metadata = self.curr_metadata.lock().unwrap();
-        // should never need to replace metadata as the compaction thread should not be running
-        // when yielding calling iter.
-        if self.try_replace_metadata(&mut curr_metadata)? {
-            self.metadata_file.seek(SeekFrom::Start(0))?;
-            self.metadata_file.write_all(&serialize(&*curr_metadata)?)?;
-        }
-
-        let sstable_data_iters = curr_metadata
-            .sstables
-            .iter()
-            .map(|sstable| sstable.data_iter())
-            .collect();
-        let metadata_lock_count = Rc::clone(&self.metadata_lock_count);
-        let compaction_iter = SizeTieredIter::new(Some(metadata_lock_count), sstable_data_iters)?
+        let compaction_iter = self
+            .range((Unbounded, Unbounded))?
             .filter_map(|entry_result| match entry_result {
                 Ok(entry) => {
                     let (key, value) = entry;
-                    value.data.map(|value| Ok((key, value)))
+                    value.data.into_value().map(|value| Ok((key, value)))
                 }
                 Err(error) => Some(Err(error)),
             });
 
         Ok(Box::new(compaction_iter))
     }
+
+    fn range(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.range_at(range, None)
+    }
+
+    fn range_snapshot(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        snapshot: &Snapshot,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.range_at(range, Some(snapshot.get_logical_time()))
+    }
+
+    fn get_sorted_runs(&mut self) -> Result<Vec<SortedRun<T, U>>> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let sorted_runs = curr_metadata
+            .sstables
+            .iter()
+            .map(|sstable| vec![Arc::clone(sstable)])
+            .collect();
+
+        Ok(sorted_runs)
+    }
+
+    fn stats(&mut self) -> Result<CompactionStats> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        let levels = curr_metadata
+            .get_buckets()
+            .iter()
+            .map(LevelStats::new)
+            .collect();
+        Ok(curr_metadata.stats.stats(levels, &curr_metadata.cache))
+    }
+
+    fn set_event_listener(&mut self, event_listener: Arc<dyn EventListener>) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .stats
+            .set_event_listener(event_listener);
+    }
+
+    fn verify(&mut self) -> Result<()> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        for sstable in &curr_metadata.sstables {
+            sstable.verify()?;
+        }
+
+        Ok(())
+    }
 }
 
 type SizeTieredIterEntry<T, U> = cmp::Reverse<(T, SSTableValue<U>, usize)>;
 
 struct SizeTieredIter<T, U> {
-    metadata_lock_count: Option<Rc<Cell<u64>>>,
     sstable_data_iters: Vec<SSTableDataIter<T, U>>,
     entries: BinaryHeap<SizeTieredIterEntry<T, U>>,
-    last_key_opt: Option<T>,
 }
 
 impl<T, U> SizeTieredIter<T, U>
@@ -577,14 +985,7 @@ where
     T: Hash + DeserializeOwned + Ord + Serialize,
     U: DeserializeOwned + Serialize,
 {
-    pub fn new(
-        metadata_lock_count: Option<Rc<Cell<u64>>>,
-        mut sstable_data_iters: Vec<SSTableDataIter<T, U>>,
-    ) -> Result<Self> {
-        if let Some(ref metadata_lock_count) = metadata_lock_count {
-            metadata_lock_count.set(metadata_lock_count.get() + 1);
-        }
-
+    pub fn new(mut sstable_data_iters: Vec<SSTableDataIter<T, U>>) -> Result<Self> {
         let mut entries = BinaryHeap::new();
 
         for (index, sstable_data_iter) in sstable_data_iters.iter_mut().enumerate() {
@@ -595,10 +996,8 @@ where
         }
 
         Ok(SizeTieredIter {
-            metadata_lock_count,
             sstable_data_iters,
             entries,
-            last_key_opt: None,
         })
     }
 }
@@ -611,35 +1010,82 @@ where
     type Item = Result<(T, SSTableValue<U>)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(cmp::Reverse((key, value, index))) = self.entries.pop() {
-            if let Some(entry) = self.sstable_data_iters[index].next() {
-                match entry {
-                    Ok(entry) => self
-                        .entries
-                        .push(cmp::Reverse((entry.key, entry.value, index))),
-                    Err(error) => return Some(Err(error)),
-                }
+        let cmp::Reverse((key, value, index)) = self.entries.pop()?;
+        if let Some(entry) = self.sstable_data_iters[index].next() {
+            match entry {
+                Ok(entry) => self
+                    .entries
+                    .push(cmp::Reverse((entry.key, entry.value, index))),
+                Err(error) => return Some(Err(error)),
             }
+        }
+        Some(Ok((key, value)))
+    }
+}
 
-            let should_return = match self.last_key_opt {
-                Some(ref last_key) => *last_key != key,
-                None => true,
-            };
+#[cfg(test)]
+mod tests {
+    use super::SizeTieredStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use std::fs;
+    use std::panic;
+    use std::path::Path;
 
-            self.last_key_opt = Some(key.clone());
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
 
-            if should_return {
-                return Some(Ok((key, value)));
-            }
-        }
-        None
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
     }
-}
 
-impl<T, U> Drop for SizeTieredIter<T, U> {
-    fn drop(&mut self) {
-        if let Some(ref mut metadata_lock_count) = self.metadata_lock_count {
-            metadata_lock_count.set(metadata_lock_count.get() - 1);
-        }
+    #[test]
+    fn test_open_legacy_metadata() {
+        let test_name = "test_size_tiered_strategy_open_legacy_metadata";
+        run_test(
+            || {
+                let sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::new(sts)?;
+
+                for key in 0..1000u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.flush()?;
+                drop(map);
+
+                // older versions stored the configuration and SSTable paths in a single file
+                let mut sstable_paths = Vec::new();
+                for entry in fs::read_dir(test_name)? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        sstable_paths.push(entry.path());
+                    }
+                }
+                let metadata = (1000u64, 4u64, 4000u64, 0.5f64, 1.5f64, sstable_paths);
+                fs::write(
+                    Path::new(test_name).join("metadata.dat"),
+                    bincode::serialize(&metadata)?,
+                )?;
+                fs::remove_file(Path::new(test_name).join("manifest.dat"))?;
+
+                let sts: SizeTieredStrategy<u32, u64> = SizeTieredStrategy::open(test_name)?;
+                let mut map = LsmMap::new(sts)?;
+                assert!(Path::new(test_name).join("manifest.dat").exists());
+                assert!(!Path::new(test_name).join("metadata.dat").exists());
+                assert_eq!(map.len()?, 1000);
+                for key in 0..1000u32 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+                Ok(())
+            },
+            test_name,
+        );
     }
 }
@@ -0,0 +1,296 @@
+use crate::lsm_tree::cache::TableCache;
+use crate::lsm_tree::{Result, SSTable, SSTableValue};
+use serde::de::DeserializeOwned;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Statistics of the SSTables in a level of a compaction strategy.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelStats {
+    /// The number of SSTables in the level.
+    pub sstable_count: usize,
+    /// The total size of the SSTables in the level in bytes.
+    pub size: u64,
+    /// The total number of entries in the SSTables in the level, including tombstones.
+    pub entry_count: usize,
+    /// The total number of tombstones in the SSTables in the level.
+    pub tombstone_count: usize,
+}
+
+impl LevelStats {
+    pub(crate) fn new<'a, T, U, I>(sstables: I) -> Self
+    where
+        T: 'a,
+        U: 'a,
+        I: IntoIterator<Item = &'a Arc<SSTable<T, U>>>,
+    {
+        sstables
+            .into_iter()
+            .fold(LevelStats::default(), |mut level_stats, sstable| {
+                level_stats.sstable_count += 1;
+                level_stats.size += sstable.summary.size;
+                level_stats.entry_count += sstable.summary.entry_count;
+                level_stats.tombstone_count += sstable.summary.tombstone_count;
+                level_stats
+            })
+    }
+}
+
+/// Statistics of a compaction strategy.
+///
+/// The statistics of the SSTables describe the current state of the compaction strategy. The
+/// counters of flushes, compactions, filter probes and caches are kept in memory and start at zero
+/// when the compaction strategy is constructed or opened.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompactionStats {
+    /// The statistics of each level of the compaction strategy. The meaning of a level depends on
+    /// the compaction strategy: `LeveledStrategy` reports the SSTables that have not been merged
+    /// into a level followed by each level, `SizeTieredStrategy` reports each bucket in ascending
+    /// order of size, and `TimeWindowStrategy` reports each time window in ascending order of
+    /// logical time.
+    pub levels: Vec<LevelStats>,
+    /// The number of in-memory trees that were flushed into SSTables.
+    pub flush_count: u64,
+    /// The total size of the SSTables written by flushes in bytes.
+    pub bytes_flushed: u64,
+    /// The number of completed compactions.
+    pub compaction_count: u64,
+    /// The total duration of the completed compactions.
+    pub compaction_duration: Duration,
+    /// The total size of the SSTables read by compactions in bytes.
+    pub bytes_read: u64,
+    /// The total size of the SSTables written by compactions in bytes.
+    pub bytes_written: u64,
+    /// The number of reads whose key was contained by the filter of a SSTable and was in the
+    /// SSTable.
+    pub filter_true_positive_count: u64,
+    /// The number of reads whose key was contained by the filter of a SSTable but was not in the
+    /// SSTable.
+    pub filter_false_positive_count: u64,
+    /// The number of index and data blocks that were read from the block cache.
+    pub block_cache_hit_count: u64,
+    /// The number of index and data blocks that were not in the block cache and were read from
+    /// disk.
+    pub block_cache_miss_count: u64,
+    /// The number of reads of index and data blocks that used an open file from the file cache.
+    pub file_cache_hit_count: u64,
+    /// The number of reads of index and data blocks that had to open a file.
+    pub file_cache_miss_count: u64,
+}
+
+impl CompactionStats {
+    /// Returns the number of bytes written by flushes and compactions for every byte flushed.
+    /// Returns `0.0` if nothing has been flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::compaction::CompactionStats;
+    ///
+    /// let stats = CompactionStats {
+    ///     bytes_flushed: 100,
+    ///     bytes_written: 300,
+    ///     ..CompactionStats::default()
+    /// };
+    /// assert_eq!(stats.write_amplification(), 4.0);
+    /// ```
+    pub fn write_amplification(&self) -> f64 {
+        if self.bytes_flushed == 0 {
+            return 0.0;
+        }
+        (self.bytes_flushed + self.bytes_written) as f64 / self.bytes_flushed as f64
+    }
+}
+
+/// An event of a compaction strategy that is reported to an `EventListener`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// An in-memory tree was flushed into a SSTable.
+    Flush {
+        /// The number of entries in the SSTable.
+        entry_count: usize,
+        /// The size of the SSTable in bytes.
+        size: u64,
+    },
+    /// A compaction started in a background thread.
+    CompactionStart,
+    /// A compaction finished.
+    CompactionFinish {
+        /// The number of SSTables that were removed by the compaction.
+        input_sstable_count: usize,
+        /// The number of SSTables that were written by the compaction.
+        output_sstable_count: usize,
+        /// The total size of the SSTables that were removed by the compaction in bytes.
+        bytes_read: u64,
+        /// The total size of the SSTables that were written by the compaction in bytes.
+        bytes_written: u64,
+        /// The duration of the compaction.
+        duration: Duration,
+    },
+}
+
+/// Trait for types that are notified of the flushes and compactions of a compaction strategy.
+///
+/// Compactions run in a background thread, so the listener may be called from a different thread
+/// than the one that owns the compaction strategy.
+///
+/// # Examples
+///
+/// ```
+/// use extended_collections::lsm_tree::compaction::{Event, EventListener};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// #[derive(Default)]
+/// struct FlushCounter(AtomicUsize);
+///
+/// impl EventListener for FlushCounter {
+///     fn on_event(&self, event: &Event) {
+///         if let Event::Flush { .. } = event {
+///             self.0.fetch_add(1, Ordering::Relaxed);
+///         }
+///     }
+/// }
+///
+/// let listener = FlushCounter::default();
+/// listener.on_event(&Event::Flush { entry_count: 1, size: 64 });
+/// listener.on_event(&Event::CompactionStart);
+/// assert_eq!(listener.0.load(Ordering::Relaxed), 1);
+/// ```
+pub trait EventListener: Send + Sync {
+    /// Called when an event occurs.
+    fn on_event(&self, event: &Event);
+}
+
+/// The counters of a compaction strategy that are shared with its compaction thread.
+#[derive(Default)]
+pub struct StatsRecorder {
+    flush_count: AtomicU64,
+    bytes_flushed: AtomicU64,
+    compaction_count: AtomicU64,
+    compaction_nanos: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    filter_true_positive_count: AtomicU64,
+    filter_false_positive_count: AtomicU64,
+    event_listener: RwLock<Option<Arc<dyn EventListener>>>,
+}
+
+impl StatsRecorder {
+    pub fn set_event_listener(&self, event_listener: Arc<dyn EventListener>) {
+        *self.event_listener.write().unwrap() = Some(event_listener);
+    }
+
+    fn notify(&self, event: Event) {
+        if let Some(event_listener) = &*self.event_listener.read().unwrap() {
+            event_listener.on_event(&event);
+        }
+    }
+
+    pub fn record_flush<T, U>(&self, sstable: &SSTable<T, U>) {
+        self.flush_count.fetch_add(1, Ordering::Relaxed);
+        self.bytes_flushed
+            .fetch_add(sstable.summary.size, Ordering::Relaxed);
+        self.notify(Event::Flush {
+            entry_count: sstable.summary.entry_count,
+            size: sstable.summary.size,
+        });
+    }
+
+    pub fn record_compaction_start(&self) {
+        self.notify(Event::CompactionStart);
+    }
+
+    /// Records a compaction from the SSTables before and after it. SSTables that are in both are
+    /// neither read nor written by the compaction.
+    pub fn record_compaction_finish<T, U>(
+        &self,
+        old_sstables: &[Arc<SSTable<T, U>>],
+        new_sstables: &[Arc<SSTable<T, U>>],
+        duration: Duration,
+    ) {
+        let old_paths: HashSet<&PathBuf> =
+            old_sstables.iter().map(|sstable| &sstable.path).collect();
+        let new_paths: HashSet<&PathBuf> =
+            new_sstables.iter().map(|sstable| &sstable.path).collect();
+        let input = LevelStats::new(
+            old_sstables
+                .iter()
+                .filter(|sstable| !new_paths.contains(&sstable.path)),
+        );
+        let output = LevelStats::new(
+            new_sstables
+                .iter()
+                .filter(|sstable| !old_paths.contains(&sstable.path)),
+        );
+
+        self.compaction_count.fetch_add(1, Ordering::Relaxed);
+        self.compaction_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.bytes_read.fetch_add(input.size, Ordering::Relaxed);
+        self.bytes_written.fetch_add(output.size, Ordering::Relaxed);
+        self.notify(Event::CompactionFinish {
+            input_sstable_count: input.sstable_count,
+            output_sstable_count: output.sstable_count,
+            bytes_read: input.size,
+            bytes_written: output.size,
+            duration,
+        });
+    }
+
+    /// Returns all versions of a key in a SSTable that are visible at a logical time, and records
+    /// whether the filter of the SSTable was a true or false positive.
+    pub fn get_versions<T, U, V>(
+        &self,
+        sstable: &SSTable<T, U>,
+        key: &V,
+        logical_time: Option<u64>,
+    ) -> Result<Vec<SSTableValue<U>>>
+    where
+        T: Borrow<V> + DeserializeOwned,
+        U: DeserializeOwned,
+        V: Ord + Hash + ?Sized,
+    {
+        if sstable.probe_filter(key) != Some(true) {
+            return Ok(Vec::new());
+        }
+
+        // reads of sstables without a filter are neither true nor false positives
+        let mut versions = sstable.read_versions(key)?;
+        if sstable.filter.is_some() {
+            let filter_count = if versions.is_empty() {
+                &self.filter_false_positive_count
+            } else {
+                &self.filter_true_positive_count
+            };
+            filter_count.fetch_add(1, Ordering::Relaxed);
+        }
+        versions.retain(|version| version.is_visible(logical_time));
+        Ok(versions)
+    }
+
+    pub fn stats(&self, levels: Vec<LevelStats>, cache: &TableCache) -> CompactionStats {
+        let cache_stats = cache.stats();
+        CompactionStats {
+            levels,
+            flush_count: self.flush_count.load(Ordering::Relaxed),
+            bytes_flushed: self.bytes_flushed.load(Ordering::Relaxed),
+            compaction_count: self.compaction_count.load(Ordering::Relaxed),
+            compaction_duration: Duration::from_nanos(
+                self.compaction_nanos.load(Ordering::Relaxed),
+            ),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            filter_true_positive_count: self.filter_true_positive_count.load(Ordering::Relaxed),
+            filter_false_positive_count: self.filter_false_positive_count.load(Ordering::Relaxed),
+            block_cache_hit_count: cache_stats.block_cache_hit_count,
+            block_cache_miss_count: cache_stats.block_cache_miss_count,
+            file_cache_hit_count: cache_stats.file_cache_hit_count,
+            file_cache_miss_count: cache_stats.file_cache_miss_count,
+        }
+    }
+}
@@ -0,0 +1,1053 @@
+use crate::lsm_tree::cache::TableCache;
+use crate::lsm_tree::compaction::manifest::{
+    self, Manifest, ManifestMetadata, SSTablePath, VersionEdit,
+};
+use crate::lsm_tree::compaction::stats::{LevelStats, StatsRecorder};
+use crate::lsm_tree::compaction::{
+    CompactionIter, CompactionStats, CompactionStrategy, EventListener,
+};
+use crate::lsm_tree::merge;
+use crate::lsm_tree::{
+    sstable, Compression, FilterPolicy, MergeOperator, MergingCursor, RangeIter, Result, SSTable,
+    SSTableBuilder, SSTableValue, Snapshot, SnapshotList, SortedRun, VersionFilter, VisibleIter,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::hash::Hash;
+use std::io::{Seek, SeekFrom};
+use std::iter::FromIterator;
+use std::marker::Send;
+use std::mem;
+use std::ops::Bound::{self, Unbounded};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned, U: DeserializeOwned"))]
+struct TimeWindowMetadata<T, U> {
+    max_in_memory_size: u64,
+    window_size: u64,
+    max_sstable_count: usize,
+    retention: Option<u64>,
+    #[serde(skip)]
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    #[serde(skip)]
+    stats: Arc<StatsRecorder>,
+    #[serde(skip)]
+    cache: Arc<TableCache>,
+    #[serde(skip)]
+    filter_policy: FilterPolicy<T>,
+    #[serde(skip)]
+    sstables: Vec<Arc<SSTable<T, U>>>,
+}
+
+impl<T, U> TimeWindowMetadata<T, U> {
+    pub fn new(
+        max_in_memory_size: u64,
+        window_size: u64,
+        max_sstable_count: usize,
+        retention: Option<u64>,
+    ) -> Self {
+        TimeWindowMetadata {
+            max_in_memory_size,
+            window_size,
+            max_sstable_count,
+            retention,
+            merge_operator: None,
+            stats: Arc::new(StatsRecorder::default()),
+            cache: Arc::new(TableCache::default()),
+            filter_policy: FilterPolicy::default(),
+            sstables: Vec::new(),
+        }
+    }
+
+    pub fn push_sstable(&mut self, sstable: Arc<SSTable<T, U>>) {
+        self.sstables.push(sstable);
+    }
+
+    // A SSTable belongs to the window of its oldest entry.
+    fn get_window(&self, sstable: &SSTable<T, U>) -> u64 {
+        sstable.summary.logical_time_range.0 / self.window_size
+    }
+
+    // Returns the SSTables of each window in ascending order of window.
+    pub fn get_windows(&self) -> BTreeMap<u64, Vec<Arc<SSTable<T, U>>>> {
+        let mut windows = BTreeMap::new();
+        for sstable in &self.sstables {
+            windows
+                .entry(self.get_window(sstable))
+                .or_insert_with(Vec::new)
+                .push(Arc::clone(sstable));
+        }
+        windows
+    }
+
+    // Returns the oldest window that should be compacted. The most recent window is compacted
+    // once it has too many SSTables, and every older window is compacted into a single SSTable.
+    pub fn get_compaction_window(&self) -> Option<u64> {
+        let mut sstable_counts = BTreeMap::new();
+        for sstable in &self.sstables {
+            *sstable_counts.entry(self.get_window(sstable)).or_insert(0) += 1;
+        }
+
+        let newest_window = *sstable_counts.keys().next_back()?;
+        sstable_counts
+            .into_iter()
+            .find(|&(window, sstable_count)| {
+                sstable_count > self.max_sstable_count
+                    || (window != newest_window && sstable_count > 1)
+            })
+            .map(|(window, _)| window)
+    }
+
+    // Removes and returns the SSTables of every window whose entries are all older than a logical
+    // time. SSTables are flushed in order of logical time and only SSTables of the same window are
+    // compacted, so the logical time ranges of SSTables never overlap and no remaining SSTable
+    // contains a version that is older than a removed version.
+    pub fn remove_expired_windows(&mut self, logical_time: u64) -> Vec<Arc<SSTable<T, U>>> {
+        let mut window_max_logical_times = BTreeMap::new();
+        for sstable in &self.sstables {
+            let window_max_logical_time = window_max_logical_times
+                .entry(self.get_window(sstable))
+                .or_insert(0);
+            *window_max_logical_time = cmp::max(
+                *window_max_logical_time,
+                sstable.summary.logical_time_range.1,
+            );
+        }
+
+        let window_size = self.window_size;
+        let (expired_sstables, sstables) =
+            mem::take(&mut self.sstables)
+                .into_iter()
+                .partition(|sstable| {
+                    let window = sstable.summary.logical_time_range.0 / window_size;
+                    window_max_logical_times[&window] < logical_time
+                });
+        self.sstables = sstables;
+        expired_sstables
+    }
+
+    fn compact<P>(
+        &mut self,
+        path: P,
+        window: u64,
+        snapshot_logical_times: BTreeSet<u64>,
+        compression: Compression,
+    ) -> Result<()>
+    where
+        T: Clone + DeserializeOwned + Hash + Ord + Serialize,
+        U: Clone + DeserializeOwned + Serialize,
+        P: AsRef<Path>,
+    {
+        let window_size = self.window_size;
+        let (old_sstables, sstables): (Vec<_>, Vec<_>) = mem::take(&mut self.sstables)
+            .into_iter()
+            .partition(|sstable| sstable.summary.logical_time_range.0 / window_size == window);
+        self.sstables = sstables;
+
+        let sstable_max_logical_time_range = old_sstables
+            .iter()
+            .map(|sstable| sstable.summary.logical_time_range.1)
+            .max();
+        let sstable_key_range = old_sstables.iter().fold(None, |range, sstable| {
+            let sstable_range = sstable.summary.key_range.clone();
+            match range {
+                Some(range) => Some(sstable::merge_ranges(range, sstable_range)),
+                None => Some(sstable_range),
+            }
+        });
+
+        // tombstones are only needed to hide older versions, so they can be purged if every other
+        // sstable with intersecting keys only contains newer versions
+        let purge_tombstone = self.sstables.iter().all(|sstable| {
+            let curr_logical_time_range = Some(sstable.summary.logical_time_range.0);
+            let is_newer_range = sstable_max_logical_time_range < curr_logical_time_range;
+            let key_intersecting = match &sstable_key_range {
+                Some(ref sstable_key_range) => {
+                    sstable::is_intersecting(sstable_key_range, &sstable.summary.key_range)
+                }
+                None => false,
+            };
+            is_newer_range || !key_intersecting
+        });
+
+        let mut sstable_builder = SSTableBuilder::new(
+            path.as_ref(),
+            old_sstables
+                .iter()
+                .map(|sstable| sstable.summary.entry_count)
+                .sum(),
+            compression,
+            &self.filter_policy,
+        )?;
+
+        let sorted_runs = old_sstables
+            .iter()
+            .map(|sstable| vec![Arc::clone(sstable)])
+            .collect();
+        let cursor = MergingCursor::new(Vec::new(), sorted_runs);
+        let compaction_iter = VersionFilter::new(
+            RangeIter::new(cursor, (Unbounded, Unbounded))?,
+            snapshot_logical_times,
+            purge_tombstone,
+            self.merge_operator.clone(),
+        );
+        for entry in compaction_iter {
+            let (key, value) = entry?;
+            sstable_builder.append(key, value)?;
+        }
+
+        if sstable_builder.key_range.is_some() {
+            let sstable = SSTable::with_cache(sstable_builder.flush()?, &self.cache)?;
+            self.push_sstable(Arc::new(sstable));
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, U> ManifestMetadata for TimeWindowMetadata<T, U> {
+    fn get_sstable_paths(&self) -> Vec<SSTablePath> {
+        self.sstables
+            .iter()
+            .map(|sstable| (None, sstable.path.clone()))
+            .collect()
+    }
+}
+
+/// A compaction strategy based on bucketing SSTables into windows of logical time and only
+/// compacting SSTables within the same window.
+///
+/// Each write increments the logical time of the compaction strategy, so a window contains a
+/// fixed number of consecutive writes. A SSTable belongs to the window of its oldest entry. The
+/// most recent window is compacted when it contains too many SSTables, and every older window is
+/// compacted into a single SSTable. Since older windows are rarely rewritten, this strategy suits
+/// append-mostly workloads such as time series, where new entries have increasing keys.
+///
+/// If a retention period is set, whole windows whose entries are all older than the retention
+/// period are dropped without being rewritten. Windows are not dropped while a snapshot is alive.
+///
+/// # Configuration Parameters
+///
+///  - `max_in_memory_size`: The maximum size of the in-memory tree before it must be flushed onto
+///    disk as a SSTable.
+///  - `window_size`: The number of logical times in each window.
+///  - `max_sstable_count`: The minimum number of SSTables in the most recent window before a
+///    compaction is triggered.
+///  - `retention`: The number of logical times after which a window is dropped, or `None` if
+///    windows are never dropped.
+pub struct TimeWindowStrategy<T, U> {
+    path: PathBuf,
+    compaction_thread_join_handle: Option<thread::JoinHandle<()>>,
+    is_compacting: Arc<AtomicBool>,
+    curr_logical_time: u64,
+    logical_time_file: fs::File,
+    snapshot_list: SnapshotList,
+    compacting_sstable_paths: HashSet<PathBuf>,
+    compression: Compression,
+    manifest: Manifest,
+    curr_metadata: Arc<Mutex<TimeWindowMetadata<T, U>>>,
+    next_metadata: Arc<Mutex<Option<TimeWindowMetadata<T, U>>>>,
+}
+
+impl<T, U> TimeWindowStrategy<T, U> {
+    /// Constructs a new `TimeWindowStrategy<T, U>` with specific configuration parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::TimeWindowStrategy;
+    ///
+    /// let tws: TimeWindowStrategy<u32, u32> =
+    ///     TimeWindowStrategy::new("time_window_strategy_new", 10000, 1000, 4, Some(10000))?;
+    /// # fs::remove_dir_all("time_window_strategy_new")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn new<P>(
+        path: P,
+        max_in_memory_size: u64,
+        window_size: u64,
+        max_sstable_count: usize,
+        retention: Option<u64>,
+    ) -> Result<Self>
+    where
+        T: Serialize,
+        U: Serialize,
+        P: AsRef<Path>,
+    {
+        assert!(window_size > 0, "Expected non-zero window size.");
+        fs::create_dir(path.as_ref())?;
+
+        let metadata = TimeWindowMetadata::new(
+            max_in_memory_size,
+            window_size,
+            max_sstable_count,
+            retention,
+        );
+        let manifest = Manifest::create(path.as_ref(), &metadata)?;
+        let logical_time_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref().join("logical_time.dat"))?;
+        Ok(TimeWindowStrategy {
+            path: PathBuf::from(path.as_ref()),
+            compaction_thread_join_handle: None,
+            is_compacting: Arc::new(AtomicBool::new(false)),
+            curr_logical_time: 0,
+            logical_time_file,
+            snapshot_list: SnapshotList::new(),
+            compacting_sstable_paths: HashSet::new(),
+            compression: Compression::None,
+            manifest,
+            curr_metadata: Arc::new(Mutex::new(metadata)),
+            next_metadata: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Opens an existing `TimeWindowStrategy<T, U>` from a folder.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::TimeWindowStrategy;
+    ///
+    /// let tws: TimeWindowStrategy<u32, u32> = TimeWindowStrategy::open("time_window_strategy_open")?;
+    /// # fs::remove_dir_all("time_window_strategy_open")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        let (manifest, mut metadata, sstable_paths): (_, TimeWindowMetadata<T, U>, _) =
+            Manifest::open(path.as_ref())?;
+        for (_, sstable_path) in sstable_paths {
+            metadata.push_sstable(Arc::new(SSTable::with_cache(
+                sstable_path,
+                &metadata.cache,
+            )?));
+        }
+        manifest::remove_unreferenced_sstables(path.as_ref(), &metadata.get_sstable_paths())?;
+
+        let mut logical_time_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref().join("logical_time.dat"))?;
+        logical_time_file.seek(SeekFrom::Start(0))?;
+        // the logical time file is not synchronized, so it may be older than the SSTables after a
+        // crash
+        let curr_logical_time = metadata
+            .sstables
+            .iter()
+            .map(|sstable| sstable.summary.logical_time_range.1 + 1)
+            .fold(logical_time_file.read_u64::<BigEndian>()?, cmp::max);
+        Ok(TimeWindowStrategy {
+            path: PathBuf::from(path.as_ref()),
+            compaction_thread_join_handle: None,
+            is_compacting: Arc::new(AtomicBool::new(false)),
+            curr_logical_time,
+            logical_time_file,
+            snapshot_list: SnapshotList::new(),
+            compacting_sstable_paths: HashSet::new(),
+            compression: Compression::None,
+            manifest,
+            curr_metadata: Arc::new(Mutex::new(metadata)),
+            next_metadata: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Sets the compression codec used for the data blocks of new SSTables. Existing SSTables are
+    /// not rewritten, but will be compressed when they are compacted. The codec is not persisted,
+    /// so it must be set again after the compaction strategy is opened. Defaults to
+    /// `Compression::None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{CompactionStrategy, TimeWindowStrategy};
+    /// use extended_collections::lsm_tree::Compression;
+    ///
+    /// let mut strategy: TimeWindowStrategy<u32, u32> =
+    ///     TimeWindowStrategy::new("time_window_strategy_set_compression", 10000, 1000, 4, None)?;
+    /// strategy.set_compression(Compression::Lz4);
+    /// assert_eq!(strategy.get_compression(), Compression::Lz4);
+    /// # fs::remove_dir_all("time_window_strategy_set_compression")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Sets the filters that are built for new SSTables. Existing SSTables keep their filters
+    /// until they are compacted. The filter policy is not persisted, so it must be set again after
+    /// the compaction strategy is opened. Defaults to `FilterPolicy::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{CompactionStrategy, TimeWindowStrategy};
+    /// use extended_collections::lsm_tree::FilterPolicy;
+    ///
+    /// let mut strategy: TimeWindowStrategy<u32, u32> =
+    ///     TimeWindowStrategy::new("time_window_strategy_set_filter_policy", 10000, 1000, 4, None)?;
+    /// strategy.set_filter_policy(FilterPolicy {
+    ///     false_positive_rate: 0.01,
+    ///     ..FilterPolicy::default()
+    /// });
+    /// assert_eq!(strategy.get_filter_policy().false_positive_rate, 0.01);
+    /// # fs::remove_dir_all("time_window_strategy_set_filter_policy")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_filter_policy(&mut self, filter_policy: FilterPolicy<T>) {
+        self.curr_metadata.lock().unwrap().filter_policy = filter_policy;
+    }
+
+    /// Sets the capacity in bytes of the block cache, which holds the checksummed and decompressed
+    /// index and data blocks that were read by point reads, range scans and cursors. Blocks are
+    /// evicted in least recently used order, and a capacity of `0` disables the block cache.
+    /// Compactions read blocks without filling the block cache. The capacity is not persisted, so
+    /// it must be set again after the compaction strategy is opened. Defaults to 8 MiB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::TimeWindowStrategy;
+    ///
+    /// let mut strategy: TimeWindowStrategy<u32, u32> =
+    ///     TimeWindowStrategy::new("time_window_strategy_set_block_cache_capacity", 10000, 1000, 4, None)?;
+    /// strategy.set_block_cache_capacity(64 * 1024 * 1024);
+    /// # fs::remove_dir_all("time_window_strategy_set_block_cache_capacity")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_block_cache_capacity(&mut self, capacity: usize) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .cache
+            .set_block_cache_capacity(capacity);
+    }
+
+    /// Sets the maximum number of index and data files of SSTables that the file cache keeps open
+    /// between reads. Files are closed in least recently used order, and a maximum of `0` disables
+    /// the file cache. The maximum is not persisted, so it must be set again after the compaction
+    /// strategy is opened. Defaults to 256.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::TimeWindowStrategy;
+    ///
+    /// let mut strategy: TimeWindowStrategy<u32, u32> =
+    ///     TimeWindowStrategy::new("time_window_strategy_set_max_open_files", 10000, 1000, 4, None)?;
+    /// strategy.set_max_open_files(1024);
+    /// # fs::remove_dir_all("time_window_strategy_set_max_open_files")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_max_open_files(&mut self, max_open_files: usize) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .cache
+            .set_max_open_files(max_open_files);
+    }
+
+    fn compact<P>(
+        path: P,
+        is_compacting: &Arc<AtomicBool>,
+        mut metadata_snapshot: TimeWindowMetadata<T, U>,
+        next_metadata: &Arc<Mutex<Option<TimeWindowMetadata<T, U>>>>,
+        window: u64,
+        snapshot_logical_times: BTreeSet<u64>,
+        compression: Compression,
+    ) -> Result<()>
+    where
+        T: Clone + DeserializeOwned + Hash + Ord + Serialize,
+        U: Clone + DeserializeOwned + Serialize,
+        P: AsRef<Path>,
+    {
+        let start_time = Instant::now();
+        metadata_snapshot.stats.record_compaction_start();
+        let old_sstables = metadata_snapshot.sstables.clone();
+
+        metadata_snapshot.compact(path, window, snapshot_logical_times, compression)?;
+        metadata_snapshot.stats.record_compaction_finish(
+            &old_sstables,
+            &metadata_snapshot.sstables,
+            start_time.elapsed(),
+        );
+        *next_metadata.lock().unwrap() = Some(metadata_snapshot);
+        is_compacting.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    fn spawn_compaction_thread(&mut self, metadata_snapshot: TimeWindowMetadata<T, U>, window: u64)
+    where
+        T: 'static + Clone + DeserializeOwned + Hash + Ord + Send + Serialize + Sync,
+        U: 'static + Clone + DeserializeOwned + Send + Serialize + Sync,
+    {
+        let path = self.path.clone();
+        let next_metadata = self.next_metadata.clone();
+        let is_compacting = self.is_compacting.clone();
+        let snapshot_logical_times = self.snapshot_list.logical_times();
+        let compression = self.compression;
+        self.compacting_sstable_paths = metadata_snapshot
+            .sstables
+            .iter()
+            .map(|sstable| sstable.path.clone())
+            .collect();
+        self.is_compacting.store(true, Ordering::Release);
+        self.compaction_thread_join_handle = Some(thread::spawn(move || {
+            let compaction_result = TimeWindowStrategy::compact(
+                path,
+                &is_compacting,
+                metadata_snapshot,
+                &next_metadata,
+                window,
+                snapshot_logical_times,
+                compression,
+            );
+
+            if compaction_result.is_err() {
+                is_compacting.store(false, Ordering::Release);
+            }
+        }));
+    }
+
+    fn try_replace_metadata(&mut self) -> Result<()> {
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let next_metadata = match self.next_metadata.lock().unwrap().take() {
+            Some(next_metadata) => next_metadata,
+            None => return Ok(()),
+        };
+
+        // sstables that were added after the compaction started are kept
+        let old_sstables = mem::replace(&mut curr_metadata.sstables, next_metadata.sstables);
+        curr_metadata.sstables.extend(
+            old_sstables
+                .iter()
+                .filter(|sstable| !self.compacting_sstable_paths.contains(&sstable.path))
+                .map(Arc::clone),
+        );
+
+        // the compaction must be recorded in the manifest before the old sstables are removed
+        let old_sstable_paths: Vec<_> = old_sstables
+            .iter()
+            .map(|sstable| (None, sstable.path.clone()))
+            .collect();
+        let edit = VersionEdit::new(&old_sstable_paths, &curr_metadata.get_sstable_paths());
+        self.manifest.append(&edit, &*curr_metadata)?;
+
+        // the old sstables are removed once they are no longer read by any cursor
+        let new_sstable_iter = curr_metadata.sstables.iter().map(|sstable| &sstable.path);
+        let new_sstable_paths: HashSet<&PathBuf> = HashSet::from_iter(new_sstable_iter);
+
+        for old_sstable in old_sstables {
+            if !new_sstable_paths.contains(&old_sstable.path) {
+                old_sstable.mark_obsolete();
+            }
+        }
+        Ok(())
+    }
+
+    // Drops the windows that are older than the retention period if there are no live snapshots.
+    // Must not be called while a compaction is running, since the compaction would add the
+    // dropped sstables back when the metadata is replaced.
+    fn try_drop_expired_windows(&mut self) -> Result<()> {
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let retention = match curr_metadata.retention {
+            Some(retention) => retention,
+            None => return Ok(()),
+        };
+
+        // every window older than the retention period contains versions that are visible to a
+        // live snapshot, and windows can only be dropped in order of logical time
+        if !self.snapshot_list.logical_times().is_empty() {
+            return Ok(());
+        }
+
+        let logical_time = self.curr_logical_time.saturating_sub(retention);
+        let expired_sstables = curr_metadata.remove_expired_windows(logical_time);
+        if expired_sstables.is_empty() {
+            return Ok(());
+        }
+
+        // the dropped windows must be recorded in the manifest before the sstables are removed
+        let edit = VersionEdit {
+            added: Vec::new(),
+            removed: expired_sstables
+                .iter()
+                .map(|sstable| sstable.path.clone())
+                .collect(),
+        };
+        self.manifest.append(&edit, &*curr_metadata)?;
+        for sstable in expired_sstables {
+            sstable.mark_obsolete();
+        }
+        Ok(())
+    }
+}
+
+impl<T, U> TimeWindowStrategy<T, U>
+where
+    T: 'static + Clone + DeserializeOwned + Hash + Ord + Send + Serialize + Sync,
+    U: 'static + Clone + DeserializeOwned + Send + Serialize + Sync,
+{
+    fn get_at<V>(&mut self, key: &V, logical_time: Option<u64>) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let mut versions = Vec::new();
+        for sstable in &curr_metadata.sstables {
+            versions.extend(
+                curr_metadata
+                    .stats
+                    .get_versions(sstable, key, logical_time)?,
+            );
+        }
+        versions.sort();
+
+        merge::resolve_versions(versions, curr_metadata.merge_operator.as_ref())
+    }
+
+    fn range_at(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        logical_time: Option<u64>,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        let prefix = curr_metadata.filter_policy.get_range_prefix(&range);
+
+        // sstables whose prefix filter does not contain the prefix of the range are skipped
+        let sorted_runs = curr_metadata
+            .sstables
+            .iter()
+            .filter(|sstable| sstable::is_intersecting_range(&sstable.summary.key_range, &range))
+            .filter(|sstable| {
+                curr_metadata
+                    .filter_policy
+                    .may_contain_prefix(sstable, prefix.as_deref())
+            })
+            .map(|sstable| vec![Arc::clone(sstable)])
+            .collect();
+        let cursor = MergingCursor::new(Vec::new(), sorted_runs);
+        let compaction_iter = VisibleIter::new(
+            RangeIter::new(cursor, range)?,
+            logical_time,
+            curr_metadata.merge_operator.clone(),
+        );
+
+        Ok(Box::new(compaction_iter))
+    }
+}
+
+impl<T, U> CompactionStrategy<T, U> for TimeWindowStrategy<T, U>
+where
+    T: 'static + Clone + DeserializeOwned + Hash + Ord + Send + Serialize + Sync,
+    U: 'static + Clone + DeserializeOwned + Send + Serialize + Sync,
+{
+    fn get_path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    fn get_filter_policy(&self) -> FilterPolicy<T> {
+        self.curr_metadata.lock().unwrap().filter_policy.clone()
+    }
+
+    fn set_merge_operator(&mut self, merge_operator: Arc<dyn MergeOperator<U>>) {
+        self.curr_metadata.lock().unwrap().merge_operator = Some(merge_operator);
+    }
+
+    fn get_max_in_memory_size(&self) -> u64 {
+        self.curr_metadata.lock().unwrap().max_in_memory_size
+    }
+
+    fn get_and_increment_logical_time(&mut self) -> Result<u64> {
+        let ret = self.curr_logical_time;
+        self.curr_logical_time += 1;
+        self.logical_time_file.seek(SeekFrom::Start(0))?;
+        self.logical_time_file
+            .write_u64::<BigEndian>(self.curr_logical_time)?;
+        Ok(ret)
+    }
+
+    fn advance_logical_time(&mut self, logical_time: u64) -> Result<()> {
+        if logical_time > self.curr_logical_time {
+            self.curr_logical_time = logical_time;
+            self.logical_time_file.seek(SeekFrom::Start(0))?;
+            self.logical_time_file
+                .write_u64::<BigEndian>(self.curr_logical_time)?;
+        }
+        Ok(())
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        self.snapshot_list.acquire(self.curr_logical_time)
+    }
+
+    fn try_compact(&mut self, mut sstable: SSTable<T, U>) -> Result<()> {
+        {
+            let mut curr_metadata = self.curr_metadata.lock().unwrap();
+            sstable.set_cache(&curr_metadata.cache);
+            curr_metadata.stats.record_flush(&sstable);
+            let edit = VersionEdit {
+                added: vec![(None, sstable.path.clone())],
+                removed: Vec::new(),
+            };
+            curr_metadata.push_sstable(Arc::new(sstable));
+            self.manifest.append(&edit, &*curr_metadata)?;
+        }
+
+        if self.is_compacting.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        // expired windows are dropped before taking a snapshot so that they are not compacted
+        self.try_replace_metadata()?;
+        self.try_drop_expired_windows()?;
+        let metadata_snapshot = self.curr_metadata.lock().unwrap().clone();
+
+        if let Some(window) = metadata_snapshot.get_compaction_window() {
+            self.spawn_compaction_thread(metadata_snapshot, window);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(compaction_thread_join_handle) = self.compaction_thread_join_handle.take() {
+            let _ = compaction_thread_join_handle.join();
+
+            self.try_replace_metadata()?;
+        }
+        Ok(())
+    }
+
+    fn compact_range(&mut self, range: (Bound<T>, Bound<T>)) -> Result<()> {
+        self.flush()?;
+        let mut metadata_snapshot = self.curr_metadata.lock().unwrap().clone();
+
+        // sstables are only compacted with the other sstables of their window
+        let windows: BTreeSet<u64> = metadata_snapshot
+            .sstables
+            .iter()
+            .filter(|sstable| sstable::is_intersecting_range(&sstable.summary.key_range, &range))
+            .map(|sstable| metadata_snapshot.get_window(sstable))
+            .collect();
+        if windows.is_empty() {
+            return Ok(());
+        }
+
+        let start_time = Instant::now();
+        metadata_snapshot.stats.record_compaction_start();
+        let old_sstables = metadata_snapshot.sstables.clone();
+        for window in windows {
+            metadata_snapshot.compact(
+                self.path.as_path(),
+                window,
+                self.snapshot_list.logical_times(),
+                self.compression,
+            )?;
+        }
+        metadata_snapshot.stats.record_compaction_finish(
+            &old_sstables,
+            &metadata_snapshot.sstables,
+            start_time.elapsed(),
+        );
+
+        self.compacting_sstable_paths = old_sstables
+            .iter()
+            .map(|sstable| sstable.path.clone())
+            .collect();
+        *self.next_metadata.lock().unwrap() = Some(metadata_snapshot);
+        self.try_replace_metadata()
+    }
+
+    fn ingest(&mut self, sstables: Vec<SSTable<T, U>>) -> Result<()> {
+        self.flush()?;
+
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let mut added = Vec::new();
+        for mut sstable in sstables {
+            sstable.set_cache(&curr_metadata.cache);
+            added.push((None, sstable.path.clone()));
+            curr_metadata.push_sstable(Arc::new(sstable));
+        }
+        let edit = VersionEdit {
+            added,
+            removed: Vec::new(),
+        };
+        self.manifest.append(&edit, &*curr_metadata)
+    }
+
+    fn checkpoint(&mut self, path: &Path) -> Result<()> {
+        self.flush()?;
+
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        manifest::create_checkpoint(path, &*curr_metadata, self.curr_logical_time)
+    }
+
+    fn get<V>(&mut self, key: &V) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.get_at(key, None)
+    }
+
+    fn get_snapshot<V>(&mut self, key: &V, snapshot: &Snapshot) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.get_at(key, Some(snapshot.get_logical_time()))
+    }
+
+    fn len_hint(&mut self) -> Result<usize> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let len_hint = curr_metadata
+            .sstables
+            .iter()
+            .map(|sstable| sstable.summary.entry_count - sstable.summary.tombstone_count)
+            .sum();
+
+        Ok(len_hint)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        Ok(self.iter()?.count())
+    }
+
+    fn is_empty(&mut self) -> Result<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        if let Some(compaction_thread_join_handle) = self.compaction_thread_join_handle.take() {
+            let _ = compaction_thread_join_handle.join();
+        }
+
+        let mut curr_metadata = self.curr_metadata.lock().unwrap();
+        let mut next_metadata = self.next_metadata.lock().unwrap();
+        let old_sstables = mem::take(&mut curr_metadata.sstables);
+        let compacted_sstables = next_metadata
+            .take()
+            .map_or_else(Vec::new, |next_metadata| next_metadata.sstables);
+
+        // the manifest is rewritten before the sstables are removed
+        self.manifest.rotate(&*curr_metadata)?;
+        for sstable in old_sstables.iter().chain(compacted_sstables.iter()) {
+            sstable.mark_obsolete();
+        }
+
+        Ok(())
+    }
+
+    fn min(&mut self) -> Result<Option<T>> {
+        match self.iter()?.next() {
+            Some(entry) => Ok(Some(entry?.0)),
+            None => Ok(None),
+        }
+    }
+
+    fn max(&mut self) -> Result<Option<T>> {
+        match self.iter()?.next_back() {
+            Some(entry) => Ok(Some(entry?.0)),
+            None => Ok(None),
+        }
+    }
+
+    fn iter(&mut self) -> Result<Box<CompactionIter<T, U>>> {
+        let compaction_iter = self
+            .range((Unbounded, Unbounded))?
+            .filter_map(|entry_result| match entry_result {
+                Ok(entry) => {
+                    let (key, value) = entry;
+                    value.data.into_value().map(|value| Ok((key, value)))
+                }
+                Err(error) => Some(Err(error)),
+            });
+
+        Ok(Box::new(compaction_iter))
+    }
+
+    fn range(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.range_at(range, None)
+    }
+
+    fn range_snapshot(
+        &mut self,
+        range: (Bound<T>, Bound<T>),
+        snapshot: &Snapshot,
+    ) -> Result<Box<CompactionIter<T, SSTableValue<U>>>> {
+        self.range_at(range, Some(snapshot.get_logical_time()))
+    }
+
+    fn get_sorted_runs(&mut self) -> Result<Vec<SortedRun<T, U>>> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        let sorted_runs = curr_metadata
+            .sstables
+            .iter()
+            .map(|sstable| vec![Arc::clone(sstable)])
+            .collect();
+
+        Ok(sorted_runs)
+    }
+
+    fn stats(&mut self) -> Result<CompactionStats> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+        let levels = curr_metadata
+            .get_windows()
+            .values()
+            .map(LevelStats::new)
+            .collect();
+        Ok(curr_metadata.stats.stats(levels, &curr_metadata.cache))
+    }
+
+    fn set_event_listener(&mut self, event_listener: Arc<dyn EventListener>) {
+        self.curr_metadata
+            .lock()
+            .unwrap()
+            .stats
+            .set_event_listener(event_listener);
+    }
+
+    fn verify(&mut self) -> Result<()> {
+        self.try_replace_metadata()?;
+        let curr_metadata = self.curr_metadata.lock().unwrap();
+
+        for sstable in &curr_metadata.sstables {
+            sstable.verify()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeWindowStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use std::fs;
+    use std::panic;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_retention() {
+        let test_name = "test_time_window_strategy_retention";
+        run_test(
+            || {
+                let tws = TimeWindowStrategy::new(test_name, 1000, 1000, 4, Some(4000))?;
+                let mut map = LsmMap::new(tws)?;
+
+                for key in 0..2000u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+
+                // windows are not dropped while a snapshot is alive
+                let snapshot = map.snapshot()?;
+                for key in 2000..10_000 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.flush()?;
+                for key in 0..2000 {
+                    assert_eq!(snapshot.get(&mut map, &key)?, Some(u64::from(key)));
+                }
+
+                drop(snapshot);
+                for key in 10_000..12_000 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.flush()?;
+
+                // entries are only dropped with whole windows that are older than the retention
+                for key in 0..5000 {
+                    assert_eq!(map.get(&key)?, None);
+                }
+                for key in 8000..12_000 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::lsm_tree::Result;
+use serde_derive::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+/// The compression codec for the data blocks of a SSTable.
+///
+/// A data block is stored uncompressed if compressing it does not reduce its size, so the codec
+/// of each block is recorded alongside the block.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Compression {
+    /// Data blocks are not compressed.
+    None,
+    /// Data blocks are compressed using LZ4.
+    Lz4,
+    /// Data blocks are compressed using Snappy.
+    Snappy,
+}
+
+impl Compression {
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz4 => 1,
+            Compression::Snappy => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Lz4),
+            2 => Ok(Compression::Snappy),
+            _ => Err(Error::new(ErrorKind::InvalidData, "Unknown compression codec.").into()),
+        }
+    }
+
+    pub(crate) fn compress(self, buffer: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(buffer.to_vec()),
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(buffer)),
+            Compression::Snappy => snap::raw::Encoder::new()
+                .compress_vec(buffer)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error).into()),
+        }
+    }
+
+    pub(crate) fn decompress(self, buffer: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(buffer),
+            Compression::Lz4 => lz4_flex::decompress_size_prepended(&buffer)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error).into()),
+            Compression::Snappy => snap::raw::Decoder::new()
+                .decompress_vec(&buffer)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+    use crate::lsm_tree::compaction::SizeTieredStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use std::fs;
+    use std::panic;
+    use std::path::Path;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    fn get_data_size<P>(path: P) -> Result<u64>
+    where
+        P: AsRef<Path>,
+    {
+        let mut size = 0;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                size += fs::metadata(entry.path().join("data.dat"))?.len();
+            }
+        }
+        Ok(size)
+    }
+
+    #[test]
+    fn test_compression() {
+        let test_name = "test_compression";
+        run_test(
+            || {
+                fs::create_dir(test_name)?;
+                let mut data_sizes = Vec::new();
+
+                for compression in &[Compression::None, Compression::Lz4, Compression::Snappy] {
+                    let path = Path::new(test_name).join(format!("{:?}", compression));
+                    let mut sts = SizeTieredStrategy::new(&path, 10_000, 4, 40_000, 0.5, 1.5)?;
+                    sts.set_compression(*compression);
+                    let mut map = LsmMap::new(sts)?;
+
+                    let get_value =
+                        |key: u32| format!("{{ id: {}, tags: [\"a\", \"b\"] }}", key % 10);
+                    for key in 0..5000 {
+                        map.insert(key, get_value(key))?;
+                    }
+                    map.flush()?;
+                    data_sizes.push(get_data_size(&path)?);
+                    drop(map);
+
+                    // tables written with a different codec are compacted together
+                    let mut sts = SizeTieredStrategy::open(&path)?;
+                    sts.set_compression(Compression::Snappy);
+                    let mut map = LsmMap::new(sts)?;
+                    for key in 5000..10_000 {
+                        map.insert(key, get_value(key))?;
+                    }
+                    for key in (0..10_000).step_by(2) {
+                        map.remove(key)?;
+                    }
+                    map.flush()?;
+
+                    for key in 0..10_000 {
+                        let expected = if key % 2 == 0 {
+                            None
+                        } else {
+                            Some(get_value(key))
+                        };
+                        assert_eq!(map.get(&key)?, expected);
+                    }
+
+                    let entries = map.iter()?.collect::<Result<Vec<(u32, String)>>>()?;
+                    let expected: Vec<_> = (0..10_000)
+                        .filter(|key| key % 2 == 1)
+                        .map(|key| (key, get_value(key)))
+                        .collect();
+                    assert_eq!(entries, expected);
+                }
+
+                assert!(data_sizes[1] < data_sizes[0]);
+                assert!(data_sizes[2] < data_sizes[0]);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
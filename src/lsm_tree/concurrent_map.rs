@@ -0,0 +1,995 @@
+use crate::lsm_tree::compaction::{CompactionIter, CompactionStrategy};
+use crate::lsm_tree::merge::{self, MergeOperator};
+use crate::lsm_tree::sstable;
+use crate::lsm_tree::{
+    Cursor, MergingCursor, RangeIter, Result, SSTable, SSTableData, SSTableValue, SortedRun,
+    SyncPolicy, VisibleIter, WriteBatch, Writer,
+};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
+
+type Memtable<T, U> = Arc<RwLock<BTreeMap<T, SSTableValue<U>>>>;
+
+// The memtables and SSTables that reads are served from. A new version is published whenever the
+// memtable is handed off to be flushed and whenever the flush completes, so a read that cloned an
+// older version keeps reading a consistent set of memtables and SSTables.
+struct Version<T, U> {
+    memtable: Memtable<T, U>,
+    immutable_memtable: Option<Memtable<T, U>>,
+    sorted_runs: Vec<SortedRun<T, U>>,
+}
+
+impl<T, U> Version<T, U>
+where
+    T: Clone + Ord,
+    U: Clone,
+{
+    // Returns the entries of the memtables in a range in ascending order of keys and then in
+    // descending order of logical times.
+    fn get_in_memory_entries(&self, range: &(Bound<T>, Bound<T>)) -> Vec<(T, SSTableValue<U>)> {
+        let mut in_memory_entries: Vec<_> = self
+            .memtable
+            .read()
+            .unwrap()
+            .range(range.clone())
+            .map(|entry| (entry.0.clone(), entry.1.clone()))
+            .collect();
+        if let Some(ref immutable_memtable) = self.immutable_memtable {
+            in_memory_entries.extend(
+                immutable_memtable
+                    .read()
+                    .unwrap()
+                    .range(range.clone())
+                    .map(|entry| (entry.0.clone(), entry.1.clone())),
+            );
+            in_memory_entries.sort_by(|l, r| l.0.cmp(&r.0).then_with(|| l.1.cmp(&r.1)));
+        }
+        in_memory_entries
+    }
+}
+
+// Returns the versions of a key in a sorted run in descending order of logical time.
+fn get_run_versions<T, U, V>(
+    sorted_run: &[Arc<SSTable<T, U>>],
+    key: &V,
+) -> Result<Vec<SSTableValue<U>>>
+where
+    T: Borrow<V> + DeserializeOwned,
+    U: DeserializeOwned,
+    V: Ord + Hash + ?Sized,
+{
+    let index = sorted_run
+        .iter()
+        .take_while(|sstable| sstable.summary.key_range.1.borrow() < key)
+        .count();
+    match sorted_run.get(index) {
+        Some(sstable) => sstable.get_versions(key, None),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// An ordered map implemented using a log structured merge-tree that can be shared between
+/// threads.
+///
+/// Unlike `LsmMap`, every method takes `&self`, so the map can be wrapped in an `Arc` and used by
+/// many readers and writers at once. Writes are serialized by a single write lock, while reads
+/// never acquire it. Reads are served from a reference-counted version of the in-memory tree and
+/// the SSTables of the compaction strategy. When the in-memory tree exceeds its size threshold, it
+/// is handed off as an immutable in-memory tree that remains readable until it has been flushed
+/// into a SSTable, so reads never wait for flushes or compactions.
+///
+/// The SSTables that reads are served from are refreshed whenever the in-memory tree is flushed,
+/// so SSTables that were replaced by a background compaction are kept on disk until the next
+/// flush.
+///
+/// Cursors and iterators read the version of the map at the time they were created and never
+/// observe later writes, so they already provide the consistency of a snapshot without holding
+/// back writers. `ConcurrentLsmMap` therefore has no separate `snapshot` method.
+///
+/// # Examples
+///
+/// ```
+/// # use extended_collections::lsm_tree::Result;
+/// # fn foo() -> Result<()> {
+/// # use std::fs;
+/// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+/// use extended_collections::lsm_tree::ConcurrentLsmMap;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map", 10000, 4, 50000, 0.5, 1.5)?;
+/// let map = Arc::new(ConcurrentLsmMap::new(sts)?);
+///
+/// let join_handles: Vec<_> = (0..4)
+///     .map(|index| {
+///         let map = Arc::clone(&map);
+///         thread::spawn(move || map.insert(index, index))
+///     })
+///     .collect();
+/// for join_handle in join_handles {
+///     join_handle.join().unwrap()?;
+/// }
+///
+/// assert_eq!(map.get(&0)?, Some(0));
+/// assert_eq!(map.len()?, 4);
+/// # drop(map);
+/// # fs::remove_dir_all("example_concurrent_lsm_map")?;
+/// # Ok(())
+/// # }
+/// # foo().unwrap();
+/// ```
+pub struct ConcurrentLsmMap<T, U, C> {
+    // the write path is only accessed while holding the write lock
+    writer: Mutex<Writer<T, U, C>>,
+    version: RwLock<Arc<Version<T, U>>>,
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+}
+
+impl<T, U, C> ConcurrentLsmMap<T, U, C>
+where
+    T: Clone + Ord + Hash + DeserializeOwned + Serialize,
+    U: Clone + DeserializeOwned + Serialize,
+    C: CompactionStrategy<T, U>,
+{
+    /// Constructs a new `ConcurrentLsmMap<T, U>` with a specific `CompactionStrategy<T, U>`. Any
+    /// writes in the write-ahead log of the compaction strategy are replayed into the in-memory
+    /// tree. The write-ahead log uses the default `SyncPolicy`, which synchronizes it to disk after
+    /// every 100 writes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_new", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map: ConcurrentLsmMap<u32, u32, _> = ConcurrentLsmMap::new(sts)?;
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_new")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn new(compaction_strategy: C) -> Result<Self> {
+        Self::with_sync_policy(compaction_strategy, SyncPolicy::default())
+    }
+
+    /// Constructs a new `ConcurrentLsmMap<T, U>` with a specific `CompactionStrategy<T, U>` and a
+    /// specific `SyncPolicy` for its write-ahead log. Any writes in the write-ahead log of the
+    /// compaction strategy are replayed into the in-memory tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{ConcurrentLsmMap, SyncPolicy};
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_with_sync_policy", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map: ConcurrentLsmMap<u32, u32, _> = ConcurrentLsmMap::with_sync_policy(sts, SyncPolicy::Every(100))?;
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_with_sync_policy")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn with_sync_policy(compaction_strategy: C, sync_policy: SyncPolicy) -> Result<Self> {
+        let (mut writer, in_memory_tree) = Writer::open(compaction_strategy, sync_policy)?;
+        let version = Version {
+            memtable: Arc::new(RwLock::new(in_memory_tree)),
+            immutable_memtable: None,
+            sorted_runs: writer.compaction_strategy.get_sorted_runs()?,
+        };
+
+        Ok(ConcurrentLsmMap {
+            writer: Mutex::new(writer),
+            version: RwLock::new(Arc::new(version)),
+            merge_operator: None,
+        })
+    }
+
+    /// Sets the merge operator that is used to fold the operands written by `merge`. The merge
+    /// operator is not persisted, so it must be set again whenever the map is constructed from an
+    /// existing compaction strategy that contains operands. Until then, reading a key that has
+    /// operands returns `Error::MissingMergeOperator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{ConcurrentLsmMap, MergeOperator};
+    ///
+    /// struct Counter;
+    ///
+    /// impl MergeOperator<u64> for Counter {
+    ///     fn full_merge(&self, existing_value: Option<&u64>, operand: &u64) -> u64 {
+    ///         existing_value.unwrap_or(&0) + operand
+    ///     }
+    /// }
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_set_merge_operator", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map: ConcurrentLsmMap<u32, u64, _> = ConcurrentLsmMap::new(sts)?;
+    /// map.set_merge_operator(Counter);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_set_merge_operator")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_merge_operator<M>(&mut self, merge_operator: M)
+    where
+        M: 'static + MergeOperator<U>,
+    {
+        let merge_operator: Arc<dyn MergeOperator<U>> = Arc::new(merge_operator);
+        self.writer
+            .get_mut()
+            .unwrap()
+            .compaction_strategy
+            .set_merge_operator(Arc::clone(&merge_operator));
+        self.merge_operator = Some(merge_operator);
+    }
+
+    fn get_version(&self) -> Arc<Version<T, U>> {
+        Arc::clone(&*self.version.read().unwrap())
+    }
+
+    // Hands off the memtable as an immutable memtable, flushes it into a SSTable, and then
+    // publishes a version with the SSTables of the compaction strategy.
+    fn try_compact(&self, writer: &mut Writer<T, U, C>) -> Result<()> {
+        let memtable = {
+            let mut version = self.version.write().unwrap();
+            let memtable = Arc::clone(&version.memtable);
+            *version = Arc::new(Version {
+                memtable: Arc::new(RwLock::new(BTreeMap::new())),
+                immutable_memtable: Some(Arc::clone(&memtable)),
+                sorted_runs: version.sorted_runs.clone(),
+            });
+            memtable
+        };
+
+        let memtable = memtable.read().unwrap();
+        writer.flush(
+            memtable
+                .iter()
+                .map(|entry| (entry.0.clone(), entry.1.clone())),
+        )?;
+
+        self.refresh_version(writer)
+    }
+
+    // Publishes a version with the current SSTables of the compaction strategy.
+    fn refresh_version(&self, writer: &mut Writer<T, U, C>) -> Result<()> {
+        let sorted_runs = writer.compaction_strategy.get_sorted_runs()?;
+        let mut version = self.version.write().unwrap();
+        *version = Arc::new(Version {
+            memtable: Arc::clone(&version.memtable),
+            immutable_memtable: None,
+            sorted_runs,
+        });
+        Ok(())
+    }
+
+    fn insert_entries(
+        &self,
+        mut writer: MutexGuard<Writer<T, U, C>>,
+        entries: Vec<(T, SSTableValue<U>)>,
+    ) -> Result<()> {
+        // all entries are inserted while holding the lock of the memtable, so reads observe
+        // either all or none of them
+        let version = self.get_version();
+        let is_full = writer.insert_entries(entries, || version.memtable.write().unwrap())?;
+
+        if is_full {
+            self.try_compact(&mut writer)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Inserts a key-value pair into the map. If the key-value pair causes the size of the
+    /// in-memory tree to exceed its size threshold, it will flush the data into a SSTable and then
+    /// compact the SSTables if necessary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_insert", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    ///
+    /// map.insert(1, 2)?;
+    /// assert_eq!(map.get(&1)?, Some(2));
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_insert")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn insert(&self, key: T, value: U) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let value = writer.new_value(SSTableData::Value(value), None)?;
+        self.insert_entries(writer, vec![(key, value)])
+    }
+
+    /// Inserts a key-value pair into the map that expires after a duration. Once the entry
+    /// expires, it is treated as if it were removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_insert_with_ttl", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert_with_ttl(1, 1, Duration::from_millis(10))?;
+    /// thread::sleep(Duration::from_millis(20));
+    /// assert_eq!(map.get(&1)?, None);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_insert_with_ttl")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn insert_with_ttl(&self, key: T, value: U, ttl: Duration) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let value = writer.new_value(SSTableData::Value(value), Some(ttl))?;
+        self.insert_entries(writer, vec![(key, value)])
+    }
+
+    /// Removes a key-value pair from the map. If the removal causes the size of the in-memory
+    /// tree to exceed its size threshold, it will flush the data into a SSTable and then compact
+    /// the SSTables if necessary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_remove", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.remove(1)?;
+    /// assert_eq!(map.get(&1)?, None);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_remove")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn remove(&self, key: T) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let value = writer.new_value(SSTableData::Tombstone, None)?;
+        self.insert_entries(writer, vec![(key, value)])
+    }
+
+    /// Writes a merge operand for a key. The operand is folded into the existing value of the key
+    /// by the merge operator when the key is read or compacted.
+    ///
+    /// Returns `Error::MissingMergeOperator` if a merge operator has not been set with
+    /// `set_merge_operator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{ConcurrentLsmMap, MergeOperator};
+    ///
+    /// struct Counter;
+    ///
+    /// impl MergeOperator<u64> for Counter {
+    ///     fn full_merge(&self, existing_value: Option<&u64>, operand: &u64) -> u64 {
+    ///         existing_value.unwrap_or(&0) + operand
+    ///     }
+    /// }
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_merge", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = ConcurrentLsmMap::new(sts)?;
+    /// map.set_merge_operator(Counter);
+    ///
+    /// map.merge(1, 1)?;
+    /// map.merge(1, 2)?;
+    /// assert_eq!(map.get(&1)?, Some(3));
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_merge")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn merge(&self, key: T, operand: U) -> Result<()> {
+        merge::get_merge_operator(self.merge_operator.as_ref())?;
+        let mut writer = self.writer.lock().unwrap();
+
+        // the memtable is only written while holding the write lock, so the version in memory
+        // cannot change before the operand is inserted
+        let value = writer.new_operand(
+            self.get_version().memtable.read().unwrap().get(&key),
+            operand,
+            self.merge_operator.as_ref(),
+        )?;
+        self.insert_entries(writer, vec![(key, value)])
+    }
+
+    /// Applies all insertions and removals in a batch atomically. All writes in the batch are
+    /// assigned the same logical time and are appended to the write-ahead log as a single record.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{ConcurrentLsmMap, WriteBatch};
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_write", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// batch.insert(1, 1);
+    /// batch.insert(2, 2);
+    /// map.write(batch)?;
+    ///
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// assert_eq!(map.get(&2)?, Some(2));
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_write")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn write(&self, batch: WriteBatch<T, U>) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+        let entries = writer.new_batch(batch)?;
+        self.insert_entries(writer, entries)
+    }
+
+    /// Checks if a key exists in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_contains_key", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// assert!(!map.contains_key(&0)?);
+    /// assert!(map.contains_key(&1)?);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_contains_key")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn contains_key<V>(&self, key: &V) -> Result<bool>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        self.get(key).map(|value| value.is_some())
+    }
+
+    /// Returns the value associated with a particular key. It will return `None` if the key does
+    /// not exist in the map. Reads do not acquire the write lock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_get", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// assert_eq!(map.get(&0)?, None);
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_get")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn get<V>(&self, key: &V) -> Result<Option<U>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        let version = self.get_version();
+        let mut versions: Vec<_> = version
+            .memtable
+            .read()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .into_iter()
+            .collect();
+
+        if !merge::is_resolved(&versions) {
+            if let Some(ref immutable_memtable) = version.immutable_memtable {
+                versions.extend(immutable_memtable.read().unwrap().get(key).cloned());
+            }
+        }
+
+        // the disk-resident data is only needed if there is no value or tombstone in memory
+        if !merge::is_resolved(&versions) {
+            let mut disk_versions = Vec::new();
+            for sorted_run in &version.sorted_runs {
+                disk_versions.extend(get_run_versions(sorted_run, key)?);
+            }
+            disk_versions.sort();
+            versions.extend(disk_versions);
+        }
+
+        Ok(
+            merge::resolve_versions(versions, self.merge_operator.as_ref())?
+                .and_then(|value| value.data.into_value()),
+        )
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_len", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// assert_eq!(map.len()?, 2);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_len")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn len(&self) -> Result<usize> {
+        let mut cursor = self.cursor()?;
+        let mut len = 0;
+        cursor.seek_to_first()?;
+        while cursor.key().is_some() {
+            len += 1;
+            cursor.next()?;
+        }
+        Ok(len)
+    }
+
+    /// Returns `true` if the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_is_empty", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// assert!(map.is_empty()?);
+    /// map.insert(1, 1)?;
+    /// assert!(!map.is_empty()?);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_is_empty")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn is_empty(&self) -> Result<bool> {
+        let mut cursor = self.cursor()?;
+        cursor.seek_to_first()?;
+        Ok(cursor.key().is_none())
+    }
+
+    /// Flushes the in-memory tree into a SSTable and waits until the current compaction thread,
+    /// if any, terminates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_flush", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.flush()?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_flush")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn flush(&self) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        if !self.get_version().memtable.read().unwrap().is_empty() {
+            self.try_compact(&mut writer)?;
+        }
+        writer.compaction_strategy.flush()?;
+        self.refresh_version(&mut writer)
+    }
+
+    /// Clears the map, removing all values. This function will wait for any ongoing compaction
+    /// thread to terminate before removing all SSTables. Cursors and iterators that were created
+    /// before the map was cleared keep reading the entries of the map at the time they were
+    /// created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_clear", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// map.clear()?;
+    /// assert!(map.is_empty()?);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_clear")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn clear(&self) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.clear()?;
+
+        // the removed sstables are only deleted once no version references them
+        let mut version = self.version.write().unwrap();
+        *version = Arc::new(Version {
+            memtable: Arc::new(RwLock::new(BTreeMap::new())),
+            immutable_memtable: None,
+            sorted_runs: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Returns a cursor over the map that is positioned before the first entry. The cursor reads
+    /// the entries of the map at the time it was created and does not observe any later writes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_cursor", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.next()?;
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_cursor")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn cursor(&self) -> Result<Cursor<T, U>> {
+        let version = self.get_version();
+        let in_memory_entries =
+            version.get_in_memory_entries(&(Bound::Unbounded, Bound::Unbounded));
+
+        Ok(Cursor::new(
+            MergingCursor::new(in_memory_entries, version.sorted_runs.clone()),
+            None,
+            self.merge_operator.clone(),
+        ))
+    }
+
+    /// Returns an iterator over the map. The iterator will yield key-value pairs in ascending
+    /// order, or in descending order if it is reversed. Like a cursor, the iterator reads the
+    /// entries of the map at the time it was created, so the in-memory tree is not flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_iter", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut iterator = map.iter()?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.iter()?.rev().map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_iter")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn iter(&self) -> Result<Box<CompactionIter<T, U>>>
+    where
+        T: 'static,
+        U: 'static,
+    {
+        self.range(..)
+    }
+
+    /// Returns an iterator over a range of keys in the map. The iterator will yield key-value
+    /// pairs in ascending order, or in descending order if it is reversed. The iterator reads the
+    /// entries of the map at the time it was created and does not observe any later writes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end of the range, or if the start and
+    /// end of the range are equal and both excluded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::ConcurrentLsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_concurrent_lsm_map_range", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map = ConcurrentLsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// map.insert(3, 3)?;
+    /// map.flush()?;
+    /// map.remove(2)?;
+    /// map.insert(4, 4)?;
+    ///
+    /// let mut iterator = map.range(2..)?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((3, 3)));
+    /// assert_eq!(iterator.next(), Some((4, 4)));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.range(..4)?.rev().map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((3, 3)));
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_concurrent_lsm_map_range")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn range<R>(&self, range: R) -> Result<Box<CompactionIter<T, U>>>
+    where
+        T: 'static,
+        U: 'static,
+        R: RangeBounds<T>,
+    {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        let version = self.get_version();
+        let in_memory_entries = version.get_in_memory_entries(&range);
+
+        // sstables that cannot contain any key in the range are skipped
+        let sorted_runs = version
+            .sorted_runs
+            .iter()
+            .map(|sorted_run| {
+                sorted_run
+                    .iter()
+                    .filter(|sstable| {
+                        sstable::is_intersecting_range(&sstable.summary.key_range, &range)
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let cursor = MergingCursor::new(in_memory_entries, sorted_runs);
+        let visible_iter = VisibleIter::new(
+            RangeIter::new(cursor, range)?,
+            None,
+            self.merge_operator.clone(),
+        );
+
+        Ok(Box::new(visible_iter.filter_map(
+            |entry_result| match entry_result {
+                Ok((key, value)) => value.data.into_value().map(|value| Ok((key, value))),
+                Err(error) => Some(Err(error)),
+            },
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConcurrentLsmMap;
+    use crate::lsm_tree::compaction::SizeTieredStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use rand::Rng;
+    use std::fs;
+    use std::panic;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        let test_name = "test_concurrent_lsm_map_concurrent_reads";
+        run_test(
+            || {
+                let sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                let map = Arc::new(ConcurrentLsmMap::new(sts)?);
+                let written_count = Arc::new(AtomicUsize::new(0));
+
+                let reader_join_handles: Vec<_> = (0..4)
+                    .map(|seed| {
+                        let map = Arc::clone(&map);
+                        let written_count = Arc::clone(&written_count);
+                        thread::spawn(move || -> Result<()> {
+                            let mut rng: rand::XorShiftRng =
+                                rand::SeedableRng::from_seed([seed + 1, 1, 1, 1]);
+                            loop {
+                                // every key that was written before the read started must be
+                                // visible
+                                let count = written_count.load(Ordering::SeqCst);
+                                if count == 5000 {
+                                    return Ok(());
+                                }
+                                if count > 0 {
+                                    let key = rng.gen_range(0, count as u32);
+                                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+
+                                    let mut cursor = map.cursor()?;
+                                    cursor.seek(&key)?;
+                                    assert_eq!(cursor.key(), Some(&key));
+                                    cursor.prev()?;
+                                    assert_eq!(cursor.key().cloned(), key.checked_sub(1));
+                                }
+                            }
+                        })
+                    })
+                    .collect();
+
+                for key in 0..5000 {
+                    map.insert(key, u64::from(key))?;
+                    written_count.store(key as usize + 1, Ordering::SeqCst);
+                }
+
+                for join_handle in reader_join_handles {
+                    join_handle.join().unwrap()?;
+                }
+
+                map.flush()?;
+                assert_eq!(map.len()?, 5000);
+                for key in 0..5000 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+                drop(map);
+
+                let sts = SizeTieredStrategy::open(test_name)?;
+                let mut map: LsmMap<u32, u64, _> = LsmMap::new(sts)?;
+                assert_eq!(map.len()?, 5000);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_iter() {
+        let test_name = "test_concurrent_lsm_map_iter";
+        run_test(
+            || {
+                let sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                let map = ConcurrentLsmMap::new(sts)?;
+                for key in 0..5000u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+
+                // iterators read the version of the map at the time they were created
+                let iter = map.range(1000..2000)?;
+                for key in 1000..2000 {
+                    map.remove(key)?;
+                }
+                assert!(iter
+                    .map(|entry| entry.unwrap())
+                    .eq((1000..2000).map(|key| (key, u64::from(key)))));
+                assert_eq!(map.range(1000..2000)?.count(), 0);
+
+                let iter = map.iter()?.rev();
+                for key in 1000..2000 {
+                    map.insert(key, u64::from(key))?;
+                }
+                assert!(iter.map(|entry| entry.unwrap()).eq((0..1000)
+                    .chain(2000..5000)
+                    .rev()
+                    .map(|key| (key, u64::from(key)))));
+                assert_eq!(map.iter()?.count(), 5000);
+
+                let iter = map.iter()?;
+                map.clear()?;
+                assert_eq!(iter.count(), 5000);
+                assert_eq!(map.iter()?.count(), 0);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
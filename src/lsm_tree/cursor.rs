@@ -0,0 +1,1210 @@
+use crate::lsm_tree::merge::{self, MergeOperator};
+use crate::lsm_tree::sstable::{self, ReadEntry, SSTable, SSTableCursor, SSTableValue};
+use crate::lsm_tree::Result;
+use serde::de::DeserializeOwned;
+use std::borrow::Borrow;
+use std::cmp;
+use std::iter;
+use std::ops::Bound;
+use std::sync::Arc;
+
+/// A sorted run of SSTables. The SSTables in a sorted run are in ascending order of keys and
+/// their key ranges do not overlap.
+pub type SortedRun<T, U> = Vec<Arc<SSTable<T, U>>>;
+
+// A cursor over a sorted run of SSTables that keeps a single SSTable open at a time.
+struct RunCursor<T, U, V> {
+    sstables: SortedRun<T, U>,
+    read_entry: ReadEntry<T, V>,
+    index: usize,
+    cursor: Option<SSTableCursor<T, U, V>>,
+}
+
+impl<T, U, V> RunCursor<T, U, V>
+where
+    T: DeserializeOwned + Ord,
+{
+    fn new(sstables: SortedRun<T, U>, read_entry: ReadEntry<T, V>) -> Self {
+        RunCursor {
+            sstables,
+            read_entry,
+            index: 0,
+            cursor: None,
+        }
+    }
+
+    fn duplicate(&self) -> Self {
+        RunCursor::new(self.sstables.clone(), self.read_entry)
+    }
+
+    fn open(&mut self, index: usize) -> &mut SSTableCursor<T, U, V> {
+        self.index = index;
+        self.cursor = Some(SSTableCursor::new(
+            Arc::clone(&self.sstables[index]),
+            self.read_entry,
+        ));
+        self.cursor.as_mut().expect("Expected opened cursor.")
+    }
+
+    fn entry(&self) -> Option<(&T, &SSTableValue<V>)> {
+        self.cursor
+            .as_ref()
+            .and_then(|cursor| cursor.entry())
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    fn seek_to_first(&mut self) -> Result<()> {
+        if self.sstables.is_empty() {
+            self.cursor = None;
+            return Ok(());
+        }
+        self.open(0).seek_to_first()
+    }
+
+    fn seek_to_last(&mut self) -> Result<()> {
+        if self.sstables.is_empty() {
+            self.cursor = None;
+            return Ok(());
+        }
+        let index = self.sstables.len() - 1;
+        self.open(index).seek_to_last()
+    }
+
+    fn seek<Q>(&mut self, key: &Q) -> Result<()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self
+            .sstables
+            .iter()
+            .take_while(|sstable| sstable.summary.key_range.1.borrow() < key)
+            .count();
+        if index == self.sstables.len() {
+            self.cursor = None;
+            return Ok(());
+        }
+        self.open(index).seek(key)
+    }
+
+    fn next(&mut self) -> Result<()> {
+        if let Some(ref mut cursor) = self.cursor {
+            cursor.next()?;
+            if cursor.entry().is_none() {
+                if self.index + 1 < self.sstables.len() {
+                    let index = self.index + 1;
+                    self.open(index).seek_to_first()?;
+                } else {
+                    self.cursor = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn prev(&mut self) -> Result<()> {
+        if let Some(ref mut cursor) = self.cursor {
+            cursor.prev()?;
+            if cursor.entry().is_none() {
+                if self.index > 0 {
+                    let index = self.index - 1;
+                    self.open(index).seek_to_last()?;
+                } else {
+                    self.cursor = None;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A cursor over a copy of the entries of the in-memory tree.
+struct MemoryCursor<T, U> {
+    entries: Vec<(T, SSTableValue<U>)>,
+    index: usize,
+}
+
+impl<T, U> MemoryCursor<T, U>
+where
+    T: Ord,
+{
+    fn entry(&self) -> Option<(&T, &SSTableValue<U>)> {
+        self.entries
+            .get(self.index)
+            .map(|entry| (&entry.0, &entry.1))
+    }
+
+    fn seek_to_first(&mut self) {
+        self.index = 0;
+    }
+
+    fn seek_to_last(&mut self) {
+        self.index = if self.entries.is_empty() {
+            0
+        } else {
+            self.entries.len() - 1
+        };
+    }
+
+    fn seek<Q>(&mut self, key: &Q)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.index = self
+            .entries
+            .iter()
+            .take_while(|entry| entry.0.borrow() < key)
+            .count();
+    }
+
+    fn next(&mut self) {
+        if self.index < self.entries.len() {
+            self.index += 1;
+        }
+    }
+
+    fn prev(&mut self) {
+        self.index = match self.index {
+            0 => self.entries.len(),
+            index if index < self.entries.len() => index - 1,
+            index => index,
+        };
+    }
+}
+
+enum SourceCursor<T, U, V> {
+    Memory(MemoryCursor<T, V>),
+    Run(RunCursor<T, U, V>),
+}
+
+impl<T, U, V> SourceCursor<T, U, V>
+where
+    T: DeserializeOwned + Ord,
+{
+    // Returns an unpositioned cursor over the same entries.
+    fn duplicate(&self) -> Self
+    where
+        T: Clone,
+        V: Clone,
+    {
+        match self {
+            SourceCursor::Memory(cursor) => SourceCursor::Memory(MemoryCursor {
+                entries: cursor.entries.clone(),
+                index: 0,
+            }),
+            SourceCursor::Run(cursor) => SourceCursor::Run(cursor.duplicate()),
+        }
+    }
+
+    fn entry(&self) -> Option<(&T, &SSTableValue<V>)> {
+        match self {
+            SourceCursor::Memory(cursor) => cursor.entry(),
+            SourceCursor::Run(cursor) => cursor.entry(),
+        }
+    }
+
+    fn seek_to_first(&mut self) -> Result<()> {
+        match self {
+            SourceCursor::Memory(cursor) => {
+                cursor.seek_to_first();
+                Ok(())
+            }
+            SourceCursor::Run(cursor) => cursor.seek_to_first(),
+        }
+    }
+
+    fn seek_to_last(&mut self) -> Result<()> {
+        match self {
+            SourceCursor::Memory(cursor) => {
+                cursor.seek_to_last();
+                Ok(())
+            }
+            SourceCursor::Run(cursor) => cursor.seek_to_last(),
+        }
+    }
+
+    fn seek<Q>(&mut self, key: &Q) -> Result<()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self {
+            SourceCursor::Memory(cursor) => {
+                cursor.seek(key);
+                Ok(())
+            }
+            SourceCursor::Run(cursor) => cursor.seek(key),
+        }
+    }
+
+    fn next(&mut self) -> Result<()> {
+        match self {
+            SourceCursor::Memory(cursor) => {
+                cursor.next();
+                Ok(())
+            }
+            SourceCursor::Run(cursor) => cursor.next(),
+        }
+    }
+
+    fn prev(&mut self) -> Result<()> {
+        match self {
+            SourceCursor::Memory(cursor) => {
+                cursor.prev();
+                Ok(())
+            }
+            SourceCursor::Run(cursor) => cursor.prev(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
+// Compares an entry with a version of a key. Entries are in ascending order of keys and then in
+// descending order of logical times.
+fn compare_entry<T, U>(entry: (&T, &SSTableValue<U>), key: &T, logical_time: u64) -> cmp::Ordering
+where
+    T: Ord,
+{
+    entry
+        .0
+        .cmp(key)
+        .then_with(|| logical_time.cmp(&entry.1.logical_time))
+}
+
+/// A cursor that merges the entries of the in-memory tree and of sorted runs of SSTables. All
+/// versions of each key are yielded in ascending order of keys and then in descending order of
+/// logical times.
+///
+/// When the cursor changes direction, every source other than the current one is repositioned
+/// around the current entry, so that the sources are always on the same side of the current
+/// entry as the direction of the cursor.
+pub struct MergingCursor<T, U, V = U> {
+    cursors: Vec<SourceCursor<T, U, V>>,
+    current: Option<usize>,
+    direction: Direction,
+}
+
+impl<T, U> MergingCursor<T, U>
+where
+    T: Clone + DeserializeOwned + Ord,
+    U: DeserializeOwned,
+{
+    pub fn new(
+        in_memory_entries: Vec<(T, SSTableValue<U>)>,
+        sorted_runs: Vec<SortedRun<T, U>>,
+    ) -> Self {
+        MergingCursor::with_reader(in_memory_entries, sorted_runs, sstable::deserialize_entry)
+    }
+}
+
+impl<T, U> MergingCursor<T, U, ()>
+where
+    T: Clone + DeserializeOwned + Ord,
+{
+    /// Constructs a cursor that yields the keys of the entries and skips deserializing their
+    /// values. Operands are yielded as values.
+    pub fn keys(
+        in_memory_entries: Vec<(T, SSTableValue<()>)>,
+        sorted_runs: Vec<SortedRun<T, U>>,
+    ) -> Self {
+        MergingCursor::with_reader(
+            in_memory_entries,
+            sorted_runs,
+            sstable::deserialize_key_entry,
+        )
+    }
+}
+
+impl<T, U, V> MergingCursor<T, U, V>
+where
+    T: Clone + DeserializeOwned + Ord,
+{
+    fn with_reader(
+        in_memory_entries: Vec<(T, SSTableValue<V>)>,
+        sorted_runs: Vec<SortedRun<T, U>>,
+        read_entry: ReadEntry<T, V>,
+    ) -> Self {
+        let mut cursors = vec![SourceCursor::Memory(MemoryCursor {
+            entries: in_memory_entries,
+            index: 0,
+        })];
+        cursors.extend(
+            sorted_runs
+                .into_iter()
+                .filter(|sorted_run| !sorted_run.is_empty())
+                .map(|sorted_run| SourceCursor::Run(RunCursor::new(sorted_run, read_entry))),
+        );
+        MergingCursor {
+            cursors,
+            current: None,
+            direction: Direction::Forward,
+        }
+    }
+
+    /// Returns an unpositioned cursor over the same entries.
+    pub fn duplicate(&self) -> Self
+    where
+        V: Clone,
+    {
+        MergingCursor {
+            cursors: self.cursors.iter().map(SourceCursor::duplicate).collect(),
+            current: None,
+            direction: Direction::Forward,
+        }
+    }
+
+    pub fn entry(&self) -> Option<(&T, &SSTableValue<V>)> {
+        self.current.and_then(|index| self.cursors[index].entry())
+    }
+
+    fn find_current(&mut self, direction: Direction) {
+        let mut current: Option<(usize, (&T, &SSTableValue<V>))> = None;
+        for (index, cursor) in self.cursors.iter().enumerate() {
+            let entry = match cursor.entry() {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let is_current = match current {
+                Some((_, current_entry)) => {
+                    let ordering =
+                        compare_entry(entry, current_entry.0, current_entry.1.logical_time);
+                    match direction {
+                        Direction::Forward => ordering == cmp::Ordering::Less,
+                        Direction::Backward => ordering == cmp::Ordering::Greater,
+                    }
+                }
+                None => true,
+            };
+            if is_current {
+                current = Some((index, entry));
+            }
+        }
+        self.current = current.map(|current| current.0);
+        self.direction = direction;
+    }
+
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        for cursor in &mut self.cursors {
+            cursor.seek_to_first()?;
+        }
+        self.find_current(Direction::Forward);
+        Ok(())
+    }
+
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        for cursor in &mut self.cursors {
+            cursor.seek_to_last()?;
+        }
+        self.find_current(Direction::Backward);
+        Ok(())
+    }
+
+    pub fn seek<Q>(&mut self, key: &Q) -> Result<()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        for cursor in &mut self.cursors {
+            cursor.seek(key)?;
+        }
+        self.find_current(Direction::Forward);
+        Ok(())
+    }
+
+    pub fn next(&mut self) -> Result<()> {
+        let current = match self.current {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+
+        if self.direction == Direction::Backward {
+            let (key, logical_time) = match self.entry() {
+                Some((key, value)) => (key.clone(), value.logical_time),
+                None => return Ok(()),
+            };
+            for (index, cursor) in self.cursors.iter_mut().enumerate() {
+                if index == current {
+                    continue;
+                }
+                cursor.seek(&key)?;
+                while let Some(entry) = cursor.entry() {
+                    if compare_entry(entry, &key, logical_time) == cmp::Ordering::Greater {
+                        break;
+                    }
+                    cursor.next()?;
+                }
+            }
+        }
+
+        self.cursors[current].next()?;
+        self.find_current(Direction::Forward);
+        Ok(())
+    }
+
+    pub fn prev(&mut self) -> Result<()> {
+        let current = match self.current {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+
+        if self.direction == Direction::Forward {
+            let (key, logical_time) = match self.entry() {
+                Some((key, value)) => (key.clone(), value.logical_time),
+                None => return Ok(()),
+            };
+            for (index, cursor) in self.cursors.iter_mut().enumerate() {
+                if index == current {
+                    continue;
+                }
+                cursor.seek(&key)?;
+                while let Some(entry) = cursor.entry() {
+                    if compare_entry(entry, &key, logical_time) != cmp::Ordering::Less {
+                        break;
+                    }
+                    cursor.next()?;
+                }
+                if cursor.entry().is_some() {
+                    cursor.prev()?;
+                } else {
+                    cursor.seek_to_last()?;
+                }
+            }
+        }
+
+        self.cursors[current].prev()?;
+        self.find_current(Direction::Backward);
+        Ok(())
+    }
+}
+
+/// An iterator over all versions of the keys in a range of a `MergingCursor<T, U>`.
+///
+/// Iterating from the back positions a second cursor over the same entries at the end of the
+/// range, and the iterator finishes once the two cursors meet.
+pub struct RangeIter<T, U, V = U> {
+    cursor: MergingCursor<T, U, V>,
+    back_cursor: Option<MergingCursor<T, U, V>>,
+    range: (Bound<T>, Bound<T>),
+    // the key and logical time of the last versions yielded from the front and from the back
+    front_version: Option<(T, u64)>,
+    back_version: Option<(T, u64)>,
+    is_done: bool,
+}
+
+impl<T, U, V> RangeIter<T, U, V>
+where
+    T: Clone + DeserializeOwned + Ord,
+{
+    pub fn new(mut cursor: MergingCursor<T, U, V>, range: (Bound<T>, Bound<T>)) -> Result<Self> {
+        match range.0 {
+            Bound::Included(ref key) | Bound::Excluded(ref key) => cursor.seek(key)?,
+            Bound::Unbounded => cursor.seek_to_first()?,
+        }
+        Ok(RangeIter {
+            cursor,
+            back_cursor: None,
+            range,
+            front_version: None,
+            back_version: None,
+            is_done: false,
+        })
+    }
+
+    // Positions a new cursor at the last version of the last key in the range.
+    fn seek_back_cursor(&self) -> Result<MergingCursor<T, U, V>>
+    where
+        V: Clone,
+    {
+        let mut back_cursor = self.cursor.duplicate();
+        match self.range.1 {
+            Bound::Included(ref key) | Bound::Excluded(ref key) => {
+                back_cursor.seek(key)?;
+                while let Some(entry) = back_cursor.entry() {
+                    if sstable::is_after_range(entry.0, &self.range) {
+                        break;
+                    }
+                    back_cursor.next()?;
+                }
+                if back_cursor.entry().is_some() {
+                    back_cursor.prev()?;
+                } else {
+                    back_cursor.seek_to_last()?;
+                }
+            }
+            Bound::Unbounded => back_cursor.seek_to_last()?,
+        }
+        Ok(back_cursor)
+    }
+}
+
+impl<T, U, V> Iterator for RangeIter<T, U, V>
+where
+    T: Clone + DeserializeOwned + Ord,
+    V: Clone,
+{
+    type Item = Result<(T, SSTableValue<V>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.is_done {
+                return None;
+            }
+
+            let entry = match self.cursor.entry() {
+                Some(entry) if !sstable::is_after_range(entry.0, &self.range) => {
+                    let is_yielded = match self.back_version {
+                        Some((ref key, logical_time)) => {
+                            compare_entry(entry, key, logical_time) != cmp::Ordering::Less
+                        }
+                        None => false,
+                    };
+                    if is_yielded {
+                        self.is_done = true;
+                        return None;
+                    }
+                    (entry.0.clone(), entry.1.clone())
+                }
+                _ => {
+                    self.is_done = true;
+                    return None;
+                }
+            };
+
+            if let Err(error) = self.cursor.next() {
+                self.is_done = true;
+                return Some(Err(error));
+            }
+
+            if !sstable::is_before_range(&entry.0, &self.range) {
+                self.front_version = Some((entry.0.clone(), entry.1.logical_time));
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+impl<T, U, V> DoubleEndedIterator for RangeIter<T, U, V>
+where
+    T: Clone + DeserializeOwned + Ord,
+    V: Clone,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+
+        if self.back_cursor.is_none() {
+            match self.seek_back_cursor() {
+                Ok(back_cursor) => self.back_cursor = Some(back_cursor),
+                Err(error) => {
+                    self.is_done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        let back_cursor = self.back_cursor.as_mut().expect("Expected back cursor.");
+
+        let entry = match back_cursor.entry() {
+            Some(entry) if !sstable::is_before_range(entry.0, &self.range) => {
+                let is_yielded = match self.front_version {
+                    Some((ref key, logical_time)) => {
+                        compare_entry(entry, key, logical_time) != cmp::Ordering::Greater
+                    }
+                    None => false,
+                };
+                if is_yielded {
+                    self.is_done = true;
+                    return None;
+                }
+                (entry.0.clone(), entry.1.clone())
+            }
+            _ => {
+                self.is_done = true;
+                return None;
+            }
+        };
+
+        if let Err(error) = back_cursor.prev() {
+            self.is_done = true;
+            return Some(Err(error));
+        }
+
+        self.back_version = Some((entry.0.clone(), entry.1.logical_time));
+        Some(Ok(entry))
+    }
+}
+
+/// An iterator adaptor that can peek at the next item from both ends of a double-ended iterator.
+/// An item that was peeked from one end is still yielded by the other end once the underlying
+/// iterator is exhausted.
+pub struct DoubleEndedPeekable<I>
+where
+    I: Iterator,
+{
+    iter: iter::Fuse<I>,
+    front: Option<I::Item>,
+    back: Option<I::Item>,
+}
+
+impl<I> DoubleEndedPeekable<I>
+where
+    I: Iterator,
+{
+    pub fn new(iter: I) -> Self {
+        DoubleEndedPeekable {
+            iter: iter.fuse(),
+            front: None,
+            back: None,
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<&I::Item> {
+        if self.front.is_none() {
+            self.front = self.iter.next().or_else(|| self.back.take());
+        }
+        self.front.as_ref()
+    }
+
+    pub fn peek_back(&mut self) -> Option<&I::Item>
+    where
+        I: DoubleEndedIterator,
+    {
+        if self.back.is_none() {
+            self.back = self.iter.next_back().or_else(|| self.front.take());
+        }
+        self.back.as_ref()
+    }
+}
+
+impl<I> Iterator for DoubleEndedPeekable<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.front
+            .take()
+            .or_else(|| self.iter.next())
+            .or_else(|| self.back.take())
+    }
+}
+
+impl<I> DoubleEndedIterator for DoubleEndedPeekable<I>
+where
+    I: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.back
+            .take()
+            .or_else(|| self.iter.next_back())
+            .or_else(|| self.front.take())
+    }
+}
+
+/// A cursor over the entries of a `LsmMap` that can move in both directions and seek to a key.
+///
+/// A cursor reads a fixed version of the map: the entries of the in-memory tree are copied, and
+/// the SSTables are reference counted, so compactions can proceed while the cursor is alive
+/// without removing the SSTables it reads. Writes made after the cursor was created are not
+/// visible to it. Only one data block of each SSTable is kept in memory at a time.
+///
+/// A cursor is initially not positioned at any entry, and must be positioned with `seek`,
+/// `seek_to_first`, or `seek_to_last`. Moving past either end of the map leaves the cursor
+/// unpositioned.
+///
+/// # Examples
+///
+/// ```
+/// # use extended_collections::lsm_tree::Result;
+/// # fn foo() -> Result<()> {
+/// # use std::fs;
+/// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+/// use extended_collections::lsm_tree::LsmMap;
+///
+/// let sts = SizeTieredStrategy::new("example_cursor", 10000, 4, 50000, 0.5, 1.5)?;
+/// let mut map = LsmMap::new(sts)?;
+/// map.insert(1, 1)?;
+/// map.insert(3, 3)?;
+/// map.insert(5, 5)?;
+///
+/// let mut cursor = map.cursor()?;
+/// cursor.seek(&2)?;
+/// assert_eq!(cursor.key(), Some(&3));
+/// assert_eq!(cursor.value(), Some(&3));
+///
+/// cursor.next()?;
+/// assert_eq!(cursor.key(), Some(&5));
+///
+/// cursor.prev()?;
+/// cursor.prev()?;
+/// assert_eq!(cursor.key(), Some(&1));
+///
+/// cursor.prev()?;
+/// assert_eq!(cursor.key(), None);
+/// # fs::remove_dir_all("example_cursor")?;
+/// # Ok(())
+/// # }
+/// # foo().unwrap();
+/// ```
+pub struct Cursor<T, U> {
+    cursor: MergingCursor<T, U>,
+    logical_time: Option<u64>,
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    direction: Direction,
+    entry: Option<(T, U)>,
+}
+
+impl<T, U> Cursor<T, U>
+where
+    T: Clone + DeserializeOwned + Ord,
+    U: Clone + DeserializeOwned,
+{
+    pub(crate) fn new(
+        cursor: MergingCursor<T, U>,
+        logical_time: Option<u64>,
+        merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    ) -> Self {
+        Cursor {
+            cursor,
+            logical_time,
+            merge_operator,
+            direction: Direction::Forward,
+            entry: None,
+        }
+    }
+
+    // Resolves the versions of the key at the position of the merging cursor and of the keys
+    // after it until a key has a value. The merging cursor is left at the first version of the
+    // key after the resolved key.
+    fn find_next_entry(&mut self) -> Result<()> {
+        self.direction = Direction::Forward;
+        loop {
+            let key = match self.cursor.entry() {
+                Some((key, _)) => key.clone(),
+                None => {
+                    self.entry = None;
+                    return Ok(());
+                }
+            };
+
+            let mut versions = Vec::new();
+            while let Some((next_key, version)) = self.cursor.entry() {
+                if *next_key != key {
+                    break;
+                }
+                if version.is_visible(self.logical_time) && !merge::is_resolved(&versions) {
+                    versions.push(version.clone());
+                }
+                self.cursor.next()?;
+            }
+
+            let value = merge::resolve_versions(versions, self.merge_operator.as_ref())?
+                .and_then(|value| value.data.into_value());
+            if let Some(value) = value {
+                self.entry = Some((key, value));
+                return Ok(());
+            }
+        }
+    }
+
+    // Resolves the versions of the key at the position of the merging cursor and of the keys
+    // before it until a key has a value. The merging cursor is left at the last version of the
+    // key before the resolved key.
+    fn find_prev_entry(&mut self) -> Result<()> {
+        self.direction = Direction::Backward;
+        loop {
+            let key = match self.cursor.entry() {
+                Some((key, _)) => key.clone(),
+                None => {
+                    self.entry = None;
+                    return Ok(());
+                }
+            };
+
+            // the versions are visited in ascending order of logical time
+            let mut versions = Vec::new();
+            while let Some((prev_key, version)) = self.cursor.entry() {
+                if *prev_key != key {
+                    break;
+                }
+                if version.is_visible(self.logical_time) {
+                    versions.push(version.clone());
+                }
+                self.cursor.prev()?;
+            }
+            versions.reverse();
+
+            let value = merge::resolve_versions(versions, self.merge_operator.as_ref())?
+                .and_then(|value| value.data.into_value());
+            if let Some(value) = value {
+                self.entry = Some((key, value));
+                return Ok(());
+            }
+        }
+    }
+
+    /// Positions the cursor at the first entry whose key is greater than or equal to `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_seek", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek(&1)?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.seek(&2)?;
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// cursor.seek(&4)?;
+    /// assert_eq!(cursor.key(), None);
+    /// # fs::remove_dir_all("example_cursor_seek")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn seek<Q>(&mut self, key: &Q) -> Result<()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.cursor.seek(key)?;
+        self.find_next_entry()
+    }
+
+    /// Positions the cursor at the entry with the minimum key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_seek_to_first", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// # fs::remove_dir_all("example_cursor_seek_to_first")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        self.cursor.seek_to_first()?;
+        self.find_next_entry()
+    }
+
+    /// Positions the cursor at the entry with the maximum key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_seek_to_last", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek_to_last()?;
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// # fs::remove_dir_all("example_cursor_seek_to_last")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        self.cursor.seek_to_last()?;
+        self.find_prev_entry()
+    }
+
+    /// Moves the cursor to the entry with the next key. Does nothing if the cursor is not
+    /// positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_next", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek_to_first()?;
+    /// cursor.next()?;
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// cursor.next()?;
+    /// assert_eq!(cursor.key(), None);
+    /// # fs::remove_dir_all("example_cursor_next")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<()> {
+        let key = match self.entry {
+            Some((ref key, _)) => key.clone(),
+            None => return Ok(()),
+        };
+
+        // the merging cursor is moved from before the current key to after it
+        if self.direction == Direction::Backward {
+            self.cursor.seek(&key)?;
+            while let Some((next_key, _)) = self.cursor.entry() {
+                if *next_key != key {
+                    break;
+                }
+                self.cursor.next()?;
+            }
+        }
+        self.find_next_entry()
+    }
+
+    /// Moves the cursor to the entry with the previous key. Does nothing if the cursor is not
+    /// positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_prev", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek_to_last()?;
+    /// cursor.prev()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// cursor.prev()?;
+    /// assert_eq!(cursor.key(), None);
+    /// # fs::remove_dir_all("example_cursor_prev")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn prev(&mut self) -> Result<()> {
+        let key = match self.entry {
+            Some((ref key, _)) => key.clone(),
+            None => return Ok(()),
+        };
+
+        // the merging cursor is moved from after the current key to before it
+        if self.direction == Direction::Forward {
+            self.cursor.seek(&key)?;
+            if self.cursor.entry().is_some() {
+                self.cursor.prev()?;
+            } else {
+                self.cursor.seek_to_last()?;
+            }
+        }
+        self.find_prev_entry()
+    }
+
+    /// Returns the key of the entry that the cursor is positioned at, or `None` if the cursor is
+    /// not positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_key", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// assert_eq!(cursor.key(), None);
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// # fs::remove_dir_all("example_cursor_key")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn key(&self) -> Option<&T> {
+        self.entry.as_ref().map(|entry| &entry.0)
+    }
+
+    /// Returns the value of the entry that the cursor is positioned at, or `None` if the cursor is
+    /// not positioned at any entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_cursor_value", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 2)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// assert_eq!(cursor.value(), None);
+    /// cursor.seek_to_first()?;
+    /// assert_eq!(cursor.value(), Some(&2));
+    /// # fs::remove_dir_all("example_cursor_value")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn value(&self) -> Option<&U> {
+        self.entry.as_ref().map(|entry| &entry.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsm_tree::compaction::SizeTieredStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use rand::Rng;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::panic;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let test_name = "test_cursor";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::new(sts)?;
+                let mut expected = BTreeMap::new();
+
+                for _ in 0..10_000 {
+                    let key = rng.gen_range(0, 5000);
+                    if rng.gen::<bool>() {
+                        let val = rng.gen::<u64>();
+                        map.insert(key, val)?;
+                        expected.insert(key, val);
+                    } else {
+                        map.remove(key)?;
+                        expected.remove(&key);
+                    }
+                }
+
+                let mut cursor = map.cursor()?;
+                let mut curr_key = None;
+                for _ in 0..10_000 {
+                    curr_key = match rng.gen_range(0, 6) {
+                        0 => {
+                            let key = rng.gen_range(0, 5001);
+                            cursor.seek(&key)?;
+                            expected.range(key..).next().map(|entry| *entry.0)
+                        }
+                        1 => {
+                            cursor.seek_to_first()?;
+                            expected.keys().next().cloned()
+                        }
+                        2 => {
+                            cursor.seek_to_last()?;
+                            expected.keys().next_back().cloned()
+                        }
+                        3 | 4 => {
+                            cursor.next()?;
+                            curr_key.and_then(|key| {
+                                expected.range(key + 1..).next().map(|entry| *entry.0)
+                            })
+                        }
+                        _ => {
+                            cursor.prev()?;
+                            curr_key.and_then(|key| {
+                                expected.range(..key).next_back().map(|entry| *entry.0)
+                            })
+                        }
+                    };
+                    assert_eq!(cursor.key(), curr_key.as_ref());
+                    assert_eq!(cursor.value(), curr_key.and_then(|key| expected.get(&key)));
+                }
+
+                // the cursor keeps reading its SSTables while later writes are compacted
+                for _ in 0..10_000 {
+                    let key = rng.gen_range(0, 5000);
+                    map.insert(key, rng.gen::<u64>())?;
+                }
+                map.flush()?;
+
+                let mut actual = Vec::new();
+                cursor.seek_to_first()?;
+                while let (Some(key), Some(value)) = (cursor.key(), cursor.value()) {
+                    actual.push((*key, *value));
+                    cursor.next()?;
+                }
+                let expected_iter: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_iter);
+
+                let mut actual = Vec::new();
+                cursor.seek_to_last()?;
+                while let (Some(key), Some(value)) = (cursor.key(), cursor.value()) {
+                    actual.push((*key, *value));
+                    cursor.prev()?;
+                }
+                actual.reverse();
+                assert_eq!(actual, expected_iter);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use crate::lsm_tree::SSTable;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use std::sync::Arc;
+
+/// Trait for types that extract a prefix from keys to build the prefix filters of SSTables.
+///
+/// All keys that are between two keys with the same prefix must also have that prefix, as is the
+/// case for a fixed-length prefix of strings or byte vectors. A range scan whose bounds have the
+/// same prefix skips every SSTable whose prefix filter does not contain the prefix.
+///
+/// # Examples
+///
+/// ```
+/// use extended_collections::lsm_tree::PrefixExtractor;
+///
+/// struct UserPrefix;
+///
+/// impl PrefixExtractor<String> for UserPrefix {
+///     fn name(&self) -> String {
+///         String::from("user_prefix")
+///     }
+///
+///     fn prefix(&self, key: &String) -> Option<Vec<u8>> {
+///         key.find('/').map(|index| key.as_bytes()[..index].to_vec())
+///     }
+/// }
+///
+/// assert_eq!(UserPrefix.prefix(&String::from("alice/1")), Some(b"alice".to_vec()));
+/// assert_eq!(UserPrefix.prefix(&String::from("alice")), None);
+/// ```
+pub trait PrefixExtractor<T>: Send + Sync {
+    /// Returns the name of the prefix extractor. The name is stored with each prefix filter, and
+    /// prefix filters that were built by a prefix extractor with a different name are ignored.
+    fn name(&self) -> String;
+
+    /// Returns the prefix of a key, or `None` if the key does not have a prefix.
+    fn prefix(&self, key: &T) -> Option<Vec<u8>>;
+}
+
+/// A prefix extractor that returns the first `len` bytes of a key. Keys that are shorter than
+/// `len` bytes do not have a prefix.
+///
+/// # Examples
+///
+/// ```
+/// use extended_collections::lsm_tree::{FixedLengthPrefix, PrefixExtractor};
+///
+/// let prefix_extractor = FixedLengthPrefix::new(2);
+/// assert_eq!(prefix_extractor.prefix(&String::from("abc")), Some(b"ab".to_vec()));
+/// assert_eq!(prefix_extractor.prefix(&vec![1u8]), None);
+/// ```
+pub struct FixedLengthPrefix {
+    len: usize,
+}
+
+impl FixedLengthPrefix {
+    /// Constructs a new `FixedLengthPrefix` that returns the first `len` bytes of a key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::FixedLengthPrefix;
+    ///
+    /// let prefix_extractor = FixedLengthPrefix::new(4);
+    /// ```
+    pub fn new(len: usize) -> Self {
+        FixedLengthPrefix { len }
+    }
+
+    fn get_prefix(&self, key: &[u8]) -> Option<Vec<u8>> {
+        if key.len() < self.len {
+            return None;
+        }
+        Some(key[..self.len].to_vec())
+    }
+}
+
+impl PrefixExtractor<String> for FixedLengthPrefix {
+    fn name(&self) -> String {
+        format!("fixed_length_prefix_{}", self.len)
+    }
+
+    fn prefix(&self, key: &String) -> Option<Vec<u8>> {
+        self.get_prefix(key.as_bytes())
+    }
+}
+
+impl PrefixExtractor<Vec<u8>> for FixedLengthPrefix {
+    fn name(&self) -> String {
+        format!("fixed_length_prefix_{}", self.len)
+    }
+
+    fn prefix(&self, key: &Vec<u8>) -> Option<Vec<u8>> {
+        self.get_prefix(key)
+    }
+}
+
+/// The filters that are built for the SSTables of a compaction strategy.
+///
+/// # Examples
+///
+/// ```
+/// use extended_collections::lsm_tree::{FilterPolicy, FixedLengthPrefix};
+/// use std::sync::Arc;
+///
+/// // a stricter key filter
+/// let filter_policy: FilterPolicy<String> = FilterPolicy {
+///     false_positive_rate: 0.01,
+///     ..FilterPolicy::default()
+/// };
+///
+/// // only a prefix filter for SSTables that are only scanned
+/// let filter_policy: FilterPolicy<String> = FilterPolicy {
+///     key_filter: false,
+///     prefix_extractor: Some(Arc::new(FixedLengthPrefix::new(4))),
+///     ..FilterPolicy::default()
+/// };
+/// ```
+pub struct FilterPolicy<T> {
+    /// The false positive rate of the bloom filters of each SSTable. Defaults to `0.05`.
+    pub false_positive_rate: f64,
+    /// Whether a bloom filter of the keys is built for each SSTable. The filter is only used by
+    /// point reads, so it can be disabled for SSTables that are only scanned. Defaults to `true`.
+    pub key_filter: bool,
+    /// The prefix extractor whose prefixes are added to a bloom filter for each SSTable, or
+    /// `None` if SSTables do not have a prefix filter. Defaults to `None`.
+    pub prefix_extractor: Option<Arc<dyn PrefixExtractor<T>>>,
+}
+
+impl<T> FilterPolicy<T> {
+    // Returns the prefix shared by both bounds of a range, if both bounds are bounded and have the
+    // same prefix. All keys in the range have the prefix.
+    pub(crate) fn get_range_prefix(&self, range: &(Bound<T>, Bound<T>)) -> Option<Vec<u8>> {
+        let prefix_extractor = self.prefix_extractor.as_ref()?;
+        let start = match range.0 {
+            Included(ref key) | Excluded(ref key) => key,
+            Unbounded => return None,
+        };
+        let end = match range.1 {
+            Included(ref key) | Excluded(ref key) => key,
+            Unbounded => return None,
+        };
+        let prefix = prefix_extractor.prefix(start)?;
+        if prefix_extractor.prefix(end)? != prefix {
+            return None;
+        }
+        Some(prefix)
+    }
+
+    // Returns `false` if the prefix filter of a SSTable shows that it does not contain any key
+    // with a prefix, and `true` if there is no prefix.
+    pub(crate) fn may_contain_prefix<U>(
+        &self,
+        sstable: &SSTable<T, U>,
+        prefix: Option<&[u8]>,
+    ) -> bool {
+        match (prefix, &self.prefix_extractor, &sstable.prefix_filter) {
+            (Some(prefix), Some(prefix_extractor), Some((name, prefix_filter))) => {
+                *name != prefix_extractor.name() || prefix_filter.contains(prefix)
+            }
+            _ => true,
+        }
+    }
+}
+
+impl<T> Clone for FilterPolicy<T> {
+    fn clone(&self) -> Self {
+        FilterPolicy {
+            false_positive_rate: self.false_positive_rate,
+            key_filter: self.key_filter,
+            prefix_extractor: self.prefix_extractor.clone(),
+        }
+    }
+}
+
+impl<T> Default for FilterPolicy<T> {
+    fn default() -> Self {
+        FilterPolicy {
+            false_positive_rate: 0.05,
+            key_filter: true,
+            prefix_extractor: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterPolicy, FixedLengthPrefix};
+    use crate::lsm_tree::compaction::{CompactionStrategy, SizeTieredStrategy};
+    use crate::lsm_tree::{LsmMap, Result};
+    use rand::Rng;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::panic;
+    use std::sync::Arc;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    fn check_filter_policy<C>(map: &mut LsmMap<String, u64, C>) -> Result<()>
+    where
+        C: CompactionStrategy<String, u64>,
+    {
+        let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+        let mut expected = BTreeMap::new();
+
+        // each prefix is only written in a few sstables
+        for i in 0..10_000u64 {
+            let key = format!("{:02}/{:04}", i / 500 * 2, rng.gen_range(0, 1000));
+            if rng.gen_range(0, 4) == 0 {
+                map.remove(key.clone())?;
+                expected.remove(&key);
+            } else {
+                map.insert(key.clone(), i)?;
+                expected.insert(key, i);
+            }
+        }
+
+        for prefix in 0..40 {
+            let start = format!("{:02}/", prefix);
+            let end = format!("{:02}/~", prefix);
+            let actual = map
+                .range(start.clone()..=end.clone())?
+                .collect::<Result<Vec<_>>>()?;
+            let expected_range: Vec<_> = expected
+                .range(start.clone()..=end.clone())
+                .map(|(key, value)| (key.clone(), *value))
+                .collect();
+            assert_eq!(actual, expected_range);
+
+            let actual = map
+                .keys_range(start.clone()..=end.clone())?
+                .rev()
+                .collect::<Result<Vec<_>>>()?;
+            let expected_keys: Vec<_> = expected
+                .range(start..=end)
+                .rev()
+                .map(|e| e.0.clone())
+                .collect();
+            assert_eq!(actual, expected_keys);
+
+            let start = format!("{:02}/0250", prefix);
+            let end = format!("{:02}/0750", prefix);
+            let actual = map
+                .range(start.clone()..end.clone())?
+                .collect::<Result<Vec<_>>>()?;
+            let expected_range: Vec<_> = expected
+                .range(start..end)
+                .map(|(key, value)| (key.clone(), *value))
+                .collect();
+            assert_eq!(actual, expected_range);
+        }
+
+        for (key, value) in &expected {
+            assert_eq!(map.get(key)?, Some(*value));
+        }
+        assert_eq!(map.get("01/0000")?, None);
+        assert_eq!(map.len()?, expected.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_filter() {
+        let test_name = "test_filter_policy_prefix_filter";
+        run_test(
+            || {
+                let mut sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                sts.set_filter_policy(FilterPolicy {
+                    false_positive_rate: 0.01,
+                    prefix_extractor: Some(Arc::new(FixedLengthPrefix::new(3))),
+                    ..FilterPolicy::default()
+                });
+                let mut map = LsmMap::new(sts)?;
+                check_filter_policy(&mut map)?;
+
+                // prefix filters that do not match the prefix extractor are ignored
+                drop(map);
+                let mut sts = SizeTieredStrategy::open(test_name)?;
+                sts.set_filter_policy(FilterPolicy {
+                    prefix_extractor: Some(Arc::new(FixedLengthPrefix::new(2))),
+                    ..FilterPolicy::default()
+                });
+                check_filter_policy(&mut LsmMap::new(sts)?)
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_no_key_filter() {
+        let test_name = "test_filter_policy_no_key_filter";
+        run_test(
+            || {
+                let mut sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                sts.set_filter_policy(FilterPolicy {
+                    key_filter: false,
+                    prefix_extractor: Some(Arc::new(FixedLengthPrefix::new(3))),
+                    ..FilterPolicy::default()
+                });
+                let mut map = LsmMap::new(sts)?;
+                check_filter_policy(&mut map)?;
+
+                // reads of sstables without a key filter are not counted
+                let stats = map.stats()?;
+                assert_eq!(stats.filter_true_positive_count, 0);
+                assert_eq!(stats.filter_false_positive_count, 0);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
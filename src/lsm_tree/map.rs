@@ -1,13 +1,25 @@
-use crate::lsm_tree::compaction::{CompactionIter, CompactionStrategy};
-use crate::lsm_tree::{Result, SSTable, SSTableBuilder, SSTableValue};
-use bincode::serialized_size;
+use crate::lsm_tree::compaction::{
+    CompactionIter, CompactionStats, CompactionStrategy, EventListener, KeyIter,
+};
+use crate::lsm_tree::merge::{self, MergeOperator};
+use crate::lsm_tree::sstable;
+use crate::lsm_tree::{
+    Cursor, DoubleEndedPeekable, Error, MergingCursor, RangeIter, Result, SSTable, SSTableData,
+    SSTableValue, Snapshot, SyncPolicy, VisibleIter, WriteBatch, Writer,
+};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use std::borrow::Borrow;
 use std::cmp;
 use std::collections::BTreeMap;
+use std::fs;
 use std::hash::Hash;
 use std::mem;
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::vec;
 
 /// An ordered map implemented using a log structured merge-tree.
 ///
@@ -18,6 +30,14 @@ use std::mem;
 /// replaced, it could occur in multiple SSTables. The value in the most recent SSTable is fetched.
 /// When an entry is deleted, a tombstone is inserted to indicate that the entry is deleted.
 ///
+/// Every write is appended to a write-ahead log in the directory of the compaction strategy before
+/// it is applied to the in-memory tree. When the map is constructed, the log is replayed so that
+/// writes that were not flushed into a SSTable before a crash are recovered. The log is truncated
+/// whenever the in-memory tree is flushed into a SSTable.
+///
+/// Every method of `LsmMap` takes `&mut self`. `ConcurrentLsmMap` can be shared between threads
+/// and allows reads to proceed concurrently with writes.
+///
 /// # Examples
 ///
 /// ```
@@ -28,7 +48,7 @@ use std::mem;
 /// use extended_collections::lsm_tree::LsmMap;
 ///
 /// let sts = SizeTieredStrategy::new("example_lsm_map", 10000, 4, 50000, 0.5, 1.5)?;
-/// let mut map = LsmMap::new(sts);
+/// let mut map = LsmMap::new(sts)?;
 ///
 /// map.insert(0, 1)?;
 /// map.insert(3, 4)?;
@@ -51,8 +71,8 @@ use std::mem;
 /// ```
 pub struct LsmMap<T, U, C> {
     in_memory_tree: BTreeMap<T, SSTableValue<U>>,
-    in_memory_usage: u64,
-    compaction_strategy: C,
+    writer: Writer<T, U, C>,
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
 }
 
 impl<T, U, C> LsmMap<T, U, C>
@@ -61,7 +81,10 @@ where
     U: Clone + DeserializeOwned + Serialize,
     C: CompactionStrategy<T, U>,
 {
-    /// Constructs a new `LsmMap<T, U>` with a specific `CompactionStrategy<T, U>`.
+    /// Constructs a new `LsmMap<T, U>` with a specific `CompactionStrategy<T, U>`. Any writes in
+    /// the write-ahead log of the compaction strategy are replayed into the in-memory tree. The
+    /// write-ahead log uses the default `SyncPolicy`, which synchronizes it to disk after every 100
+    /// writes.
     ///
     /// # Examples
     ///
@@ -73,31 +96,98 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_new", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let map: LsmMap<u32, u32, _> = LsmMap::new(sts);
+    /// let map: LsmMap<u32, u32, _> = LsmMap::new(sts)?;
     /// # fs::remove_dir_all("example_lsm_map_new")?;
     /// # Ok(())
     /// # }
     /// # foo().unwrap();
     /// ```
-    pub fn new(compaction_strategy: C) -> Self {
-        LsmMap {
-            in_memory_tree: BTreeMap::new(),
-            in_memory_usage: 0,
-            compaction_strategy,
-        }
+    pub fn new(compaction_strategy: C) -> Result<Self> {
+        Self::with_sync_policy(compaction_strategy, SyncPolicy::default())
+    }
+
+    /// Constructs a new `LsmMap<T, U>` with a specific `CompactionStrategy<T, U>` and a specific
+    /// `SyncPolicy` for its write-ahead log. Any writes in the write-ahead log of the compaction
+    /// strategy are replayed into the in-memory tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{LsmMap, SyncPolicy};
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_with_sync_policy", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let map: LsmMap<u32, u32, _> = LsmMap::with_sync_policy(sts, SyncPolicy::Every(100))?;
+    /// # fs::remove_dir_all("example_lsm_map_with_sync_policy")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn with_sync_policy(compaction_strategy: C, sync_policy: SyncPolicy) -> Result<Self> {
+        let (writer, in_memory_tree) = Writer::open(compaction_strategy, sync_policy)?;
+        Ok(LsmMap {
+            in_memory_tree,
+            writer,
+            merge_operator: None,
+        })
+    }
+
+    /// Sets the merge operator that is used to fold the operands written by `merge`. The merge
+    /// operator is not persisted, so it must be set again whenever the map is constructed from an
+    /// existing compaction strategy that contains operands. Until then, reading a key that has
+    /// operands returns `Error::MissingMergeOperator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{LsmMap, MergeOperator};
+    ///
+    /// struct Counter;
+    ///
+    /// impl MergeOperator<u64> for Counter {
+    ///     fn full_merge(&self, existing_value: Option<&u64>, operand: &u64) -> u64 {
+    ///         existing_value.unwrap_or(&0) + operand
+    ///     }
+    /// }
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_set_merge_operator", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map: LsmMap<u32, u64, _> = LsmMap::new(sts)?;
+    /// map.set_merge_operator(Counter);
+    /// # fs::remove_dir_all("example_lsm_map_set_merge_operator")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_merge_operator<M>(&mut self, merge_operator: M)
+    where
+        M: 'static + MergeOperator<U>,
+    {
+        let merge_operator: Arc<dyn MergeOperator<U>> = Arc::new(merge_operator);
+        self.writer
+            .compaction_strategy
+            .set_merge_operator(Arc::clone(&merge_operator));
+        self.merge_operator = Some(merge_operator);
     }
 
     fn try_compact(&mut self) -> Result<()> {
-        self.in_memory_usage = 0;
-        let mut sstable_builder = SSTableBuilder::new(
-            self.compaction_strategy.get_path(),
-            self.in_memory_tree.len(),
-        )?;
-        for entry in mem::replace(&mut self.in_memory_tree, BTreeMap::new()) {
-            sstable_builder.append(entry.0, entry.1)?;
+        self.writer
+            .flush(mem::take(&mut self.in_memory_tree).into_iter())
+    }
+
+    fn insert_entries(&mut self, entries: Vec<(T, SSTableValue<U>)>) -> Result<()> {
+        let in_memory_tree = &mut self.in_memory_tree;
+        if self.writer.insert_entries(entries, || in_memory_tree)? {
+            self.try_compact()
+        } else {
+            Ok(())
         }
-        let sstable = SSTable::new(sstable_builder.flush()?)?;
-        self.compaction_strategy.try_compact(sstable)
     }
 
     /// Inserts a key-value pair into the map. If the key-value pair causes the size of the
@@ -114,7 +204,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_insert", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// assert_eq!(map.get(&1)?, Some(1));
@@ -127,26 +217,49 @@ where
     /// # foo().unwrap();
     /// ```
     pub fn insert(&mut self, key: T, value: U) -> Result<()> {
-        let value = SSTableValue {
-            data: Some(value),
-            logical_time: self.compaction_strategy.get_and_increment_logical_time()?,
-        };
-        let key_size = serialized_size(&key)?;
-        let value_size = serialized_size(&value)?;
-
-        if let Some(ref value) = self.in_memory_tree.get(&key) {
-            let value_size = serialized_size(value)?;
-            self.in_memory_usage -= key_size + value_size;
-        }
-
-        self.in_memory_usage += key_size + value_size;
-        self.in_memory_tree.insert(key, value);
+        let value = self.writer.new_value(SSTableData::Value(value), None)?;
+        self.insert_entries(vec![(key, value)])
+    }
 
-        if self.in_memory_usage > self.compaction_strategy.get_max_in_memory_size() {
-            self.try_compact()
-        } else {
-            Ok(())
-        }
+    /// Inserts a key-value pair into the map that expires after a duration. Once the key-value
+    /// pair expires, reads treat it as if it was removed, and it is dropped when the SSTables that
+    /// contain it are compacted. Expiry is based on the system clock, and its resolution is one
+    /// millisecond. If the key-value pair causes the size of the in-memory tree to exceed its
+    /// size threshold, it will flush the data into a SSTable and then compact the SSTables if
+    /// necessary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_insert_with_ttl", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert_with_ttl(1, 1, Duration::from_millis(50))?;
+    /// map.insert_with_ttl(2, 2, Duration::from_secs(3600))?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    ///
+    /// thread::sleep(Duration::from_millis(100));
+    /// assert_eq!(map.get(&1)?, None);
+    /// assert_eq!(map.get(&2)?, Some(2));
+    /// assert_eq!(map.len()?, 1);
+    /// # fs::remove_dir_all("example_lsm_map_insert_with_ttl")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn insert_with_ttl(&mut self, key: T, value: U, ttl: Duration) -> Result<()> {
+        let value = self
+            .writer
+            .new_value(SSTableData::Value(value), Some(ttl))?;
+        self.insert_entries(vec![(key, value)])
     }
 
     /// Removes a key-value pair into the map by inserting a tombstone. If the key-value pair causes
@@ -163,7 +276,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_remove", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// assert_eq!(map.get(&1)?, Some(1));
@@ -176,26 +289,97 @@ where
     /// # foo().unwrap();
     /// ```
     pub fn remove(&mut self, key: T) -> Result<()> {
-        let key_size = serialized_size(&key)?;
-        let value = SSTableValue {
-            data: None,
-            logical_time: self.compaction_strategy.get_and_increment_logical_time()?,
-        };
-
-        if let Some(ref value) = self.in_memory_tree.get(&key) {
-            let value_size = serialized_size(value)?;
-            self.in_memory_usage -= key_size + value_size;
-        }
+        let value = self.writer.new_value(SSTableData::Tombstone, None)?;
+        self.insert_entries(vec![(key, value)])
+    }
 
-        self.in_memory_usage += serialized_size(&key)?;
-        self.in_memory_usage += serialized_size(&value)?;
-        self.in_memory_tree.insert(key, value);
+    /// Writes a merge operand for a key without reading its existing value. The operand is folded
+    /// into the existing value using the merge operator when the key is read, or when the SSTables
+    /// that contain the operand are compacted. If the operand causes the size of the in-memory
+    /// tree to exceed its size threshold, it will flush the data into a SSTable and then compact
+    /// the SSTables if necessary.
+    ///
+    /// Returns `Error::MissingMergeOperator` if a merge operator has not been set with
+    /// `set_merge_operator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{LsmMap, MergeOperator};
+    ///
+    /// struct Counter;
+    ///
+    /// impl MergeOperator<u64> for Counter {
+    ///     fn full_merge(&self, existing_value: Option<&u64>, operand: &u64) -> u64 {
+    ///         existing_value.unwrap_or(&0) + operand
+    ///     }
+    /// }
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_merge", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.set_merge_operator(Counter);
+    ///
+    /// map.merge(1, 1)?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    ///
+    /// map.merge(1, 2)?;
+    /// assert_eq!(map.get(&1)?, Some(3));
+    /// # fs::remove_dir_all("example_lsm_map_merge")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn merge(&mut self, key: T, operand: U) -> Result<()> {
+        merge::get_merge_operator(self.merge_operator.as_ref())?;
+        let value = self.writer.new_operand(
+            self.in_memory_tree.get(&key),
+            operand,
+            self.merge_operator.as_ref(),
+        )?;
+        self.insert_entries(vec![(key, value)])
+    }
 
-        if self.in_memory_usage > self.compaction_strategy.get_max_in_memory_size() {
-            self.try_compact()
-        } else {
-            Ok(())
+    /// Applies all writes in a `WriteBatch<T, U>` atomically. All writes in the batch are assigned
+    /// the same logical time and are logged as a single record in the write-ahead log, so either
+    /// all or none of them are recovered after a crash. The in-memory tree is only flushed after
+    /// the entire batch is applied, so a batch is never split across SSTables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{LsmMap, WriteBatch};
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_write", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// batch.remove(1);
+    /// batch.insert(2, 2);
+    /// map.write(batch)?;
+    ///
+    /// assert_eq!(map.get(&1)?, None);
+    /// assert_eq!(map.get(&2)?, Some(2));
+    /// # fs::remove_dir_all("example_lsm_map_write")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn write(&mut self, batch: WriteBatch<T, U>) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
         }
+
+        let entries = self.writer.new_batch(batch)?;
+        self.insert_entries(entries)
     }
 
     /// Checks if a key exists in the map.
@@ -210,7 +394,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_contains_key", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// assert!(!map.contains_key(&0)?);
@@ -241,7 +425,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_get", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// assert_eq!(map.get(&0)?, None);
@@ -256,20 +440,46 @@ where
         T: Borrow<V>,
         V: Ord + Hash + ?Sized,
     {
-        if let Some(value) = self.in_memory_tree.get(&key) {
-            Ok(value.data.clone())
-        } else {
-            self.compaction_strategy
-                .get(key)
-                .map(|value_opt| value_opt.and_then(|value| value.data))
+        self.get_at(key, None)
+    }
+
+    pub(crate) fn get_at<V>(&mut self, key: &V, snapshot: Option<&Snapshot>) -> Result<Option<U>>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
+    {
+        let logical_time = snapshot.map(|snapshot| snapshot.get_logical_time());
+        let mut versions: Vec<_> = self
+            .in_memory_tree
+            .get(&key)
+            .filter(|value| value.is_visible(logical_time))
+            .cloned()
+            .into_iter()
+            .collect();
+
+        // the disk-resident data is only needed if there is no value or tombstone in memory
+        if !merge::is_resolved(&versions) {
+            let value_opt = match snapshot {
+                Some(snapshot) => self
+                    .writer
+                    .compaction_strategy
+                    .get_snapshot(key, snapshot)?,
+                None => self.writer.compaction_strategy.get(key)?,
+            };
+            versions.extend(value_opt);
         }
+
+        Ok(
+            merge::resolve_versions(versions, self.merge_operator.as_ref())?
+                .and_then(|value| value.data.into_value()),
+        )
     }
 
     /// Returns the approximate number of elements in the map. The length returned will always be
     /// greater than or equal to the actual length. It counts all the non-tombstone entries stored
-    /// in the SSTables, so it will overcount if there are duplicate entries or if a tombstone
-    /// overrides previous entries. For an accurate, but slower way of getting the length, see
-    /// `len`.
+    /// in the SSTables, so it will overcount if there are duplicate entries, if a tombstone
+    /// overrides previous entries, or if entries have expired, but have not been compacted yet.
+    /// For an accurate, but slower way of getting the length, see `len`.
     ///
     /// # Examples
     ///
@@ -281,7 +491,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_len_hint", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// assert!(map.len_hint()? >= 1);
@@ -291,7 +501,7 @@ where
     /// # foo().unwrap();
     /// ```
     pub fn len_hint(&mut self) -> Result<usize> {
-        Ok(self.in_memory_tree.len() + self.compaction_strategy.len_hint()?)
+        Ok(self.in_memory_tree.len() + self.writer.compaction_strategy.len_hint()?)
     }
 
     /// Returns the number of elements in the map by first flushing the in-memory tree and then
@@ -308,7 +518,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_len", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// assert_eq!(map.len()?, 1);
@@ -319,7 +529,7 @@ where
     /// ```
     pub fn len(&mut self) -> Result<usize> {
         self.flush()?;
-        self.compaction_strategy.len()
+        self.writer.compaction_strategy.len()
     }
 
     /// Returns `true` if the map is empty. The in-memory tree is flushed and then a full scan of
@@ -335,7 +545,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_is_empty", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     /// assert!(map.is_empty()?);
     ///
     /// map.insert(1, 1)?;
@@ -362,7 +572,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_clear", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// map.insert(2, 2)?;
@@ -375,7 +585,7 @@ where
     /// ```
     pub fn clear(&mut self) -> Result<()> {
         self.in_memory_tree.clear();
-        self.compaction_strategy.clear()
+        self.writer.clear()
     }
 
     /// Returns the minimum key of the map. Returns `None` if the map is empty.
@@ -390,7 +600,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_min", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// map.insert(3, 3)?;
@@ -401,12 +611,13 @@ where
     /// # foo().unwrap();
     /// ```
     pub fn min(&mut self) -> Result<Option<T>> {
+        let current_time = sstable::current_time();
         let in_memory_min = self
             .in_memory_tree
             .iter()
-            .find(|entry| entry.1.data.is_some())
+            .find(|entry| entry.1.is_live(current_time))
             .map(|entry| entry.0.clone());
-        let disk_min = self.compaction_strategy.min()?;
+        let disk_min = self.writer.compaction_strategy.min()?;
 
         if in_memory_min.is_none() {
             Ok(disk_min)
@@ -429,7 +640,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_max", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// map.insert(3, 3)?;
@@ -440,18 +651,19 @@ where
     /// # foo().unwrap();
     /// ```
     pub fn max(&mut self) -> Result<Option<T>> {
+        let current_time = sstable::current_time();
         Ok(cmp::max(
             self.in_memory_tree
                 .iter()
                 .rev()
-                .find(|entry| entry.1.data.is_some())
+                .find(|entry| entry.1.is_live(current_time))
                 .map(|entry| entry.0.clone()),
-            self.compaction_strategy.max()?,
+            self.writer.compaction_strategy.max()?,
         ))
     }
 
-    /// Flushes the in-memory tree into a SSTable if it is not empty. The map must be flushed
-    /// before being dropped or the contents of the in-memory tree will be lost.
+    /// Flushes the in-memory tree into a SSTable if it is not empty and truncates the write-ahead
+    /// log.
     ///
     /// # Examples
     ///
@@ -463,7 +675,7 @@ where
     /// use extended_collections::lsm_tree::LsmMap;
     ///
     /// let sts = SizeTieredStrategy::new("example_lsm_map_flush", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let mut map = LsmMap::new(sts)?;
     ///
     /// map.insert(1, 1)?;
     /// map.insert(3, 3)?;
@@ -477,12 +689,13 @@ where
         if !self.in_memory_tree.is_empty() {
             self.try_compact()?;
         }
-        self.compaction_strategy.flush()
+        self.writer.compaction_strategy.flush()
     }
 
-    /// Returns an iterator over the map. The iterator will yield key-value pairs in ascending
-    /// order. The in-memory tree will be flushed before yielding the iterator. The map will not
-    /// perform any compactions if there are any undropped iterators.
+    /// Flushes the in-memory tree and then compacts every SSTable whose keys intersect a range into
+    /// a fully merged run. The versions that are shadowed by a newer version and are not visible to
+    /// any live snapshot are dropped, and so are tombstones that no longer hide an older version.
+    /// This is useful for reclaiming space after removing many keys.
     ///
     /// # Examples
     ///
@@ -493,36 +706,1715 @@ where
     /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
     /// use extended_collections::lsm_tree::LsmMap;
     ///
-    /// let sts = SizeTieredStrategy::new("example_lsm_map_iter", 10000, 4, 50000, 0.5, 1.5)?;
-    /// let mut map = LsmMap::new(sts);
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_compact_range", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// for key in 0..100 {
+    ///     map.insert(key, key)?;
+    /// }
+    /// map.flush()?;
+    /// for key in 0..50 {
+    ///     map.remove(key)?;
+    /// }
+    ///
+    /// map.compact_range(0..50)?;
+    /// assert_eq!(map.len()?, 50);
+    /// assert_eq!(map.stats()?.levels.iter().map(|level| level.tombstone_count).sum::<usize>(), 0);
+    /// # fs::remove_dir_all("example_lsm_map_compact_range")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn compact_range<R>(&mut self, range: R) -> Result<()>
+    where
+        R: RangeBounds<T>,
+    {
+        self.flush()?;
+        self.writer
+            .compaction_strategy
+            .compact_range((range.start_bound().cloned(), range.end_bound().cloned()))
+    }
+
+    /// Flushes the in-memory tree and then compacts all SSTables into a fully merged run. This is
+    /// equivalent to calling `compact_range` with an unbounded range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::LeveledStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let ls = LeveledStrategy::new("example_lsm_map_compact_all", 10000, 4, 50000, 10, 10)?;
+    /// let mut map = LsmMap::new(ls)?;
     ///
     /// map.insert(1, 1)?;
-    /// map.insert(2, 2)?;
+    /// map.flush()?;
+    /// map.remove(1)?;
     ///
-    /// let mut iterator = map.iter()?.map(|value| value.unwrap());
-    /// assert_eq!(iterator.next(), Some((1, 1)));
-    /// assert_eq!(iterator.next(), Some((2, 2)));
-    /// assert_eq!(iterator.next(), None);
-    /// # fs::remove_dir_all("example_lsm_map_iter")?;
+    /// map.compact_all()?;
+    /// assert!(map.is_empty()?);
+    /// assert!(map.stats()?.levels.iter().all(|level| level.sstable_count == 0));
+    /// # fs::remove_dir_all("example_lsm_map_compact_all")?;
     /// # Ok(())
     /// # }
     /// # foo().unwrap();
     /// ```
-    pub fn iter(&mut self) -> Result<Box<CompactionIter<T, U>>> {
+    pub fn compact_all(&mut self) -> Result<()> {
+        self.compact_range(..)
+    }
+
+    /// Ingests SSTables that were written by `SstWriter`s into the map. The in-memory tree is
+    /// flushed first, and then each SSTable is assigned a logical time, so its entries replace
+    /// the existing entries with the same keys. The SSTables are hard linked or copied into the
+    /// directory of the compaction strategy and placed directly into a level, bucket, or window
+    /// instead of being rewritten. The source SSTables are removed after the compaction strategy
+    /// records the ingested SSTables, so they are left in place if ingesting fails. Returns
+    /// `Error::OverlappingSSTables` without ingesting any SSTable if the key ranges of two
+    /// SSTables overlap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::{Compression, FilterPolicy, LsmMap, SstWriter};
+    ///
+    /// # fs::create_dir("example_lsm_map_ingest_import")?;
+    /// let mut paths = Vec::new();
+    /// for part in 0..2u32 {
+    ///     let mut writer = SstWriter::new(
+    ///         "example_lsm_map_ingest_import",
+    ///         100,
+    ///         Compression::None,
+    ///         &FilterPolicy::default(),
+    ///     )?;
+    ///     for key in part * 100..(part + 1) * 100 {
+    ///         writer.insert(key, key)?;
+    ///     }
+    ///     paths.push(writer.finish()?);
+    /// }
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_ingest", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 0)?;
+    /// map.ingest(paths)?;
+    /// assert_eq!(map.get(&1)?, Some(1));
+    /// assert_eq!(map.len()?, 200);
+    /// # fs::remove_dir_all("example_lsm_map_ingest")?;
+    /// # fs::remove_dir_all("example_lsm_map_ingest_import")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn ingest<I, P>(&mut self, paths: I) -> Result<()>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut sstables = paths
+            .into_iter()
+            .map(SSTable::new)
+            .collect::<Result<Vec<SSTable<T, U>>>>()?;
+        sstables.sort_by(|sstable_1, sstable_2| {
+            sstable_1
+                .summary
+                .key_range
+                .0
+                .cmp(&sstable_2.summary.key_range.0)
+        });
+        for pair in sstables.windows(2) {
+            if pair[0].summary.key_range.1 >= pair[1].summary.key_range.0 {
+                return Err(Error::OverlappingSSTables(
+                    pair[0].path.clone(),
+                    pair[1].path.clone(),
+                ));
+            }
+        }
+
         self.flush()?;
-        self.compaction_strategy.iter()
+        let mut source_paths = Vec::new();
+        let mut ingested_sstables = Vec::new();
+        for sstable in sstables {
+            let logical_time = self
+                .writer
+                .compaction_strategy
+                .get_and_increment_logical_time()?;
+            source_paths.push(sstable.path.clone());
+            ingested_sstables
+                .push(sstable.ingest(self.writer.compaction_strategy.get_path(), logical_time)?);
+        }
+        self.writer.compaction_strategy.ingest(ingested_sstables)?;
+
+        // the sources are only removed once the manifest references the ingested SSTables
+        for source_path in source_paths {
+            fs::remove_dir_all(source_path)?;
+        }
+        Ok(())
     }
-}
 
-// impl<'a, T, U> IntoIterator for &'a LsmMap<T, U>
-// where
-//     T: 'a,
-//     U: 'a,
-// {
-//     type Item = Result<(T, U)>;
-//     type IntoIter = BpMapIterMut<'a, T, U>;
+    /// Flushes the in-memory tree and writes a consistent checkpoint of the map into a new
+    /// directory. The SSTables of the map are hard linked into the checkpoint if possible, so a
+    /// checkpoint is cheap to create and does not block later writes. The checkpoint can be opened
+    /// with the `open` function of the compaction strategy of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts: SizeTieredStrategy<u32, u32> =
+    ///     SizeTieredStrategy::new("example_lsm_map_checkpoint", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// map.insert(1, 1)?;
+    /// map.checkpoint("example_lsm_map_checkpoint_backup")?;
+    /// map.insert(1, 2)?;
+    ///
+    /// let sts: SizeTieredStrategy<u32, u32> =
+    ///     SizeTieredStrategy::open("example_lsm_map_checkpoint_backup")?;
+    /// let mut backup = LsmMap::new(sts)?;
+    /// assert_eq!(backup.get(&1)?, Some(1));
+    /// # fs::remove_dir_all("example_lsm_map_checkpoint")?;
+    /// # fs::remove_dir_all("example_lsm_map_checkpoint_backup")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn checkpoint<P>(&mut self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.flush()?;
+        self.writer.compaction_strategy.checkpoint(path.as_ref())
+    }
 
-//     fn into_iter(self) -> Self::IntoIter {
-//         self.iter_mut().unwrap()
-//     }
-// }
+    /// Verifies the checksums of all SSTables of the map. Returns `Error::Corruption` for the
+    /// first corrupted block or file that is found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_verify", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.flush()?;
+    /// map.verify()?;
+    /// # fs::remove_dir_all("example_lsm_map_verify")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn verify(&mut self) -> Result<()> {
+        self.writer.compaction_strategy.verify()
+    }
+
+    /// Returns statistics of the SSTables of the map, and of the flushes, compactions and filter
+    /// probes since the compaction strategy of the map was constructed or opened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_stats", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.flush()?;
+    ///
+    /// let stats = map.stats()?;
+    /// assert_eq!(stats.flush_count, 1);
+    /// assert_eq!(stats.levels[0].sstable_count, 1);
+    /// assert_eq!(stats.levels[0].entry_count, 1);
+    /// # fs::remove_dir_all("example_lsm_map_stats")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn stats(&mut self) -> Result<CompactionStats> {
+        self.writer.compaction_strategy.stats()
+    }
+
+    /// Sets the listener that is notified when the in-memory tree is flushed and when compactions
+    /// start or finish. The listener is not persisted, so it must be set again whenever the map is
+    /// constructed from an existing compaction strategy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::{Event, EventListener, SizeTieredStrategy};
+    /// use extended_collections::lsm_tree::LsmMap;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// #[derive(Default)]
+    /// struct FlushCounter(AtomicUsize);
+    ///
+    /// impl EventListener for FlushCounter {
+    ///     fn on_event(&self, event: &Event) {
+    ///         if let Event::Flush { .. } = event {
+    ///             self.0.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_set_event_listener", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    /// let listener = Arc::new(FlushCounter::default());
+    /// map.set_event_listener(listener.clone());
+    ///
+    /// map.insert(1, 1)?;
+    /// map.flush()?;
+    /// assert_eq!(listener.0.load(Ordering::Relaxed), 1);
+    /// # fs::remove_dir_all("example_lsm_map_set_event_listener")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn set_event_listener(&mut self, event_listener: Arc<dyn EventListener>) {
+        self.writer
+            .compaction_strategy
+            .set_event_listener(event_listener);
+    }
+
+    /// Returns an iterator over the map. The iterator will yield key-value pairs in ascending
+    /// order, or in descending order if it is reversed. The in-memory tree will be flushed before
+    /// yielding the iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_iter", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut iterator = map.iter()?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.iter()?.rev().map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_lsm_map_iter")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn iter(&mut self) -> Result<Box<CompactionIter<T, U>>> {
+        self.flush()?;
+        self.writer.compaction_strategy.iter()
+    }
+
+    /// Returns an iterator over a range of keys in the map. The iterator will yield key-value
+    /// pairs in ascending order, or in descending order if it is reversed. Unlike `iter`, the
+    /// in-memory tree is not flushed; its entries are merged with the disk-resident data instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end of the range, or if the start and
+    /// end of the range are equal and both excluded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_range", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// map.insert(3, 3)?;
+    /// map.flush()?;
+    /// map.remove(2)?;
+    /// map.insert(4, 4)?;
+    ///
+    /// let mut iterator = map.range(2..)?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((3, 3)));
+    /// assert_eq!(iterator.next(), Some((4, 4)));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.range(..4)?.rev().map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((3, 3)));
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_lsm_map_range")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn range<R>(&mut self, range: R) -> Result<Box<CompactionIter<T, U>>>
+    where
+        T: 'static,
+        U: 'static,
+        R: RangeBounds<T>,
+    {
+        self.range_at(range, None)
+    }
+
+    pub(crate) fn range_at<R>(
+        &mut self,
+        range: R,
+        snapshot: Option<&Snapshot>,
+    ) -> Result<Box<CompactionIter<T, U>>>
+    where
+        T: 'static,
+        U: 'static,
+        R: RangeBounds<T>,
+    {
+        let logical_time = snapshot.map(|snapshot| snapshot.get_logical_time());
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        let in_memory_entries: Vec<_> = self
+            .in_memory_tree
+            .range(range.clone())
+            .filter(|entry| entry.1.is_visible(logical_time))
+            .map(|entry| (entry.0.clone(), entry.1.clone()))
+            .collect();
+        let disk_iter = match snapshot {
+            Some(snapshot) => self
+                .writer
+                .compaction_strategy
+                .range_snapshot(range, snapshot)?,
+            None => self.writer.compaction_strategy.range(range)?,
+        };
+
+        Ok(Box::new(LsmMapIter {
+            in_memory_iter: DoubleEndedPeekable::new(in_memory_entries.into_iter()),
+            disk_iter: DoubleEndedPeekable::new(disk_iter),
+            merge_operator: self.merge_operator.clone(),
+        }))
+    }
+
+    /// Returns an iterator over the keys of the map. The iterator will yield keys in ascending
+    /// order, or in descending order if it is reversed. Values are not deserialized, and the
+    /// in-memory tree is not flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_keys", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut iterator = map.keys()?.map(|key| key.unwrap());
+    /// assert_eq!(iterator.next(), Some(1));
+    /// assert_eq!(iterator.next(), Some(2));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.keys()?.rev().map(|key| key.unwrap());
+    /// assert_eq!(iterator.next(), Some(2));
+    /// assert_eq!(iterator.next(), Some(1));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_lsm_map_keys")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn keys(&mut self) -> Result<Box<KeyIter<T>>>
+    where
+        T: 'static,
+        U: 'static,
+    {
+        self.keys_range(..)
+    }
+
+    /// Returns an iterator over a range of keys in the map. The iterator will yield keys in
+    /// ascending order, or in descending order if it is reversed. Values are not deserialized, and
+    /// SSTables that do not intersect the range are not read. If the map has a prefix extractor
+    /// and both bounds of the range have the same prefix, SSTables whose prefix filter does not
+    /// contain the prefix are skipped as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end of the range, or if the start and
+    /// end of the range are equal and both excluded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_keys_range", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// map.insert(3, 3)?;
+    /// map.flush()?;
+    /// map.remove(2)?;
+    /// map.insert(4, 4)?;
+    ///
+    /// let mut iterator = map.keys_range(2..)?.map(|key| key.unwrap());
+    /// assert_eq!(iterator.next(), Some(3));
+    /// assert_eq!(iterator.next(), Some(4));
+    /// assert_eq!(iterator.next(), None);
+    ///
+    /// let mut iterator = map.keys_range(..4)?.rev().map(|key| key.unwrap());
+    /// assert_eq!(iterator.next(), Some(3));
+    /// assert_eq!(iterator.next(), Some(1));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_lsm_map_keys_range")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn keys_range<R>(&mut self, range: R) -> Result<Box<KeyIter<T>>>
+    where
+        T: 'static,
+        U: 'static,
+        R: RangeBounds<T>,
+    {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        let in_memory_entries: Vec<_> = self
+            .in_memory_tree
+            .range(range.clone())
+            .map(|entry| (entry.0.clone(), entry.1.without_value()))
+            .collect();
+
+        // sstables that cannot contain any key in the range are skipped
+        let filter_policy = self.writer.compaction_strategy.get_filter_policy();
+        let prefix = filter_policy.get_range_prefix(&range);
+        let sorted_runs = self
+            .writer
+            .compaction_strategy
+            .get_sorted_runs()?
+            .into_iter()
+            .map(|sorted_run| {
+                sorted_run
+                    .into_iter()
+                    .filter(|sstable| {
+                        sstable::is_intersecting_range(&sstable.summary.key_range, &range)
+                            && filter_policy.may_contain_prefix(sstable, prefix.as_deref())
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let cursor = MergingCursor::keys(in_memory_entries, sorted_runs);
+        let iter = VisibleIter::new(RangeIter::new(cursor, range)?, None, None);
+        Ok(Box::new(iter.filter_map(|entry| match entry {
+            Ok((key, value)) => {
+                if value.data.is_tombstone() {
+                    None
+                } else {
+                    Some(Ok(key))
+                }
+            }
+            Err(error) => Some(Err(error)),
+        })))
+    }
+
+    /// Returns a cursor over the map that is positioned before the first entry. The cursor reads
+    /// the entries of the map at the time it was created and does not observe any later writes.
+    /// The SSTables read by the cursor are kept until the cursor is dropped, so the map can still
+    /// perform compactions while the cursor is alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_cursor", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut cursor = map.cursor()?;
+    /// cursor.seek_to_last()?;
+    /// assert_eq!(cursor.key(), Some(&2));
+    /// assert_eq!(cursor.value(), Some(&2));
+    /// # fs::remove_dir_all("example_lsm_map_cursor")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn cursor(&mut self) -> Result<Cursor<T, U>> {
+        self.cursor_at(None)
+    }
+
+    pub(crate) fn cursor_at(&mut self, snapshot: Option<&Snapshot>) -> Result<Cursor<T, U>> {
+        let logical_time = snapshot.map(|snapshot| snapshot.get_logical_time());
+        let in_memory_entries: Vec<_> = self
+            .in_memory_tree
+            .iter()
+            .map(|entry| (entry.0.clone(), entry.1.clone()))
+            .collect();
+        let sorted_runs = self.writer.compaction_strategy.get_sorted_runs()?;
+
+        Ok(Cursor::new(
+            MergingCursor::new(in_memory_entries, sorted_runs),
+            logical_time,
+            self.merge_operator.clone(),
+        ))
+    }
+
+    /// Returns a snapshot of the map pinned at the current logical time. Reads through the
+    /// snapshot will not observe any writes that occur after the snapshot is taken. The in-memory
+    /// tree is flushed so that later writes do not overwrite versions visible to the snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_lsm_map_snapshot", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// let snapshot = map.snapshot()?;
+    /// map.insert(1, 2)?;
+    ///
+    /// assert_eq!(map.get(&1)?, Some(2));
+    /// assert_eq!(snapshot.get(&mut map, &1)?, Some(1));
+    /// # fs::remove_dir_all("example_lsm_map_snapshot")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn snapshot(&mut self) -> Result<Snapshot> {
+        let snapshot = self.writer.compaction_strategy.snapshot();
+        if !self.in_memory_tree.is_empty() {
+            self.try_compact()?;
+        }
+        Ok(snapshot)
+    }
+}
+
+// impl<'a, T, U> IntoIterator for &'a LsmMap<T, U>
+// where
+//     T: 'a,
+//     U: 'a,
+// {
+//     type Item = Result<(T, U)>;
+//     type IntoIter = BpMapIterMut<'a, T, U>;
+
+//     fn into_iter(self) -> Self::IntoIter {
+//         self.iter_mut().unwrap()
+//     }
+// }
+
+struct LsmMapIter<T, U> {
+    in_memory_iter: DoubleEndedPeekable<vec::IntoIter<(T, SSTableValue<U>)>>,
+    disk_iter: DoubleEndedPeekable<Box<CompactionIter<T, SSTableValue<U>>>>,
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+}
+
+impl<T, U> LsmMapIter<T, U>
+where
+    T: Ord,
+{
+    fn next_entry(&mut self) -> Option<Result<(T, SSTableValue<U>)>> {
+        let ordering = match (self.in_memory_iter.peek(), self.disk_iter.peek()) {
+            (None, None) => return None,
+            (Some(_), None) => cmp::Ordering::Less,
+            (_, Some(Err(_))) | (None, Some(_)) => cmp::Ordering::Greater,
+            (Some(in_memory_entry), Some(Ok(disk_entry))) => in_memory_entry.0.cmp(&disk_entry.0),
+        };
+
+        match ordering {
+            cmp::Ordering::Less => {
+                let (key, value) = self.in_memory_iter.next()?;
+                self.resolve_entry(key, vec![value])
+            }
+            cmp::Ordering::Greater => self.disk_iter.next(),
+            cmp::Ordering::Equal => {
+                let (key, in_memory_value) = self.in_memory_iter.next()?;
+                match self.disk_iter.next()? {
+                    Ok(disk_entry) => self.resolve_entry(key, vec![in_memory_value, disk_entry.1]),
+                    Err(error) => Some(Err(error)),
+                }
+            }
+        }
+    }
+
+    fn next_back_entry(&mut self) -> Option<Result<(T, SSTableValue<U>)>> {
+        let ordering = match (self.in_memory_iter.peek_back(), self.disk_iter.peek_back()) {
+            (None, None) => return None,
+            (Some(_), None) => cmp::Ordering::Greater,
+            (_, Some(Err(_))) | (None, Some(_)) => cmp::Ordering::Less,
+            (Some(in_memory_entry), Some(Ok(disk_entry))) => in_memory_entry.0.cmp(&disk_entry.0),
+        };
+
+        match ordering {
+            cmp::Ordering::Greater => {
+                let (key, value) = self.in_memory_iter.next_back()?;
+                self.resolve_entry(key, vec![value])
+            }
+            cmp::Ordering::Less => self.disk_iter.next_back(),
+            cmp::Ordering::Equal => {
+                let (key, in_memory_value) = self.in_memory_iter.next_back()?;
+                match self.disk_iter.next_back()? {
+                    Ok(disk_entry) => self.resolve_entry(key, vec![in_memory_value, disk_entry.1]),
+                    Err(error) => Some(Err(error)),
+                }
+            }
+        }
+    }
+
+    fn resolve_entry(
+        &self,
+        key: T,
+        mut versions: Vec<SSTableValue<U>>,
+    ) -> Option<Result<(T, SSTableValue<U>)>> {
+        versions.sort();
+        match merge::resolve_versions(versions, self.merge_operator.as_ref()) {
+            Ok(value) => value.map(|value| Ok((key, value))),
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+impl<T, U> Iterator for LsmMapIter<T, U>
+where
+    T: Ord,
+{
+    type Item = Result<(T, U)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_entry()? {
+                Ok((key, value)) => {
+                    if let Some(data) = value.data.into_value() {
+                        return Some(Ok((key, data)));
+                    }
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+impl<T, U> DoubleEndedIterator for LsmMapIter<T, U>
+where
+    T: Ord,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_back_entry()? {
+                Ok((key, value)) => {
+                    if let Some(data) = value.data.into_value() {
+                        return Some(Ok((key, data)));
+                    }
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LsmMap;
+    use crate::lsm_tree::compaction::{Event, EventListener, SizeTieredStrategy};
+    use crate::lsm_tree::{
+        Compression, Error, FilterPolicy, MergeOperator, Result, SstWriter, SyncPolicy, WriteBatch,
+    };
+    use rand::Rng;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io::Write;
+    use std::panic;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    struct Counter;
+
+    impl MergeOperator<u64> for Counter {
+        fn full_merge(&self, existing_value: Option<&u64>, operand: &u64) -> u64 {
+            existing_value.unwrap_or(&0) + operand
+        }
+    }
+
+    fn new_map(test_name: &str) -> Result<LsmMap<u32, u64, SizeTieredStrategy<u32, u64>>> {
+        LsmMap::new(SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?)
+    }
+
+    fn open_map(test_name: &str) -> Result<LsmMap<u32, u64, SizeTieredStrategy<u32, u64>>> {
+        LsmMap::new(SizeTieredStrategy::open(test_name)?)
+    }
+
+    // Alternates between the front and the back of an iterator and returns the yielded entries in
+    // ascending order.
+    fn collect_alternating<I>(mut iter: I) -> Result<Vec<(u32, u64)>>
+    where
+        I: DoubleEndedIterator<Item = Result<(u32, u64)>>,
+    {
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        while let Some(entry) = iter.next() {
+            front.push(entry?);
+            match iter.next_back() {
+                Some(entry) => back.push(entry?),
+                None => break,
+            }
+        }
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+        front.extend(back.into_iter().rev());
+        Ok(front)
+    }
+
+    #[test]
+    fn test_write_ahead_log() {
+        let test_name = "test_lsm_map_write_ahead_log";
+        run_test(
+            || {
+                let sts = SizeTieredStrategy::new(test_name, 1_000_000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::with_sync_policy(sts, SyncPolicy::Every(10))?;
+
+                for key in 0..100u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+                for key in 0..10u32 {
+                    map.remove(key)?;
+                }
+                drop(map);
+
+                let mut log_file = fs::OpenOptions::new()
+                    .append(true)
+                    .open(Path::new(test_name).join("wal.dat"))?;
+                log_file.write_all(&[0, 0, 0, 0, 0, 0, 0, 100, 1, 2, 3])?;
+                drop(log_file);
+                // simulates a logical time file that was lost with a power failure
+                let logical_time_path = Path::new(test_name).join("logical_time.dat");
+                fs::write(&logical_time_path, [0; 8])?;
+
+                let mut map = open_map(test_name)?;
+                for key in 0..10u32 {
+                    assert_eq!(map.get(&key)?, None);
+                }
+                for key in 10..100u32 {
+                    assert_eq!(map.get(&key)?, Some(u64::from(key)));
+                }
+                // new versions are newer than the replayed versions
+                let snapshot = map.snapshot()?;
+                map.insert(10, 0)?;
+                assert_eq!(snapshot.get(&mut map, &10)?, Some(10));
+                assert_eq!(map.get(&10)?, Some(0));
+                drop(snapshot);
+
+                map.insert(100, 100)?;
+                map.flush()?;
+                assert_eq!(fs::metadata(Path::new(test_name).join("wal.dat"))?.len(), 0);
+                drop(map);
+                fs::write(&logical_time_path, [0; 8])?;
+
+                let mut map = open_map(test_name)?;
+                assert_eq!(map.len()?, 91);
+                assert_eq!(map.get(&100)?, Some(100));
+                // new versions are newer than the versions in SSTables
+                map.insert(100, 0)?;
+                map.flush()?;
+                assert_eq!(map.get(&100)?, Some(0));
+
+                // a corrupted length in the middle of the log is not mistaken for a torn write
+                for key in 0..10u32 {
+                    map.insert(key, 0)?;
+                }
+                drop(map);
+                let log_path = Path::new(test_name).join("wal.dat");
+                let mut buffer = fs::read(&log_path)?;
+                buffer[7] ^= 1;
+                fs::write(&log_path, &buffer)?;
+                match open_map(test_name) {
+                    Err(Error::Corruption { offset: 0, .. }) => {}
+                    _ => panic!("Expected corruption."),
+                }
+                assert_eq!(fs::read(&log_path)?, buffer);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let test_name = "test_lsm_map_write";
+        run_test(
+            || {
+                let mut map = new_map(test_name)?;
+
+                for round in 0..1000u64 {
+                    let mut batch = WriteBatch::new();
+                    for key in 0..10u32 {
+                        batch.insert(key, round);
+                    }
+                    batch.remove(10 + round as u32);
+                    map.write(batch)?;
+
+                    let snapshot = map.snapshot()?;
+                    let values = snapshot
+                        .iter(&mut map)?
+                        .map(|entry| entry.map(|(_, value)| value))
+                        .collect::<Result<Vec<u64>>>()?;
+                    assert_eq!(values, vec![round; 10]);
+                }
+                map.insert(10, 10)?;
+
+                let mut batch = WriteBatch::new();
+                for key in 0..10u32 {
+                    batch.insert(key, 1000);
+                }
+                batch.remove(10);
+                map.write(batch)?;
+                drop(map);
+
+                // simulates a torn write of the last batch
+                let log_path = Path::new(test_name).join("wal.dat");
+                let log_len = fs::metadata(&log_path)?.len();
+                fs::OpenOptions::new()
+                    .write(true)
+                    .open(&log_path)?
+                    .set_len(log_len - 1)?;
+
+                let mut map = open_map(test_name)?;
+                for key in 0..10u32 {
+                    assert_eq!(map.get(&key)?, Some(999));
+                }
+                assert_eq!(map.get(&10)?, Some(10));
+                assert_eq!(map.len()?, 11);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let test_name = "test_lsm_map_range";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let mut map = new_map(test_name)?;
+                let mut expected = BTreeMap::new();
+
+                for _ in 0..10_000 {
+                    let key = rng.gen_range(0, 5000);
+                    if rng.gen::<bool>() {
+                        let val = rng.gen::<u64>();
+                        map.insert(key, val)?;
+                        expected.insert(key, val);
+                    } else {
+                        map.remove(key)?;
+                        expected.remove(&key);
+                    }
+                }
+
+                for _ in 0..100 {
+                    let start = rng.gen_range(0, 5000);
+                    let end = rng.gen_range(start, 5001);
+                    let actual = map.range(start..end)?.collect::<Result<Vec<_>>>()?;
+                    let expected_range: Vec<_> =
+                        expected.range(start..end).map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_range);
+
+                    let actual = map.range(start..)?.collect::<Result<Vec<_>>>()?;
+                    let expected_range: Vec<_> =
+                        expected.range(start..).map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_range);
+
+                    let actual = map.range(..=end)?.collect::<Result<Vec<_>>>()?;
+                    let expected_range: Vec<_> =
+                        expected.range(..=end).map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_range);
+                }
+
+                let actual = map.range(..)?.collect::<Result<Vec<_>>>()?;
+                let expected_range: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_range);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_rev() {
+        let test_name = "test_lsm_map_rev";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let mut map = new_map(test_name)?;
+                let mut expected = BTreeMap::new();
+                let mut snapshot = None;
+                let mut snapshot_expected = BTreeMap::new();
+
+                for i in 0..10_000 {
+                    // a snapshot retains older versions of keys in the sstables
+                    if i == 5000 {
+                        snapshot = Some(map.snapshot()?);
+                        snapshot_expected = expected.clone();
+                    }
+                    let key = rng.gen_range(0, 5000);
+                    if rng.gen::<bool>() {
+                        let val = rng.gen::<u64>();
+                        map.insert(key, val)?;
+                        expected.insert(key, val);
+                    } else {
+                        map.remove(key)?;
+                        expected.remove(&key);
+                    }
+                }
+
+                for _ in 0..100 {
+                    let start = rng.gen_range(0, 5000);
+                    let end = rng.gen_range(start, 5001);
+                    let actual = map.range(start..end)?.rev().collect::<Result<Vec<_>>>()?;
+                    let expected_range: Vec<_> = expected
+                        .range(start..end)
+                        .rev()
+                        .map(|e| (*e.0, *e.1))
+                        .collect();
+                    assert_eq!(actual, expected_range);
+
+                    let actual = map.range(start..=end)?.rev().collect::<Result<Vec<_>>>()?;
+                    let expected_range: Vec<_> = expected
+                        .range(start..=end)
+                        .rev()
+                        .map(|e| (*e.0, *e.1))
+                        .collect();
+                    assert_eq!(actual, expected_range);
+
+                    let actual = collect_alternating(map.range(..end)?)?;
+                    let expected_range: Vec<_> =
+                        expected.range(..end).map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_range);
+                }
+
+                let snapshot = snapshot.expect("Expected snapshot.");
+                let actual = snapshot
+                    .range(&mut map, 1000..4000)?
+                    .rev()
+                    .collect::<Result<Vec<_>>>()?;
+                let expected_range: Vec<_> = snapshot_expected
+                    .range(1000..4000)
+                    .rev()
+                    .map(|e| (*e.0, *e.1))
+                    .collect();
+                assert_eq!(actual, expected_range);
+
+                assert_eq!(map.max()?, expected.keys().next_back().cloned());
+                let actual = map.iter()?.rev().collect::<Result<Vec<_>>>()?;
+                let expected_entries: Vec<_> =
+                    expected.iter().rev().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_entries);
+
+                let actual = collect_alternating(map.iter()?)?;
+                let expected_entries: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_entries);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_keys() {
+        let test_name = "test_lsm_map_keys";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let mut map = new_map(test_name)?;
+                let mut expected = BTreeMap::new();
+                map.set_merge_operator(Counter);
+
+                // a snapshot retains older versions of keys in the sstables
+                let snapshot = map.snapshot()?;
+                for _ in 0..10_000 {
+                    let key = rng.gen_range(0, 5000);
+                    match rng.gen_range(0, 4) {
+                        0 => {
+                            map.remove(key)?;
+                            expected.remove(&key);
+                        }
+                        1 => {
+                            let operand = rng.gen_range(0, 100);
+                            map.merge(key, operand)?;
+                            *expected.entry(key).or_insert(0) += operand;
+                        }
+                        _ => {
+                            let val = rng.gen::<u64>();
+                            map.insert(key, val)?;
+                            expected.insert(key, val);
+                        }
+                    }
+                }
+
+                // expired values are not yielded, but merging into an expired value yields a key
+                for key in (0..1000).step_by(10) {
+                    map.insert_with_ttl(key, 0, Duration::from_millis(100))?;
+                    expected.remove(&key);
+                }
+                thread::sleep(Duration::from_millis(200));
+                for key in (0..1000).step_by(20) {
+                    map.merge(key, 1)?;
+                    expected.insert(key, 1);
+                }
+
+                for _ in 0..100 {
+                    let start = rng.gen_range(0, 5000);
+                    let end = rng.gen_range(start, 5001);
+                    let actual = map.keys_range(start..end)?.collect::<Result<Vec<_>>>()?;
+                    let expected_keys: Vec<_> = expected.range(start..end).map(|e| *e.0).collect();
+                    assert_eq!(actual, expected_keys);
+
+                    let actual = map
+                        .keys_range(start..=end)?
+                        .rev()
+                        .collect::<Result<Vec<_>>>()?;
+                    let expected_keys: Vec<_> =
+                        expected.range(start..=end).rev().map(|e| *e.0).collect();
+                    assert_eq!(actual, expected_keys);
+                }
+
+                let actual = map.keys()?.collect::<Result<Vec<_>>>()?;
+                let expected_keys: Vec<_> = expected.keys().cloned().collect();
+                assert_eq!(actual, expected_keys);
+                let actual = map.keys()?.rev().collect::<Result<Vec<_>>>()?;
+                let expected_keys: Vec<_> = expected.keys().rev().cloned().collect();
+                assert_eq!(actual, expected_keys);
+
+                let actual = map.iter()?.map(|entry| entry.map(|entry| entry.0));
+                assert_eq!(
+                    actual.collect::<Result<Vec<_>>>()?,
+                    map.keys()?.collect::<Result<Vec<_>>>()?
+                );
+                drop(snapshot);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let test_name = "test_lsm_map_merge";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let mut map = new_map(test_name)?;
+                map.set_merge_operator(Counter);
+                let mut expected = BTreeMap::new();
+                let mut snapshots = Vec::new();
+
+                for round in 0..5 {
+                    for _ in 0..4000 {
+                        let key = rng.gen_range(0, 500);
+                        match rng.gen_range(0, 10) {
+                            0 => {
+                                map.remove(key)?;
+                                expected.remove(&key);
+                            }
+                            1 => {
+                                let val = rng.gen_range(0, 100);
+                                map.insert(key, val)?;
+                                expected.insert(key, val);
+                            }
+                            _ => {
+                                let operand = rng.gen_range(0, 100);
+                                map.merge(key, operand)?;
+                                *expected.entry(key).or_insert(0) += operand;
+                            }
+                        }
+                    }
+
+                    if round % 2 == 0 {
+                        snapshots.push((map.snapshot()?, expected.clone()));
+                    }
+                }
+
+                for key in 0..500 {
+                    assert_eq!(map.get(&key)?, expected.get(&key).cloned());
+                }
+
+                map.flush()?;
+
+                for (snapshot, expected) in &snapshots {
+                    for key in 0..500 {
+                        assert_eq!(snapshot.get(&mut map, &key)?, expected.get(&key).cloned());
+                    }
+
+                    let actual = snapshot.iter(&mut map)?.collect::<Result<Vec<_>>>()?;
+                    let expected_iter: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_iter);
+
+                    let actual = snapshot.iter(&mut map)?.rev().collect::<Result<Vec<_>>>()?;
+                    let expected_iter: Vec<_> =
+                        expected.iter().rev().map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_iter);
+                }
+
+                snapshots.clear();
+                for key in 0..500 {
+                    map.merge(key, 1)?;
+                    *expected.entry(key).or_insert(0) += 1;
+                }
+                map.flush()?;
+
+                let actual = map.range(100..400)?.collect::<Result<Vec<_>>>()?;
+                let expected_range: Vec<_> =
+                    expected.range(100..400).map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_range);
+
+                let actual = map.range(100..400)?.rev().collect::<Result<Vec<_>>>()?;
+                let expected_range: Vec<_> = expected
+                    .range(100..400)
+                    .rev()
+                    .map(|e| (*e.0, *e.1))
+                    .collect();
+                assert_eq!(actual, expected_range);
+
+                // the merge operator is not persisted, so unresolved operands in the write-ahead
+                // log cannot be read without it
+                map.merge(0, 1)?;
+                *expected.entry(0).or_insert(0) += 1;
+                drop(map);
+
+                let mut map = open_map(test_name)?;
+                match map.get(&0) {
+                    Err(Error::MissingMergeOperator) => {}
+                    _ => panic!("Expected missing merge operator."),
+                }
+                match map.merge(0, 1) {
+                    Err(Error::MissingMergeOperator) => {}
+                    _ => panic!("Expected missing merge operator."),
+                }
+
+                map.set_merge_operator(Counter);
+                for key in 0..500 {
+                    assert_eq!(map.get(&key)?, expected.get(&key).cloned());
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_insert_with_ttl() {
+        let test_name = "test_lsm_map_insert_with_ttl";
+        run_test(
+            || {
+                let mut map = new_map(test_name)?;
+                map.set_merge_operator(Counter);
+                let mut expected = BTreeMap::new();
+
+                // the expiring entries hide older entries that are already on disk
+                for key in 0..1000 {
+                    map.insert(key, 0)?;
+                }
+                map.flush()?;
+
+                for key in 0..1000 {
+                    if key % 2 == 0 {
+                        map.insert_with_ttl(key, u64::from(key), Duration::from_millis(100))?;
+                    } else {
+                        map.insert_with_ttl(key, u64::from(key), Duration::from_secs(3600))?;
+                        expected.insert(key, u64::from(key));
+                    }
+                }
+                thread::sleep(Duration::from_millis(200));
+
+                for key in (0..200).step_by(4) {
+                    map.merge(key, 1)?;
+                    map.merge(key + 1, 1)?;
+                    expected.insert(key, 1);
+                    *expected.get_mut(&(key + 1)).unwrap() += 1;
+                }
+
+                for key in 1000..3000 {
+                    map.insert(key, u64::from(key))?;
+                    expected.insert(key, u64::from(key));
+                }
+
+                for key in 0..3000 {
+                    assert_eq!(map.get(&key)?, expected.get(&key).cloned());
+                }
+                assert_eq!(map.min()?, Some(0));
+                assert_eq!(map.max()?, Some(2999));
+
+                let actual = map.iter()?.collect::<Result<Vec<_>>>()?;
+                let expected_iter: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_iter);
+                assert_eq!(map.len()?, expected.len());
+                assert!(map.len_hint()? >= expected.len());
+                drop(map);
+
+                let mut map = open_map(test_name)?;
+                map.set_merge_operator(Counter);
+                for key in 0..3000 {
+                    assert_eq!(map.get(&key)?, expected.get(&key).cloned());
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_compact_range() {
+        let test_name = "test_lsm_map_compact_range";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let mut map = new_map(test_name)?;
+                let mut expected = BTreeMap::new();
+                for _ in 0..10_000 {
+                    let key = rng.gen::<u32>();
+                    let val = rng.gen::<u64>();
+                    map.insert(key, val)?;
+                    expected.insert(key, val);
+                }
+                map.flush()?;
+
+                // the versions that are visible to a snapshot are kept
+                let snapshot = map.snapshot()?;
+                let snapshot_expected = expected.clone();
+                let middle = u32::MAX / 2;
+                let removed_keys: Vec<u32> =
+                    expected.range(..middle).map(|entry| *entry.0).collect();
+                for key in removed_keys {
+                    map.remove(key)?;
+                    expected.remove(&key);
+                }
+                map.compact_range(..middle)?;
+                for (key, value) in snapshot_expected.iter().step_by(10) {
+                    assert_eq!(snapshot.get(&mut map, key)?, Some(*value));
+                }
+                drop(snapshot);
+
+                map.compact_range(..middle)?;
+                let actual = map.iter()?.collect::<Result<Vec<_>>>()?;
+                assert_eq!(actual, expected.clone().into_iter().collect::<Vec<_>>());
+
+                map.compact_all()?;
+                let actual = map.iter()?.collect::<Result<Vec<_>>>()?;
+                assert_eq!(actual, expected.clone().into_iter().collect::<Vec<_>>());
+
+                let stats = map.stats()?;
+                assert_eq!(
+                    stats
+                        .levels
+                        .iter()
+                        .map(|level| level.sstable_count)
+                        .sum::<usize>(),
+                    1
+                );
+                assert_eq!(stats.levels[0].entry_count, expected.len());
+                assert_eq!(stats.levels[0].tombstone_count, 0);
+
+                drop(map);
+                let mut map = open_map(test_name)?;
+                assert_eq!(map.len()?, expected.len());
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[derive(Default)]
+    struct EventRecorder(Mutex<Vec<Event>>);
+
+    impl EventListener for EventRecorder {
+        fn on_event(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let test_name = "test_lsm_map_stats";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let mut map = new_map(test_name)?;
+                let event_recorder = Arc::new(EventRecorder::default());
+                map.set_event_listener(event_recorder.clone());
+
+                map.remove(0)?;
+                map.flush()?;
+                let stats = map.stats()?;
+                assert_eq!(stats.flush_count, 1);
+                assert_eq!(stats.compaction_count, 0);
+                assert_eq!(
+                    stats
+                        .levels
+                        .iter()
+                        .map(|level| level.sstable_count)
+                        .sum::<usize>(),
+                    1
+                );
+                assert_eq!(
+                    stats
+                        .levels
+                        .iter()
+                        .map(|level| level.tombstone_count)
+                        .sum::<usize>(),
+                    1
+                );
+
+                let mut keys = Vec::new();
+                for _ in 0..10_000 {
+                    let key = rng.gen_range(1, u32::MAX);
+                    map.insert(key, rng.gen::<u64>())?;
+                    keys.push(key);
+                }
+                map.flush()?;
+
+                let stats = map.stats()?;
+                let events = event_recorder.0.lock().unwrap().clone();
+                let mut flush_count = 0;
+                let mut bytes_flushed = 0;
+                let mut compaction_start_count = 0;
+                let mut compaction_finish_count = 0;
+                let mut bytes_read = 0;
+                let mut bytes_written = 0;
+                for event in events {
+                    match event {
+                        Event::Flush { size, .. } => {
+                            flush_count += 1;
+                            bytes_flushed += size;
+                        }
+                        Event::CompactionStart => compaction_start_count += 1,
+                        Event::CompactionFinish {
+                            bytes_read: event_bytes_read,
+                            bytes_written: event_bytes_written,
+                            ..
+                        } => {
+                            compaction_finish_count += 1;
+                            bytes_read += event_bytes_read;
+                            bytes_written += event_bytes_written;
+                        }
+                    }
+                }
+
+                assert_eq!(stats.flush_count, flush_count);
+                assert_eq!(stats.bytes_flushed, bytes_flushed);
+                assert!(stats.compaction_count > 0);
+                assert_eq!(stats.compaction_count, compaction_start_count);
+                assert_eq!(stats.compaction_count, compaction_finish_count);
+                assert_eq!(stats.bytes_read, bytes_read);
+                assert_eq!(stats.bytes_written, bytes_written);
+                assert!(stats.write_amplification() > 1.0);
+                assert!(
+                    stats
+                        .levels
+                        .iter()
+                        .map(|level| level.entry_count)
+                        .sum::<usize>()
+                        >= map.len()?
+                );
+
+                for key in keys.iter().take(100) {
+                    assert!(map.contains_key(key)?);
+                }
+                let filter_true_positive_count = map.stats()?.filter_true_positive_count;
+                assert!(filter_true_positive_count - stats.filter_true_positive_count >= 100);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    fn write_sstable<I>(import_path: &Path, entries: I) -> Result<PathBuf>
+    where
+        I: IntoIterator<Item = (u32, Option<u64>)>,
+    {
+        let mut writer = SstWriter::new(
+            import_path,
+            1000,
+            Compression::Lz4,
+            &FilterPolicy::default(),
+        )?;
+        for (key, value) in entries {
+            match value {
+                Some(value) => writer.insert(key, value)?,
+                None => writer.remove(key)?,
+            }
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn test_ingest() {
+        let test_name = "test_lsm_map_ingest";
+        run_test(
+            || {
+                let db_path = Path::new(test_name).join("db");
+                let import_path = Path::new(test_name).join("import");
+                fs::create_dir_all(import_path.as_path())?;
+
+                let sts = SizeTieredStrategy::new(db_path.as_path(), 1000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::new(sts)?;
+                let mut expected = BTreeMap::new();
+                for key in 0..3000 {
+                    map.insert(key, 0)?;
+                    expected.insert(key, 0);
+                }
+                map.flush()?;
+                let snapshot = map.snapshot()?;
+
+                // the first sstable overwrites existing keys and removes every tenth key
+                let first_path = write_sstable(
+                    &import_path,
+                    (500..1500).map(|key| {
+                        if key % 10 == 0 {
+                            (key, None)
+                        } else {
+                            (key, Some(u64::from(key) + 1))
+                        }
+                    }),
+                )?;
+                let second_path = write_sstable(
+                    &import_path,
+                    (5000..6000).map(|key| (key, Some(u64::from(key) + 1))),
+                )?;
+
+                // overlapping sstables are rejected before any sstable is ingested
+                let overlapping_path = write_sstable(
+                    &import_path,
+                    (1400..1600).map(|key| (key, Some(u64::from(key)))),
+                )?;
+                let paths = vec![
+                    second_path.clone(),
+                    first_path.clone(),
+                    overlapping_path.clone(),
+                ];
+                match map.ingest(paths) {
+                    Err(Error::OverlappingSSTables(path_1, path_2)) => {
+                        assert_eq!(path_1, first_path);
+                        assert_eq!(path_2, overlapping_path);
+                    }
+                    _ => panic!("Expected overlapping SSTables."),
+                }
+                assert!(first_path.exists());
+                assert!(second_path.exists());
+                fs::remove_dir_all(overlapping_path)?;
+
+                map.ingest(vec![second_path.clone(), first_path.clone()])?;
+                assert!(!first_path.exists());
+                assert!(!second_path.exists());
+                for key in 500..1500 {
+                    if key % 10 == 0 {
+                        expected.remove(&key);
+                    } else {
+                        expected.insert(key, u64::from(key) + 1);
+                    }
+                }
+                for key in 5000..6000 {
+                    expected.insert(key, u64::from(key) + 1);
+                }
+
+                // ingested entries are newer than existing snapshots
+                assert_eq!(snapshot.get(&mut map, &501)?, Some(0));
+                assert_eq!(snapshot.get(&mut map, &5000)?, None);
+                drop(snapshot);
+
+                // entries inserted after an ingestion are newer than the ingested entries
+                map.insert(1001, 0)?;
+                expected.insert(1001, 0);
+
+                assert_eq!(map.len()?, expected.len());
+                let actual = map.range(..)?.collect::<Result<Vec<_>>>()?;
+                let expected_range: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_range);
+
+                map.compact_all()?;
+                for (key, value) in &expected {
+                    assert_eq!(map.get(key)?, Some(*value));
+                }
+                assert_eq!(map.get(&500)?, None);
+                map.flush()?;
+                drop(map);
+
+                let sts = SizeTieredStrategy::open(db_path.as_path())?;
+                let mut map: LsmMap<u32, u64, _> = LsmMap::new(sts)?;
+                let actual = map.range(..)?.collect::<Result<Vec<_>>>()?;
+                assert_eq!(actual, expected_range);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let test_name = "test_lsm_map_checkpoint";
+        run_test(
+            || {
+                fs::create_dir(test_name)?;
+                let db_path = Path::new(test_name).join("db");
+                let checkpoint_path = Path::new(test_name).join("checkpoint");
+
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let sts = SizeTieredStrategy::new(db_path.as_path(), 1000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::new(sts)?;
+                let mut expected = BTreeMap::new();
+                for _ in 0..10_000 {
+                    let key = rng.gen_range(0, 5000);
+                    if rng.gen_range(0, 4) == 0 {
+                        map.remove(key)?;
+                        expected.remove(&key);
+                    } else {
+                        let value = rng.gen::<u64>();
+                        map.insert(key, value)?;
+                        expected.insert(key, value);
+                    }
+                }
+                map.checkpoint(&checkpoint_path)?;
+
+                // the checkpoint is not affected by later writes and compactions of the map
+                let mut latest = expected.clone();
+                for key in 0..2500 {
+                    map.insert(key, 0)?;
+                    latest.insert(key, 0);
+                }
+                map.compact_all()?;
+                let actual = map.iter()?.collect::<Result<Vec<_>>>()?;
+                let latest: Vec<_> = latest.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, latest);
+
+                // a checkpoint cannot be written into an existing directory
+                assert!(map.checkpoint(&checkpoint_path).is_err());
+
+                let sts = SizeTieredStrategy::open(checkpoint_path.as_path())?;
+                let mut checkpoint_map = LsmMap::new(sts)?;
+                let actual = checkpoint_map.iter()?.collect::<Result<Vec<_>>>()?;
+                let expected_entries: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_entries);
+                assert_eq!(checkpoint_map.len()?, expected.len());
+                checkpoint_map.verify()?;
+
+                // the checkpoint is writable and new entries are newer than the entries of the
+                // checkpoint
+                checkpoint_map.insert(0, 1)?;
+                checkpoint_map.remove(1)?;
+                checkpoint_map.flush()?;
+                assert_eq!(checkpoint_map.get(&0)?, Some(1));
+                assert_eq!(checkpoint_map.get(&1)?, None);
+                assert_eq!(map.get(&1)?, Some(0));
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let test_name = "test_lsm_map_verify";
+        run_test(
+            || {
+                // the entries are flushed into a single SSTable, so that no compaction is running
+                // while the SSTable is corrupted
+                let sts = SizeTieredStrategy::new(test_name, 1_000_000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::new(sts)?;
+
+                for key in 0..5000u32 {
+                    map.insert(key, u64::from(key))?;
+                }
+                map.flush()?;
+                map.verify()?;
+
+                let mut data_paths = Vec::new();
+                for entry in fs::read_dir(test_name)? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        data_paths.push(entry.path().join("data.dat"));
+                    }
+                }
+                assert_eq!(data_paths.len(), 1);
+                let data_path = data_paths.pop().expect("Expected SSTable.");
+
+                let mut buffer = fs::read(&data_path)?;
+                let offset = buffer.len() / 2;
+                buffer[offset] ^= 1;
+                fs::write(&data_path, buffer)?;
+
+                match map.verify() {
+                    Err(Error::Corruption { path, .. }) => assert_eq!(path, data_path),
+                    _ => panic!("Expected corruption."),
+                }
+                match map.iter()?.collect::<Result<Vec<_>>>() {
+                    Err(Error::Corruption { path, .. }) => assert_eq!(path, data_path),
+                    _ => panic!("Expected corruption."),
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
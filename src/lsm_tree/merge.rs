@@ -0,0 +1,141 @@
+use crate::lsm_tree::sstable::{self, SSTableData, SSTableValue};
+use crate::lsm_tree::{Error, Result};
+use std::sync::Arc;
+
+/// Trait for types that combine merge operands with the values of a `LsmMap`.
+///
+/// A merge operand is a partial update of a value, such as an increment of a counter or an item
+/// to append to a list. Operands are written with `LsmMap::merge` without reading the existing
+/// value, and are folded into the existing value when the key is read or when the SSTables that
+/// contain the operands are compacted.
+///
+/// # Examples
+///
+/// ```
+/// use extended_collections::lsm_tree::MergeOperator;
+///
+/// struct Counter;
+///
+/// impl MergeOperator<u64> for Counter {
+///     fn full_merge(&self, existing_value: Option<&u64>, operand: &u64) -> u64 {
+///         existing_value.unwrap_or(&0) + operand
+///     }
+/// }
+///
+/// assert_eq!(Counter.full_merge(None, &1), 1);
+/// assert_eq!(Counter.full_merge(Some(&1), &2), 3);
+/// assert_eq!(Counter.partial_merge(&1, &2), 3);
+/// ```
+pub trait MergeOperator<U>: Send + Sync {
+    /// Returns the result of applying an operand to the existing value of a key, or to `None` if
+    /// the key does not exist.
+    fn full_merge(&self, existing_value: Option<&U>, operand: &U) -> U;
+
+    /// Combines two operands into a single operand that has the same effect as applying `left`
+    /// and then `right`. Defaults to applying `right` to `left` with `full_merge`.
+    fn partial_merge(&self, left: &U, right: &U) -> U {
+        self.full_merge(Some(left), right)
+    }
+}
+
+/// Returns the merge operator of a map, or `Error::MissingMergeOperator` if it is not set.
+pub fn get_merge_operator<U>(
+    merge_operator: Option<&Arc<dyn MergeOperator<U>>>,
+) -> Result<&dyn MergeOperator<U>> {
+    merge_operator
+        .map(|merge_operator| &**merge_operator)
+        .ok_or(Error::MissingMergeOperator)
+}
+
+/// Returns the result of writing an operand at a logical time over the most recent version of
+/// a key. An expired value is treated as a tombstone, and the result of applying an operand to a
+/// value keeps the expiry time of the value. Returns `Error::MissingMergeOperator` if the operand
+/// has to be merged and no merge operator is set.
+pub fn apply_operand<U>(
+    existing_value: Option<&SSTableValue<U>>,
+    operand: U,
+    logical_time: u64,
+    merge_operator: Option<&Arc<dyn MergeOperator<U>>>,
+) -> Result<SSTableValue<U>> {
+    let is_expired = match existing_value {
+        Some(value) => value.is_expired(sstable::current_time()),
+        None => false,
+    };
+    let (data, expiry_time) = match existing_value.map(|value| (&value.data, value.expiry_time)) {
+        None => (SSTableData::Operand(operand), None),
+        Some((SSTableData::Value(value), expiry_time)) if !is_expired => {
+            let value = get_merge_operator(merge_operator)?.full_merge(Some(value), &operand);
+            (SSTableData::Value(value), expiry_time)
+        }
+        Some((SSTableData::Operand(value), _)) => {
+            let operand = get_merge_operator(merge_operator)?.partial_merge(value, &operand);
+            (SSTableData::Operand(operand), None)
+        }
+        Some(_) => {
+            let value = get_merge_operator(merge_operator)?.full_merge(None, &operand);
+            (SSTableData::Value(value), None)
+        }
+    };
+    Ok(SSTableValue {
+        data,
+        logical_time,
+        expiry_time,
+    })
+}
+
+/// Returns `true` if the versions of a key contain a value or a tombstone that all newer operands
+/// can be applied to.
+pub fn is_resolved<U>(versions: &[SSTableValue<U>]) -> bool {
+    versions.iter().any(|version| !version.data.is_operand())
+}
+
+/// Folds the versions of a key in descending order of logical time into the most recent version.
+/// Operands are applied to the most recent value or tombstone, and all older versions are
+/// ignored. If there is no value or tombstone, the operands are applied to `None`. Expired values
+/// are treated as tombstones. Returns `Error::MissingMergeOperator` if there are operands to apply
+/// and no merge operator is set.
+pub fn resolve_versions<U, I>(
+    versions: I,
+    merge_operator: Option<&Arc<dyn MergeOperator<U>>>,
+) -> Result<Option<SSTableValue<U>>>
+where
+    I: IntoIterator<Item = SSTableValue<U>>,
+{
+    let current_time = sstable::current_time();
+    let mut versions = versions
+        .into_iter()
+        .map(|version| version.expire(current_time));
+    let version = match versions.next() {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+    let mut operands = match version.data {
+        SSTableData::Operand(operand) => vec![operand],
+        _ => return Ok(Some(version)),
+    };
+
+    let mut existing_value = None;
+    let mut expiry_time = None;
+    for version in versions {
+        match version.data {
+            SSTableData::Operand(operand) => operands.push(operand),
+            SSTableData::Value(value) => {
+                existing_value = Some(value);
+                expiry_time = version.expiry_time;
+                break;
+            }
+            SSTableData::Tombstone => break,
+        }
+    }
+
+    let merge_operator = get_merge_operator(merge_operator)?;
+    for operand in operands.iter().rev() {
+        existing_value = Some(merge_operator.full_merge(existing_value.as_ref(), operand));
+    }
+
+    Ok(Some(SSTableValue {
+        data: SSTableData::from(existing_value),
+        logical_time: version.logical_time,
+        expiry_time,
+    }))
+}
@@ -1,15 +1,40 @@
 //! Hybrid tree comprised of disk-resident sorted runs of data and memory-resident tree.
 
+mod cache;
 pub mod compaction;
+mod compression;
+mod concurrent_map;
+mod cursor;
+mod filter;
 mod map;
+mod merge;
+mod snapshot;
+mod sst_writer;
 mod sstable;
+mod wal;
+mod write_batch;
+mod writer;
 
+pub use self::compression::Compression;
+pub use self::concurrent_map::ConcurrentLsmMap;
+pub use self::cursor::Cursor;
+use self::cursor::{DoubleEndedPeekable, MergingCursor, RangeIter, SortedRun};
+pub use self::filter::{FilterPolicy, FixedLengthPrefix, PrefixExtractor};
 pub use self::map::LsmMap;
-use self::sstable::{SSTable, SSTableBuilder, SSTableDataIter, SSTableValue};
+pub use self::merge::MergeOperator;
+pub use self::snapshot::Snapshot;
+use self::snapshot::{SnapshotList, VersionFilter, VisibleIter};
+pub use self::sst_writer::SstWriter;
+use self::sstable::{SSTable, SSTableBuilder, SSTableData, SSTableDataIter, SSTableValue};
+use self::wal::WriteAheadLog;
+pub use self::write_batch::WriteBatch;
+use self::writer::Writer;
+pub use crate::wal::SyncPolicy;
 use bincode;
 use std::error;
 use std::fmt;
 use std::io;
+use std::path::PathBuf;
 use std::result;
 
 /// Convenience `Error` enum for `lsm_tree`.
@@ -19,6 +44,19 @@ pub enum Error {
     IOError(io::Error),
     /// A serialization or deserialization error.
     SerdeError(bincode::Error),
+    /// A checksum mismatch or an incomplete block in a file.
+    Corruption {
+        /// The path of the corrupted file.
+        path: PathBuf,
+        /// The offset of the corrupted block in the file.
+        offset: u64,
+    },
+    /// A key that is not greater than the previous key was written to a `SstWriter`.
+    UnsortedKey,
+    /// Two SSTables that were ingested have overlapping key ranges.
+    OverlappingSSTables(PathBuf, PathBuf),
+    /// A merge operand was written or read without a merge operator set on the map.
+    MissingMergeOperator,
 }
 
 impl From<io::Error> for Error {
@@ -38,6 +76,10 @@ impl error::Error for Error {
         match self {
             Error::IOError(ref error) => error.source(),
             Error::SerdeError(ref error) => error.source(),
+            Error::Corruption { .. }
+            | Error::UnsortedKey
+            | Error::OverlappingSSTables(..)
+            | Error::MissingMergeOperator => None,
         }
     }
 }
@@ -47,6 +89,17 @@ impl fmt::Display for Error {
         match self {
             Error::IOError(ref error) => write!(f, "{}", error),
             Error::SerdeError(ref error) => write!(f, "{}", error),
+            Error::Corruption { ref path, offset } => {
+                write!(f, "Corruption in {} at offset {}.", path.display(), offset)
+            }
+            Error::UnsortedKey => write!(f, "Keys must be written in ascending order."),
+            Error::OverlappingSSTables(ref path_1, ref path_2) => write!(
+                f,
+                "Ingested SSTables {} and {} have overlapping key ranges.",
+                path_1.display(),
+                path_2.display()
+            ),
+            Error::MissingMergeOperator => write!(f, "Expected merge operator to be set."),
         }
     }
 }
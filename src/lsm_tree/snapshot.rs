@@ -0,0 +1,642 @@
+use crate::lsm_tree::compaction::{CompactionIter, CompactionStrategy};
+use crate::lsm_tree::merge::{self, MergeOperator};
+use crate::lsm_tree::sstable::{self, SSTableData, SSTableValue};
+use crate::lsm_tree::{Cursor, DoubleEndedPeekable, LsmMap, Result};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::hash::Hash;
+use std::iter::Peekable;
+use std::ops::Bound::{Excluded, Included};
+use std::ops::RangeBounds;
+use std::sync::{Arc, Mutex};
+
+/// A read-only view of a `LsmMap` that is pinned at a particular logical time.
+///
+/// Reads through a snapshot ignore all writes that occurred after the snapshot was taken. While a
+/// snapshot is alive, compactions will keep the versions of entries that are visible to the
+/// snapshot. A snapshot must only be used with the map that created it.
+pub struct Snapshot {
+    logical_time: u64,
+    snapshot_list: SnapshotList,
+}
+
+impl Snapshot {
+    /// Returns the logical time of the snapshot. Only writes with a logical time strictly less
+    /// than the logical time of the snapshot are visible to the snapshot.
+    pub fn get_logical_time(&self) -> u64 {
+        self.logical_time
+    }
+
+    /// Returns the value associated with a particular key at the time of the snapshot. It will
+    /// return `None` if the key did not exist in the map when the snapshot was taken.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_snapshot_get", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// let snapshot = map.snapshot()?;
+    /// map.remove(1)?;
+    /// map.insert(2, 2)?;
+    ///
+    /// assert_eq!(snapshot.get(&mut map, &1)?, Some(1));
+    /// assert_eq!(snapshot.get(&mut map, &2)?, None);
+    /// # fs::remove_dir_all("example_snapshot_get")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn get<T, U, C, V>(&self, map: &mut LsmMap<T, U, C>, key: &V) -> Result<Option<U>>
+    where
+        T: Borrow<V> + Clone + Ord + Hash + DeserializeOwned + Serialize,
+        U: Clone + DeserializeOwned + Serialize,
+        C: CompactionStrategy<T, U>,
+        V: Ord + Hash + ?Sized,
+    {
+        map.get_at(key, Some(self))
+    }
+
+    /// Returns an iterator over the map at the time of the snapshot. The iterator will yield
+    /// key-value pairs in ascending order, or in descending order if it is reversed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_snapshot_iter", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// let snapshot = map.snapshot()?;
+    /// map.insert(1, 3)?;
+    /// map.insert(3, 3)?;
+    ///
+    /// let mut iterator = snapshot.iter(&mut map)?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((1, 1)));
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_snapshot_iter")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn iter<T, U, C>(&self, map: &mut LsmMap<T, U, C>) -> Result<Box<CompactionIter<T, U>>>
+    where
+        T: 'static + Clone + Ord + Hash + DeserializeOwned + Serialize,
+        U: 'static + Clone + DeserializeOwned + Serialize,
+        C: CompactionStrategy<T, U>,
+    {
+        map.range_at(.., Some(self))
+    }
+
+    /// Returns an iterator over a range of keys in the map at the time of the snapshot. The
+    /// iterator will yield key-value pairs in ascending order, or in descending order if it is
+    /// reversed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end of the range, or if the start and
+    /// end of the range are equal and both excluded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_snapshot_range", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// map.insert(2, 2)?;
+    /// let snapshot = map.snapshot()?;
+    /// map.remove(2)?;
+    ///
+    /// let mut iterator = snapshot.range(&mut map, 2..)?.map(|value| value.unwrap());
+    /// assert_eq!(iterator.next(), Some((2, 2)));
+    /// assert_eq!(iterator.next(), None);
+    /// # fs::remove_dir_all("example_snapshot_range")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn range<T, U, C, R>(
+        &self,
+        map: &mut LsmMap<T, U, C>,
+        range: R,
+    ) -> Result<Box<CompactionIter<T, U>>>
+    where
+        T: 'static + Clone + Ord + Hash + DeserializeOwned + Serialize,
+        U: 'static + Clone + DeserializeOwned + Serialize,
+        C: CompactionStrategy<T, U>,
+        R: RangeBounds<T>,
+    {
+        map.range_at(range, Some(self))
+    }
+
+    /// Returns a cursor over the map at the time of the snapshot that is positioned before the
+    /// first entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+    /// use extended_collections::lsm_tree::LsmMap;
+    ///
+    /// let sts = SizeTieredStrategy::new("example_snapshot_cursor", 10000, 4, 50000, 0.5, 1.5)?;
+    /// let mut map = LsmMap::new(sts)?;
+    ///
+    /// map.insert(1, 1)?;
+    /// let snapshot = map.snapshot()?;
+    /// map.insert(2, 2)?;
+    ///
+    /// let mut cursor = snapshot.cursor(&mut map)?;
+    /// cursor.seek_to_last()?;
+    /// assert_eq!(cursor.key(), Some(&1));
+    /// # fs::remove_dir_all("example_snapshot_cursor")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn cursor<T, U, C>(&self, map: &mut LsmMap<T, U, C>) -> Result<Cursor<T, U>>
+    where
+        T: 'static + Clone + Ord + Hash + DeserializeOwned + Serialize,
+        U: 'static + Clone + DeserializeOwned + Serialize,
+        C: CompactionStrategy<T, U>,
+    {
+        map.cursor_at(Some(self))
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.snapshot_list.release(self.logical_time);
+    }
+}
+
+/// A reference-counted collection of the logical times of all live snapshots.
+#[derive(Clone, Default)]
+pub struct SnapshotList {
+    logical_times: Arc<Mutex<BTreeMap<u64, usize>>>,
+}
+
+impl SnapshotList {
+    pub fn new() -> Self {
+        SnapshotList::default()
+    }
+
+    pub fn acquire(&self, logical_time: u64) -> Snapshot {
+        *self
+            .logical_times
+            .lock()
+            .unwrap()
+            .entry(logical_time)
+            .or_insert(0) += 1;
+        Snapshot {
+            logical_time,
+            snapshot_list: self.clone(),
+        }
+    }
+
+    fn release(&self, logical_time: u64) {
+        let mut logical_times = self.logical_times.lock().unwrap();
+        let is_last = {
+            let count = logical_times
+                .get_mut(&logical_time)
+                .expect("Expected snapshot to be acquired.");
+            *count -= 1;
+            *count == 0
+        };
+        if is_last {
+            logical_times.remove(&logical_time);
+        }
+    }
+
+    pub fn logical_times(&self) -> BTreeSet<u64> {
+        self.logical_times.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+/// An iterator adaptor that takes an iterator yielding all versions of each key in ascending
+/// order of keys and descending order of logical times, and yields the most recent version of each
+/// key that is visible at a particular logical time. Merge operands are folded into the most recent
+/// value or tombstone that is visible at the logical time. If the iterator is double-ended, the
+/// keys can also be yielded in descending order.
+pub struct VisibleIter<I, U>
+where
+    I: Iterator,
+{
+    iter: DoubleEndedPeekable<I>,
+    logical_time: Option<u64>,
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+}
+
+impl<I, U> VisibleIter<I, U>
+where
+    I: Iterator,
+{
+    pub fn new(
+        iter: I,
+        logical_time: Option<u64>,
+        merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    ) -> Self {
+        VisibleIter {
+            iter: DoubleEndedPeekable::new(iter),
+            logical_time,
+            merge_operator,
+        }
+    }
+}
+
+impl<I, T, U> Iterator for VisibleIter<I, U>
+where
+    I: Iterator<Item = Result<(T, SSTableValue<U>)>>,
+    T: Ord,
+{
+    type Item = Result<(T, SSTableValue<U>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = match self.iter.next()? {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error)),
+            };
+
+            if !value.is_visible(self.logical_time) {
+                continue;
+            }
+
+            // all older versions are visible, but are only needed to resolve operands
+            let mut versions = vec![value];
+            while let Some(Ok((next_key, _))) = self.iter.peek() {
+                if *next_key != key {
+                    break;
+                }
+                let version = match self.iter.next() {
+                    Some(Ok((_, version))) => version,
+                    _ => unreachable!(),
+                };
+                if !merge::is_resolved(&versions) {
+                    versions.push(version);
+                }
+            }
+
+            return match merge::resolve_versions(versions, self.merge_operator.as_ref()) {
+                Ok(value) => value.map(|value| Ok((key, value))),
+                Err(error) => Some(Err(error)),
+            };
+        }
+    }
+}
+
+impl<I, T, U> DoubleEndedIterator for VisibleIter<I, U>
+where
+    I: DoubleEndedIterator<Item = Result<(T, SSTableValue<U>)>>,
+    T: Ord,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = match self.iter.next_back()? {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error)),
+            };
+
+            // the versions are visited in ascending order of logical time
+            let mut versions = vec![value];
+            while let Some(Ok((prev_key, _))) = self.iter.peek_back() {
+                if *prev_key != key {
+                    break;
+                }
+                match self.iter.next_back() {
+                    Some(Ok((_, version))) => versions.push(version),
+                    _ => unreachable!(),
+                }
+            }
+
+            // the newest visible version and the older versions needed to resolve it are kept
+            let mut resolved_versions = Vec::new();
+            for version in versions.into_iter().rev() {
+                if !version.is_visible(self.logical_time) {
+                    continue;
+                }
+                if merge::is_resolved(&resolved_versions) {
+                    break;
+                }
+                resolved_versions.push(version);
+            }
+
+            match merge::resolve_versions(resolved_versions, self.merge_operator.as_ref()) {
+                Ok(Some(value)) => return Some(Ok((key, value))),
+                Ok(None) => {}
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// An iterator adaptor for compactions that takes an iterator yielding all versions of each key
+/// in ascending order of keys and descending order of logical times, and yields only the versions
+/// that are visible to the current time or to a live snapshot.
+///
+/// Versions that are not separated by a live snapshot are visible to the same reads, so only the
+/// most recent of them is needed. If it is a merge operand, the older versions are folded into it
+/// if there is a merge operator, and are otherwise retained until the most recent value or
+/// tombstone.
+///
+/// Values that have expired are replaced with tombstones. If tombstones are purged, a tombstone is
+/// dropped unless an older version of the same key is retained, since the tombstone is needed to
+/// hide the older version. Operands that are older
+/// than every value and tombstone of a key are then applied to `None`, since there are no older
+/// versions of the key.
+pub struct VersionFilter<I, T, U>
+where
+    I: Iterator,
+{
+    iter: Peekable<I>,
+    snapshot_logical_times: BTreeSet<u64>,
+    purge_tombstones: bool,
+    merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    current_time: u64,
+    entries: VecDeque<(T, SSTableValue<U>)>,
+}
+
+impl<I, T, U> VersionFilter<I, T, U>
+where
+    I: Iterator,
+{
+    pub fn new(
+        iter: I,
+        snapshot_logical_times: BTreeSet<u64>,
+        purge_tombstones: bool,
+        merge_operator: Option<Arc<dyn MergeOperator<U>>>,
+    ) -> Self {
+        VersionFilter {
+            iter: iter.peekable(),
+            snapshot_logical_times,
+            purge_tombstones,
+            merge_operator,
+            current_time: sstable::current_time(),
+            entries: VecDeque::new(),
+        }
+    }
+
+    // Folds a run of operands in descending order of logical time that are not separated by a
+    // live snapshot into the value or tombstone that precedes them, and appends the result to the
+    // retained versions. If there is no such value or tombstone, the operands are folded into a
+    // single operand, unless there are no older versions of the key.
+    fn push_operands(
+        &self,
+        versions: &mut Vec<SSTableValue<U>>,
+        operands: &mut Vec<SSTableValue<U>>,
+        existing_value: Option<SSTableValue<U>>,
+        is_oldest: bool,
+    ) -> Result<()> {
+        match self.merge_operator {
+            Some(_) if existing_value.is_some() || is_oldest => {
+                let operand_iter = operands.drain(..).chain(existing_value);
+                versions.extend(merge::resolve_versions(
+                    operand_iter,
+                    self.merge_operator.as_ref(),
+                )?);
+            }
+            Some(ref merge_operator) => {
+                let logical_time = operands[0].logical_time;
+                let operand = operands
+                    .drain(..)
+                    .rev()
+                    .filter_map(|operand| match operand.data {
+                        SSTableData::Operand(operand) => Some(operand),
+                        _ => None,
+                    })
+                    .fold(None, |acc: Option<U>, operand| match acc {
+                        Some(acc) => Some(merge_operator.partial_merge(&acc, &operand)),
+                        None => Some(operand),
+                    });
+                versions.extend(operand.map(|operand| SSTableValue {
+                    data: SSTableData::Operand(operand),
+                    logical_time,
+                    expiry_time: None,
+                }));
+            }
+            None => {
+                versions.append(operands);
+                versions.extend(existing_value);
+            }
+        }
+        Ok(())
+    }
+
+    fn filter_versions(&self, versions: Vec<SSTableValue<U>>) -> Result<Vec<SSTableValue<U>>> {
+        let mut retained_versions = Vec::new();
+        let mut operands = Vec::new();
+        let mut newer_logical_time = None;
+        let mut is_resolved = false;
+
+        for version in versions {
+            // a version is visible to different reads than the newer version if there is a
+            // snapshot that is taken after the version, but before the newer version
+            let is_new_run = match newer_logical_time {
+                Some(newer_logical_time) => self
+                    .snapshot_logical_times
+                    .range((Excluded(version.logical_time), Included(newer_logical_time)))
+                    .next()
+                    .is_some(),
+                None => true,
+            };
+            newer_logical_time = Some(version.logical_time);
+
+            if is_new_run {
+                if !operands.is_empty() {
+                    self.push_operands(&mut retained_versions, &mut operands, None, false)?;
+                }
+                is_resolved = false;
+            }
+
+            if is_resolved {
+                continue;
+            }
+
+            if version.data.is_operand() {
+                operands.push(version);
+            } else if operands.is_empty() {
+                retained_versions.push(version);
+                is_resolved = true;
+            } else {
+                self.push_operands(&mut retained_versions, &mut operands, Some(version), false)?;
+                is_resolved = true;
+            }
+        }
+
+        if !operands.is_empty() {
+            self.push_operands(
+                &mut retained_versions,
+                &mut operands,
+                None,
+                self.purge_tombstones,
+            )?;
+        }
+
+        if self.purge_tombstones {
+            let retained_count = retained_versions
+                .iter()
+                .rposition(|version| !version.data.is_tombstone())
+                .map_or(0, |index| index + 1);
+            retained_versions.truncate(retained_count);
+        }
+
+        Ok(retained_versions)
+    }
+}
+
+impl<I, T, U> Iterator for VersionFilter<I, T, U>
+where
+    I: Iterator<Item = Result<(T, SSTableValue<U>)>>,
+    T: Clone + Ord,
+{
+    type Item = Result<(T, SSTableValue<U>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Some(Ok(entry));
+            }
+
+            let (key, value) = match self.iter.next()? {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error)),
+            };
+
+            let mut versions = vec![value.expire(self.current_time)];
+            while let Some(Ok((next_key, _))) = self.iter.peek() {
+                if *next_key != key {
+                    break;
+                }
+                match self.iter.next() {
+                    Some(Ok((_, version))) => versions.push(version.expire(self.current_time)),
+                    _ => unreachable!(),
+                }
+            }
+
+            let versions = match self.filter_versions(versions) {
+                Ok(versions) => versions,
+                Err(error) => return Some(Err(error)),
+            };
+            for version in versions {
+                self.entries.push_back((key.clone(), version));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsm_tree::compaction::SizeTieredStrategy;
+    use crate::lsm_tree::{LsmMap, Result};
+    use rand::Rng;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::panic;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let test_name = "test_snapshot";
+        run_test(
+            || {
+                let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+                let sts = SizeTieredStrategy::new(test_name, 1000, 4, 4000, 0.5, 1.5)?;
+                let mut map = LsmMap::new(sts)?;
+                let mut expected = BTreeMap::new();
+                let mut snapshots = Vec::new();
+
+                for round in 0..5 {
+                    for _ in 0..2000 {
+                        let key = rng.gen_range(0, 1000);
+                        if rng.gen_range(0, 4) != 0 {
+                            let val = rng.gen::<u64>();
+                            map.insert(key, val)?;
+                            expected.insert(key, val);
+                        } else {
+                            map.remove(key)?;
+                            expected.remove(&key);
+                        }
+                    }
+
+                    if round % 2 == 0 {
+                        snapshots.push((map.snapshot()?, expected.clone()));
+                    }
+                }
+
+                map.flush()?;
+
+                for (snapshot, expected) in &snapshots {
+                    for key in 0..1000 {
+                        assert_eq!(snapshot.get(&mut map, &key)?, expected.get(&key).cloned());
+                    }
+
+                    let actual = snapshot.iter(&mut map)?.collect::<Result<Vec<_>>>()?;
+                    let expected_iter: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_iter);
+
+                    let actual = snapshot
+                        .range(&mut map, 250..750)?
+                        .collect::<Result<Vec<_>>>()?;
+                    let expected_range: Vec<_> =
+                        expected.range(250..750).map(|e| (*e.0, *e.1)).collect();
+                    assert_eq!(actual, expected_range);
+                }
+
+                snapshots.clear();
+                for _ in 0..2000 {
+                    let key = rng.gen_range(0, 1000);
+                    let val = rng.gen::<u64>();
+                    map.insert(key, val)?;
+                    expected.insert(key, val);
+                }
+                map.flush()?;
+
+                let actual = map.iter()?.collect::<Result<Vec<_>>>()?;
+                let expected_iter: Vec<_> = expected.iter().map(|e| (*e.0, *e.1)).collect();
+                assert_eq!(actual, expected_iter);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+}
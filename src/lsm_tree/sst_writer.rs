@@ -0,0 +1,218 @@
+use crate::lsm_tree::{
+    Compression, Error, FilterPolicy, Result, SSTableBuilder, SSTableData, SSTableValue,
+};
+use serde::ser::Serialize;
+use std::fs;
+use std::hash::Hash;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// A writer of a SSTable outside of a map, which can be ingested into a map with
+/// `LsmMap::ingest`.
+///
+/// Keys must be written in strictly ascending order. Writing a sorted run directly into a SSTable
+/// and ingesting it avoids the write-ahead log, the in-memory tree, and the compactions that
+/// inserting each entry would cause. The logical time of the entries is assigned when the SSTable
+/// is ingested, so the entries of an ingested SSTable are newer than all entries in the map.
+///
+/// # Examples
+///
+/// ```
+/// # use extended_collections::lsm_tree::Result;
+/// # fn foo() -> Result<()> {
+/// # use std::fs;
+/// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+/// use extended_collections::lsm_tree::{Compression, FilterPolicy, LsmMap, SstWriter};
+///
+/// # fs::create_dir("example_sst_writer_import")?;
+/// let mut writer = SstWriter::new(
+///     "example_sst_writer_import",
+///     1000,
+///     Compression::Lz4,
+///     &FilterPolicy::default(),
+/// )?;
+/// for key in 0..1000u32 {
+///     writer.insert(key, u64::from(key) * 2)?;
+/// }
+/// let sstable_path = writer.finish()?;
+///
+/// let sts: SizeTieredStrategy<u32, u64> =
+///     SizeTieredStrategy::new("example_sst_writer", 10000, 4, 50000, 0.5, 1.5)?;
+/// let mut map = LsmMap::new(sts)?;
+/// map.ingest(vec![sstable_path])?;
+/// assert_eq!(map.get(&10)?, Some(20));
+/// # fs::remove_dir_all("example_sst_writer")?;
+/// # fs::remove_dir_all("example_sst_writer_import")?;
+/// # Ok(())
+/// # }
+/// # foo().unwrap();
+/// ```
+pub struct SstWriter<T, U> {
+    sstable_builder: SSTableBuilder<T, U>,
+}
+
+impl<T, U> SstWriter<T, U>
+where
+    T: Clone + Hash + Ord + Serialize,
+    U: Serialize,
+{
+    /// Constructs a new `SstWriter<T, U>` that writes a SSTable into a new directory inside of an
+    /// existing directory. `entry_count_hint` is the expected number of entries, which is used to
+    /// size the filters of the SSTable. The filter policy should match the filter policy of the
+    /// compaction strategy that the SSTable is ingested into.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::{Compression, FilterPolicy, SstWriter};
+    ///
+    /// # fs::create_dir("example_sst_writer_new")?;
+    /// let writer: SstWriter<u32, u32> = SstWriter::new(
+    ///     "example_sst_writer_new",
+    ///     1000,
+    ///     Compression::None,
+    ///     &FilterPolicy::default(),
+    /// )?;
+    /// # fs::remove_dir_all("example_sst_writer_new")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn new<P>(
+        path: P,
+        entry_count_hint: usize,
+        compression: Compression,
+        filter_policy: &FilterPolicy<T>,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(SstWriter {
+            sstable_builder: SSTableBuilder::new(
+                path,
+                entry_count_hint,
+                compression,
+                filter_policy,
+            )?,
+        })
+    }
+
+    fn append(&mut self, key: T, data: SSTableData<U>) -> Result<()> {
+        if let Some((_, last_key)) = &self.sstable_builder.key_range {
+            if key <= *last_key {
+                return Err(Error::UnsortedKey);
+            }
+        }
+
+        let value = SSTableValue {
+            data,
+            logical_time: 0,
+            expiry_time: None,
+        };
+        self.sstable_builder.append(key, value)
+    }
+
+    /// Writes a key-value pair into the SSTable. Returns `Error::UnsortedKey` if the key is not
+    /// greater than the previous key that was written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::{Compression, Error, FilterPolicy, SstWriter};
+    ///
+    /// # fs::create_dir("example_sst_writer_insert")?;
+    /// let mut writer = SstWriter::new(
+    ///     "example_sst_writer_insert",
+    ///     1000,
+    ///     Compression::None,
+    ///     &FilterPolicy::default(),
+    /// )?;
+    /// writer.insert(1, 1)?;
+    /// writer.insert(3, 3)?;
+    /// match writer.insert(2, 2) {
+    ///     Err(Error::UnsortedKey) => {}
+    ///     _ => panic!("Expected an unsorted key."),
+    /// }
+    /// # fs::remove_dir_all("example_sst_writer_insert")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn insert(&mut self, key: T, value: U) -> Result<()> {
+        self.append(key, SSTableData::Value(value))
+    }
+
+    /// Writes a tombstone for a key into the SSTable, which removes the key from the map that the
+    /// SSTable is ingested into. Returns `Error::UnsortedKey` if the key is not greater than the
+    /// previous key that was written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::{Compression, FilterPolicy, SstWriter};
+    ///
+    /// # fs::create_dir("example_sst_writer_remove")?;
+    /// let mut writer: SstWriter<u32, u32> = SstWriter::new(
+    ///     "example_sst_writer_remove",
+    ///     1000,
+    ///     Compression::None,
+    ///     &FilterPolicy::default(),
+    /// )?;
+    /// writer.insert(1, 1)?;
+    /// writer.remove(2)?;
+    /// # fs::remove_dir_all("example_sst_writer_remove")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn remove(&mut self, key: T) -> Result<()> {
+        self.append(key, SSTableData::Tombstone)
+    }
+
+    /// Writes the remaining blocks, the summary, and the filters of the SSTable, and returns the
+    /// path of the SSTable. Returns an error and removes the SSTable if no entries were written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use extended_collections::lsm_tree::Result;
+    /// # fn foo() -> Result<()> {
+    /// # use std::fs;
+    /// use extended_collections::lsm_tree::{Compression, FilterPolicy, SstWriter};
+    ///
+    /// # fs::create_dir("example_sst_writer_finish")?;
+    /// let mut writer = SstWriter::new(
+    ///     "example_sst_writer_finish",
+    ///     1000,
+    ///     Compression::None,
+    ///     &FilterPolicy::default(),
+    /// )?;
+    /// writer.insert(1, 1)?;
+    /// let sstable_path = writer.finish()?;
+    /// assert!(sstable_path.starts_with("example_sst_writer_finish"));
+    /// # fs::remove_dir_all("example_sst_writer_finish")?;
+    /// # Ok(())
+    /// # }
+    /// # foo().unwrap();
+    /// ```
+    pub fn finish(mut self) -> Result<PathBuf> {
+        if self.sstable_builder.key_range.is_none() {
+            fs::remove_dir_all(self.sstable_builder.sstable_path.as_path())?;
+            let message = "Expected a non-empty SSTable.";
+            return Err(Error::from(io::Error::new(
+                ErrorKind::InvalidInput,
+                message,
+            )));
+        }
+        self.sstable_builder.flush()
+    }
+}
@@ -1,21 +1,39 @@
 use crate::entry::Entry;
-use crate::lsm_tree::{Error, Result};
+use crate::lsm_tree::cache::{Block, FileKind, TableCache};
+use crate::lsm_tree::{Compression, Error, FilterPolicy, PrefixExtractor, Result};
 use bincode::{deserialize, serialize};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use probabilistic_collections::bloom::BloomFilter;
 use rand::{thread_rng, Rng};
-use serde::de::{self, Deserialize, DeserializeOwned, Deserializer};
-use serde::ser::{Serialize, Serializer};
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::cmp;
 use std::fmt::{self, Debug};
 use std::fs;
 use std::hash::Hash;
-use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
 use std::path::{Path, PathBuf};
 use std::result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Summaries of SSTables written before data files were divided into blocks do not have a header.
+const SUMMARY_MAGIC: u64 = 0x4c53_4d54_5353_5441;
+const SUMMARY_HEADER_SIZE: usize = 16;
+const FORMAT_VERSION: u32 = 3;
+// The format version of SSTables written before SSTables could be ingested.
+const UNINGESTED_FORMAT_VERSION: u32 = 2;
+
+// The uncompressed size in bytes after which a data block is written.
+const DATA_BLOCK_SIZE: usize = 4096;
+const DATA_BLOCK_HEADER_SIZE: u64 = 13;
+const INDEX_BLOCK_HEADER_SIZE: u64 = 12;
+const CHECKSUM_SIZE: usize = 4;
 
 pub fn merge_ranges<T>(range_1: (T, T), range_2: (T, T)) -> (T, T)
 where
@@ -36,10 +54,290 @@ where
     l <= r
 }
 
-#[derive(Deserialize, Serialize)]
+pub fn is_intersecting_range<T>(key_range: &(T, T), range: &(Bound<T>, Bound<T>)) -> bool
+where
+    T: Clone + Ord,
+{
+    let start = match range.0 {
+        Included(ref key) | Excluded(ref key) => key.clone(),
+        Unbounded => key_range.0.clone(),
+    };
+    let end = match range.1 {
+        Included(ref key) | Excluded(ref key) => key.clone(),
+        Unbounded => key_range.1.clone(),
+    };
+    is_intersecting(&(start, end), key_range)
+}
+
+pub fn is_before_range<T>(key: &T, range: &(Bound<T>, Bound<T>)) -> bool
+where
+    T: Ord,
+{
+    match range.0 {
+        Included(ref start) => key < start,
+        Excluded(ref start) => key <= start,
+        Unbounded => false,
+    }
+}
+
+pub fn is_after_range<T>(key: &T, range: &(Bound<T>, Bound<T>)) -> bool
+where
+    T: Ord,
+{
+    match range.1 {
+        Included(ref end) => key > end,
+        Excluded(ref end) => key >= end,
+        Unbounded => false,
+    }
+}
+
+fn corruption(path: &Path, offset: u64) -> Error {
+    Error::Corruption {
+        path: path.to_path_buf(),
+        offset,
+    }
+}
+
+// Prepends the CRC32C checksum of a buffer to the buffer.
+fn with_checksum(buffer: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(CHECKSUM_SIZE + buffer.len());
+    ret.extend_from_slice(&crc32c::crc32c(buffer).to_be_bytes());
+    ret.extend_from_slice(buffer);
+    ret
+}
+
+// Verifies and strips the checksum that was prepended to the contents of a file.
+fn without_checksum<'a>(buffer: &'a [u8], path: &Path) -> Result<&'a [u8]> {
+    if buffer.len() < CHECKSUM_SIZE {
+        return Err(corruption(path, 0));
+    }
+    let (checksum, buffer) = buffer.split_at(CHECKSUM_SIZE);
+    if BigEndian::read_u32(checksum) != crc32c::crc32c(buffer) {
+        return Err(corruption(path, 0));
+    }
+    Ok(buffer)
+}
+
+// Reads a block of `size` bytes, treating a block that extends past the end of the file as
+// corruption.
+fn read_block<R>(reader: &mut R, size: u64, path: &Path, offset: u64) -> Result<Vec<u8>>
+where
+    R: Read,
+{
+    let mut buffer = Vec::new();
+    reader.take(size).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 != size {
+        return Err(corruption(path, offset));
+    }
+    Ok(buffer)
+}
+
+fn write_synced<P>(path: P, buffer: &[u8]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let mut file = fs::File::create(path)?;
+    file.write_all(buffer)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// The data of a version of an entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SSTableData<U> {
+    Tombstone,
+    Value(U),
+    Operand(U),
+}
+
+impl<U> SSTableData<U> {
+    pub fn is_tombstone(&self) -> bool {
+        matches!(self, SSTableData::Tombstone)
+    }
+
+    pub fn is_operand(&self) -> bool {
+        matches!(self, SSTableData::Operand(_))
+    }
+
+    pub fn into_value(self) -> Option<U> {
+        match self {
+            SSTableData::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<U> From<Option<U>> for SSTableData<U> {
+    fn from(value: Option<U>) -> Self {
+        match value {
+            Some(value) => SSTableData::Value(value),
+            None => SSTableData::Tombstone,
+        }
+    }
+}
+
+/// Returns the current time in milliseconds since the Unix epoch, which is the unit of the expiry
+/// times of values.
+pub fn current_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() * 1000 + u64::from(duration.subsec_millis()))
+        .unwrap_or(0)
+}
+
+/// A version of an entry.
+///
+/// A version is encoded with the same layout as `(Option<U>, u64)`, so that tombstones and values
+/// written by previous versions can still be read. Operands and values with an expiry time are
+/// encoded with additional tags.
+#[derive(Clone, Debug)]
 pub struct SSTableValue<U> {
-    pub data: Option<U>,
+    pub data: SSTableData<U>,
     pub logical_time: u64,
+    /// The time in milliseconds since the Unix epoch after which a value is treated as a
+    /// tombstone. Only values can have an expiry time.
+    pub expiry_time: Option<u64>,
+}
+
+impl<U> SSTableValue<U> {
+    pub fn is_visible(&self, logical_time: Option<u64>) -> bool {
+        match logical_time {
+            Some(logical_time) => self.logical_time < logical_time,
+            None => true,
+        }
+    }
+
+    pub fn is_expired(&self, current_time: u64) -> bool {
+        match self.expiry_time {
+            Some(expiry_time) => expiry_time <= current_time,
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the version is a value or an operand that has not expired.
+    pub fn is_live(&self, current_time: u64) -> bool {
+        !self.data.is_tombstone() && !self.is_expired(current_time)
+    }
+
+    /// Replaces the version with a tombstone if it has expired.
+    pub fn expire(self, current_time: u64) -> Self {
+        if self.is_expired(current_time) {
+            SSTableValue {
+                data: SSTableData::Tombstone,
+                logical_time: self.logical_time,
+                expiry_time: None,
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Returns the version without its value. Operands are converted to values, since applying
+    /// operands always results in a value.
+    pub fn without_value(&self) -> SSTableValue<()> {
+        let data = match self.data {
+            SSTableData::Tombstone => SSTableData::Tombstone,
+            SSTableData::Value(_) | SSTableData::Operand(_) => SSTableData::Value(()),
+        };
+        SSTableValue {
+            data,
+            logical_time: self.logical_time,
+            expiry_time: self.expiry_time,
+        }
+    }
+}
+
+impl<U> Serialize for SSTableValue<U>
+where
+    U: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (tag, value) = match (&self.data, self.expiry_time) {
+            (SSTableData::Tombstone, _) => (0u8, None),
+            (SSTableData::Value(value), None) => (1u8, Some(value)),
+            (SSTableData::Operand(value), _) => (2u8, Some(value)),
+            (SSTableData::Value(value), Some(_)) => (3u8, Some(value)),
+        };
+        let mut tuple = serializer.serialize_tuple(4)?;
+        tuple.serialize_element(&tag)?;
+        if let Some(value) = value {
+            tuple.serialize_element(value)?;
+        }
+        if let (3, Some(expiry_time)) = (tag, self.expiry_time) {
+            tuple.serialize_element(&expiry_time)?;
+        }
+        tuple.serialize_element(&self.logical_time)?;
+        tuple.end()
+    }
+}
+
+impl<'de, U> Deserialize<'de> for SSTableValue<U>
+where
+    U: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> result::Result<SSTableValue<U>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        fn next_element<'de, A, V>(seq: &mut A, index: usize) -> result::Result<V, A::Error>
+        where
+            A: SeqAccess<'de>,
+            V: Deserialize<'de>,
+        {
+            seq.next_element()?
+                .ok_or_else(|| de::Error::invalid_length(index, &"a complete version"))
+        }
+
+        struct SSTableValueVisitor<U>(PhantomData<U>);
+
+        impl<'de, U> Visitor<'de> for SSTableValueVisitor<U>
+        where
+            U: Deserialize<'de>,
+        {
+            type Value = SSTableValue<U>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(
+                    f,
+                    "a tag, an optional value, an optional expiry time, and a logical time"
+                )
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> result::Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let tag: u8 = next_element(&mut seq, 0)?;
+                let (data, expiry_time) = match tag {
+                    0 => (SSTableData::Tombstone, None),
+                    1 => (SSTableData::Value(next_element(&mut seq, 1)?), None),
+                    2 => (SSTableData::Operand(next_element(&mut seq, 1)?), None),
+                    3 => {
+                        let value = next_element(&mut seq, 1)?;
+                        (SSTableData::Value(value), Some(next_element(&mut seq, 2)?))
+                    }
+                    _ => return Err(de::Error::custom("Unknown data tag.")),
+                };
+                let logical_time_index = match tag {
+                    0 => 1,
+                    3 => 3,
+                    _ => 2,
+                };
+                let logical_time = next_element(&mut seq, logical_time_index)?;
+
+                Ok(SSTableValue {
+                    data,
+                    logical_time,
+                    expiry_time,
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(4, SSTableValueVisitor(PhantomData))
+    }
 }
 
 impl<U> PartialEq for SSTableValue<U> {
@@ -70,6 +368,92 @@ pub struct SSTableSummary<T> {
     pub key_range: (T, T),
     pub logical_time_range: (u64, u64),
     pub index: Vec<(T, u64)>,
+    // `None` if each entry in the data file is stored individually instead of in data blocks.
+    pub compression: Option<Compression>,
+    // The logical time that was assigned to all entries of an ingested SSTable, which replaces the
+    // logical times in its data file.
+    pub ingested_logical_time: Option<u64>,
+}
+
+impl<T> SSTableSummary<T> {
+    fn serialize_with_header(&self) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        let mut buffer = Vec::new();
+        buffer.write_u64::<BigEndian>(SUMMARY_MAGIC)?;
+        buffer.write_u32::<BigEndian>(FORMAT_VERSION)?;
+        buffer.extend_from_slice(&with_checksum(&serialize(self)?));
+        Ok(buffer)
+    }
+
+    fn deserialize_with_header(buffer: &[u8], path: &Path) -> Result<Self>
+    where
+        T: DeserializeOwned,
+    {
+        if buffer.len() < SUMMARY_HEADER_SIZE || BigEndian::read_u64(buffer) != SUMMARY_MAGIC {
+            let summary: LegacySSTableSummary<T> = deserialize(buffer)?;
+            return Ok(SSTableSummary {
+                entry_count: summary.entry_count,
+                tombstone_count: summary.tombstone_count,
+                size: summary.size,
+                key_range: summary.key_range,
+                logical_time_range: summary.logical_time_range,
+                index: summary.index,
+                compression: None,
+                ingested_logical_time: None,
+            });
+        }
+
+        let version = BigEndian::read_u32(&buffer[8..]);
+        if version != FORMAT_VERSION && version != UNINGESTED_FORMAT_VERSION {
+            let message = format!("Unsupported SSTable format version {}.", version);
+            return Err(Error::from(io::Error::new(ErrorKind::InvalidData, message)));
+        }
+        let buffer = without_checksum(&buffer[SUMMARY_HEADER_SIZE - CHECKSUM_SIZE..], path)?;
+        if version == UNINGESTED_FORMAT_VERSION {
+            let summary: UningestedSSTableSummary<T> = deserialize(buffer)?;
+            return Ok(SSTableSummary {
+                entry_count: summary.entry_count,
+                tombstone_count: summary.tombstone_count,
+                size: summary.size,
+                key_range: summary.key_range,
+                logical_time_range: summary.logical_time_range,
+                index: summary.index,
+                compression: summary.compression,
+                ingested_logical_time: None,
+            });
+        }
+        deserialize(buffer).map_err(Error::SerdeError)
+    }
+
+    // SSTables written before data files were divided into blocks do not have checksums.
+    fn has_checksums(&self) -> bool {
+        self.compression.is_some()
+    }
+}
+
+// The summary of a SSTable whose data file stores each entry individually.
+#[derive(Deserialize)]
+struct LegacySSTableSummary<T> {
+    entry_count: usize,
+    tombstone_count: usize,
+    size: u64,
+    key_range: (T, T),
+    logical_time_range: (u64, u64),
+    index: Vec<(T, u64)>,
+}
+
+// The summary of a SSTable that was written before SSTables could be ingested.
+#[derive(Deserialize)]
+struct UningestedSSTableSummary<T> {
+    entry_count: usize,
+    tombstone_count: usize,
+    size: u64,
+    key_range: (T, T),
+    logical_time_range: (u64, u64),
+    index: Vec<(T, u64)>,
+    compression: Option<Compression>,
 }
 
 pub struct SSTableBuilder<T, U> {
@@ -82,12 +466,16 @@ pub struct SSTableBuilder<T, U> {
     pub logical_time_range: Option<(u64, u64)>,
     pub index: Vec<(T, u64)>,
 
-    block_index: usize,
-    block_size: usize,
+    compression: Compression,
+    index_block_size: usize,
     index_block: Vec<(T, u64)>,
-    filter: BloomFilter<T>,
+    filter: Option<BloomFilter<T>>,
+    prefix_filter: Option<(String, BloomFilter<Vec<u8>>)>,
+    prefix_extractor: Option<Arc<dyn PrefixExtractor<T>>>,
     index_offset: u64,
     index_stream: BufWriter<fs::File>,
+    data_block_key: Option<T>,
+    data_block: Vec<u8>,
     data_offset: u64,
     data_stream: BufWriter<fs::File>,
     _marker: PhantomData<U>,
@@ -98,7 +486,12 @@ impl<T, U> SSTableBuilder<T, U> {
         thread_rng().gen_ascii_chars().take(32).collect()
     }
 
-    pub fn new<P>(db_path: P, entry_count_hint: usize) -> Result<Self>
+    pub fn new<P>(
+        db_path: P,
+        entry_count_hint: usize,
+        compression: Compression,
+        filter_policy: &FilterPolicy<T>,
+    ) -> Result<Self>
     where
         P: AsRef<Path>,
     {
@@ -122,12 +515,30 @@ impl<T, U> SSTableBuilder<T, U> {
             logical_time_range: None,
             index: Vec::new(),
 
-            block_index: 0,
-            block_size: (entry_count_hint as f64).sqrt().ceil() as usize,
+            compression,
+            index_block_size: (entry_count_hint as f64).sqrt().ceil() as usize,
             index_block: Vec::new(),
-            filter: BloomFilter::new(entry_count_hint, 0.05),
+            filter: if filter_policy.key_filter {
+                Some(BloomFilter::new(
+                    entry_count_hint,
+                    filter_policy.false_positive_rate,
+                ))
+            } else {
+                None
+            },
+            prefix_filter: filter_policy
+                .prefix_extractor
+                .as_ref()
+                .map(|prefix_extractor| {
+                    let prefix_filter =
+                        BloomFilter::new(entry_count_hint, filter_policy.false_positive_rate);
+                    (prefix_extractor.name(), prefix_filter)
+                }),
+            prefix_extractor: filter_policy.prefix_extractor.clone(),
             index_offset: 0,
             index_stream,
+            data_block_key: None,
+            data_block: Vec::new(),
             data_offset: 0,
             data_stream,
             _marker: PhantomData,
@@ -141,7 +552,7 @@ impl<T, U> SSTableBuilder<T, U> {
     {
         let logical_time = value.logical_time;
         self.entry_count += 1;
-        if value.data.is_none() {
+        if value.data.is_tombstone() {
             self.tombstone_count += 1;
         }
         match self.key_range.take() {
@@ -157,48 +568,106 @@ impl<T, U> SSTableBuilder<T, U> {
             None => self.logical_time_range = Some((logical_time, logical_time)),
         }
 
-        self.filter.insert(&key);
-        self.index_block.push((key.clone(), self.data_offset));
+        if let Some(filter) = &mut self.filter {
+            filter.insert(&key);
+        }
+        if let (Some(prefix_extractor), Some((_, prefix_filter))) =
+            (&self.prefix_extractor, &mut self.prefix_filter)
+        {
+            if let Some(prefix) = prefix_extractor.prefix(&key) {
+                prefix_filter.insert(&prefix);
+            }
+        }
+        if self.data_block_key.is_none() {
+            self.data_block_key = Some(key.clone());
+        }
 
         let serialized_entry = serialize(&(key, value))?;
-        self.data_stream
+        self.data_block
             .write_u64::<BigEndian>(serialized_entry.len() as u64)?;
-        self.data_stream.write_all(&serialized_entry)?;
-        self.data_offset += 8 + serialized_entry.len() as u64;
-        self.size += 8 + serialized_entry.len() as u64;
-        self.block_index += 1;
-
-        if self.block_index == self.block_size {
-            self.index
-                .push((self.index_block[0].0.clone(), self.index_offset));
+        self.data_block.extend_from_slice(&serialized_entry);
 
-            let serialized_index_block = serialize(&self.index_block)?;
-            self.index_stream
-                .write_u64::<BigEndian>(serialized_index_block.len() as u64)?;
-            self.index_stream.write_all(&serialized_index_block)?;
-            self.index_offset += 8 + serialized_index_block.len() as u64;
-            self.size += 8 + serialized_index_block.len() as u64;
-            self.block_index = 0;
-            self.index_block.clear();
+        if self.data_block.len() >= DATA_BLOCK_SIZE {
+            self.flush_data_block()?;
         }
 
         Ok(())
     }
 
-    pub fn flush(&mut self) -> Result<PathBuf>
+    fn flush_data_block(&mut self) -> Result<()>
     where
         T: Clone + Serialize,
     {
-        if !self.index_block.is_empty() {
-            self.index
-                .push((self.index_block[0].0.clone(), self.index_offset));
+        let data_block_key = match self.data_block_key.take() {
+            Some(data_block_key) => data_block_key,
+            None => return Ok(()),
+        };
+
+        let compressed_data_block = match self.compression {
+            Compression::None => None,
+            compression => Some(compression.compress(&self.data_block)?),
+        };
+
+        // the data block is stored uncompressed if compression does not reduce its size
+        let (compression, data_block) = match compressed_data_block {
+            Some(ref compressed_data_block)
+                if compressed_data_block.len() < self.data_block.len() =>
+            {
+                (self.compression, compressed_data_block.as_slice())
+            }
+            _ => (Compression::None, self.data_block.as_slice()),
+        };
+
+        let checksum = crc32c::crc32c_append(crc32c::crc32c(&[compression.to_u8()]), data_block);
+        self.data_stream
+            .write_u64::<BigEndian>(data_block.len() as u64)?;
+        self.data_stream.write_u8(compression.to_u8())?;
+        self.data_stream.write_u32::<BigEndian>(checksum)?;
+        self.data_stream.write_all(data_block)?;
+
+        self.index_block.push((data_block_key, self.data_offset));
+        self.data_offset += DATA_BLOCK_HEADER_SIZE + data_block.len() as u64;
+        self.size += DATA_BLOCK_HEADER_SIZE + data_block.len() as u64;
+        self.data_block.clear();
 
-            let serialized_index_block = serialize(&self.index_block)?;
-            self.index_stream
-                .write_u64::<BigEndian>(serialized_index_block.len() as u64)?;
-            self.index_stream.write_all(&serialized_index_block)?;
+        if self.index_block.len() == self.index_block_size {
+            self.flush_index_block()?;
         }
 
+        Ok(())
+    }
+
+    fn flush_index_block(&mut self) -> Result<()>
+    where
+        T: Clone + Serialize,
+    {
+        if self.index_block.is_empty() {
+            return Ok(());
+        }
+
+        self.index
+            .push((self.index_block[0].0.clone(), self.index_offset));
+
+        let serialized_index_block = serialize(&self.index_block)?;
+        self.index_stream
+            .write_u64::<BigEndian>(serialized_index_block.len() as u64)?;
+        self.index_stream
+            .write_u32::<BigEndian>(crc32c::crc32c(&serialized_index_block))?;
+        self.index_stream.write_all(&serialized_index_block)?;
+        self.index_offset += INDEX_BLOCK_HEADER_SIZE + serialized_index_block.len() as u64;
+        self.size += INDEX_BLOCK_HEADER_SIZE + serialized_index_block.len() as u64;
+        self.index_block.clear();
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<PathBuf>
+    where
+        T: Clone + Serialize,
+    {
+        self.flush_data_block()?;
+        self.flush_index_block()?;
+
         let key_range = {
             match self.key_range.clone() {
                 Some(key_range) => key_range,
@@ -213,29 +682,55 @@ impl<T, U> SSTableBuilder<T, U> {
             }
         };
 
-        let serialized_summary = serialize(&SSTableSummary {
+        let serialized_summary = SSTableSummary {
             entry_count: self.entry_count,
             tombstone_count: self.tombstone_count,
             size: self.size,
             key_range,
             logical_time_range,
             index: self.index.clone(),
-        })?;
-        fs::write(self.sstable_path.join("summary.dat"), &serialized_summary)?;
+            compression: Some(self.compression),
+            ingested_logical_time: None,
+        }
+        .serialize_with_header()?;
+        write_synced(self.sstable_path.join("summary.dat"), &serialized_summary)?;
 
-        let serialized_filter = serialize(&self.filter)?;
-        fs::write(self.sstable_path.join("filter.dat"), &serialized_filter)?;
+        // the filters are optional, so a SSTable without a filter file is read in full
+        if let Some(filter) = &self.filter {
+            let serialized_filter = with_checksum(&serialize(filter)?);
+            write_synced(self.sstable_path.join("filter.dat"), &serialized_filter)?;
+        }
+        if let Some(prefix_filter) = &self.prefix_filter {
+            let serialized_prefix_filter = with_checksum(&serialize(prefix_filter)?);
+            write_synced(
+                self.sstable_path.join("prefix_filter.dat"),
+                &serialized_prefix_filter,
+            )?;
+        }
 
         self.index_stream.flush()?;
+        self.index_stream.get_ref().sync_all()?;
         self.data_stream.flush()?;
+        self.data_stream.get_ref().sync_all()?;
         Ok(self.sstable_path.clone())
     }
 }
 
+/// A SSTable on disk.
+///
+/// SSTables are shared between the metadata of compaction strategies and cursors through
+/// reference counting. A SSTable that is compacted is marked as obsolete instead of being removed
+/// immediately, and its directory is removed once the last reference to it is dropped.
 pub struct SSTable<T, U> {
     pub path: PathBuf,
     pub summary: SSTableSummary<T>,
-    pub filter: BloomFilter<T>,
+    pub filter: Option<BloomFilter<T>>,
+    // The name of the prefix extractor that built the prefix filter and the prefix filter.
+    pub prefix_filter: Option<(String, BloomFilter<Vec<u8>>)>,
+    // The cache that the index and data blocks of the SSTable are read through, and the identifier
+    // of the SSTable in the cache.
+    cache: Option<(Arc<TableCache>, u64)>,
+    is_obsolete: AtomicBool,
     _marker: PhantomData<U>,
 }
 
@@ -245,133 +740,737 @@ impl<T, U> SSTable<T, U> {
         T: DeserializeOwned,
         P: AsRef<Path>,
     {
-        let buffer = fs::read(path.as_ref().join("summary.dat"))?;
-        let summary = deserialize(&buffer)?;
+        let summary_path = path.as_ref().join("summary.dat");
+        let buffer = fs::read(summary_path.as_path())?;
+        let summary = SSTableSummary::deserialize_with_header(&buffer, summary_path.as_path())?;
 
-        let buffer = fs::read(path.as_ref().join("filter.dat"))?;
-        let filter = deserialize(&buffer)?;
+        let filter_path = path.as_ref().join("filter.dat");
+        let filter = if !filter_path.exists() {
+            None
+        } else if summary.has_checksums() {
+            let buffer = fs::read(filter_path.as_path())?;
+            Some(deserialize(without_checksum(
+                &buffer,
+                filter_path.as_path(),
+            )?)?)
+        } else {
+            Some(deserialize(&fs::read(filter_path.as_path())?)?)
+        };
+
+        let prefix_filter_path = path.as_ref().join("prefix_filter.dat");
+        let prefix_filter = if prefix_filter_path.exists() {
+            let buffer = fs::read(prefix_filter_path.as_path())?;
+            Some(deserialize(without_checksum(
+                &buffer,
+                prefix_filter_path.as_path(),
+            )?)?)
+        } else {
+            None
+        };
 
         Ok(SSTable {
             path: PathBuf::from(path.as_ref()),
             summary,
             filter,
+            prefix_filter,
+            cache: None,
+            is_obsolete: AtomicBool::new(false),
             _marker: PhantomData,
         })
     }
 
-    fn floor_offset<V>(index: &[(T, u64)], key: &V) -> Option<usize>
+    /// Opens a SSTable whose index and data blocks are read through a cache.
+    pub fn with_cache<P>(path: P, cache: &Arc<TableCache>) -> Result<Self>
     where
-        T: Borrow<V>,
-        V: Ord + ?Sized,
+        T: DeserializeOwned,
+        P: AsRef<Path>,
     {
-        let mut lo = 0isize;
-        let mut hi = index.len() as isize - 1;
-        while lo <= hi {
-            let mid = (lo + hi) / 2;
-            if index[mid as usize].0.borrow() <= key {
-                lo = mid + 1;
-            } else {
-                hi = mid - 1;
+        let mut sstable = SSTable::new(path)?;
+        sstable.set_cache(cache);
+        Ok(sstable)
+    }
+
+    /// Adds a SSTable that was written outside of a compaction strategy to the directory of the
+    /// compaction strategy and assigns a logical time to all of its entries. The files of the
+    /// SSTable are hard linked if possible and are otherwise copied, and the summary is rewritten
+    /// with the logical time. The source SSTable is left in place, so the caller can remove it
+    /// once the new SSTable is recorded in the manifest.
+    pub fn ingest<P>(mut self, db_path: P, logical_time: u64) -> Result<Self>
+    where
+        T: DeserializeOwned + Serialize,
+        P: AsRef<Path>,
+    {
+        let sstable_path = db_path
+            .as_ref()
+            .join(SSTableBuilder::<T, U>::generate_file_name());
+        fs::create_dir(sstable_path.as_path())?;
+        for dir_entry in fs::read_dir(self.path.as_path())? {
+            let dir_entry = dir_entry?;
+            // the summary is not linked, so rewriting it does not modify the source SSTable
+            if dir_entry.file_name() == "summary.dat" {
+                continue;
+            }
+            let file_path = sstable_path.join(dir_entry.file_name());
+            if fs::hard_link(dir_entry.path(), file_path.as_path()).is_err() {
+                fs::copy(dir_entry.path(), file_path.as_path())?;
+                fs::File::open(file_path.as_path())?.sync_all()?;
             }
         }
 
-        if hi == -1 {
-            None
-        } else {
-            Some(hi as usize)
+        self.summary.logical_time_range = (logical_time, logical_time);
+        self.summary.ingested_logical_time = Some(logical_time);
+        write_synced(
+            sstable_path.join("summary.dat"),
+            &self.summary.serialize_with_header()?,
+        )?;
+        fs::File::open(sstable_path.as_path())?.sync_all()?;
+        SSTable::new(sstable_path)
+    }
+
+    /// Sets the cache that the index and data blocks of the SSTable are read through.
+    pub fn set_cache(&mut self, cache: &Arc<TableCache>) {
+        if let Some((cache, id)) = self.cache.take() {
+            cache.remove_files(id);
         }
+        self.cache = Some((Arc::clone(cache), cache.next_id()));
     }
 
-    pub fn get<V>(&self, key: &V) -> Result<Option<SSTableValue<U>>>
+    // Reads the block at an offset of a file of the SSTable with `read_block` after seeking to the
+    // offset, or returns the block from the cache of the SSTable.
+    fn get_block<F>(&self, kind: FileKind, offset: u64, read_block: F) -> Result<Option<Block>>
+    where
+        F: FnOnce(&mut fs::File, &Path) -> Result<Option<(Vec<u8>, u64)>>,
+    {
+        let path = self.path.join(kind.file_name());
+        match &self.cache {
+            Some((cache, id)) => cache.get_block(*id, kind, path.as_path(), offset, read_block),
+            None => {
+                let mut file = fs::File::open(path.as_path())?;
+                file.seek(SeekFrom::Start(offset))?;
+                let block = read_block(&mut file, path.as_path())?;
+                Ok(block.map(|(buffer, size)| (Arc::from(buffer), size)))
+            }
+        }
+    }
+
+    /// Marks the SSTable as obsolete, so that its directory is removed when it is dropped.
+    pub fn mark_obsolete(&self) {
+        self.is_obsolete.store(true, Ordering::Release);
+    }
+
+    pub fn get<V>(&self, key: &V, logical_time: Option<u64>) -> Result<Option<SSTableValue<U>>>
+    where
+        T: Borrow<V> + DeserializeOwned,
+        U: DeserializeOwned,
+        V: Ord + Hash + ?Sized,
+    {
+        Ok(self.get_versions(key, logical_time)?.into_iter().next())
+    }
+
+    /// Returns all versions of a key that are visible at a logical time in descending order of
+    /// logical time.
+    pub fn get_versions<V>(
+        &self,
+        key: &V,
+        logical_time: Option<u64>,
+    ) -> Result<Vec<SSTableValue<U>>>
     where
         T: Borrow<V> + DeserializeOwned,
         U: DeserializeOwned,
         V: Ord + Hash + ?Sized,
+    {
+        if self.probe_filter(key) != Some(true) {
+            return Ok(Vec::new());
+        }
+
+        let mut versions = self.read_versions(key)?;
+        versions.retain(|version| version.is_visible(logical_time));
+        Ok(versions)
+    }
+
+    /// Returns `None` if a key is outside the key range of the SSTable, and otherwise whether the
+    /// filter of the SSTable may contain the key. A SSTable without a filter may contain any key in
+    /// its key range.
+    pub fn probe_filter<V>(&self, key: &V) -> Option<bool>
+    where
+        T: Borrow<V>,
+        V: Ord + Hash + ?Sized,
     {
         if key < self.summary.key_range.0.borrow() || key > self.summary.key_range.1.borrow() {
-            return Ok(None);
+            return None;
         }
+        match &self.filter {
+            Some(filter) => Some(filter.contains(key)),
+            None => Some(true),
+        }
+    }
+
+    /// Returns all versions of a key in descending order of logical time without probing the
+    /// filter of the SSTable.
+    pub fn read_versions<V>(&self, key: &V) -> Result<Vec<SSTableValue<U>>>
+    where
+        T: Borrow<V> + DeserializeOwned,
+        U: DeserializeOwned,
+        V: Ord + ?Sized,
+    {
+        let mut versions = Vec::new();
+        let mut data_iter = self.data_iter();
+        data_iter.offset = self.lower_bound_offset(key)?;
+        data_iter.cache = self.cache.clone();
 
-        if !self.filter.contains(key) {
-            return Ok(None);
+        // versions of the same key are sorted in descending order of logical time
+        while let Some(entry) = data_iter.next_entry() {
+            let entry = entry?;
+            match entry.key.borrow().cmp(key) {
+                cmp::Ordering::Less => continue,
+                cmp::Ordering::Greater => break,
+                cmp::Ordering::Equal => versions.push(entry.value),
+            }
         }
 
-        let index = match Self::floor_offset(&self.summary.index, key) {
-            Some(index) => index,
-            None => return Ok(None),
+        Ok(versions)
+    }
+
+    fn read_index_block(&self, index: usize) -> Result<Vec<(T, u64)>>
+    where
+        T: DeserializeOwned,
+    {
+        let offset = self.summary.index[index].1;
+        let has_checksums = self.summary.has_checksums();
+        let header_size = if has_checksums {
+            INDEX_BLOCK_HEADER_SIZE
+        } else {
+            8
         };
 
-        let mut index_file = fs::File::open(self.path.join("index.dat"))?;
-        index_file.seek(SeekFrom::Start(self.summary.index[index].1))?;
-        let size = index_file.read_u64::<BigEndian>()?;
-        let mut buffer = vec![0; size as usize];
-        index_file.read_exact(buffer.as_mut_slice())?;
-        let index_block: Vec<(T, u64)> = deserialize(&buffer)?;
-
-        let index = {
-            match index_block.binary_search_by_key(&key, |index_entry| index_entry.0.borrow()) {
-                Ok(index) => index,
-                Err(_) => return Ok(None),
+        let index_block = self.get_block(FileKind::Index, offset, |index_file, index_path| {
+            let header = read_block(index_file, header_size, index_path, offset)?;
+            let size = BigEndian::read_u64(&header);
+            let buffer = read_block(index_file, size, index_path, offset)?;
+
+            if has_checksums && BigEndian::read_u32(&header[8..]) != crc32c::crc32c(&buffer) {
+                return Err(corruption(index_path, offset));
             }
-        };
+            Ok(Some((buffer, header_size + size)))
+        })?;
+
+        match index_block {
+            Some((buffer, _)) => deserialize(&buffer).map_err(Error::SerdeError),
+            None => Err(corruption(self.path.join("index.dat").as_path(), offset)),
+        }
+    }
+
+    /// Verifies the checksums of all files and blocks of the SSTable, and that every entry can
+    /// be deserialized.
+    pub fn verify(&self) -> Result<()>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+    {
+        // blocks are read from disk instead of the cache
+        let sstable = SSTable::<T, U>::new(self.path.as_path())?;
+
+        for index in 0..sstable.summary.index.len() {
+            sstable.read_index_block(index)?;
+        }
+
+        let mut data_iter = sstable.data_iter();
+        while let Some(entry) = data_iter.next_entry() {
+            entry?;
+        }
+
+        Ok(())
+    }
+
+    // Returns the offset of the last data block whose first key is less than `key`, or the offset
+    // of the first data block if there is no such block. Versions of the same key may span
+    // multiple data blocks, so the first entry whose key is greater than or equal to `key` is at
+    // or after the returned offset.
+    fn lower_bound_offset<V>(&self, key: &V) -> Result<u64>
+    where
+        T: Borrow<V> + DeserializeOwned,
+        V: Ord + ?Sized,
+    {
+        let index = self
+            .summary
+            .index
+            .iter()
+            .take_while(|index_entry| index_entry.0.borrow() < key)
+            .count();
+        if index == 0 {
+            return Ok(0);
+        }
 
-        let mut data_file = fs::File::open(self.path.join("data.dat"))?;
-        data_file.seek(SeekFrom::Start(index_block[index].1))?;
-        let size = data_file.read_u64::<BigEndian>()?;
-        let mut buffer = vec![0; size as usize];
-        data_file.read_exact(buffer.as_mut_slice())?;
-        deserialize(&buffer)
-            .map_err(Error::SerdeError)
-            .map(|entry: Entry<T, SSTableValue<U>>| Some(entry.value))
+        let index_block = self.read_index_block(index - 1)?;
+        let index = index_block
+            .iter()
+            .take_while(|index_entry| index_entry.0.borrow() < key)
+            .count();
+        Ok(index_block[index - 1].1)
     }
 
+    /// Returns an iterator over all entries of the SSTable that reads data blocks from disk
+    /// without filling the cache.
     pub fn data_iter(&self) -> SSTableDataIter<T, U> {
         SSTableDataIter {
             data_path: self.path.join("data.dat"),
             data_file: None,
+            cache: None,
+            offset: 0,
+            range: (Unbounded, Unbounded),
+            has_data_blocks: self.summary.compression.is_some(),
+            data_block: io::Cursor::new(Arc::from(Vec::new())),
+            ingested_logical_time: self.summary.ingested_logical_time,
             _marker: PhantomData,
         }
     }
+
+    pub fn range_iter(&self, range: (Bound<T>, Bound<T>)) -> Result<SSTableDataIter<T, U>>
+    where
+        T: DeserializeOwned + Ord,
+    {
+        let offset = match range.0 {
+            Included(ref key) | Excluded(ref key) => self.lower_bound_offset(key)?,
+            Unbounded => 0,
+        };
+
+        Ok(SSTableDataIter {
+            data_path: self.path.join("data.dat"),
+            data_file: None,
+            cache: self.cache.clone(),
+            offset,
+            range,
+            has_data_blocks: self.summary.compression.is_some(),
+            data_block: io::Cursor::new(Arc::from(Vec::new())),
+            ingested_logical_time: self.summary.ingested_logical_time,
+            _marker: PhantomData,
+        })
+    }
 }
 
 pub struct SSTableDataIter<T, U> {
     data_path: PathBuf,
     data_file: Option<fs::File>,
+    cache: Option<(Arc<TableCache>, u64)>,
+    offset: u64,
+    range: (Bound<T>, Bound<T>),
+    has_data_blocks: bool,
+    data_block: io::Cursor<Arc<[u8]>>,
+    ingested_logical_time: Option<u64>,
     _marker: PhantomData<(T, U)>,
 }
 
-impl<T, U> Iterator for SSTableDataIter<T, U>
+impl<T, U> SSTableDataIter<T, U>
 where
     T: DeserializeOwned,
     U: DeserializeOwned,
+{
+    fn next_entry(&mut self) -> Option<Result<Entry<T, SSTableValue<U>>>> {
+        match self.try_next_entry() {
+            Ok(entry_opt) => entry_opt.map(|mut entry| {
+                if let Some(logical_time) = self.ingested_logical_time {
+                    entry.value.logical_time = logical_time;
+                }
+                Ok(entry)
+            }),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn try_next_entry(&mut self) -> Result<Option<Entry<T, SSTableValue<U>>>> {
+        if !self.has_data_blocks {
+            return read_entry(self.open_data_file()?, deserialize_entry);
+        }
+
+        while self.data_block.position() == self.data_block.get_ref().len() as u64 {
+            match self.read_next_data_block()? {
+                Some((data_block, size)) => {
+                    self.data_block = io::Cursor::new(data_block);
+                    self.offset += size;
+                }
+                None => return Ok(None),
+            }
+        }
+
+        read_entry(&mut self.data_block, deserialize_entry)
+    }
+
+    fn open_data_file(&mut self) -> Result<&mut fs::File> {
+        if self.data_file.is_none() {
+            let mut data_file = fs::File::open(self.data_path.as_path())?;
+            data_file.seek(SeekFrom::Start(self.offset))?;
+            self.data_file = Some(data_file);
+        }
+        Ok(self.data_file.as_mut().expect("Expected opened file."))
+    }
+
+    // Reads the data block at the current offset through the cache, if the iterator has one.
+    fn read_next_data_block(&mut self) -> Result<Option<Block>> {
+        let offset = self.offset;
+        if let Some((cache, id)) = &self.cache {
+            return cache.get_block(
+                *id,
+                FileKind::Data,
+                self.data_path.as_path(),
+                offset,
+                |data_file, data_path| read_data_block(data_file, data_path, offset),
+            );
+        }
+
+        let data_path = self.data_path.clone();
+        let data_block = read_data_block(self.open_data_file()?, data_path.as_path(), offset)?;
+        Ok(data_block.map(|(buffer, size)| (Arc::from(buffer), size)))
+    }
+}
+
+/// A function that decodes a serialized entry into its key and a version of type
+/// `SSTableValue<V>`.
+pub type ReadEntry<T, V> = fn(&[u8]) -> Result<Entry<T, SSTableValue<V>>>;
+
+/// Decodes the key and the version of a serialized entry.
+pub fn deserialize_entry<T, U>(buffer: &[u8]) -> Result<Entry<T, SSTableValue<U>>>
+where
+    T: DeserializeOwned,
+    U: DeserializeOwned,
+{
+    deserialize(buffer).map_err(Error::SerdeError)
+}
+
+/// Decodes the key of a serialized entry and the version without its value. The tag of a version
+/// directly follows the key, and its expiry time and logical time are at the end of the entry, so
+/// the value is skipped without being deserialized. Operands are decoded as values, since applying
+/// operands always results in a value.
+pub fn deserialize_key_entry<T>(buffer: &[u8]) -> Result<Entry<T, SSTableValue<()>>>
+where
+    T: DeserializeOwned,
+{
+    fn invalid_version(message: &str) -> Error {
+        Error::SerdeError(de::Error::custom(message))
+    }
+
+    let mut reader = buffer;
+    let key = bincode::deserialize_from(&mut reader)?;
+    let tag = match reader.first() {
+        Some(tag) => *tag,
+        None => return Err(invalid_version("Expected a complete version.")),
+    };
+    let trailing_size = match tag {
+        0..=2 => 8,
+        3 => 16,
+        _ => return Err(invalid_version("Unknown data tag.")),
+    };
+    if reader.len() < 1 + trailing_size {
+        return Err(invalid_version("Expected a complete version."));
+    }
+
+    let logical_time = LittleEndian::read_u64(&reader[reader.len() - 8..]);
+    let (data, expiry_time) = match tag {
+        0 => (SSTableData::Tombstone, None),
+        3 => {
+            let expiry_time = LittleEndian::read_u64(&reader[reader.len() - 16..]);
+            (SSTableData::Value(()), Some(expiry_time))
+        }
+        _ => (SSTableData::Value(()), None),
+    };
+    Ok(Entry {
+        key,
+        value: SSTableValue {
+            data,
+            logical_time,
+            expiry_time,
+        },
+    })
+}
+
+// Reads a length-prefixed entry. Returns `None` if the reader is at its end.
+fn read_entry<R, T, V>(
+    reader: &mut R,
+    read: ReadEntry<T, V>,
+) -> Result<Option<Entry<T, SSTableValue<V>>>>
+where
+    R: Read,
+{
+    let size = match reader.read_u64::<BigEndian>() {
+        Ok(size) => size,
+        Err(ref error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(Error::from(error)),
+    };
+
+    let mut buffer = vec![0; size as usize];
+    reader.read_exact(buffer.as_mut_slice())?;
+    read(&buffer).map(Some)
+}
+
+// Reads, verifies, and decompresses a data block. Returns the data block and its size in the
+// data file, or `None` if the reader is at its end.
+fn read_data_block<R>(reader: &mut R, path: &Path, offset: u64) -> Result<Option<(Vec<u8>, u64)>>
+where
+    R: Read,
+{
+    let mut first_byte = [0; 1];
+    if reader.read(&mut first_byte)? == 0 {
+        return Ok(None);
+    }
+    let header_rest = read_block(reader, DATA_BLOCK_HEADER_SIZE - 1, path, offset)?;
+    let header = [&first_byte[..], &header_rest].concat();
+
+    let size = BigEndian::read_u64(&header);
+    let compression = header[8];
+    let checksum = BigEndian::read_u32(&header[9..]);
+    let buffer = read_block(reader, size, path, offset)?;
+
+    if crc32c::crc32c_append(crc32c::crc32c(&[compression]), &buffer) != checksum {
+        return Err(corruption(path, offset));
+    }
+
+    let data_block = Compression::from_u8(compression)?.decompress(buffer)?;
+    Ok(Some((data_block, DATA_BLOCK_HEADER_SIZE + size)))
+}
+
+impl<T, U> Iterator for SSTableDataIter<T, U>
+where
+    T: DeserializeOwned + Ord,
+    U: DeserializeOwned,
 {
     type Item = Result<Entry<T, SSTableValue<U>>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.data_file.is_none() {
-            match fs::File::open(self.data_path.as_path()) {
-                Ok(data_file) => self.data_file = Some(data_file),
-                Err(error) => return Some(Err(Error::from(error))),
+        loop {
+            let entry = match self.next_entry()? {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error)),
+            };
+
+            if is_after_range(&entry.key, &self.range) {
+                return None;
+            }
+
+            if !is_before_range(&entry.key, &self.range) {
+                return Some(Ok(entry));
             }
         }
+    }
+}
 
-        let data_file = self.data_file.as_mut().expect("Expected opened file.");
+impl<T, U> Drop for SSTable<T, U> {
+    fn drop(&mut self) {
+        if let Some((cache, id)) = &self.cache {
+            cache.remove_files(*id);
+        }
 
-        let size = match data_file.read_u64::<BigEndian>() {
-            Ok(size) => size,
-            Err(error) => match error.kind() {
-                ErrorKind::UnexpectedEof => return None,
-                _ => return Some(Err(Error::from(error))),
-            },
-        };
+        // a directory that cannot be removed is not referenced by the manifest, so it is removed
+        // when the compaction strategy is opened
+        if self.is_obsolete.load(Ordering::Acquire) {
+            fs::remove_dir_all(self.path.as_path()).ok();
+        }
+    }
+}
+
+/// A cursor over the entries of a SSTable that can move in both directions. Only the index block
+/// and the data block of the current entry are kept in memory, and data blocks are read when the
+/// cursor moves into them.
+pub struct SSTableCursor<T, U, V = U> {
+    sstable: Arc<SSTable<T, U>>,
+    read_entry: ReadEntry<T, V>,
+    data_file: Option<fs::File>,
+    index_block_index: usize,
+    index_block: Vec<(T, u64)>,
+    data_block_index: usize,
+    entries: Vec<Entry<T, SSTableValue<V>>>,
+    entry_index: usize,
+}
 
-        let mut buffer = vec![0; size as usize];
-        let result = data_file.read_exact(buffer.as_mut_slice());
-        if let Err(error) = result {
-            return Some(Err(Error::from(error)));
+impl<T, U, V> SSTableCursor<T, U, V>
+where
+    T: DeserializeOwned,
+{
+    /// Constructs a cursor over a SSTable that is not positioned at any entry and decodes its
+    /// entries with `read_entry`.
+    pub fn new(sstable: Arc<SSTable<T, U>>, read_entry: ReadEntry<T, V>) -> Self {
+        SSTableCursor {
+            sstable,
+            read_entry,
+            data_file: None,
+            index_block_index: 0,
+            index_block: Vec::new(),
+            data_block_index: 0,
+            entries: Vec::new(),
+            entry_index: 0,
         }
+    }
 
-        Some(deserialize(&buffer).map_err(Error::SerdeError))
+    fn load_index_block(&mut self, index: usize) -> Result<()> {
+        self.index_block = self.sstable.read_index_block(index)?;
+        self.index_block_index = index;
+        Ok(())
+    }
+
+    fn load_data_block(&mut self, index: usize) -> Result<()> {
+        let data_path = self.sstable.path.join("data.dat");
+        let offset = self.index_block[index].1;
+
+        // each entry of SSTables without data blocks is indexed individually
+        let mut entries = Vec::new();
+        if self.sstable.summary.compression.is_some() {
+            let data_block =
+                self.sstable
+                    .get_block(FileKind::Data, offset, |data_file, data_path| {
+                        read_data_block(data_file, data_path, offset)
+                    })?;
+            if let Some((data_block, _)) = data_block {
+                let mut data_block = io::Cursor::new(data_block);
+                while let Some(entry) = read_entry(&mut data_block, self.read_entry)? {
+                    entries.push(entry);
+                }
+            }
+        } else {
+            if self.data_file.is_none() {
+                self.data_file = Some(fs::File::open(data_path.as_path())?);
+            }
+            let data_file = self.data_file.as_mut().expect("Expected opened file.");
+            data_file.seek(SeekFrom::Start(offset))?;
+            entries.extend(read_entry(data_file, self.read_entry)?);
+        }
+
+        if entries.is_empty() {
+            return Err(corruption(data_path.as_path(), offset));
+        }
+        if let Some(logical_time) = self.sstable.summary.ingested_logical_time {
+            for entry in &mut entries {
+                entry.value.logical_time = logical_time;
+            }
+        }
+
+        self.data_block_index = index;
+        self.entries = entries;
+        Ok(())
+    }
+
+    fn invalidate(&mut self) {
+        self.entries.clear();
+        self.entry_index = 0;
+    }
+
+    /// Returns the entry that the cursor is positioned at, or `None` if the cursor is not
+    /// positioned at any entry.
+    pub fn entry(&self) -> Option<&Entry<T, SSTableValue<V>>> {
+        self.entries.get(self.entry_index)
+    }
+
+    /// Positions the cursor at the first entry of the SSTable.
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        if self.sstable.summary.index.is_empty() {
+            self.invalidate();
+            return Ok(());
+        }
+
+        self.load_index_block(0)?;
+        self.load_data_block(0)?;
+        self.entry_index = 0;
+        Ok(())
+    }
+
+    /// Positions the cursor at the last entry of the SSTable.
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        if self.sstable.summary.index.is_empty() {
+            self.invalidate();
+            return Ok(());
+        }
+
+        self.load_index_block(self.sstable.summary.index.len() - 1)?;
+        self.load_data_block(self.index_block.len() - 1)?;
+        self.entry_index = self.entries.len() - 1;
+        Ok(())
+    }
+
+    /// Positions the cursor at the first entry whose key is greater than or equal to `key`.
+    pub fn seek<Q>(&mut self, key: &Q) -> Result<()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self
+            .sstable
+            .summary
+            .index
+            .iter()
+            .take_while(|index_entry| index_entry.0.borrow() < key)
+            .count();
+        if index == 0 {
+            return self.seek_to_first();
+        }
+
+        // versions of the same key may span multiple data blocks, so the first entry whose key is
+        // greater than or equal to `key` is at or after the last data block whose first key is
+        // less than `key`
+        self.load_index_block(index - 1)?;
+        let index = self
+            .index_block
+            .iter()
+            .take_while(|index_entry| index_entry.0.borrow() < key)
+            .count();
+        self.load_data_block(index - 1)?;
+
+        self.entry_index = self
+            .entries
+            .iter()
+            .take_while(|entry| entry.key.borrow() < key)
+            .count();
+        if self.entry_index == self.entries.len() {
+            self.entry_index -= 1;
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor to the next entry. The cursor is no longer positioned at any entry if it
+    /// was positioned at the last entry.
+    pub fn next(&mut self) -> Result<()> {
+        if self.entry().is_none() {
+            return Ok(());
+        }
+
+        self.entry_index += 1;
+        if self.entry_index < self.entries.len() {
+            return Ok(());
+        }
+
+        if self.data_block_index + 1 < self.index_block.len() {
+            self.load_data_block(self.data_block_index + 1)?;
+        } else if self.index_block_index + 1 < self.sstable.summary.index.len() {
+            self.load_index_block(self.index_block_index + 1)?;
+            self.load_data_block(0)?;
+        } else {
+            self.invalidate();
+            return Ok(());
+        }
+        self.entry_index = 0;
+        Ok(())
+    }
+
+    /// Moves the cursor to the previous entry. The cursor is no longer positioned at any entry if
+    /// it was positioned at the first entry.
+    pub fn prev(&mut self) -> Result<()> {
+        if self.entry().is_none() {
+            return Ok(());
+        }
+
+        if self.entry_index > 0 {
+            self.entry_index -= 1;
+            return Ok(());
+        }
+
+        if self.data_block_index > 0 {
+            self.load_data_block(self.data_block_index - 1)?;
+        } else if self.index_block_index > 0 {
+            self.load_index_block(self.index_block_index - 1)?;
+            self.load_data_block(self.index_block.len() - 1)?;
+        } else {
+            self.invalidate();
+            return Ok(());
+        }
+        self.entry_index = self.entries.len() - 1;
+        Ok(())
     }
 }
 
@@ -409,3 +1508,444 @@ where
         writeln!(f, "key range: {:?}", self.summary.key_range)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        deserialize_entry, deserialize_key_entry, SSTable, SSTableBuilder, SSTableCursor,
+        SSTableData, SSTableValue,
+    };
+    use crate::lsm_tree::cache::TableCache;
+    use crate::lsm_tree::{Compression, Error, FilterPolicy, PrefixExtractor, Result};
+    use bincode::{deserialize, serialize};
+    use byteorder::{BigEndian, WriteBytesExt};
+    use probabilistic_collections::bloom::BloomFilter;
+    use std::fs;
+    use std::ops::Bound::{Excluded, Included};
+    use std::panic;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    fn teardown(test_name: &str) {
+        fs::remove_dir_all(test_name).ok();
+    }
+
+    fn run_test<T>(test: T, test_name: &str)
+    where
+        T: FnOnce() -> Result<()> + panic::UnwindSafe,
+    {
+        let result = panic::catch_unwind(|| test().unwrap());
+
+        teardown(test_name);
+
+        assert!(result.is_ok());
+    }
+
+    fn get_entries() -> Vec<(u32, SSTableValue<u64>)> {
+        (0..1000)
+            .map(|key| {
+                let value = SSTableValue {
+                    data: SSTableData::Value(u64::from(key) % 10),
+                    logical_time: u64::from(key),
+                    expiry_time: None,
+                };
+                (key * 2, value)
+            })
+            .collect()
+    }
+
+    fn check_sstable(sstable: SSTable<u32, u64>) -> Result<()> {
+        for key in 0..2000 {
+            let expected = if key % 2 == 0 {
+                Some(u64::from(key / 2) % 10)
+            } else {
+                None
+            };
+            let value = sstable
+                .get(&key, None)?
+                .and_then(|value| value.data.into_value());
+            assert_eq!(value, expected);
+        }
+
+        let entries = sstable.data_iter().collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1000);
+
+        let keys = sstable
+            .range_iter((Included(501), Excluded(600)))?
+            .map(|entry| entry.map(|entry| entry.key))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(keys, (502..600).step_by(2).collect::<Vec<_>>());
+
+        let mut cursor = SSTableCursor::new(Arc::new(sstable), deserialize_entry::<u32, u64>);
+        let mut keys = Vec::new();
+        cursor.seek(&501)?;
+        while let Some(entry) = cursor.entry() {
+            if entry.key >= 600 {
+                break;
+            }
+            keys.push(entry.key);
+            cursor.next()?;
+        }
+        assert_eq!(keys, (502..600).step_by(2).collect::<Vec<_>>());
+
+        let mut keys = Vec::new();
+        cursor.seek_to_last()?;
+        while let Some(entry) = cursor.entry() {
+            keys.push(entry.key);
+            cursor.prev()?;
+        }
+        assert_eq!(keys, (0..2000).step_by(2).rev().collect::<Vec<_>>());
+
+        cursor.seek(&2000)?;
+        assert!(cursor.entry().is_none());
+        Ok(())
+    }
+
+    // Writes a SSTable in the format used before data files were divided into blocks.
+    fn write_legacy_sstable(path: &Path, entries: &[(u32, SSTableValue<u64>)]) -> Result<()> {
+        fs::create_dir(path)?;
+
+        let mut data = Vec::new();
+        let mut index_data = Vec::new();
+        let mut index = Vec::new();
+        let mut index_block = Vec::new();
+        let mut filter: BloomFilter<u32> = BloomFilter::new(entries.len(), 0.05);
+
+        for (index_block_index, (key, value)) in entries.iter().enumerate() {
+            filter.insert(key);
+            index_block.push((*key, data.len() as u64));
+
+            let serialized_entry = serialize(&(key, value))?;
+            data.write_u64::<BigEndian>(serialized_entry.len() as u64)?;
+            data.extend_from_slice(&serialized_entry);
+
+            if index_block_index % 32 == 31 || index_block_index + 1 == entries.len() {
+                index.push((index_block[0].0, index_data.len() as u64));
+                let serialized_index_block = serialize(&index_block)?;
+                index_data.write_u64::<BigEndian>(serialized_index_block.len() as u64)?;
+                index_data.extend_from_slice(&serialized_index_block);
+                index_block.clear();
+            }
+        }
+
+        let summary = (
+            entries.len(),
+            0usize,
+            (data.len() + index_data.len()) as u64,
+            (entries[0].0, entries[entries.len() - 1].0),
+            (0u64, entries.len() as u64 - 1),
+            index,
+        );
+        fs::write(path.join("data.dat"), data)?;
+        fs::write(path.join("index.dat"), index_data)?;
+        fs::write(path.join("summary.dat"), serialize(&summary)?)?;
+        fs::write(path.join("filter.dat"), serialize(&filter)?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_sstable() {
+        let test_name = "test_legacy_sstable";
+        run_test(
+            || {
+                write_legacy_sstable(Path::new(test_name), &get_entries())?;
+                let sstable = SSTable::new(test_name)?;
+                assert_eq!(sstable.summary.compression, None);
+                check_sstable(sstable)
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_compression() {
+        let test_name = "test_compression";
+        run_test(
+            || {
+                fs::create_dir(test_name)?;
+                for compression in &[Compression::None, Compression::Lz4, Compression::Snappy] {
+                    let mut sstable_builder = SSTableBuilder::new(
+                        test_name,
+                        1000,
+                        *compression,
+                        &FilterPolicy::default(),
+                    )?;
+                    for (key, value) in get_entries() {
+                        sstable_builder.append(key, value)?;
+                    }
+                    let sstable_path = sstable_builder.flush()?;
+                    let sstable = SSTable::new(&sstable_path)?;
+                    assert_eq!(sstable.summary.compression, Some(*compression));
+                    check_sstable(sstable)?;
+
+                    // blocks are evicted from a cache that only holds a few blocks
+                    let cache = Arc::new(TableCache::new(3 * 4096, 0));
+                    check_sstable(SSTable::with_cache(&sstable_path, &cache)?)?;
+                    assert!(cache.stats().block_cache_hit_count > 0);
+                }
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_ingest() {
+        let test_name = "test_ingest";
+        run_test(
+            || {
+                let import_path = Path::new(test_name).join("import");
+                let db_path = Path::new(test_name).join("db");
+                fs::create_dir_all(import_path.as_path())?;
+                fs::create_dir_all(db_path.as_path())?;
+
+                let sstable_path = build_sstable(import_path.to_str().unwrap())?;
+                let sstable: SSTable<u32, u64> =
+                    SSTable::new(&sstable_path)?.ingest(&db_path, 5000)?;
+                assert!(sstable.path.starts_with(db_path.as_path()));
+                assert_eq!(sstable.summary.logical_time_range, (5000, 5000));
+
+                // the source SSTable is left unmodified
+                let source = SSTable::<u32, u64>::new(&sstable_path)?;
+                assert_eq!(source.summary.ingested_logical_time, None);
+                assert_eq!(
+                    source.get(&10, None)?.map(|value| value.logical_time),
+                    Some(5)
+                );
+
+                // the logical time of every entry is overridden, including after reopening
+                let sstable = SSTable::<u32, u64>::new(&sstable.path)?;
+                assert_eq!(sstable.summary.ingested_logical_time, Some(5000));
+                assert_eq!(
+                    sstable.get(&10, None)?.map(|value| value.logical_time),
+                    Some(5000)
+                );
+                assert!(sstable.get(&10, Some(4999))?.is_none());
+                for entry in sstable.data_iter() {
+                    assert_eq!(entry?.value.logical_time, 5000);
+                }
+                for entry in sstable.range_iter((Included(501), Excluded(600)))? {
+                    assert_eq!(entry?.value.logical_time, 5000);
+                }
+                let mut cursor =
+                    SSTableCursor::new(Arc::new(sstable), deserialize_entry::<u32, u64>);
+                cursor.seek(&501)?;
+                assert_eq!(
+                    cursor.entry().map(|entry| entry.value.logical_time),
+                    Some(5000)
+                );
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    fn flip_byte(path: &Path, offset: usize) -> Result<()> {
+        let mut buffer = fs::read(path)?;
+        buffer[offset] ^= 1;
+        fs::write(path, buffer)?;
+        Ok(())
+    }
+
+    fn assert_corruption<T>(result: Result<T>, expected_path: &Path) {
+        match result {
+            Err(Error::Corruption { path, .. }) => assert_eq!(path, expected_path),
+            _ => panic!("Expected corruption."),
+        }
+    }
+
+    fn build_sstable(test_name: &str) -> Result<PathBuf> {
+        let mut sstable_builder =
+            SSTableBuilder::new(test_name, 1000, Compression::Lz4, &FilterPolicy::default())?;
+        for (key, value) in get_entries() {
+            sstable_builder.append(key, value)?;
+        }
+        sstable_builder.flush()
+    }
+
+    #[test]
+    fn test_corruption() {
+        let test_name = "test_corruption";
+        run_test(
+            || {
+                fs::create_dir(test_name)?;
+
+                for file_name in &["summary.dat", "filter.dat"] {
+                    let path = build_sstable(test_name)?.join(file_name);
+                    flip_byte(&path, fs::metadata(&path)?.len() as usize / 2)?;
+                    assert_corruption(SSTable::<u32, u64>::new(path.parent().unwrap()), &path);
+                }
+
+                for file_name in &["data.dat", "index.dat"] {
+                    let sstable_path = build_sstable(test_name)?;
+                    let sstable: SSTable<u32, u64> = SSTable::new(&sstable_path)?;
+                    sstable.verify()?;
+
+                    let path = sstable_path.join(file_name);
+                    flip_byte(&path, fs::metadata(&path)?.len() as usize / 2)?;
+                    assert_corruption(sstable.verify(), &path);
+                    let entries = sstable.data_iter().collect::<Result<Vec<_>>>();
+                    if *file_name == "data.dat" {
+                        assert_corruption(entries, &path);
+                    }
+                }
+
+                let sstable_path = build_sstable(test_name)?;
+                let sstable: SSTable<u32, u64> = SSTable::new(&sstable_path)?;
+                let path = sstable_path.join("data.dat");
+                let len = fs::metadata(&path)?.len();
+                fs::OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(len - 1)?;
+                assert_corruption(sstable.verify(), &path);
+                Ok(())
+            },
+            test_name,
+        );
+    }
+
+    struct HundredsPrefix;
+
+    impl PrefixExtractor<u32> for HundredsPrefix {
+        fn name(&self) -> String {
+            String::from("hundreds")
+        }
+
+        fn prefix(&self, key: &u32) -> Option<Vec<u8>> {
+            Some((key / 100).to_be_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn test_filter_policy() {
+        let test_name = "test_filter_policy";
+        run_test(
+            || {
+                fs::create_dir(test_name)?;
+                let filter_policy = FilterPolicy {
+                    false_positive_rate: 0.01,
+                    key_filter: false,
+                    prefix_extractor: Some(Arc::new(HundredsPrefix)),
+                };
+                let mut sstable_builder =
+                    SSTableBuilder::new(test_name, 1000, Compression::Lz4, &filter_policy)?;
+                for (key, value) in get_entries() {
+                    sstable_builder.append(key, value)?;
+                }
+                let sstable_path = sstable_builder.flush()?;
+                assert!(!sstable_path.join("filter.dat").exists());
+
+                let sstable: SSTable<u32, u64> = SSTable::new(&sstable_path)?;
+                sstable.verify()?;
+                assert!(sstable.filter.is_none());
+                assert_eq!(sstable.probe_filter(&1), Some(true));
+                assert_eq!(sstable.probe_filter(&2000), None);
+
+                let (name, prefix_filter) = sstable.prefix_filter.as_ref().unwrap();
+                assert_eq!(name, "hundreds");
+                for key in (0..2000).step_by(100) {
+                    assert!(prefix_filter.contains(&HundredsPrefix.prefix(&key).unwrap()));
+                }
+                assert!(filter_policy.may_contain_prefix(&sstable, Some(&19u32.to_be_bytes())));
+                assert!(filter_policy.may_contain_prefix(&sstable, None));
+
+                check_sstable(sstable)
+            },
+            test_name,
+        );
+    }
+
+    #[test]
+    fn test_data_layout() -> Result<()> {
+        let tombstone: SSTableValue<u64> = SSTableValue {
+            data: SSTableData::Tombstone,
+            logical_time: 1,
+            expiry_time: None,
+        };
+        assert_eq!(serialize(&tombstone)?, serialize(&(None::<u64>, 1u64))?);
+        let value = SSTableValue {
+            data: SSTableData::Value(1u64),
+            logical_time: 2,
+            expiry_time: None,
+        };
+        assert_eq!(serialize(&value)?, serialize(&(Some(1u64), 2u64))?);
+
+        let legacy_value: SSTableValue<u64> = deserialize(&serialize(&(Some(1u64), 2u64))?)?;
+        assert_eq!(legacy_value.data, SSTableData::Value(1));
+        assert_eq!(legacy_value.logical_time, 2);
+        assert_eq!(legacy_value.expiry_time, None);
+
+        let operand = SSTableValue {
+            data: SSTableData::Operand(1u64),
+            logical_time: 3,
+            expiry_time: None,
+        };
+        let operand: SSTableValue<u64> = deserialize(&serialize(&operand)?)?;
+        assert_eq!(operand.data, SSTableData::Operand(1));
+        assert_eq!(operand.logical_time, 3);
+
+        let expiring_value = SSTableValue {
+            data: SSTableData::Value(1u64),
+            logical_time: 4,
+            expiry_time: Some(5),
+        };
+        let expiring_value: SSTableValue<u64> = deserialize(&serialize(&expiring_value)?)?;
+        assert_eq!(expiring_value.data, SSTableData::Value(1));
+        assert_eq!(expiring_value.logical_time, 4);
+        assert_eq!(expiring_value.expiry_time, Some(5));
+        assert!(expiring_value.is_expired(5));
+        assert!(!expiring_value.is_expired(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_key_entry() -> Result<()> {
+        let versions = vec![
+            SSTableValue {
+                data: SSTableData::Tombstone,
+                logical_time: 1,
+                expiry_time: None,
+            },
+            SSTableValue {
+                data: SSTableData::Value(String::from("value")),
+                logical_time: 2,
+                expiry_time: None,
+            },
+            SSTableValue {
+                data: SSTableData::Operand(String::from("operand")),
+                logical_time: 3,
+                expiry_time: None,
+            },
+            SSTableValue {
+                data: SSTableData::Value(String::from("expiring value")),
+                logical_time: 4,
+                expiry_time: Some(5),
+            },
+        ];
+
+        for version in versions {
+            let buffer = serialize(&(String::from("key"), &version))?;
+            let entry = deserialize_entry::<String, String>(&buffer)?;
+            assert_eq!(entry.value.data, version.data);
+
+            let key_entry = deserialize_key_entry::<String>(&buffer)?;
+            assert_eq!(key_entry.key, "key");
+            assert_eq!(key_entry.value.data, version.without_value().data);
+            assert_eq!(key_entry.value.logical_time, version.logical_time);
+            assert_eq!(key_entry.value.expiry_time, version.expiry_time);
+        }
+
+        let mut buffer = serialize(&String::from("key"))?;
+        match deserialize_key_entry::<String>(&buffer) {
+            Err(Error::SerdeError(_)) => {}
+            _ => panic!("Expected serde error."),
+        }
+        buffer.extend_from_slice(&[4; 9]);
+        match deserialize_key_entry::<String>(&buffer) {
+            Err(Error::SerdeError(_)) => {}
+            _ => panic!("Expected serde error."),
+        }
+        Ok(())
+    }
+}
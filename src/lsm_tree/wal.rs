@@ -0,0 +1,142 @@
+use crate::lsm_tree::{Error, Result, SSTableValue};
+use crate::wal::{self, SyncPolicy};
+use bincode::{deserialize, serialize};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// An append-only log of the writes that are in the in-memory tree, but not yet in a SSTable.
+///
+/// Each record is stored as its length, a CRC32C checksum of its contents, and then the
+/// serialized entries of a single write. An incomplete record at the end of the log is assumed to
+/// be the result of a torn write and is discarded, so the entries of a write are either all
+/// replayed or not replayed at all. A record whose checksum does not match in the middle of the
+/// log is reported as corruption instead of silently discarding the records that follow it.
+pub struct WriteAheadLog<T, U> {
+    path: PathBuf,
+    log_file: fs::File,
+    sync_policy: SyncPolicy,
+    unsynced_count: usize,
+    _marker: PhantomData<(T, U)>,
+}
+
+impl<T, U> WriteAheadLog<T, U> {
+    pub fn open<P>(path: P, sync_policy: SyncPolicy) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let log_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref())?;
+
+        Ok(WriteAheadLog {
+            path: PathBuf::from(path.as_ref()),
+            log_file,
+            sync_policy,
+            unsynced_count: 0,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Reads the entries of all valid records in the log and discards a torn record at the end of
+    /// the log. Returns `Error::Corruption` if a record in the middle of the log is corrupted.
+    pub fn replay(&mut self) -> Result<Vec<(T, SSTableValue<U>)>>
+    where
+        T: DeserializeOwned,
+        U: DeserializeOwned,
+    {
+        let records =
+            wal::read_records(&mut self.log_file)?.map_err(|offset| Error::Corruption {
+                path: self.path.clone(),
+                offset,
+            })?;
+        let mut entries = Vec::new();
+        for record in records {
+            let record_entries: Vec<(T, SSTableValue<U>)> = deserialize(&record)?;
+            entries.extend(record_entries);
+        }
+
+        Ok(entries)
+    }
+
+    /// Appends the entries of a single write to the log as one record and synchronizes the log
+    /// according to the sync policy.
+    pub fn append(&mut self, entries: &[(T, SSTableValue<U>)]) -> Result<()>
+    where
+        T: Serialize,
+        U: Serialize,
+    {
+        self.log_file
+            .write_all(&wal::encode_record(&serialize(entries)?))?;
+
+        self.unsynced_count += 1;
+        if self.sync_policy.should_sync(self.unsynced_count) {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Forces all appended entries onto disk.
+    pub fn sync(&mut self) -> Result<()> {
+        self.log_file.sync_data()?;
+        self.unsynced_count = 0;
+        Ok(())
+    }
+
+    /// Removes all entries from the log. This should only be called once all entries in the log
+    /// are persisted in a SSTable.
+    pub fn truncate(&mut self) -> Result<()> {
+        self.log_file.set_len(0)?;
+        self.log_file.seek(SeekFrom::Start(0))?;
+        if self.sync_policy != SyncPolicy::Never {
+            self.log_file.sync_all()?;
+        }
+        self.unsynced_count = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyncPolicy, WriteAheadLog};
+    use crate::lsm_tree::{Error, SSTableData, SSTableValue};
+    use std::fs;
+
+    #[test]
+    fn test_replay_corruption() {
+        let test_name = "test_wal_replay_corruption";
+        let value = SSTableValue {
+            data: SSTableData::Value(1),
+            logical_time: 0,
+            expiry_time: None,
+        };
+        let mut log = WriteAheadLog::open(test_name, SyncPolicy::Never).unwrap();
+        log.append(&[(0u32, value.clone())]).unwrap();
+        log.append(&[(1u32, value)]).unwrap();
+        drop(log);
+
+        // corrupts the length and then the contents of the first record
+        let buffer = fs::read(test_name).unwrap();
+        for index in &[7, 16] {
+            let mut corrupted_buffer = buffer.clone();
+            corrupted_buffer[*index] ^= 1;
+            fs::write(test_name, &corrupted_buffer).unwrap();
+
+            let mut log: WriteAheadLog<u32, u64> =
+                WriteAheadLog::open(test_name, SyncPolicy::Never).unwrap();
+            match log.replay() {
+                Err(Error::Corruption { offset: 0, .. }) => {}
+                _ => panic!("Expected corruption."),
+            }
+            assert_eq!(fs::read(test_name).unwrap(), corrupted_buffer);
+        }
+        fs::remove_file(test_name).unwrap();
+    }
+}
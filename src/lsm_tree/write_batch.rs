@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+/// A collection of insertions and removals that are applied atomically to a `LsmMap`.
+///
+/// All writes in a batch are assigned the same logical time when the batch is written, so they
+/// are either all visible or all hidden. If the same key is written more than once in a batch,
+/// the last write takes precedence.
+///
+/// # Examples
+///
+/// ```
+/// # use extended_collections::lsm_tree::Result;
+/// # fn foo() -> Result<()> {
+/// # use std::fs;
+/// use extended_collections::lsm_tree::compaction::SizeTieredStrategy;
+/// use extended_collections::lsm_tree::{LsmMap, WriteBatch};
+///
+/// let sts = SizeTieredStrategy::new("example_write_batch", 10000, 4, 50000, 0.5, 1.5)?;
+/// let mut map = LsmMap::new(sts)?;
+/// map.insert(0, 0)?;
+///
+/// let mut batch = WriteBatch::new();
+/// batch.insert(1, 1);
+/// batch.insert(2, 2);
+/// batch.remove(0);
+/// assert_eq!(batch.len(), 3);
+///
+/// map.write(batch)?;
+/// assert_eq!(map.get(&0)?, None);
+/// assert_eq!(map.get(&1)?, Some(1));
+/// assert_eq!(map.get(&2)?, Some(2));
+/// # fs::remove_dir_all("example_write_batch")?;
+/// # Ok(())
+/// # }
+/// # foo().unwrap();
+/// ```
+pub struct WriteBatch<T, U> {
+    entries: BTreeMap<T, Option<U>>,
+}
+
+impl<T, U> WriteBatch<T, U>
+where
+    T: Ord,
+{
+    /// Constructs a new, empty `WriteBatch<T, U>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::WriteBatch;
+    ///
+    /// let batch: WriteBatch<u32, u32> = WriteBatch::new();
+    /// assert!(batch.is_empty());
+    /// ```
+    pub fn new() -> Self {
+        WriteBatch {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the insertion of a key-value pair to the batch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::WriteBatch;
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// batch.insert(1, 1);
+    /// assert_eq!(batch.len(), 1);
+    /// ```
+    pub fn insert(&mut self, key: T, value: U) {
+        self.entries.insert(key, Some(value));
+    }
+
+    /// Adds the removal of a key to the batch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::WriteBatch;
+    ///
+    /// let mut batch: WriteBatch<u32, u32> = WriteBatch::new();
+    /// batch.remove(1);
+    /// assert_eq!(batch.len(), 1);
+    /// ```
+    pub fn remove(&mut self, key: T) {
+        self.entries.insert(key, None);
+    }
+
+    /// Returns the number of distinct keys written by the batch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::WriteBatch;
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// batch.insert(1, 1);
+    /// batch.insert(1, 2);
+    /// assert_eq!(batch.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the batch is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::WriteBatch;
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// assert!(batch.is_empty());
+    /// batch.insert(1, 1);
+    /// assert!(!batch.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Clears the batch, removing all writes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use extended_collections::lsm_tree::WriteBatch;
+    ///
+    /// let mut batch = WriteBatch::new();
+    /// batch.insert(1, 1);
+    /// batch.clear();
+    /// assert!(batch.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn into_entries(self) -> BTreeMap<T, Option<U>> {
+        self.entries
+    }
+}
+
+impl<T, U> Default for WriteBatch<T, U>
+where
+    T: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,174 @@
+use crate::lsm_tree::compaction::CompactionStrategy;
+use crate::lsm_tree::merge::{self, MergeOperator};
+use crate::lsm_tree::sstable;
+use crate::lsm_tree::{
+    Result, SSTable, SSTableBuilder, SSTableData, SSTableValue, SyncPolicy, WriteAheadLog,
+    WriteBatch,
+};
+use bincode::serialized_size;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The write path that is shared by `LsmMap` and `ConcurrentLsmMap`.
+///
+/// A writer assigns logical times to writes, appends them to the write-ahead log, inserts them
+/// into the in-memory tree while tracking its serialized size, and flushes the in-memory tree
+/// into a SSTable. The in-memory tree itself is owned by the map, since `ConcurrentLsmMap` shares
+/// it with concurrent readers.
+pub struct Writer<T, U, C> {
+    pub compaction_strategy: C,
+    in_memory_usage: u64,
+    write_ahead_log: WriteAheadLog<T, U>,
+}
+
+impl<T, U, C> Writer<T, U, C>
+where
+    T: Clone + Ord + Hash + DeserializeOwned + Serialize,
+    U: Clone + DeserializeOwned + Serialize,
+    C: CompactionStrategy<T, U>,
+{
+    /// Opens the write-ahead log of a compaction strategy and replays it into a new in-memory
+    /// tree.
+    pub fn open(
+        mut compaction_strategy: C,
+        sync_policy: SyncPolicy,
+    ) -> Result<(Self, BTreeMap<T, SSTableValue<U>>)> {
+        let mut write_ahead_log =
+            WriteAheadLog::open(compaction_strategy.get_path().join("wal.dat"), sync_policy)?;
+        let mut in_memory_tree = BTreeMap::new();
+        let mut in_memory_usage = 0;
+        let mut next_logical_time = 0;
+
+        for (key, value) in write_ahead_log.replay()? {
+            let key_size = serialized_size(&key)?;
+            if let Some(ref value) = in_memory_tree.get(&key) {
+                in_memory_usage -= key_size + serialized_size(value)?;
+            }
+            in_memory_usage += key_size + serialized_size(&value)?;
+            next_logical_time = cmp::max(next_logical_time, value.logical_time + 1);
+            in_memory_tree.insert(key, value);
+        }
+        // the logical time of the compaction strategy is not synchronized with the write-ahead
+        // log, so it may be older than the replayed entries after a crash
+        compaction_strategy.advance_logical_time(next_logical_time)?;
+
+        let writer = Writer {
+            compaction_strategy,
+            in_memory_usage,
+            write_ahead_log,
+        };
+        Ok((writer, in_memory_tree))
+    }
+
+    /// Returns a new version with the next logical time that expires after a duration if one is
+    /// given.
+    pub fn new_value(
+        &mut self,
+        data: SSTableData<U>,
+        ttl: Option<Duration>,
+    ) -> Result<SSTableValue<U>> {
+        let expiry_time = ttl.map(|ttl| {
+            let ttl = ttl.as_secs() * 1000 + u64::from(ttl.subsec_millis());
+            sstable::current_time().saturating_add(ttl)
+        });
+        Ok(SSTableValue {
+            data,
+            logical_time: self.compaction_strategy.get_and_increment_logical_time()?,
+            expiry_time,
+        })
+    }
+
+    /// Returns a new version with the next logical time that folds an operand into the version of
+    /// the key in the in-memory tree, since the in-memory tree only keeps the most recent version
+    /// of each key.
+    pub fn new_operand(
+        &mut self,
+        existing_value: Option<&SSTableValue<U>>,
+        operand: U,
+        merge_operator: Option<&Arc<dyn MergeOperator<U>>>,
+    ) -> Result<SSTableValue<U>> {
+        let logical_time = self.compaction_strategy.get_and_increment_logical_time()?;
+        merge::apply_operand(existing_value, operand, logical_time, merge_operator)
+    }
+
+    /// Returns the entries of a batch, which all share the next logical time.
+    pub fn new_batch(&mut self, batch: WriteBatch<T, U>) -> Result<Vec<(T, SSTableValue<U>)>> {
+        let logical_time = self.compaction_strategy.get_and_increment_logical_time()?;
+        Ok(batch
+            .into_entries()
+            .into_iter()
+            .map(|(key, data)| {
+                let value = SSTableValue {
+                    data: SSTableData::from(data),
+                    logical_time,
+                    expiry_time: None,
+                };
+                (key, value)
+            })
+            .collect())
+    }
+
+    /// Appends the entries of a single write to the write-ahead log and then inserts them into the
+    /// in-memory tree, which is only borrowed once the log has been written. Returns `true` if the
+    /// in-memory tree exceeds its size threshold and should be flushed.
+    pub fn insert_entries<F, M>(
+        &mut self,
+        entries: Vec<(T, SSTableValue<U>)>,
+        get_in_memory_tree: F,
+    ) -> Result<bool>
+    where
+        F: FnOnce() -> M,
+        M: DerefMut<Target = BTreeMap<T, SSTableValue<U>>>,
+    {
+        self.write_ahead_log.append(&entries)?;
+
+        let mut in_memory_tree = get_in_memory_tree();
+        for (key, value) in entries {
+            let key_size = serialized_size(&key)?;
+            if let Some(ref value) = in_memory_tree.get(&key) {
+                let value_size = serialized_size(value)?;
+                self.in_memory_usage -= key_size + value_size;
+            }
+
+            self.in_memory_usage += key_size + serialized_size(&value)?;
+            in_memory_tree.insert(key, value);
+        }
+
+        Ok(self.in_memory_usage > self.compaction_strategy.get_max_in_memory_size())
+    }
+
+    /// Writes the entries of the in-memory tree into a SSTable, hands the SSTable to the
+    /// compaction strategy, and truncates the write-ahead log.
+    pub fn flush<I>(&mut self, entries: I) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = (T, SSTableValue<U>)>,
+    {
+        self.in_memory_usage = 0;
+        let mut sstable_builder = SSTableBuilder::new(
+            self.compaction_strategy.get_path(),
+            entries.len(),
+            self.compaction_strategy.get_compression(),
+            &self.compaction_strategy.get_filter_policy(),
+        )?;
+        for entry in entries {
+            sstable_builder.append(entry.0, entry.1)?;
+        }
+        let sstable = SSTable::new(sstable_builder.flush()?)?;
+        self.compaction_strategy.try_compact(sstable)?;
+        self.write_ahead_log.truncate()
+    }
+
+    /// Truncates the write-ahead log and removes all SSTables. The in-memory tree must be cleared
+    /// by the caller.
+    pub fn clear(&mut self) -> Result<()> {
+        self.in_memory_usage = 0;
+        self.write_ahead_log.truncate()?;
+        self.compaction_strategy.clear()
+    }
+}
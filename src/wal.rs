@@ -0,0 +1,243 @@
+use byteorder::{BigEndian, ByteOrder};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::result;
+
+// The size of the length, the CRC32C checksum of the length, and the CRC32C checksum of the
+// contents that precede the contents of every record.
+const RECORD_HEADER_SIZE: u64 = 16;
+
+/// The policy for synchronizing a write-ahead log to disk.
+///
+/// Every write is appended to the write-ahead log before it returns, so a crash of the process
+/// will never lose a write that has returned. The sync policy determines how often the log is
+/// forced onto stable storage, which protects writes against a crash of the operating system or a
+/// loss of power. Defaults to `SyncPolicy::Every(100)`, so at most the last 100 writes are lost
+/// with a loss of power.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Synchronizes the log after every write.
+    Always,
+    /// Synchronizes the log after every `n` writes.
+    Every(usize),
+    /// Never explicitly synchronizes the log and leaves flushing to the operating system.
+    Never,
+}
+
+impl SyncPolicy {
+    // Returns `true` if a log with a number of appended records that have not been synchronized
+    // yet should be synchronized.
+    pub(crate) fn should_sync(self, unsynced_count: usize) -> bool {
+        match self {
+            SyncPolicy::Always => true,
+            SyncPolicy::Every(count) => unsynced_count >= count,
+            SyncPolicy::Never => false,
+        }
+    }
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        SyncPolicy::Every(100)
+    }
+}
+
+// Frames the contents of a record with its length, a CRC32C checksum of its length, and a CRC32C
+// checksum of its contents.
+pub fn encode_record(buffer: &[u8]) -> Vec<u8> {
+    let mut record = vec![0; RECORD_HEADER_SIZE as usize];
+    BigEndian::write_u64(&mut record[..8], buffer.len() as u64);
+    let length_checksum = crc32c::crc32c(&record[..8]);
+    BigEndian::write_u32(&mut record[8..12], length_checksum);
+    BigEndian::write_u32(&mut record[12..], crc32c::crc32c(buffer));
+    record.extend_from_slice(buffer);
+    record
+}
+
+// Returns `true` if a buffer starts with a complete record whose length and contents match their
+// checksums.
+fn starts_with_record(buffer: &[u8]) -> bool {
+    if (buffer.len() as u64) < RECORD_HEADER_SIZE {
+        return false;
+    }
+    let size = BigEndian::read_u64(&buffer[..8]);
+    if crc32c::crc32c(&buffer[..8]) != BigEndian::read_u32(&buffer[8..12]) {
+        return false;
+    }
+    if size > buffer.len() as u64 - RECORD_HEADER_SIZE {
+        return false;
+    }
+    let record = &buffer[RECORD_HEADER_SIZE as usize..(RECORD_HEADER_SIZE + size) as usize];
+    crc32c::crc32c(record) == BigEndian::read_u32(&buffer[12..16])
+}
+
+// Returns the contents of all valid records in a buffer and the length of the prefix of the
+// buffer that they occupy. An incomplete record, or a record whose checksum does not match and
+// that ends the buffer, is the result of a torn write and marks the end of the valid records. A
+// record whose checksum does not match and that is followed by more data cannot be the result of
+// a torn write, so its offset is returned as an error. The length of a record whose length
+// checksum does not match cannot be trusted, so the record is only the result of a torn write if
+// no valid record follows it.
+fn decode_records(buffer: &[u8]) -> result::Result<(Vec<&[u8]>, u64), u64> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset + RECORD_HEADER_SIZE <= buffer.len() as u64 {
+        let header = &buffer[offset as usize..(offset + RECORD_HEADER_SIZE) as usize];
+        let size = BigEndian::read_u64(&header[..8]);
+        let length_checksum = BigEndian::read_u32(&header[8..12]);
+        let checksum = BigEndian::read_u32(&header[12..]);
+
+        if crc32c::crc32c(&header[..8]) != length_checksum {
+            let rest = &buffer[offset as usize + 1..];
+            if (0..rest.len()).any(|start| starts_with_record(&rest[start..])) {
+                return Err(offset);
+            }
+            break;
+        }
+
+        let start = offset + RECORD_HEADER_SIZE;
+        if size > buffer.len() as u64 - start {
+            break;
+        }
+
+        let record = &buffer[start as usize..(start + size) as usize];
+        if crc32c::crc32c(record) != checksum {
+            if start + size < buffer.len() as u64 {
+                return Err(offset);
+            }
+            break;
+        }
+
+        records.push(record);
+        offset = start + size;
+    }
+    Ok((records, offset))
+}
+
+// Reads the contents of all valid records in a log, discards a torn record at the end of the log,
+// and positions the log after the last valid record. Returns the offset of a corrupted record in
+// the middle of the log as an error, in which case the log is not modified.
+pub fn read_records(log_file: &mut File) -> io::Result<result::Result<Vec<Vec<u8>>, u64>> {
+    let mut buffer = Vec::new();
+    log_file.seek(SeekFrom::Start(0))?;
+    log_file.read_to_end(&mut buffer)?;
+
+    let (records, offset) = match decode_records(&buffer) {
+        Ok(decoded_records) => decoded_records,
+        Err(offset) => return Ok(Err(offset)),
+    };
+
+    if offset != buffer.len() as u64 {
+        log_file.set_len(offset)?;
+        log_file.sync_all()?;
+    }
+    log_file.seek(SeekFrom::Start(offset))?;
+
+    Ok(Ok(records
+        .into_iter()
+        .map(|record| record.to_vec())
+        .collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_records, encode_record, read_records};
+    use std::fs;
+
+    fn encode_records(records: &[&[u8]]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for record in records {
+            buffer.extend(encode_record(record));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_decode_records() {
+        let buffer = encode_records(&[b"abc", b"", b"defg"]);
+        let records: Vec<&[u8]> = vec![b"abc", b"", b"defg"];
+        assert_eq!(decode_records(&buffer), Ok((records, buffer.len() as u64)));
+    }
+
+    #[test]
+    fn test_decode_records_torn_tail() {
+        let buffer = encode_records(&[b"abc", b"defg"]);
+        let records: Vec<&[u8]> = vec![b"abc"];
+
+        // an incomplete final record
+        for len in 19..buffer.len() {
+            assert_eq!(decode_records(&buffer[..len]), Ok((records.clone(), 19)));
+        }
+
+        // a complete final record whose contents were not written
+        let mut torn_buffer = buffer.clone();
+        let len = torn_buffer.len();
+        torn_buffer[len - 1] ^= 1;
+        assert_eq!(decode_records(&torn_buffer), Ok((records.clone(), 19)));
+
+        // a final record whose length was not written
+        let mut torn_buffer = buffer.clone();
+        torn_buffer[19] ^= 1;
+        assert_eq!(decode_records(&torn_buffer), Ok((records.clone(), 19)));
+
+        // a final record that was never written, but was allocated in the file
+        let mut torn_buffer = buffer[..19].to_vec();
+        torn_buffer.extend(vec![0; 100]);
+        assert_eq!(decode_records(&torn_buffer), Ok((records, 19)));
+    }
+
+    #[test]
+    fn test_decode_records_corruption() {
+        let mut buffer = encode_records(&[b"abc", b"defg", b"hi"]);
+        buffer[36] ^= 1;
+        assert_eq!(decode_records(&buffer), Err(19));
+    }
+
+    #[test]
+    fn test_decode_records_corrupted_length() {
+        let buffer = encode_records(&[b"abc", b"defg", b"hi"]);
+        for index in 0..8 {
+            let mut corrupted_buffer = buffer.clone();
+            corrupted_buffer[index] ^= 1;
+            assert_eq!(decode_records(&corrupted_buffer), Err(0));
+        }
+    }
+
+    #[test]
+    fn test_read_records() {
+        let test_name = "test_read_records";
+        let mut buffer = encode_records(&[b"abc", b"defg"]);
+        buffer.extend(&encode_record(b"hi")[..5]);
+        fs::write(test_name, &buffer).unwrap();
+
+        let result = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(test_name)
+            .and_then(|mut log_file| read_records(&mut log_file));
+        let len = fs::metadata(test_name).unwrap().len();
+        fs::remove_file(test_name).unwrap();
+
+        assert_eq!(result.unwrap(), Ok(vec![b"abc".to_vec(), b"defg".to_vec()]));
+        assert_eq!(len, 39);
+    }
+
+    #[test]
+    fn test_read_records_corruption() {
+        let test_name = "test_read_records_corruption";
+        let mut buffer = encode_records(&[b"abc", b"defg"]);
+        buffer[7] ^= 1;
+        fs::write(test_name, &buffer).unwrap();
+
+        let result = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(test_name)
+            .and_then(|mut log_file| read_records(&mut log_file));
+        let len = fs::metadata(test_name).unwrap().len();
+        fs::remove_file(test_name).unwrap();
+
+        assert_eq!(result.unwrap(), Err(0));
+        assert_eq!(len, buffer.len() as u64);
+    }
+}
@@ -1,11 +1,13 @@
 use extended_collections::bp_tree::{BpMap, Result};
 use rand::{thread_rng, Rng};
+use std::collections::BTreeMap;
 use std::fs;
 use std::panic;
 use std::vec::Vec;
 
 fn teardown(test_name: &str) {
     fs::remove_file(format!("{}.dat", test_name)).ok();
+    fs::remove_file(format!("{}.dat-wal", test_name)).ok();
 }
 
 fn run_test<T>(test: T, test_name: &str) -> Result<()>
@@ -38,6 +40,7 @@ fn int_test_bp_map() -> Result<()> {
             expected.sort_by(|l, r| l.0.cmp(&r.0));
             expected.dedup_by_key(|pair| pair.0);
 
+            drop(map);
             map = BpMap::open(&format!("{}.dat", test_name))?;
 
             assert_eq!(map.len(), expected.len());
@@ -66,3 +69,167 @@ fn int_test_bp_map() -> Result<()> {
         test_name,
     )
 }
+
+#[test]
+fn int_test_bp_map_variable_length() -> Result<()> {
+    let test_name = "int_test_bp_map_variable_length";
+    let file_name = &format!("{}.dat", test_name);
+    run_test(
+        || {
+            let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+            let mut map = BpMap::new(file_name, 24, 64)?;
+            let mut expected = Vec::new();
+            for _ in 0..2_000 {
+                // keys that are longer than 16 characters are stored in overflow pages
+                let key_len = rng.gen_range(0, 100);
+                let key = rng.gen_ascii_chars().take(key_len).collect::<String>();
+                let val_len = rng.gen_range(0, 2_000);
+                let val = rng.gen_iter::<u8>().take(val_len).collect::<Vec<_>>();
+
+                map.insert(key.clone(), val.clone())?;
+                expected.push((key, val));
+            }
+
+            expected.reverse();
+            expected.sort_by(|l, r| l.0.cmp(&r.0));
+            expected.dedup_by(|l, r| l.0 == r.0);
+
+            drop(map);
+            map = BpMap::open(file_name)?;
+
+            assert_eq!(map.len(), expected.len());
+            for entry in &expected {
+                assert_eq!(map.get(&entry.0)?, Some(entry.1.clone()));
+            }
+
+            thread_rng().shuffle(&mut expected);
+
+            for entry in expected {
+                assert_eq!(map.remove(&entry.0)?, Some(entry));
+            }
+            assert!(map.is_empty());
+
+            Ok(())
+        },
+        test_name,
+    )
+}
+
+#[test]
+fn int_test_bp_map_range() -> Result<()> {
+    let test_name = "int_test_bp_map_range";
+    let file_name = &format!("{}.dat", test_name);
+    run_test(
+        || {
+            let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+            let mut map = BpMap::with_degrees(file_name, 4, 8, 3, 3)?;
+            let mut expected = BTreeMap::new();
+            for _ in 0..5_000 {
+                let key = rng.gen_range(0, 10_000u32);
+                let val = rng.gen::<u64>();
+                if rng.gen::<bool>() {
+                    map.insert(key, val)?;
+                    expected.insert(key, val);
+                } else {
+                    map.remove(&key)?;
+                    expected.remove(&key);
+                }
+            }
+
+            for _ in 0..100 {
+                let start = rng.gen_range(0, 10_000u32);
+                let end = rng.gen_range(start, 10_000u32);
+                let entries = map.range(start..end)?.collect::<Result<Vec<_>>>()?;
+                let expected_entries: Vec<_> = expected
+                    .range(start..end)
+                    .map(|(key, val)| (*key, *val))
+                    .collect();
+                assert_eq!(entries, expected_entries);
+
+                let entries = map.range(start..=end)?.rev().collect::<Result<Vec<_>>>()?;
+                let expected_entries: Vec<_> = expected
+                    .range(start..=end)
+                    .rev()
+                    .map(|(key, val)| (*key, *val))
+                    .collect();
+                assert_eq!(entries, expected_entries);
+
+                assert_eq!(
+                    map.floor(&start)?,
+                    expected.range(..=start).next_back().map(|entry| *entry.0),
+                );
+                assert_eq!(
+                    map.ceil(&start)?,
+                    expected.range(start..).next().map(|entry| *entry.0),
+                );
+            }
+
+            let mut cursor = map.cursor();
+            cursor.seek(&5_000)?;
+            let mut expected_iter = expected.range(5_000..);
+            for _ in 0..100 {
+                let expected_entry = expected_iter.next();
+                assert_eq!(cursor.key(), expected_entry.map(|entry| entry.0));
+                assert_eq!(cursor.value(), expected_entry.map(|entry| entry.1));
+                cursor.next()?;
+            }
+            for _ in 0..200 {
+                cursor.prev()?;
+            }
+            let expected_entry = expected.range(..5_000).nth_back(99);
+            assert_eq!(cursor.key(), expected_entry.map(|entry| entry.0));
+
+            Ok(())
+        },
+        test_name,
+    )
+}
+
+#[test]
+fn int_test_bp_map_bulk_load() -> Result<()> {
+    let test_name = "int_test_bp_map_bulk_load";
+    let file_name = &format!("{}.dat", test_name);
+    run_test(
+        || {
+            let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 1, 1, 1]);
+            let mut expected = BTreeMap::new();
+            for _ in 0..10_000 {
+                expected.insert(rng.gen::<u32>(), rng.gen::<u64>());
+            }
+
+            let entries = expected.iter().map(|(key, val)| (*key, *val));
+            let mut map = BpMap::bulk_load(file_name, 4, 8, entries, 0.7)?;
+            assert_eq!(map.len(), expected.len());
+
+            for _ in 0..10_000 {
+                let key = rng.gen::<u32>();
+                let val = rng.gen::<u64>();
+                if rng.gen::<bool>() {
+                    assert_eq!(
+                        map.insert(key, val)?.map(|entry| entry.1),
+                        expected.insert(key, val),
+                    );
+                } else {
+                    let key = match expected.range(key..).next() {
+                        Some(entry) => *entry.0,
+                        None => key,
+                    };
+                    assert_eq!(
+                        map.remove(&key)?.map(|entry| entry.1),
+                        expected.remove(&key),
+                    );
+                }
+            }
+
+            drop(map);
+            map = BpMap::open(file_name)?;
+            assert_eq!(map.len(), expected.len());
+            let entries = map.iter()?.collect::<Result<Vec<_>>>()?;
+            let expected_entries: Vec<_> = expected.into_iter().collect();
+            assert_eq!(entries, expected_entries);
+
+            Ok(())
+        },
+        test_name,
+    )
+}